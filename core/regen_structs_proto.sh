@@ -1,13 +1,16 @@
 #!/bin/sh
 
-# This script regenerates the `src/structs_proto.rs` and `src/keys_proto.rs` files from
-# `structs.proto` and `keys.proto`.
+# This script regenerates the `src/keys_proto.rs` and `src/envelope_proto.rs` files from
+# `keys.proto` and `envelope.proto`.
 
 sudo docker run --rm -v `pwd`:/usr/code:z -w /usr/code rust /bin/bash -c " \
     apt-get update; \
     apt-get install -y protobuf-compiler; \
     cargo install --version 2.3.0 protobuf-codegen; \
-    protoc --rust_out . keys.proto"
+    protoc --rust_out . keys.proto; \
+    protoc --rust_out . envelope.proto"
 
 sudo chown $USER:$USER keys.rs
 mv -f keys.rs ./src/keys_proto.rs
+sudo chown $USER:$USER envelope.rs
+mv -f envelope.rs ./src/envelope_proto.rs
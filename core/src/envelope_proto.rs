@@ -0,0 +1,749 @@
+// This file is generated by rust-protobuf 2.3.0. Do not edit
+// @generated
+
+// https://github.com/Manishearth/rust-clippy/issues/702
+#![allow(unknown_lints)]
+#![allow(clippy::all)]
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+
+#![allow(box_pointers)]
+#![allow(dead_code)]
+#![allow(missing_docs)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(trivial_casts)]
+#![allow(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_results)]
+
+use protobuf::Message as Message_imported_for_functions;
+use protobuf::ProtobufEnum as ProtobufEnum_imported_for_functions;
+
+#[derive(PartialEq,Clone,Default)]
+pub struct Envelope {
+    // message fields
+    pub public_key: ::std::vec::Vec<u8>,
+    pub payload_type: ::std::vec::Vec<u8>,
+    pub payload: ::std::vec::Vec<u8>,
+    pub signature: ::std::vec::Vec<u8>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl Envelope {
+    pub fn new() -> Envelope {
+        ::std::default::Default::default()
+    }
+
+    // bytes public_key = 1;
+
+    pub fn clear_public_key(&mut self) {
+        self.public_key.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_public_key(&mut self, v: ::std::vec::Vec<u8>) {
+        self.public_key = v;
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_public_key(&mut self) -> &mut ::std::vec::Vec<u8> {
+        &mut self.public_key
+    }
+
+    // Take field
+    pub fn take_public_key(&mut self) -> ::std::vec::Vec<u8> {
+        ::std::mem::replace(&mut self.public_key, ::std::vec::Vec::new())
+    }
+
+    pub fn get_public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    // bytes payload_type = 2;
+
+    pub fn clear_payload_type(&mut self) {
+        self.payload_type.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_payload_type(&mut self, v: ::std::vec::Vec<u8>) {
+        self.payload_type = v;
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_payload_type(&mut self) -> &mut ::std::vec::Vec<u8> {
+        &mut self.payload_type
+    }
+
+    // Take field
+    pub fn take_payload_type(&mut self) -> ::std::vec::Vec<u8> {
+        ::std::mem::replace(&mut self.payload_type, ::std::vec::Vec::new())
+    }
+
+    pub fn get_payload_type(&self) -> &[u8] {
+        &self.payload_type
+    }
+
+    // bytes payload = 3;
+
+    pub fn clear_payload(&mut self) {
+        self.payload.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_payload(&mut self, v: ::std::vec::Vec<u8>) {
+        self.payload = v;
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_payload(&mut self) -> &mut ::std::vec::Vec<u8> {
+        &mut self.payload
+    }
+
+    // Take field
+    pub fn take_payload(&mut self) -> ::std::vec::Vec<u8> {
+        ::std::mem::replace(&mut self.payload, ::std::vec::Vec::new())
+    }
+
+    pub fn get_payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    // bytes signature = 5;
+
+    pub fn clear_signature(&mut self) {
+        self.signature.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_signature(&mut self, v: ::std::vec::Vec<u8>) {
+        self.signature = v;
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_signature(&mut self) -> &mut ::std::vec::Vec<u8> {
+        &mut self.signature
+    }
+
+    // Take field
+    pub fn take_signature(&mut self) -> ::std::vec::Vec<u8> {
+        ::std::mem::replace(&mut self.signature, ::std::vec::Vec::new())
+    }
+
+    pub fn get_signature(&self) -> &[u8] {
+        &self.signature
+    }
+}
+
+impl ::protobuf::Message for Envelope {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    ::protobuf::rt::read_singular_proto3_bytes_into(wire_type, is, &mut self.public_key)?;
+                },
+                2 => {
+                    ::protobuf::rt::read_singular_proto3_bytes_into(wire_type, is, &mut self.payload_type)?;
+                },
+                3 => {
+                    ::protobuf::rt::read_singular_proto3_bytes_into(wire_type, is, &mut self.payload)?;
+                },
+                5 => {
+                    ::protobuf::rt::read_singular_proto3_bytes_into(wire_type, is, &mut self.signature)?;
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        if !self.public_key.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(1, &self.public_key);
+        }
+        if !self.payload_type.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(2, &self.payload_type);
+        }
+        if !self.payload.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(3, &self.payload);
+        }
+        if !self.signature.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(5, &self.signature);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        if !self.public_key.is_empty() {
+            os.write_bytes(1, &self.public_key)?;
+        }
+        if !self.payload_type.is_empty() {
+            os.write_bytes(2, &self.payload_type)?;
+        }
+        if !self.payload.is_empty() {
+            os.write_bytes(3, &self.payload)?;
+        }
+        if !self.signature.is_empty() {
+            os.write_bytes(5, &self.signature)?;
+        }
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> Envelope {
+        Envelope::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_simple_field_accessor::<_, ::protobuf::types::ProtobufTypeBytes>(
+                    "public_key",
+                    |m: &Envelope| { &m.public_key },
+                    |m: &mut Envelope| { &mut m.public_key },
+                ));
+                fields.push(::protobuf::reflect::accessor::make_simple_field_accessor::<_, ::protobuf::types::ProtobufTypeBytes>(
+                    "payload_type",
+                    |m: &Envelope| { &m.payload_type },
+                    |m: &mut Envelope| { &mut m.payload_type },
+                ));
+                fields.push(::protobuf::reflect::accessor::make_simple_field_accessor::<_, ::protobuf::types::ProtobufTypeBytes>(
+                    "payload",
+                    |m: &Envelope| { &m.payload },
+                    |m: &mut Envelope| { &mut m.payload },
+                ));
+                fields.push(::protobuf::reflect::accessor::make_simple_field_accessor::<_, ::protobuf::types::ProtobufTypeBytes>(
+                    "signature",
+                    |m: &Envelope| { &m.signature },
+                    |m: &mut Envelope| { &mut m.signature },
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Envelope>(
+                    "Envelope",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+
+    fn default_instance() -> &'static Envelope {
+        static mut instance: ::protobuf::lazy::Lazy<Envelope> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Envelope,
+        };
+        unsafe {
+            instance.get(Envelope::new)
+        }
+    }
+}
+
+impl ::protobuf::Clear for Envelope {
+    fn clear(&mut self) {
+        self.clear_public_key();
+        self.clear_payload_type();
+        self.clear_payload();
+        self.clear_signature();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for Envelope {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for Envelope {
+    fn as_ref(&self) -> ::protobuf::reflect::ProtobufValueRef<'_> {
+        ::protobuf::reflect::ProtobufValueRef::Message(self)
+    }
+}
+
+#[derive(PartialEq,Clone,Default)]
+pub struct PeerRecord {
+    // message fields
+    pub peer_id: ::std::vec::Vec<u8>,
+    pub seq: u64,
+    pub addresses: ::protobuf::RepeatedField<PeerRecord_AddressInfo>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl PeerRecord {
+    pub fn new() -> PeerRecord {
+        ::std::default::Default::default()
+    }
+
+    // bytes peer_id = 1;
+
+    pub fn clear_peer_id(&mut self) {
+        self.peer_id.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_peer_id(&mut self, v: ::std::vec::Vec<u8>) {
+        self.peer_id = v;
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_peer_id(&mut self) -> &mut ::std::vec::Vec<u8> {
+        &mut self.peer_id
+    }
+
+    // Take field
+    pub fn take_peer_id(&mut self) -> ::std::vec::Vec<u8> {
+        ::std::mem::replace(&mut self.peer_id, ::std::vec::Vec::new())
+    }
+
+    pub fn get_peer_id(&self) -> &[u8] {
+        &self.peer_id
+    }
+
+    // uint64 seq = 2;
+
+    pub fn clear_seq(&mut self) {
+        self.seq = 0;
+    }
+
+    // Param is passed by value, moved
+    pub fn set_seq(&mut self, v: u64) {
+        self.seq = v;
+    }
+
+    pub fn get_seq(&self) -> u64 {
+        self.seq
+    }
+
+    // repeated .PeerRecord.AddressInfo addresses = 3;
+
+    pub fn clear_addresses(&mut self) {
+        self.addresses.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_addresses(&mut self, v: ::protobuf::RepeatedField<PeerRecord_AddressInfo>) {
+        self.addresses = v;
+    }
+
+    // Mutable pointer to the field.
+    pub fn mut_addresses(&mut self) -> &mut ::protobuf::RepeatedField<PeerRecord_AddressInfo> {
+        &mut self.addresses
+    }
+
+    // Take field
+    pub fn take_addresses(&mut self) -> ::protobuf::RepeatedField<PeerRecord_AddressInfo> {
+        ::std::mem::replace(&mut self.addresses, ::protobuf::RepeatedField::new())
+    }
+
+    pub fn get_addresses(&self) -> &[PeerRecord_AddressInfo] {
+        &self.addresses
+    }
+}
+
+impl ::protobuf::Message for PeerRecord {
+    fn is_initialized(&self) -> bool {
+        for v in &self.addresses {
+            if !v.is_initialized() {
+                return false;
+            }
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    ::protobuf::rt::read_singular_proto3_bytes_into(wire_type, is, &mut self.peer_id)?;
+                },
+                2 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_uint64()?;
+                    self.seq = tmp;
+                },
+                3 => {
+                    ::protobuf::rt::read_repeated_message_into(wire_type, is, &mut self.addresses)?;
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        if !self.peer_id.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(1, &self.peer_id);
+        }
+        if self.seq != 0 {
+            my_size += ::protobuf::rt::value_size(2, self.seq, ::protobuf::wire_format::WireTypeVarint);
+        }
+        for value in &self.addresses {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        if !self.peer_id.is_empty() {
+            os.write_bytes(1, &self.peer_id)?;
+        }
+        if self.seq != 0 {
+            os.write_uint64(2, self.seq)?;
+        }
+        for v in &self.addresses {
+            os.write_tag(3, ::protobuf::wire_format::WireTypeLengthDelimited)?;
+            os.write_raw_varint32(v.get_cached_size())?;
+            v.write_to_with_cached_sizes(os)?;
+        };
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> PeerRecord {
+        PeerRecord::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_simple_field_accessor::<_, ::protobuf::types::ProtobufTypeBytes>(
+                    "peer_id",
+                    |m: &PeerRecord| { &m.peer_id },
+                    |m: &mut PeerRecord| { &mut m.peer_id },
+                ));
+                fields.push(::protobuf::reflect::accessor::make_simple_field_accessor::<_, ::protobuf::types::ProtobufTypeUint64>(
+                    "seq",
+                    |m: &PeerRecord| { &m.seq },
+                    |m: &mut PeerRecord| { &mut m.seq },
+                ));
+                fields.push(::protobuf::reflect::accessor::make_repeated_field_accessor::<_, ::protobuf::types::ProtobufTypeMessage<PeerRecord_AddressInfo>>(
+                    "addresses",
+                    |m: &PeerRecord| { &m.addresses },
+                    |m: &mut PeerRecord| { &mut m.addresses },
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<PeerRecord>(
+                    "PeerRecord",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+
+    fn default_instance() -> &'static PeerRecord {
+        static mut instance: ::protobuf::lazy::Lazy<PeerRecord> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const PeerRecord,
+        };
+        unsafe {
+            instance.get(PeerRecord::new)
+        }
+    }
+}
+
+impl ::protobuf::Clear for PeerRecord {
+    fn clear(&mut self) {
+        self.clear_peer_id();
+        self.clear_seq();
+        self.clear_addresses();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for PeerRecord {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for PeerRecord {
+    fn as_ref(&self) -> ::protobuf::reflect::ProtobufValueRef<'_> {
+        ::protobuf::reflect::ProtobufValueRef::Message(self)
+    }
+}
+
+#[derive(PartialEq,Clone,Default)]
+pub struct PeerRecord_AddressInfo {
+    // message fields
+    pub multiaddr: ::std::vec::Vec<u8>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl PeerRecord_AddressInfo {
+    pub fn new() -> PeerRecord_AddressInfo {
+        ::std::default::Default::default()
+    }
+
+    // bytes multiaddr = 1;
+
+    pub fn clear_multiaddr(&mut self) {
+        self.multiaddr.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_multiaddr(&mut self, v: ::std::vec::Vec<u8>) {
+        self.multiaddr = v;
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_multiaddr(&mut self) -> &mut ::std::vec::Vec<u8> {
+        &mut self.multiaddr
+    }
+
+    // Take field
+    pub fn take_multiaddr(&mut self) -> ::std::vec::Vec<u8> {
+        ::std::mem::replace(&mut self.multiaddr, ::std::vec::Vec::new())
+    }
+
+    pub fn get_multiaddr(&self) -> &[u8] {
+        &self.multiaddr
+    }
+}
+
+impl ::protobuf::Message for PeerRecord_AddressInfo {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    ::protobuf::rt::read_singular_proto3_bytes_into(wire_type, is, &mut self.multiaddr)?;
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        if !self.multiaddr.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(1, &self.multiaddr);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        if !self.multiaddr.is_empty() {
+            os.write_bytes(1, &self.multiaddr)?;
+        }
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> PeerRecord_AddressInfo {
+        PeerRecord_AddressInfo::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_simple_field_accessor::<_, ::protobuf::types::ProtobufTypeBytes>(
+                    "multiaddr",
+                    |m: &PeerRecord_AddressInfo| { &m.multiaddr },
+                    |m: &mut PeerRecord_AddressInfo| { &mut m.multiaddr },
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<PeerRecord_AddressInfo>(
+                    "PeerRecord_AddressInfo",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+
+    fn default_instance() -> &'static PeerRecord_AddressInfo {
+        static mut instance: ::protobuf::lazy::Lazy<PeerRecord_AddressInfo> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const PeerRecord_AddressInfo,
+        };
+        unsafe {
+            instance.get(PeerRecord_AddressInfo::new)
+        }
+    }
+}
+
+impl ::protobuf::Clear for PeerRecord_AddressInfo {
+    fn clear(&mut self) {
+        self.clear_multiaddr();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for PeerRecord_AddressInfo {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for PeerRecord_AddressInfo {
+    fn as_ref(&self) -> ::protobuf::reflect::ProtobufValueRef<'_> {
+        ::protobuf::reflect::ProtobufValueRef::Message(self)
+    }
+}
+
+static file_descriptor_proto_data: &'static [u8] = b"\
+    \n\x0eenvelope.proto\"\x84\x01\n\x08Envelope\x12\x1d\n\npublic_key\x18\
+    \x01\x20\x01(\x0cR\tpublicKey\x12!\n\x0cpayload_type\x18\x02\x20\x01(\
+    \x0cR\x0bpayloadType\x12\x18\n\x07payload\x18\x03\x20\x01(\x0cR\x07paylo\
+    ad\x12\x1c\n\tsignature\x18\x05\x20\x01(\x0cR\tsignature\"\x9b\x01\n\nPe\
+    erRecord\x12\x17\n\x07peer_id\x18\x01\x20\x01(\x0cR\x06peerId\x12\x10\n\
+    \x03seq\x18\x02\x20\x01(\x04R\x03seq\x125\n\taddresses\x18\x03\x20\x03(\
+    \x0b2\x17.PeerRecord.AddressInfoR\taddresses\x1a+\n\x0bAddressInfo\x12\
+    \x1c\n\tmultiaddr\x18\x01\x20\x01(\x0cR\tmultiaddrb\x06proto3\
+";
+
+static mut file_descriptor_proto_lazy: ::protobuf::lazy::Lazy<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::lazy::Lazy {
+    lock: ::protobuf::lazy::ONCE_INIT,
+    ptr: 0 as *const ::protobuf::descriptor::FileDescriptorProto,
+};
+
+fn parse_descriptor_proto() -> ::protobuf::descriptor::FileDescriptorProto {
+    ::protobuf::parse_from_bytes(file_descriptor_proto_data).unwrap()
+}
+
+pub fn file_descriptor_proto() -> &'static ::protobuf::descriptor::FileDescriptorProto {
+    unsafe {
+        file_descriptor_proto_lazy.get(|| {
+            parse_descriptor_proto()
+        })
+    }
+}
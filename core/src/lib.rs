@@ -39,6 +39,7 @@
 pub use multiaddr;
 pub use multistream_select::Negotiated;
 
+mod envelope_proto;
 mod keys_proto;
 mod peer_id;
 mod translation;
@@ -50,12 +51,16 @@ pub mod either;
 pub mod identity;
 pub mod muxing;
 pub mod nodes;
+pub mod peer_record;
+pub mod signed_envelope;
 pub mod transport;
 pub mod upgrade;
 
 pub use multiaddr::Multiaddr;
 pub use muxing::StreamMuxer;
 pub use peer_id::PeerId;
+pub use peer_record::PeerRecord;
+pub use signed_envelope::SignedEnvelope;
 pub use identity::PublicKey;
 pub use transport::Transport;
 pub use translation::address_translation;
@@ -20,7 +20,7 @@
 
 use crate::muxing::StreamMuxer;
 use crate::{
-    ConnectedPoint, Multiaddr, PeerId, address_translation,
+    ConnectedPoint, Endpoint, Multiaddr, PeerId, address_translation,
     nodes::{
         collection::{
             CollectionEvent,
@@ -70,8 +70,8 @@ where
     /// This needs to be a separate struct in order to handle multiple mutable borrows issues.
     reach_attempts: ReachAttempts<TPeerId>,
 
-    /// Max numer of incoming connections.
-    incoming_limit: Option<u32>,
+    /// Limits on the number of pending and established connections.
+    limits: ConnectionLimits,
 
     /// Unfinished take over message to be delivered.
     ///
@@ -96,7 +96,7 @@ where
             .field("listeners", &self.listeners)
             .field("active_nodes", &self.active_nodes)
             .field("reach_attempts", &self.reach_attempts)
-            .field("incoming_limit", &self.incoming_limit)
+            .field("limits", &self.limits)
             .field("take_over_to_complete", &self.take_over_to_complete)
             .finish()
     }
@@ -144,6 +144,69 @@ where
     }
 }
 
+impl<TPeerId> ReachAttempts<TPeerId>
+where
+    TPeerId: Eq + Hash,
+{
+    /// Checks whether a new outgoing connection attempt is within the given limits.
+    fn check_outgoing(&self, limits: &ConnectionLimits) -> Result<(), ConnectionLimit> {
+        if let Some(limit) = limits.max_pending_outgoing {
+            let num_pending = self.out_reach_attempts.len() + self.other_reach_attempts
+                .iter()
+                .filter(|(_, endpoint)| endpoint.is_dialer())
+                .count();
+            if num_pending >= limit as usize {
+                return Err(ConnectionLimit::PendingOutgoing(limit))
+            }
+        }
+
+        if let Some(limit) = limits.max_established_outgoing {
+            let num_established = self.connected_points
+                .values()
+                .filter(|endpoint| endpoint.is_dialer())
+                .count();
+            if num_established >= limit as usize {
+                return Err(ConnectionLimit::EstablishedOutgoing(limit))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether accepting a new connection to `peer_id` on the given endpoint
+    /// is within the given limits.
+    fn check_established(&self, limits: &ConnectionLimits, peer_id: &TPeerId, endpoint: Endpoint)
+        -> Result<(), ConnectionLimit>
+    {
+        if let Some(limit) = limits.max_established_per_peer {
+            let num_connections = if self.connected_points.contains_key(peer_id) { 2 } else { 1 };
+            if num_connections > limit as usize {
+                return Err(ConnectionLimit::EstablishedPerPeer(limit))
+            }
+        }
+
+        let (limit, to_error): (_, fn(u32) -> ConnectionLimit) = if endpoint.is_dialer() {
+            (limits.max_established_outgoing, ConnectionLimit::EstablishedOutgoing)
+        } else {
+            (limits.max_established_incoming, ConnectionLimit::EstablishedIncoming)
+        };
+
+        if let Some(limit) = limit {
+            // An existing connection to the same peer is replaced by the new
+            // one and thus not counted.
+            let num_established = self.connected_points
+                .iter()
+                .filter(|(p, e)| *p != peer_id && e.is_dialer() == endpoint.is_dialer())
+                .count();
+            if num_established >= limit as usize {
+                return Err(to_error(limit))
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// Attempt to reach a peer.
 #[derive(Debug, Clone)]
 struct OutReachAttempt {
@@ -384,6 +447,8 @@ enum InternalReachErr<TTransErr, TConnInfo> {
     },
     /// The negotiated `PeerId` is the same as the one of the local node.
     FoundLocalPeerId,
+    /// The dial was refused because of a connection limit.
+    ConnectionLimit(ConnectionLimit),
 }
 
 impl<TTransErr, TConnInfo> fmt::Display for InternalReachErr<TTransErr, TConnInfo>
@@ -400,6 +465,7 @@ where
             InternalReachErr::FoundLocalPeerId => {
                 write!(f, "Remote has the same PeerId as us")
             }
+            InternalReachErr::ConnectionLimit(limit) => write!(f, "{}", limit),
         }
     }
 }
@@ -414,6 +480,7 @@ where
             InternalReachErr::Transport(err) => Some(err),
             InternalReachErr::PeerIdMismatch { .. } => None,
             InternalReachErr::FoundLocalPeerId => None,
+            InternalReachErr::ConnectionLimit(limit) => Some(limit),
         }
     }
 }
@@ -432,6 +499,113 @@ pub enum PeerState {
     NotConnected,
 }
 
+/// Limits on the number of connections of a `Network`.
+///
+/// By default, no limit is enforced.
+#[derive(Debug, Default, Clone)]
+pub struct ConnectionLimits {
+    max_pending_incoming: Option<u32>,
+    max_pending_outgoing: Option<u32>,
+    max_established_incoming: Option<u32>,
+    max_established_outgoing: Option<u32>,
+    max_established_per_peer: Option<u32>,
+}
+
+impl ConnectionLimits {
+    /// Configures the maximum number of incoming connections that are being negotiated.
+    ///
+    /// While the limit is reached, the listeners are not polled for new connections.
+    pub fn with_max_pending_incoming(mut self, limit: Option<u32>) -> Self {
+        self.max_pending_incoming = limit;
+        self
+    }
+
+    /// Configures the maximum number of outgoing connections that are being negotiated,
+    /// i.e. the maximum number of concurrent dials.
+    pub fn with_max_pending_outgoing(mut self, limit: Option<u32>) -> Self {
+        self.max_pending_outgoing = limit;
+        self
+    }
+
+    /// Configures the maximum number of established incoming connections.
+    pub fn with_max_established_incoming(mut self, limit: Option<u32>) -> Self {
+        self.max_established_incoming = limit;
+        self
+    }
+
+    /// Configures the maximum number of established outgoing connections.
+    pub fn with_max_established_outgoing(mut self, limit: Option<u32>) -> Self {
+        self.max_established_outgoing = limit;
+        self
+    }
+
+    /// Configures the maximum number of connections per peer, including the
+    /// newly established connection.
+    ///
+    /// The `Network` keeps a single connection per peer, and a new connection
+    /// to a peer normally replaces the existing one. With a limit of `1`, a new
+    /// connection to an already connected peer is closed instead.
+    pub fn with_max_established_per_peer(mut self, limit: Option<u32>) -> Self {
+        self.max_established_per_peer = limit;
+        self
+    }
+
+    /// Returns the maximum number of incoming connections that are being negotiated.
+    pub fn max_pending_incoming(&self) -> Option<u32> {
+        self.max_pending_incoming
+    }
+
+    /// Returns the maximum number of outgoing connections that are being negotiated.
+    pub fn max_pending_outgoing(&self) -> Option<u32> {
+        self.max_pending_outgoing
+    }
+
+    /// Returns the maximum number of established incoming connections.
+    pub fn max_established_incoming(&self) -> Option<u32> {
+        self.max_established_incoming
+    }
+
+    /// Returns the maximum number of established outgoing connections.
+    pub fn max_established_outgoing(&self) -> Option<u32> {
+        self.max_established_outgoing
+    }
+
+    /// Returns the maximum number of connections per peer.
+    pub fn max_established_per_peer(&self) -> Option<u32> {
+        self.max_established_per_peer
+    }
+}
+
+/// A connection limit that has been reached, with the value of the limit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConnectionLimit {
+    /// The maximum number of outgoing connections being negotiated.
+    PendingOutgoing(u32),
+    /// The maximum number of established incoming connections.
+    EstablishedIncoming(u32),
+    /// The maximum number of established outgoing connections.
+    EstablishedOutgoing(u32),
+    /// The maximum number of connections per peer.
+    EstablishedPerPeer(u32),
+}
+
+impl fmt::Display for ConnectionLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionLimit::PendingOutgoing(limit) =>
+                write!(f, "Limit of {} pending outgoing connections reached", limit),
+            ConnectionLimit::EstablishedIncoming(limit) =>
+                write!(f, "Limit of {} established incoming connections reached", limit),
+            ConnectionLimit::EstablishedOutgoing(limit) =>
+                write!(f, "Limit of {} established outgoing connections reached", limit),
+            ConnectionLimit::EstablishedPerPeer(limit) =>
+                write!(f, "Limit of {} connections per peer reached", limit),
+        }
+    }
+}
+
+impl error::Error for ConnectionLimit {}
+
 /// Error that can happen when trying to reach a node.
 #[derive(Debug)]
 pub enum NetworkReachError<TTransErr, TConnInfo> {
@@ -443,7 +617,10 @@ pub enum NetworkReachError<TTransErr, TConnInfo> {
     PeerIdMismatch {
         /// The information about the other connection.
         obtained: TConnInfo,
-    }
+    },
+
+    /// The connection was refused or closed because of a connection limit.
+    ConnectionLimit(ConnectionLimit),
 }
 
 impl<TTransErr, TConnInfo> fmt::Display for NetworkReachError<TTransErr, TConnInfo>
@@ -457,6 +634,7 @@ where
             NetworkReachError::PeerIdMismatch { obtained } => {
                 write!(f, "Peer ID mismatch, obtained: {:?}", obtained)
             },
+            NetworkReachError::ConnectionLimit(limit) => write!(f, "{}", limit),
         }
     }
 }
@@ -470,6 +648,7 @@ where
         match self {
             NetworkReachError::Transport(err) => Some(err),
             NetworkReachError::PeerIdMismatch { .. } => None,
+            NetworkReachError::ConnectionLimit(limit) => Some(limit),
         }
     }
 }
@@ -481,6 +660,8 @@ pub enum UnknownPeerDialErr<TTransErr> {
     Transport(TransportError<TTransErr>),
     /// The negotiated `PeerId` is the same as the local node.
     FoundLocalPeerId,
+    /// The dial was refused because of a connection limit.
+    ConnectionLimit(ConnectionLimit),
 }
 
 impl<TTransErr> fmt::Display for UnknownPeerDialErr<TTransErr>
@@ -492,6 +673,7 @@ where TTransErr: fmt::Display
             UnknownPeerDialErr::FoundLocalPeerId => {
                 write!(f, "Unknown peer has same PeerId as us")
             },
+            UnknownPeerDialErr::ConnectionLimit(limit) => write!(f, "{}", limit),
         }
     }
 }
@@ -503,6 +685,7 @@ where TTransErr: error::Error + 'static
         match self {
             UnknownPeerDialErr::Transport(err) => Some(err),
             UnknownPeerDialErr::FoundLocalPeerId => None,
+            UnknownPeerDialErr::ConnectionLimit(limit) => Some(limit),
         }
     }
 }
@@ -518,6 +701,8 @@ pub enum IncomingError<TTransErr> {
     DeniedLowerPriority,
     /// The negotiated `PeerId` is the same as the local node.
     FoundLocalPeerId,
+    /// Denied the incoming connection because of a connection limit.
+    ConnectionLimit(ConnectionLimit),
 }
 
 impl<TTransErr> fmt::Display for IncomingError<TTransErr>
@@ -532,6 +717,7 @@ where TTransErr: fmt::Display
             IncomingError::FoundLocalPeerId => {
                 write!(f, "Incoming connection has same PeerId as us")
             },
+            IncomingError::ConnectionLimit(limit) => write!(f, "{}", limit),
         }
     }
 }
@@ -544,6 +730,7 @@ where TTransErr: error::Error + 'static
             IncomingError::Transport(err) => Some(err),
             IncomingError::DeniedLowerPriority => None,
             IncomingError::FoundLocalPeerId => None,
+            IncomingError::ConnectionLimit(limit) => Some(limit),
         }
     }
 }
@@ -684,27 +871,22 @@ where
 {
     /// Creates a new node events stream.
     pub fn new(transport: TTrans, local_peer_id: TPeerId) -> Self {
-        // TODO: with_capacity?
-        Network {
-            listeners: ListenersStream::new(transport),
-            active_nodes: CollectionStream::new(),
-            reach_attempts: ReachAttempts {
-                local_peer_id,
-                out_reach_attempts: Default::default(),
-                other_reach_attempts: Vec::new(),
-                connected_points: Default::default(),
-            },
-            incoming_limit: None,
-            take_over_to_complete: None
-        }
+        Self::new_with_limits(transport, local_peer_id, ConnectionLimits::default())
     }
 
     /// Creates a new node event stream with incoming connections limit.
     pub fn new_with_incoming_limit(transport: TTrans,
         local_peer_id: TPeerId, incoming_limit: Option<u32>) -> Self
     {
+        let limits = ConnectionLimits::default().with_max_pending_incoming(incoming_limit);
+        Self::new_with_limits(transport, local_peer_id, limits)
+    }
+
+    /// Creates a new node event stream with the given connection limits.
+    pub fn new_with_limits(transport: TTrans, local_peer_id: TPeerId, limits: ConnectionLimits) -> Self {
+        // TODO: with_capacity?
         Network {
-            incoming_limit,
+            limits,
             listeners: ListenersStream::new(transport),
             active_nodes: CollectionStream::new(),
             reach_attempts: ReachAttempts {
@@ -739,7 +921,12 @@ where
 
     /// Returns limit on incoming connections.
     pub fn incoming_limit(&self) -> Option<u32> {
-        self.incoming_limit
+        self.limits.max_pending_incoming
+    }
+
+    /// Returns the connection limits of the network.
+    pub fn connection_limits(&self) -> &ConnectionLimits {
+        &self.limits
     }
 
     /// Call this function in order to know which address remotes should dial to
@@ -792,20 +979,26 @@ where
     {
         let local_peer_id = self.reach_attempts.local_peer_id.clone();
         let connected_point = ConnectedPoint::Dialer { address: addr.clone() };
-        let future = self.transport().clone().dial(addr)?
-            .map_err(|err| InternalReachErr::Transport(TransportError::Other(err)))
-            .and_then({
-                let connected_point = connected_point.clone();
-                move |(peer_id, muxer)| {
-                    if *peer_id.peer_id() == local_peer_id {
-                        Err(InternalReachErr::FoundLocalPeerId)
-                    } else {
-                        Ok(((peer_id, connected_point), muxer))
+
+        let reach_id = if let Err(limit) = self.reach_attempts.check_outgoing(&self.limits) {
+            let future = future::err(InternalReachErr::ConnectionLimit(limit));
+            self.active_nodes.add_reach_attempt(future, handler)
+        } else {
+            let future = self.transport().clone().dial(addr)?
+                .map_err(|err| InternalReachErr::Transport(TransportError::Other(err)))
+                .and_then({
+                    let connected_point = connected_point.clone();
+                    move |(peer_id, muxer)| {
+                        if *peer_id.peer_id() == local_peer_id {
+                            Err(InternalReachErr::FoundLocalPeerId)
+                        } else {
+                            Ok(((peer_id, connected_point), muxer))
+                        }
                     }
-                }
-            });
+                });
+            self.active_nodes.add_reach_attempt(future, handler)
+        };
 
-        let reach_id = self.active_nodes.add_reach_attempt(future, handler);
         self.reach_attempts.other_reach_attempts.push((reach_id, connected_point));
         Ok(())
     }
@@ -945,25 +1138,30 @@ where
         TConnInfo: Send + 'static,
         TPeerId: Send + 'static,
     {
-        let reach_id = match self.transport().clone().dial(first.clone()) {
-            Ok(fut) => {
-                let expected_peer_id = peer_id.clone();
-                let connected_point = ConnectedPoint::Dialer { address: first.clone() };
-                let fut = fut
-                    .map_err(|err| InternalReachErr::Transport(TransportError::Other(err)))
-                    .and_then(move |(actual_conn_info, muxer)| {
-                        if *actual_conn_info.peer_id() == expected_peer_id {
-                            Ok(((actual_conn_info, connected_point), muxer))
-                        } else {
-                            Err(InternalReachErr::PeerIdMismatch { obtained: actual_conn_info })
-                        }
-                    });
-                self.active_nodes.add_reach_attempt(fut, handler)
-            },
-            Err(err) => {
-                let fut = future::err(InternalReachErr::Transport(err));
-                self.active_nodes.add_reach_attempt(fut, handler)
-            },
+        let reach_id = if let Err(limit) = self.reach_attempts.check_outgoing(&self.limits) {
+            let fut = future::err(InternalReachErr::ConnectionLimit(limit));
+            self.active_nodes.add_reach_attempt(fut, handler)
+        } else {
+            match self.transport().clone().dial(first.clone()) {
+                Ok(fut) => {
+                    let expected_peer_id = peer_id.clone();
+                    let connected_point = ConnectedPoint::Dialer { address: first.clone() };
+                    let fut = fut
+                        .map_err(|err| InternalReachErr::Transport(TransportError::Other(err)))
+                        .and_then(move |(actual_conn_info, muxer)| {
+                            if *actual_conn_info.peer_id() == expected_peer_id {
+                                Ok(((actual_conn_info, connected_point), muxer))
+                            } else {
+                                Err(InternalReachErr::PeerIdMismatch { obtained: actual_conn_info })
+                            }
+                        });
+                    self.active_nodes.add_reach_attempt(fut, handler)
+                },
+                Err(err) => {
+                    let fut = future::err(InternalReachErr::Transport(err));
+                    self.active_nodes.add_reach_attempt(fut, handler)
+                },
+            }
         };
 
         let former = self.reach_attempts.out_reach_attempts.insert(
@@ -999,7 +1197,7 @@ where
     {
         // Start by polling the listeners for events, but only if the number
         // of incoming connections does not exceed the limit.
-        match self.incoming_limit {
+        match self.limits.max_pending_incoming {
             Some(x) if self.incoming_negotiated().count() >= (x as usize)
                 => (),
             _ => {
@@ -1056,7 +1254,7 @@ where
         match self.active_nodes.poll() {
             Async::NotReady => return Async::NotReady,
             Async::Ready(CollectionEvent::NodeReached(reach_event)) => {
-                let (a, e) = handle_node_reached(&mut self.reach_attempts, reach_event);
+                let (a, e) = handle_node_reached(&mut self.reach_attempts, &self.limits, reach_event);
                 action = a;
                 out_event = e;
             }
@@ -1145,6 +1343,7 @@ impl<THandler, TPeerId> Default for ActionItem<THandler, TPeerId> {
 /// >           panics will likely happen.
 fn handle_node_reached<'a, TTrans, TMuxer, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId>(
     reach_attempts: &mut ReachAttempts<TPeerId>,
+    limits: &ConnectionLimits,
     event: CollectionReachEvent<'_, TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, (), (TConnInfo, ConnectedPoint), TPeerId>,
 ) -> (ActionItem<THandler, TPeerId>, NetworkEvent<'a, TTrans, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId>)
 where
@@ -1181,6 +1380,36 @@ where
             }
         }
 
+        // The connection is subject to the limits on established connections, now that we know
+        // the peer it is with.
+        let endpoint = opened_endpoint.to_endpoint();
+        if let Err(limit) = reach_attempts.check_established(limits, event.peer_id(), endpoint) {
+            let peer_id = event.peer_id().clone();
+            event.deny();
+            match opened_endpoint {
+                ConnectedPoint::Listener { local_addr, send_back_addr } => {
+                    return (Default::default(), NetworkEvent::IncomingConnectionError {
+                        local_addr,
+                        send_back_addr,
+                        error: IncomingError::ConnectionLimit(limit),
+                    });
+                }
+                ConnectedPoint::Dialer { address } => {
+                    let new_state = if reach_attempts.connected_points.contains_key(&peer_id) {
+                        PeerState::Connected
+                    } else {
+                        PeerState::NotConnected
+                    };
+                    return (Default::default(), NetworkEvent::DialError {
+                        new_state,
+                        peer_id,
+                        multiaddr: address,
+                        error: NetworkReachError::ConnectionLimit(limit),
+                    });
+                }
+            }
+        }
+
         // Set the endpoint for this peer.
         let closed_endpoint = reach_attempts.connected_points.insert(event.peer_id().clone(), opened_endpoint.clone());
 
@@ -1241,6 +1470,22 @@ where
             .expect("is_outgoing_and_ok is true only if reach_attempts.out_reach_attempts.get(event.peer_id()) \
                         returned Some");
 
+        if let Err(limit) = reach_attempts.check_established(limits, event.peer_id(), Endpoint::Dialer) {
+            let peer_id = event.peer_id().clone();
+            event.deny();
+            let new_state = if reach_attempts.connected_points.contains_key(&peer_id) {
+                PeerState::Connected
+            } else {
+                PeerState::NotConnected
+            };
+            return (Default::default(), NetworkEvent::DialError {
+                new_state,
+                peer_id,
+                multiaddr: attempt.cur_attempted,
+                error: NetworkReachError::ConnectionLimit(limit),
+            });
+        }
+
         let opened_endpoint = ConnectedPoint::Dialer {
             address: attempt.cur_attempted,
         };
@@ -1321,6 +1566,14 @@ where
         let num_remain = attempt.next_attempts.len();
         let failed_addr = attempt.cur_attempted.clone();
 
+        // A connection limit applies to all addresses, hence the remaining
+        // addresses are not attempted.
+        let num_remain = if let InternalReachErr::ConnectionLimit(_) = error {
+            0
+        } else {
+            num_remain
+        };
+
         let new_state = if reach_attempts.connected_points.contains_key(&peer_id) {
             PeerState::Connected
         } else if num_remain == 0 {
@@ -1332,7 +1585,7 @@ where
             }
         };
 
-        let action = if !attempt.next_attempts.is_empty() {
+        let action = if num_remain != 0 {
             let mut attempt = attempt;
             let next_attempt = attempt.next_attempts.remove(0);
             ActionItem {
@@ -1352,6 +1605,7 @@ where
                 unreachable!("We only generate FoundLocalPeerId within dial() or accept(); neither \
                               of these methods add an entry to out_reach_attempts; QED")
             },
+            InternalReachErr::ConnectionLimit(limit) => NetworkReachError::ConnectionLimit(limit),
         };
 
         return (action, NetworkEvent::DialError {
@@ -1374,6 +1628,7 @@ where
                 let error = match error {
                     InternalReachErr::Transport(err) => UnknownPeerDialErr::Transport(err),
                     InternalReachErr::FoundLocalPeerId => UnknownPeerDialErr::FoundLocalPeerId,
+                    InternalReachErr::ConnectionLimit(limit) => UnknownPeerDialErr::ConnectionLimit(limit),
                     InternalReachErr::PeerIdMismatch { .. } => {
                         unreachable!("We only generate PeerIdMismatch within start_dial_out(),
                                       which doesn't add any entry in other_reach_attempts; QED")
@@ -1393,6 +1648,11 @@ where
                         unreachable!("We only generate PeerIdMismatch within start_dial_out(),
                                       which doesn't add any entry in other_reach_attempts; QED")
                     },
+                    InternalReachErr::ConnectionLimit(_) => {
+                        unreachable!("We only generate ConnectionLimit within dial() and \
+                                      start_dial_out(), neither of which is used for incoming \
+                                      connections; QED")
+                    },
                 };
                 return (Default::default(), NetworkEvent::IncomingConnectionError {
                     local_addr,
@@ -443,3 +443,126 @@ fn limit_incoming_connections() {
         assert!(network.incoming_negotiated().count() <= (limit as usize));
     }
 }
+
+#[test]
+fn limit_pending_outgoing_connections() {
+    let mut transport = DummyTransport::new();
+    transport.make_dial_fail();
+    let limits = ConnectionLimits::default().with_max_pending_outgoing(Some(1));
+    let mut network = Network::<_, _, _, Handler, _>::new_with_limits(transport, PeerId::random(), limits);
+    assert_eq!(network.connection_limits().max_pending_outgoing(), Some(1));
+
+    let addr = "/memory/0".parse::<Multiaddr>().expect("bad multiaddr");
+    assert!(network.dial(addr.clone(), Handler::default()).is_ok());
+    assert!(network.dial(addr, Handler::default()).is_ok());
+    assert_eq!(network.unknown_dials().count(), 2);
+
+    let network = Arc::new(Mutex::new(network));
+    let mut rt = Runtime::new().unwrap();
+    let mut errors = Vec::new();
+    while errors.len() < 2 {
+        let network_fut = network.clone();
+        let error = rt.block_on(future::poll_fn(move || -> Poll<_, ()> {
+            let mut network = network_fut.lock();
+            match network.poll() {
+                Async::NotReady => Ok(Async::Ready(None)),
+                Async::Ready(NetworkEvent::UnknownPeerDialError { error, .. }) => Ok(Async::Ready(Some(error))),
+                Async::Ready(event) => panic!("Unexpected event: {:?}", event),
+            }
+        })).expect("tokio works");
+        errors.extend(error);
+    }
+
+    assert_eq!(errors.iter().filter(|e| match e {
+        UnknownPeerDialErr::ConnectionLimit(ConnectionLimit::PendingOutgoing(1)) => true,
+        _ => false
+    }).count(), 1);
+}
+
+#[test]
+fn limit_established_connections_to_unknown_peers() {
+    let limit = 2;
+    let limits = ConnectionLimits::default().with_max_established_outgoing(Some(limit));
+    let mut network = Network::<_, _, _, Handler, _>::new_with_limits(DummyTransport::new(), PeerId::random(), limits);
+
+    // All the dials are pending at the same time, so none is rejected when started.
+    let addr = "/memory/0".parse::<Multiaddr>().expect("bad multiaddr");
+    for _ in 0 ..= limit {
+        assert!(network.dial(addr.clone(), Handler::default()).is_ok());
+    }
+    assert_eq!(network.unknown_dials().count(), limit as usize + 1);
+
+    let network = Arc::new(Mutex::new(network));
+    let mut rt = Runtime::new().unwrap();
+    let mut connected = 0;
+    let mut denied = 0;
+    while connected + denied <= limit {
+        let network_fut = network.clone();
+        let (c, d) = rt.block_on(future::poll_fn(move || -> Poll<_, ()> {
+            let mut network = network_fut.lock();
+            match network.poll() {
+                Async::NotReady => Ok(Async::Ready((0, 0))),
+                Async::Ready(NetworkEvent::Connected { .. }) => Ok(Async::Ready((1, 0))),
+                Async::Ready(NetworkEvent::DialError {
+                    error: NetworkReachError::ConnectionLimit(ConnectionLimit::EstablishedOutgoing(2)), ..
+                }) => Ok(Async::Ready((0, 1))),
+                Async::Ready(event) => panic!("Unexpected event: {:?}", event),
+            }
+        })).expect("tokio works");
+        connected += c;
+        denied += d;
+    }
+
+    assert_eq!((connected, denied), (limit, 1));
+    assert_eq!(network.lock().connected_peers().count(), limit as usize);
+}
+
+#[test]
+fn limit_established_connections_per_peer() {
+    let mut transport = DummyTransport::new();
+    let peer_id = PeerId::random();
+    let muxer = DummyMuxer::new();
+
+    let mut events = vec![ListenerEvent::NewAddress("/ip4/127.0.0.1/tcp/1234".parse().unwrap())];
+    events.extend(std::iter::repeat(
+        ListenerEvent::Upgrade {
+            upgrade: (peer_id.clone(), muxer.clone()),
+            local_addr: "/ip4/127.0.0.1/tcp/1234".parse().unwrap(),
+            remote_addr: "/ip4/127.0.0.1/tcp/32111".parse().unwrap()
+        }
+    ).take(2));
+    transport.set_initial_listener_state(ListenerState::Events(events));
+
+    let limits = ConnectionLimits::default().with_max_established_per_peer(Some(1));
+    let mut network = Network::<_, _, _, Handler, _>::new_with_limits(transport, PeerId::random(), limits);
+    network.listen_on("/memory/0".parse().unwrap()).unwrap();
+
+    let network = Arc::new(Mutex::new(network));
+    let mut rt = Runtime::new().unwrap();
+    let mut connected = 0;
+    let mut denied = 0;
+    while connected + denied < 2 {
+        let network_fut = network.clone();
+        let (c, d) = rt.block_on(future::poll_fn(move || -> Poll<_, ()> {
+            let mut network = network_fut.lock();
+            match network.poll() {
+                Async::NotReady => Ok(Async::Ready((0, 0))),
+                Async::Ready(NetworkEvent::NewListenerAddress { .. }) => Ok(Async::Ready((0, 0))),
+                Async::Ready(NetworkEvent::IncomingConnection(incoming)) => {
+                    incoming.accept(Handler::default());
+                    Ok(Async::Ready((0, 0)))
+                }
+                Async::Ready(NetworkEvent::Connected { .. }) => Ok(Async::Ready((1, 0))),
+                Async::Ready(NetworkEvent::IncomingConnectionError {
+                    error: IncomingError::ConnectionLimit(ConnectionLimit::EstablishedPerPeer(1)), ..
+                }) => Ok(Async::Ready((0, 1))),
+                Async::Ready(event) => panic!("Unexpected event: {:?}", event),
+            }
+        })).expect("tokio works");
+        connected += c;
+        denied += d;
+    }
+
+    assert_eq!((connected, denied), (1, 1));
+    assert_eq!(network.lock().connected_peers().count(), 1);
+}
@@ -0,0 +1,221 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Signed peer records, as specified in [RFC 0003].
+//!
+//! A `PeerRecord` lists the addresses of a peer. It is wrapped in a `SignedEnvelope` signed by
+//! that peer, which lets the nodes that receive it check that the addresses are the ones the
+//! peer itself advertises, and not ones forged by a third party.
+//!
+//! [RFC 0003]: https://github.com/libp2p/specs/blob/master/RFC/0003-routing-records.md
+
+use crate::envelope_proto;
+use crate::identity::{Keypair, error::{DecodingError, SigningError}};
+use crate::signed_envelope::{ReadPayloadError, SignedEnvelope};
+use crate::{Multiaddr, PeerId};
+use protobuf::{Message, RepeatedField};
+use std::{convert::TryFrom, error, fmt, time::SystemTime};
+
+/// The domain separation string of the signature of peer records.
+pub const DOMAIN_SEPARATION: &str = "libp2p-routing-state";
+
+/// The payload type of peer records, i.e. the `libp2p-peer-record` multicodec.
+pub const PAYLOAD_TYPE: &[u8] = &[0x03, 0x01];
+
+/// The addresses of a peer, signed by that peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerRecord {
+    peer_id: PeerId,
+    seq: u64,
+    addresses: Vec<Multiaddr>,
+    /// The envelope the record has been decoded from or signed into.
+    envelope: SignedEnvelope,
+}
+
+impl PeerRecord {
+    /// Builds and signs a new record of the given addresses of the local node.
+    ///
+    /// The sequence number is the current UNIX time in milliseconds, so that a newer record
+    /// always has a greater sequence number than an older one.
+    pub fn new(key: &Keypair, addresses: Vec<Multiaddr>) -> Result<Self, SigningError> {
+        let seq = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs() * 1000 + u64::from(d.subsec_millis()))
+            .unwrap_or(0);
+        Self::with_seq(key, seq, addresses)
+    }
+
+    /// Builds and signs a new record of the given addresses of the local node, with the given
+    /// sequence number.
+    pub fn with_seq(key: &Keypair, seq: u64, addresses: Vec<Multiaddr>) -> Result<Self, SigningError> {
+        let peer_id = key.public().into_peer_id();
+
+        let mut record = envelope_proto::PeerRecord::new();
+        record.set_peer_id(peer_id.clone().into_bytes());
+        record.set_seq(seq);
+        record.set_addresses(addresses
+            .iter()
+            .map(|addr| {
+                let mut info = envelope_proto::PeerRecord_AddressInfo::new();
+                info.set_multiaddr(addr.to_vec());
+                info
+            })
+            .collect::<RepeatedField<_>>());
+
+        let payload = record
+            .write_to_bytes()
+            .expect("Encoding peer record should never fail.");
+        let envelope = SignedEnvelope::new(key, DOMAIN_SEPARATION, PAYLOAD_TYPE.to_vec(), payload)?;
+
+        Ok(PeerRecord { peer_id, seq, addresses, envelope })
+    }
+
+    /// Decodes a record from a signed envelope, checking its signature and that it has been
+    /// signed by the peer it describes.
+    pub fn from_signed_envelope(envelope: SignedEnvelope) -> Result<Self, FromEnvelopeError> {
+        let payload = envelope.payload(DOMAIN_SEPARATION, PAYLOAD_TYPE)
+            .map_err(FromEnvelopeError::BadPayload)?;
+        let mut record = protobuf::parse_from_bytes::<envelope_proto::PeerRecord>(payload)
+            .map_err(|e| FromEnvelopeError::InvalidPeerRecord(DecodingError::new("PeerRecord").source(e)))?;
+
+        let peer_id = PeerId::from_bytes(record.take_peer_id())
+            .map_err(|_| FromEnvelopeError::InvalidPeerRecord(DecodingError::new("PeerRecord peer ID")))?;
+        if peer_id.is_public_key(envelope.key()) != Some(true) {
+            return Err(FromEnvelopeError::MismatchedSignature);
+        }
+
+        let addresses = record.take_addresses()
+            .into_iter()
+            .map(|mut info| Multiaddr::try_from(info.take_multiaddr()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| FromEnvelopeError::InvalidPeerRecord(DecodingError::new("PeerRecord address").source(e)))?;
+
+        Ok(PeerRecord {
+            peer_id,
+            seq: record.get_seq(),
+            addresses,
+            envelope,
+        })
+    }
+
+    /// Returns the peer the record describes.
+    pub fn peer_id(&self) -> &PeerId {
+        &self.peer_id
+    }
+
+    /// Returns the sequence number of the record. A greater sequence number means a more recent
+    /// record.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Returns the addresses of the peer.
+    pub fn addresses(&self) -> &[Multiaddr] {
+        &self.addresses
+    }
+
+    /// Returns the signed envelope of the record, to transmit it to other nodes.
+    pub fn envelope(&self) -> &SignedEnvelope {
+        &self.envelope
+    }
+
+    /// Turns the record into its signed envelope.
+    pub fn into_signed_envelope(self) -> SignedEnvelope {
+        self.envelope
+    }
+}
+
+/// Error that can happen when decoding a `PeerRecord` from a `SignedEnvelope`.
+#[derive(Debug)]
+pub enum FromEnvelopeError {
+    /// The payload of the envelope can't be read.
+    BadPayload(ReadPayloadError),
+    /// The payload of the envelope isn't a valid peer record.
+    InvalidPeerRecord(DecodingError),
+    /// The envelope hasn't been signed by the peer the record describes.
+    MismatchedSignature,
+}
+
+impl fmt::Display for FromEnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromEnvelopeError::BadPayload(err) =>
+                write!(f, "Failed to read the payload of the envelope: {}", err),
+            FromEnvelopeError::InvalidPeerRecord(err) =>
+                write!(f, "Failed to decode the peer record: {}", err),
+            FromEnvelopeError::MismatchedSignature =>
+                write!(f, "The peer record isn't signed by the peer it describes"),
+        }
+    }
+}
+
+impl error::Error for FromEnvelopeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FromEnvelopeError::BadPayload(err) => Some(err),
+            FromEnvelopeError::InvalidPeerRecord(err) => Some(err),
+            FromEnvelopeError::MismatchedSignature => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_roundtrip() {
+        let keypair = Keypair::generate_ed25519();
+        let addresses: Vec<Multiaddr> = vec![
+            "/ip4/1.2.3.4/tcp/1234".parse().unwrap(),
+            "/ip6/::1/udp/5678".parse().unwrap(),
+        ];
+        let record = PeerRecord::with_seq(&keypair, 7, addresses.clone()).unwrap();
+
+        let bytes = record.clone().into_signed_envelope().into_protobuf_encoding();
+        let envelope = SignedEnvelope::from_protobuf_encoding(&bytes).unwrap();
+        let decoded = PeerRecord::from_signed_envelope(envelope).unwrap();
+
+        assert_eq!(decoded, record);
+        assert_eq!(decoded.peer_id(), &keypair.public().into_peer_id());
+        assert_eq!(decoded.seq(), 7);
+        assert_eq!(decoded.addresses(), &addresses[..]);
+    }
+
+    #[test]
+    fn record_signed_by_other_peer_is_rejected() {
+        let keypair = Keypair::generate_ed25519();
+        let attacker = Keypair::generate_ed25519();
+
+        // The attacker signs a record pretending to describe `keypair`.
+        let mut record = envelope_proto::PeerRecord::new();
+        record.set_peer_id(keypair.public().into_peer_id().into_bytes());
+        record.set_seq(1);
+        let payload = record.write_to_bytes().unwrap();
+        let envelope = SignedEnvelope::new(&attacker, DOMAIN_SEPARATION, PAYLOAD_TYPE.to_vec(), payload)
+            .unwrap();
+
+        match PeerRecord::from_signed_envelope(envelope) {
+            Err(FromEnvelopeError::MismatchedSignature) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Signed envelopes, as specified in [RFC 0002].
+//!
+//! A `SignedEnvelope` wraps an arbitrary payload together with the public key of its signer and
+//! a signature of the payload. The signature covers a *domain separation* string, which is never
+//! transmitted, so that a signature produced for one purpose can't be reused for another.
+//!
+//! [RFC 0002]: https://github.com/libp2p/specs/blob/master/RFC/0002-signed-envelopes.md
+
+use crate::envelope_proto;
+use crate::identity::{Keypair, PublicKey, error::{DecodingError, SigningError}};
+use protobuf::Message;
+use std::{error, fmt};
+
+/// A payload signed by the key of a node, along with that key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedEnvelope {
+    key: PublicKey,
+    payload_type: Vec<u8>,
+    payload: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl SignedEnvelope {
+    /// Signs the given payload with the keypair, within the given domain.
+    pub fn new(
+        key: &Keypair,
+        domain_separation: &str,
+        payload_type: Vec<u8>,
+        payload: Vec<u8>,
+    ) -> Result<Self, SigningError> {
+        let buffer = signature_payload(domain_separation, &payload_type, &payload);
+        let signature = key.sign(&buffer)?;
+
+        Ok(SignedEnvelope {
+            key: key.public(),
+            payload_type,
+            payload,
+            signature,
+        })
+    }
+
+    /// Returns true if the signature of the envelope is valid for the given domain.
+    pub fn verify(&self, domain_separation: &str) -> bool {
+        let buffer = signature_payload(domain_separation, &self.payload_type, &self.payload);
+        self.key.verify(&buffer, &self.signature)
+    }
+
+    /// Returns the payload of the envelope, after checking its signature and its type.
+    pub fn payload(&self, domain_separation: &str, expected_payload_type: &[u8])
+        -> Result<&[u8], ReadPayloadError>
+    {
+        if self.payload_type != expected_payload_type {
+            return Err(ReadPayloadError::UnexpectedPayloadType {
+                expected: expected_payload_type.to_vec(),
+                got: self.payload_type.clone(),
+            });
+        }
+
+        if !self.verify(domain_separation) {
+            return Err(ReadPayloadError::InvalidSignature);
+        }
+
+        Ok(&self.payload)
+    }
+
+    /// Returns the public key of the signer of the envelope.
+    pub fn key(&self) -> &PublicKey {
+        &self.key
+    }
+
+    /// Returns the type of the payload of the envelope.
+    pub fn payload_type(&self) -> &[u8] {
+        &self.payload_type
+    }
+
+    /// Encodes the envelope into its protobuf representation.
+    pub fn into_protobuf_encoding(self) -> Vec<u8> {
+        let mut envelope = envelope_proto::Envelope::new();
+        envelope.set_public_key(self.key.into_protobuf_encoding());
+        envelope.set_payload_type(self.payload_type);
+        envelope.set_payload(self.payload);
+        envelope.set_signature(self.signature);
+
+        envelope
+            .write_to_bytes()
+            .expect("Encoding envelope should never fail.")
+    }
+
+    /// Decodes an envelope from its protobuf representation.
+    ///
+    /// > **Note**: The signature is not checked. Use `payload` to access the payload, which
+    /// >           checks it.
+    pub fn from_protobuf_encoding(bytes: &[u8]) -> Result<Self, DecodingError> {
+        let mut envelope = protobuf::parse_from_bytes::<envelope_proto::Envelope>(bytes)
+            .map_err(|e| DecodingError::new("Envelope").source(e))?;
+
+        Ok(SignedEnvelope {
+            key: PublicKey::from_protobuf_encoding(envelope.get_public_key())?,
+            payload_type: envelope.take_payload_type(),
+            payload: envelope.take_payload(),
+            signature: envelope.take_signature(),
+        })
+    }
+}
+
+/// Builds the buffer that is signed: the domain, the payload type and the payload, each of them
+/// prefixed with its length as an unsigned varint.
+fn signature_payload(domain_separation: &str, payload_type: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut domain_len = unsigned_varint::encode::usize_buffer();
+    let domain_len = unsigned_varint::encode::usize(domain_separation.len(), &mut domain_len);
+    let mut payload_type_len = unsigned_varint::encode::usize_buffer();
+    let payload_type_len = unsigned_varint::encode::usize(payload_type.len(), &mut payload_type_len);
+    let mut payload_len = unsigned_varint::encode::usize_buffer();
+    let payload_len = unsigned_varint::encode::usize(payload.len(), &mut payload_len);
+
+    let mut buffer = Vec::with_capacity(
+        domain_len.len() + domain_separation.len()
+        + payload_type_len.len() + payload_type.len()
+        + payload_len.len() + payload.len()
+    );
+    buffer.extend_from_slice(domain_len);
+    buffer.extend_from_slice(domain_separation.as_bytes());
+    buffer.extend_from_slice(payload_type_len);
+    buffer.extend_from_slice(payload_type);
+    buffer.extend_from_slice(payload_len);
+    buffer.extend_from_slice(payload);
+    buffer
+}
+
+/// Error that can happen when reading the payload of a `SignedEnvelope`.
+#[derive(Debug)]
+pub enum ReadPayloadError {
+    /// The signature of the envelope is invalid.
+    InvalidSignature,
+    /// The payload of the envelope isn't of the expected type.
+    UnexpectedPayloadType {
+        /// The expected payload type.
+        expected: Vec<u8>,
+        /// The payload type of the envelope.
+        got: Vec<u8>,
+    },
+}
+
+impl fmt::Display for ReadPayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadPayloadError::InvalidSignature =>
+                write!(f, "Invalid signature of the envelope"),
+            ReadPayloadError::UnexpectedPayloadType { expected, got } =>
+                write!(f, "Unexpected payload type, expected {:?} but got {:?}", expected, got),
+        }
+    }
+}
+
+impl error::Error for ReadPayloadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_read_payload() {
+        let keypair = Keypair::generate_ed25519();
+        let envelope = SignedEnvelope::new(&keypair, "domain", b"type".to_vec(), b"payload".to_vec())
+            .unwrap();
+
+        let decoded = SignedEnvelope::from_protobuf_encoding(&envelope.clone().into_protobuf_encoding())
+            .unwrap();
+        assert_eq!(decoded, envelope);
+        assert_eq!(decoded.payload("domain", b"type").unwrap(), b"payload");
+
+        match decoded.payload("other domain", b"type") {
+            Err(ReadPayloadError::InvalidSignature) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        match decoded.payload("domain", b"other type") {
+            Err(ReadPayloadError::UnexpectedPayloadType { .. }) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tampered_payload_is_rejected() {
+        let keypair = Keypair::generate_ed25519();
+        let mut envelope = SignedEnvelope::new(&keypair, "domain", b"type".to_vec(), b"payload".to_vec())
+            .unwrap();
+        envelope.payload = b"tampered".to_vec();
+        assert!(!envelope.verify("domain"));
+    }
+}
@@ -243,6 +243,20 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         })
     };
 
+    // Build the list of statements to put in the body of `inject_incoming_connection_error()`.
+    let inject_incoming_connection_error_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_incoming_connection_error(local_addr, send_back_addr, error); },
+                None => quote!{ self.#field_n.inject_incoming_connection_error(local_addr, send_back_addr, error); },
+            })
+        })
+    };
+
     // Build the list of statements to put in the body of `inject_new_listen_addr()`.
     let inject_new_listen_addr_stmts = {
         data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
@@ -285,6 +299,20 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         })
     };
 
+    // Build the list of statements to put in the body of `inject_expired_external_addr()`.
+    let inject_expired_external_addr_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_expired_external_addr(addr); },
+                None => quote!{ self.#field_n.inject_expired_external_addr(addr); },
+            })
+        })
+    };
+
     // Build the list of statements to put in the body of `inject_listener_error()`.
     let inject_listener_error_stmts = {
         data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
@@ -437,6 +465,30 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                     Async::Ready(#network_behaviour_action::ReportObservedAddr { address }) => {
                         return Async::Ready(#network_behaviour_action::ReportObservedAddr { address });
                     }
+                    Async::Ready(#network_behaviour_action::AddExternalAddr { address }) => {
+                        return Async::Ready(#network_behaviour_action::AddExternalAddr { address });
+                    }
+                    Async::Ready(#network_behaviour_action::RemoveExternalAddr { address }) => {
+                        return Async::Ready(#network_behaviour_action::RemoveExternalAddr { address });
+                    }
+                    Async::Ready(#network_behaviour_action::TagPeer { peer_id, tag, value }) => {
+                        return Async::Ready(#network_behaviour_action::TagPeer { peer_id, tag, value });
+                    }
+                    Async::Ready(#network_behaviour_action::UntagPeer { peer_id, tag }) => {
+                        return Async::Ready(#network_behaviour_action::UntagPeer { peer_id, tag });
+                    }
+                    Async::Ready(#network_behaviour_action::ProtectPeer { peer_id, tag }) => {
+                        return Async::Ready(#network_behaviour_action::ProtectPeer { peer_id, tag });
+                    }
+                    Async::Ready(#network_behaviour_action::UnprotectPeer { peer_id, tag }) => {
+                        return Async::Ready(#network_behaviour_action::UnprotectPeer { peer_id, tag });
+                    }
+                    Async::Ready(#network_behaviour_action::DisconnectPeer { peer_id }) => {
+                        return Async::Ready(#network_behaviour_action::DisconnectPeer { peer_id });
+                    }
+                    Async::Ready(#network_behaviour_action::BanPeer { peer_id }) => {
+                        return Async::Ready(#network_behaviour_action::BanPeer { peer_id });
+                    }
                     Async::NotReady => break,
                 }
             }
@@ -482,6 +534,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 #(#inject_dial_failure_stmts);*
             }
 
+            fn inject_incoming_connection_error(&mut self, local_addr: &#multiaddr, send_back_addr: &#multiaddr, error: &dyn std::error::Error) {
+                #(#inject_incoming_connection_error_stmts);*
+            }
+
             fn inject_new_listen_addr(&mut self, addr: &#multiaddr) {
                 #(#inject_new_listen_addr_stmts);*
             }
@@ -494,6 +550,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 #(#inject_new_external_addr_stmts);*
             }
 
+            fn inject_expired_external_addr(&mut self, addr: &#multiaddr) {
+                #(#inject_expired_external_addr_stmts);*
+            }
+
             fn inject_listener_error(&mut self, id: #listener_id, err: &(dyn std::error::Error + 'static)) {
                 #(#inject_listener_error_stmts);*
             }
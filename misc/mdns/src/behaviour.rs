@@ -18,7 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::service::{MdnsService, MdnsPacket};
+use crate::service::{MdnsConfig, MdnsService, MdnsPacket};
 use futures::prelude::*;
 use libp2p_core::{address_translation, ConnectedPoint, Multiaddr, PeerId, multiaddr::Protocol};
 use libp2p_swarm::{
@@ -40,6 +40,9 @@ pub struct Mdns<TSubstream> {
     /// The inner service.
     service: MdnsService,
 
+    /// TTL of the records we send in our responses.
+    ttl: Duration,
+
     /// List of nodes that we have discovered, the address, and when their TTL expires.
     ///
     /// Each combination of `PeerId` and `Multiaddr` can only appear once, but the same `PeerId`
@@ -56,10 +59,16 @@ pub struct Mdns<TSubstream> {
 }
 
 impl<TSubstream> Mdns<TSubstream> {
-    /// Builds a new `Mdns` behaviour.
+    /// Builds a new `Mdns` behaviour with the default configuration.
     pub fn new() -> io::Result<Mdns<TSubstream>> {
+        Self::with_config(MdnsConfig::default())
+    }
+
+    /// Builds a new `Mdns` behaviour with the given configuration.
+    pub fn with_config(config: MdnsConfig) -> io::Result<Mdns<TSubstream>> {
         Ok(Mdns {
-            service: MdnsService::new()?,
+            ttl: config.ttl(),
+            service: MdnsService::with_config(config)?,
             discovered_nodes: SmallVec::new(),
             closest_expiration: None,
             marker: PhantomData,
@@ -220,7 +229,7 @@ where
                     let _ = query.respond(
                         params.local_peer_id().clone(),
                         params.listened_addresses(),
-                        Duration::from_secs(5 * 60)
+                        self.ttl
                     );
                 },
                 MdnsPacket::Response(response) => {
@@ -264,7 +273,7 @@ where
                     break discovered;
                 },
                 MdnsPacket::ServiceDiscovery(disc) => {
-                    disc.respond(Duration::from_secs(5 * 60));
+                    disc.respond(self.ttl);
                 },
             }
         };
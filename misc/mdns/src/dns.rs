@@ -21,7 +21,7 @@
 //! Contains methods that handle the DNS encoding and decoding capabilities not available in the
 //! `dns_parser` library.
 
-use crate::{META_QUERY_SERVICE, SERVICE_NAME};
+use crate::META_QUERY_SERVICE;
 use data_encoding;
 use libp2p_core::{Multiaddr, PeerId};
 use rand;
@@ -47,9 +47,9 @@ pub fn decode_character_string(mut from: &[u8]) -> Result<Cow<'_, [u8]>, ()> {
     Ok(Cow::Borrowed(from))
 }
 
-/// Builds the binary representation of a DNS query to send on the network.
-pub fn build_query() -> Vec<u8> {
-    let mut out = Vec::with_capacity(33);
+/// Builds the binary representation of a DNS query for `service_name` to send on the network.
+pub fn build_query(service_name: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(18 + service_name.len());
 
     // Program-generated transaction ID; unused by our implementation.
     append_u16(&mut out, rand::random());
@@ -67,23 +67,25 @@ pub fn build_query() -> Vec<u8> {
 
     // Our single question.
     // The name.
-    append_qname(&mut out, SERVICE_NAME);
+    append_qname(&mut out, service_name);
 
     // Flags.
     append_u16(&mut out, 0x0c);
     append_u16(&mut out, 0x01);
 
-    // Since the output is constant, we reserve the right amount ahead of time.
+    // Since the output size only depends on the service name, we reserve the right amount ahead
+    // of time.
     // If this assert fails, adjust the capacity of `out` in the source code.
     debug_assert_eq!(out.capacity(), out.len());
     out
 }
 
-/// Builds the response to the DNS query.
+/// Builds the response to the DNS query for `service_name`.
 ///
 /// If there are more than 2^16-1 addresses, ignores the rest.
 pub fn build_query_response(
     id: u16,
+    service_name: &[u8],
     peer_id: PeerId,
     addresses: impl ExactSizeIterator<Item = Multiaddr>,
     ttl: Duration,
@@ -108,7 +110,7 @@ pub fn build_query_response(
 
     // Our single answer.
     // The name.
-    append_qname(&mut out, SERVICE_NAME);
+    append_qname(&mut out, service_name);
 
     // Flags.
     append_u16(&mut out, 0x000c);
@@ -123,7 +125,7 @@ pub fn build_query_response(
     let peer_name = format!(
         "{}.{}",
         data_encoding::BASE32_DNSCURVE.encode(&peer_id.into_bytes()),
-        str::from_utf8(SERVICE_NAME).expect("SERVICE_NAME is always ASCII")
+        str::from_utf8(service_name).expect("the service name is always ASCII")
     );
     let mut peer_id_bytes = Vec::with_capacity(64);
     append_qname(&mut peer_id_bytes, peer_name.as_bytes());
@@ -147,13 +149,13 @@ pub fn build_query_response(
     Ok(out)
 }
 
-/// Builds the response to the DNS query.
-pub fn build_service_discovery_response(id: u16, ttl: Duration) -> Vec<u8> {
+/// Builds the response to the DNS-SD query, advertising `service_name`.
+pub fn build_service_discovery_response(id: u16, service_name: &[u8], ttl: Duration) -> Vec<u8> {
     // Convert the TTL into seconds.
     let ttl = duration_to_secs(ttl);
 
     // This capacity was determined empirically.
-    let mut out = Vec::with_capacity(69);
+    let mut out = Vec::with_capacity(54 + service_name.len());
 
     append_u16(&mut out, id);
     // 0x84 flag for an answer.
@@ -178,12 +180,13 @@ pub fn build_service_discovery_response(id: u16, ttl: Duration) -> Vec<u8> {
     // Service name.
     {
         let mut name = Vec::new();
-        append_qname(&mut name, SERVICE_NAME);
+        append_qname(&mut name, service_name);
         append_u16(&mut out, name.len() as u16);
         out.extend_from_slice(&name);
     }
 
-    // Since the output size is constant, we reserve the right amount ahead of time.
+    // Since the output size only depends on the service name, we reserve the right amount ahead
+    // of time.
     // If this assert fails, adjust the capacity of `out` in the source code.
     debug_assert_eq!(out.capacity(), out.len());
     out
@@ -336,16 +339,24 @@ impl error::Error for MdnsResponseError {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::SERVICE_NAME;
     use dns_parser::Packet;
     use libp2p_core::identity;
     use std::time::Duration;
 
     #[test]
     fn build_query_correct() {
-        let query = build_query();
+        let query = build_query(SERVICE_NAME);
         assert!(Packet::parse(&query).is_ok());
     }
 
+    #[test]
+    fn build_query_custom_service_name() {
+        let query = build_query(b"_my-app._udp.local");
+        let packet = Packet::parse(&query).unwrap();
+        assert_eq!(packet.questions[0].qname.to_string(), "_my-app._udp.local");
+    }
+
     #[test]
     fn build_query_response_correct() {
         let my_peer_id = identity::Keypair::generate_ed25519().public().into_peer_id();
@@ -353,6 +364,7 @@ mod tests {
         let addr2 = "/ip6/::1/udp/10000".parse().unwrap();
         let query = build_query_response(
             0xf8f8,
+            SERVICE_NAME,
             my_peer_id,
             vec![addr1, addr2].into_iter(),
             Duration::from_secs(60),
@@ -363,7 +375,7 @@ mod tests {
 
     #[test]
     fn build_service_discovery_response_correct() {
-        let query = build_service_discovery_response(0x1234, Duration::from_secs(120));
+        let query = build_service_discovery_response(0x1234, SERVICE_NAME, Duration::from_secs(120));
         assert!(Packet::parse(&query).is_ok());
     }
 
@@ -29,14 +29,19 @@
 //! This crate provides the `Mdns` struct which implements the `NetworkBehaviour` trait. This
 //! struct will automatically discover other libp2p nodes on the local network.
 //!
+//! The service name, the query interval, the TTL of the records and whether we respond to
+//! queries can be customized with an `MdnsConfig`. Nodes using different service names don't
+//! discover each other.
+//!
 
-/// Hardcoded name of the mDNS service. Part of the mDNS libp2p specifications.
+/// Default name of the mDNS service. Part of the mDNS libp2p specifications. It can be changed
+/// with `MdnsConfig::with_service_name`.
 const SERVICE_NAME: &[u8] = b"_p2p._udp.local";
 /// Hardcoded name of the service used for DNS-SD.
 const META_QUERY_SERVICE: &[u8] = b"_services._dns-sd._udp.local";
 
 pub use self::behaviour::{Mdns, MdnsEvent};
-pub use self::service::MdnsService;
+pub use self::service::{MdnsConfig, MdnsService};
 
 mod behaviour;
 mod dns;
@@ -23,13 +23,131 @@ use dns_parser::{Packet, RData};
 use futures::{prelude::*, task};
 use libp2p_core::{Multiaddr, PeerId};
 use multiaddr::Protocol;
-use std::{fmt, io, net::Ipv4Addr, net::SocketAddr, str, time::Duration};
+use get_if_addrs::{IfAddr, get_if_addrs};
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use log::debug;
+use net2::UdpSocketExt;
+use smallvec::SmallVec;
+#[cfg(unix)]
+use std::ffi::CString;
+use std::{
+    collections::HashMap,
+    fmt,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
+    str,
+    time::Duration
+};
 use tokio_reactor::Handle;
 use wasm_timer::{Instant, Interval};
 use tokio_udp::UdpSocket;
 
 pub use dns::MdnsResponseError;
 
+/// The port used by mDNS.
+const MDNS_PORT: u16 = 5353;
+/// The IPv4 multicast address of mDNS.
+const MDNS_ADDR_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+/// The IPv6 multicast address of mDNS.
+const MDNS_ADDR_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+/// Interval at which we check whether interfaces went up or down.
+const IF_WATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Configuration of an `MdnsService` or of an `Mdns` behaviour.
+#[derive(Debug, Clone)]
+pub struct MdnsConfig {
+    /// Name of the service that we query and advertise.
+    service_name: Vec<u8>,
+    /// Interval between two queries.
+    query_interval: Duration,
+    /// TTL of the records we send in our responses.
+    ttl: Duration,
+    /// Whether we respond to the queries of other nodes.
+    respond: bool,
+}
+
+impl MdnsConfig {
+    /// Creates a new configuration for mDNS with the following default settings:
+    ///
+    ///   * service name of `_p2p._udp.local`, as per the libp2p mDNS specification
+    ///   * query interval of 20 seconds
+    ///   * record TTL of 5 minutes
+    ///   * responding to queries enabled
+    pub fn new() -> Self {
+        MdnsConfig {
+            service_name: SERVICE_NAME.to_vec(),
+            query_interval: Duration::from_secs(20),
+            ttl: Duration::from_secs(5 * 60),
+            respond: true,
+        }
+    }
+
+    /// Sets the name of the service to query and advertise, for example `_my-app._udp.local`.
+    ///
+    /// Only the nodes using the same service name discover each other, which isolates unrelated
+    /// networks sharing the same LAN.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the name isn't ASCII, or has an empty label or a label longer than 63 bytes.
+    pub fn with_service_name(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        assert!(name.is_ascii(), "The service name must be ASCII");
+        for label in name.split('.') {
+            assert!(!label.is_empty(), "The service name contains an empty label");
+            assert!(label.len() <= 63, "The service name has a label too long");
+        }
+        self.service_name = name.into_bytes();
+        self
+    }
+
+    /// Sets the interval between two queries.
+    pub fn with_query_interval(mut self, interval: Duration) -> Self {
+        self.query_interval = interval;
+        self
+    }
+
+    /// Sets the TTL of the records we send in our responses.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets whether we respond to the queries of other nodes. If disabled, the queries we
+    /// receive are ignored and other nodes can't discover us, although we can still discover
+    /// them.
+    pub fn with_responding(mut self, respond: bool) -> Self {
+        self.respond = respond;
+        self
+    }
+
+    /// Returns the name of the service that we query and advertise.
+    pub fn service_name(&self) -> &str {
+        str::from_utf8(&self.service_name).expect("the service name is always ASCII")
+    }
+
+    /// Returns the interval between two queries.
+    pub fn query_interval(&self) -> Duration {
+        self.query_interval
+    }
+
+    /// Returns the TTL of the records we send in our responses.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns whether we respond to the queries of other nodes.
+    pub fn responding(&self) -> bool {
+        self.respond
+    }
+}
+
+impl Default for MdnsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A running service that discovers libp2p peers and responds to other libp2p peers' queries on
 /// the local network.
 ///
@@ -49,6 +167,11 @@ pub use dns::MdnsResponseError;
 /// When you receive an `MdnsResponse`, use the provided methods to query the information received
 /// in the response.
 ///
+/// The service joins the mDNS multicast group, both over IPv4 (`224.0.0.251`) and IPv6
+/// (`ff02::fb`), on every interface of the host, and periodically checks for interfaces going up
+/// or down. Queries are answered on the interface they arrived on, and only with the addresses
+/// that are reachable from that interface.
+///
 /// # Example
 ///
 /// ```rust
@@ -92,207 +215,521 @@ pub use dns::MdnsResponseError;
 /// }).for_each(|_| Ok(()));
 /// # }
 pub struct MdnsService {
-    /// Main socket for listening.
-    socket: UdpSocket,
-    /// Socket for sending queries on the network.
-    query_socket: UdpSocket,
+    /// Socket receiving the multicast traffic of the IPv4 interfaces, if IPv4 is available.
+    socket_v4: Option<UdpSocket>,
+    /// Socket receiving the multicast traffic of the IPv6 interfaces, if IPv6 is available.
+    socket_v6: Option<UdpSocket>,
+    /// The interfaces we are active on, by address.
+    interfaces: HashMap<IpAddr, Interface>,
+    /// Name of the service that we query and advertise.
+    service_name: Vec<u8>,
+    /// Whether we respond to the queries of other nodes.
+    respond: bool,
     /// Interval for sending queries.
     query_interval: Interval,
+    /// Interval for checking whether interfaces went up or down.
+    if_watch: Interval,
     /// Whether we send queries on the network at all.
     /// Note that we still need to have an interval for querying, as we need to wake up the socket
     /// regularly to recover from errors. Otherwise we could simply use an `Option<Interval>`.
     silent: bool,
-    /// Buffer used for receiving data from the main socket.
+    /// Buffer used for receiving data from the sockets.
     recv_buffer: [u8; 2048],
-    /// Buffers pending to send on the main socket.
+}
+
+/// An address of a network interface, on which we send queries and responses.
+struct Interface {
+    /// Name of the interface.
+    name: String,
+    /// The network of the address.
+    net: IpNet,
+    /// Index of the interface, or 0 if unknown. Used to scope IPv6 multicast.
+    index: u32,
+    /// Socket bound to the address on the mDNS port, used to send packets on this interface.
+    /// It also receives the unicast packets sent to the address.
+    socket: UdpSocket,
+    /// Buffers pending to send on the socket.
     send_buffers: Vec<Vec<u8>>,
-    /// Buffers pending to send on the query socket.
-    query_send_buffers: Vec<Vec<u8>>,
+}
+
+impl Interface {
+    /// Creates the socket of an interface address.
+    fn new(name: String, net: IpNet, index: u32) -> io::Result<Interface> {
+        let socket = match net.addr() {
+            IpAddr::V4(ip) => {
+                let builder = net2::UdpBuilder::new_v4()?;
+                builder.reuse_address(true)?;
+                reuse_port(&builder)?;
+                let socket = builder.bind((ip, MDNS_PORT))?;
+                socket.set_multicast_if_v4(&ip)?;
+                socket.set_multicast_loop_v4(true)?;
+                socket.set_multicast_ttl_v4(255)?;
+                socket
+            }
+            IpAddr::V6(ip) => {
+                let builder = net2::UdpBuilder::new_v6()?;
+                builder.only_v6(true)?;
+                builder.reuse_address(true)?;
+                reuse_port(&builder)?;
+                let socket = builder.bind(SocketAddrV6::new(ip, MDNS_PORT, 0, index))?;
+                socket.set_multicast_if_v6(index)?;
+                socket.set_multicast_loop_v6(true)?;
+                socket
+            }
+        };
+
+        Ok(Interface {
+            name,
+            net,
+            index,
+            socket: UdpSocket::from_std(socket, &Handle::default())?,
+            send_buffers: Vec::new(),
+        })
+    }
+
+    /// Returns the multicast address to send packets to on this interface.
+    fn multicast_addr(&self) -> SocketAddr {
+        match self.net {
+            IpNet::V4(_) => SocketAddr::new(MDNS_ADDR_V4.into(), MDNS_PORT),
+            IpNet::V6(_) => SocketAddr::V6(SocketAddrV6::new(MDNS_ADDR_V6, MDNS_PORT, 0, self.index)),
+        }
+    }
+
+    /// Sends the pending buffers.
+    fn flush(&mut self) {
+        let dest = self.multicast_addr();
+        while !self.send_buffers.is_empty() {
+            let to_send = self.send_buffers.remove(0);
+            match self.socket.poll_send_to(&to_send, &dest) {
+                Ok(Async::Ready(bytes_written)) => {
+                    debug_assert_eq!(bytes_written, to_send.len());
+                }
+                Ok(Async::NotReady) => {
+                    self.send_buffers.insert(0, to_send);
+                    break;
+                }
+                Err(err) => {
+                    // Errors are non-fatal because they can happen for example if we lose
+                    // connection to the network.
+                    debug!("Failed to send mDNS packet on {}: {:?}", self.name, err);
+                    self.send_buffers.clear();
+                    break;
+                }
+            }
+        }
+    }
 }
 
 impl MdnsService {
-    /// Starts a new mDNS service.
+    /// Starts a new mDNS service with the default configuration.
     #[inline]
     pub fn new() -> io::Result<MdnsService> {
-        Self::new_inner(false)
+        Self::new_inner(MdnsConfig::default(), false)
     }
 
     /// Same as `new`, but we don't send automatically send queries on the network.
     #[inline]
     pub fn silent() -> io::Result<MdnsService> {
-        Self::new_inner(true)
+        Self::new_inner(MdnsConfig::default(), true)
+    }
+
+    /// Starts a new mDNS service with the given configuration.
+    #[inline]
+    pub fn with_config(config: MdnsConfig) -> io::Result<MdnsService> {
+        Self::new_inner(config, false)
     }
 
     /// Starts a new mDNS service.
-    fn new_inner(silent: bool) -> io::Result<MdnsService> {
-        let socket = {
-            #[cfg(unix)]
-            fn platform_specific(s: &net2::UdpBuilder) -> io::Result<()> {
-                net2::unix::UnixUdpBuilderExt::reuse_port(s, true)?;
-                Ok(())
-            }
-            #[cfg(not(unix))]
-            fn platform_specific(_: &net2::UdpBuilder) -> io::Result<()> { Ok(()) }
+    fn new_inner(config: MdnsConfig, silent: bool) -> io::Result<MdnsService> {
+        let socket_v4 = {
             let builder = net2::UdpBuilder::new_v4()?;
             builder.reuse_address(true)?;
-            platform_specific(&builder)?;
-            builder.bind(("0.0.0.0", 5353))?
+            reuse_port(&builder)?;
+            let socket = builder.bind((Ipv4Addr::UNSPECIFIED, MDNS_PORT))?;
+            let socket = UdpSocket::from_std(socket, &Handle::default())?;
+            socket.set_multicast_loop_v4(true)?;
+            socket.set_multicast_ttl_v4(255)?;
+            Ok(socket)
         };
 
-        let socket = UdpSocket::from_std(socket, &Handle::default())?;
-        socket.set_multicast_loop_v4(true)?;
-        socket.set_multicast_ttl_v4(255)?;
-        // TODO: correct interfaces?
-        socket.join_multicast_v4(&From::from([224, 0, 0, 251]), &Ipv4Addr::UNSPECIFIED)?;
+        let socket_v6 = (|| -> io::Result<UdpSocket> {
+            let builder = net2::UdpBuilder::new_v6()?;
+            builder.only_v6(true)?;
+            builder.reuse_address(true)?;
+            reuse_port(&builder)?;
+            let socket = builder.bind((Ipv6Addr::UNSPECIFIED, MDNS_PORT))?;
+            let socket = UdpSocket::from_std(socket, &Handle::default())?;
+            socket.set_multicast_loop_v6(true)?;
+            Ok(socket)
+        })();
+
+        let (socket_v4, socket_v6) = match (socket_v4, socket_v6) {
+            (Err(err), Err(_)) => return Err(err),
+            (v4, v6) => {
+                if let Err(ref err) = v4 {
+                    debug!("IPv4 unavailable for mDNS: {:?}", err);
+                }
+                if let Err(ref err) = v6 {
+                    debug!("IPv6 unavailable for mDNS: {:?}", err);
+                }
+                (v4.ok(), v6.ok())
+            }
+        };
 
-        Ok(MdnsService {
-            socket,
-            query_socket: UdpSocket::bind(&From::from(([0, 0, 0, 0], 0)))?,
-            query_interval: Interval::new(Instant::now(), Duration::from_secs(20)),
+        let mut service = MdnsService {
+            socket_v4,
+            socket_v6,
+            interfaces: HashMap::new(),
+            service_name: config.service_name,
+            respond: config.respond,
+            query_interval: Interval::new(Instant::now(), config.query_interval),
+            if_watch: Interval::new(Instant::now() + IF_WATCH_INTERVAL, IF_WATCH_INTERVAL),
             silent,
             recv_buffer: [0; 2048],
-            send_buffers: Vec::new(),
-            query_send_buffers: Vec::new(),
-        })
+        };
+        service.refresh_interfaces();
+        Ok(service)
     }
 
-    /// Polls the service for packets.
-    pub fn poll(&mut self) -> Async<MdnsPacket<'_>> {
-        // Send a query every time `query_interval` fires.
-        // Note that we don't use a loop here—it is pretty unlikely that we need it, and there is
-        // no point in sending multiple requests in a row.
-        match self.query_interval.poll() {
-            Ok(Async::Ready(_)) => {
-                if !self.silent {
-                    let query = dns::build_query();
-                    self.query_send_buffers.push(query.to_vec());
-                }
+    /// Compares the interfaces of the host with the ones we are active on, joins the multicast
+    /// group on the new ones and leaves it on the ones that went down.
+    fn refresh_interfaces(&mut self) {
+        let host_ifaces = match get_if_addrs() {
+            Ok(ifaces) => ifaces,
+            Err(err) => {
+                debug!("Failed to list the network interfaces: {:?}", err);
+                return;
             }
-            Ok(Async::NotReady) => (),
-            _ => unreachable!("A wasm_timer::Interval never errors"), // TODO: is that true?
         };
 
-        // Flush the send buffer of the main socket.
-        while !self.send_buffers.is_empty() {
-            let to_send = self.send_buffers.remove(0);
-            match self
-                .socket
-                .poll_send_to(&to_send, &From::from(([224, 0, 0, 251], 5353)))
-            {
-                Ok(Async::Ready(bytes_written)) => {
-                    debug_assert_eq!(bytes_written, to_send.len());
+        let mut current = HashMap::new();
+        for iface in host_ifaces {
+            let net = match iface.addr {
+                IfAddr::V4(ref ip4) if self.socket_v4.is_some() => {
+                    let prefix_len = (!u32::from_be_bytes(ip4.netmask.octets())).leading_zeros();
+                    let ipnet = Ipv4Net::new(ip4.ip, prefix_len as u8)
+                        .expect("prefix_len is the number of bits in a u32, so can not exceed 32");
+                    IpNet::V4(ipnet)
                 }
-                Ok(Async::NotReady) => {
-                    self.send_buffers.insert(0, to_send);
-                    break;
+                IfAddr::V6(ref ip6) if self.socket_v6.is_some() => {
+                    let prefix_len = (!u128::from_be_bytes(ip6.netmask.octets())).leading_zeros();
+                    let ipnet = Ipv6Net::new(ip6.ip, prefix_len as u8)
+                        .expect("prefix_len is the number of bits in a u128, so can not exceed 128");
+                    IpNet::V6(ipnet)
                 }
-                Err(_) => {
-                    // Errors are non-fatal because they can happen for example if we lose
-                    // connection to the network.
-                    self.send_buffers.clear();
-                    break;
+                _ => continue,
+            };
+            current.insert(iface.ip(), (iface.name, net));
+        }
+
+        // Leave the multicast group on the interfaces that went down.
+        let removed = self.interfaces.keys()
+            .filter(|ip| !current.contains_key(ip))
+            .cloned()
+            .collect::<Vec<_>>();
+        for ip in removed {
+            let iface = match self.interfaces.remove(&ip) {
+                Some(iface) => iface,
+                None => continue,
+            };
+            debug!("Interface {} ({}) is down", iface.name, ip);
+            let result = match ip {
+                IpAddr::V4(ip) => self.socket_v4.as_ref()
+                    .map(|s| s.leave_multicast_v4(&MDNS_ADDR_V4, &ip)),
+                IpAddr::V6(_) => {
+                    // The IPv6 membership is per interface, not per address.
+                    let still_up = self.interfaces.values()
+                        .any(|i| i.index == iface.index && i.net.addr().is_ipv6());
+                    if still_up {
+                        None
+                    } else {
+                        self.socket_v6.as_ref()
+                            .map(|s| s.leave_multicast_v6(&MDNS_ADDR_V6, iface.index))
+                    }
                 }
+            };
+            if let Some(Err(err)) = result {
+                debug!("Failed to leave the mDNS multicast group on {}: {:?}", iface.name, err);
             }
         }
 
-        // Flush the query send buffer.
-        // This has to be after the push to `query_send_buffers`.
-        while !self.query_send_buffers.is_empty() {
-            let to_send = self.query_send_buffers.remove(0);
-            match self
-                .query_socket
-                .poll_send_to(&to_send, &From::from(([224, 0, 0, 251], 5353)))
-            {
-                Ok(Async::Ready(bytes_written)) => {
-                    debug_assert_eq!(bytes_written, to_send.len());
+        // Join the multicast group on the new interfaces.
+        for (ip, (name, net)) in current {
+            if self.interfaces.contains_key(&ip) {
+                continue;
+            }
+
+            let index = interface_index(&name);
+            let joined = match ip {
+                IpAddr::V4(ip) => self.socket_v4.as_ref()
+                    .map_or(Ok(()), |s| s.join_multicast_v4(&MDNS_ADDR_V4, &ip)),
+                IpAddr::V6(_) => {
+                    let already_joined = self.interfaces.values()
+                        .any(|i| i.index == index && i.net.addr().is_ipv6());
+                    if already_joined {
+                        Ok(())
+                    } else {
+                        self.socket_v6.as_ref()
+                            .map_or(Ok(()), |s| s.join_multicast_v6(&MDNS_ADDR_V6, index))
+                    }
                 }
-                Ok(Async::NotReady) => {
-                    self.query_send_buffers.insert(0, to_send);
-                    break;
+            };
+            if let Err(err) = joined {
+                debug!("Failed to join the mDNS multicast group on {} ({}): {:?}", name, ip, err);
+                continue;
+            }
+
+            match Interface::new(name, net, index) {
+                Ok(mut iface) => {
+                    debug!("Interface {} ({}) is up", iface.name, ip);
+                    if !self.silent {
+                        iface.send_buffers.push(dns::build_query(&self.service_name));
+                    }
+                    self.interfaces.insert(ip, iface);
                 }
+                Err(err) => debug!("Failed to bind an mDNS socket on {}: {:?}", ip, err),
+            }
+        }
+    }
+
+    /// Returns the name, network and index of each interface address we are active on.
+    fn interface_addrs(&self) -> impl Iterator<Item = (&str, IpNet, u32)> + Clone {
+        self.interfaces.values().map(|i| (i.name.as_str(), i.net, i.index))
+    }
+
+    /// Returns the address of the interface a packet sent by `from` arrived on.
+    fn interface_of(&self, from: &SocketAddr) -> Option<IpAddr> {
+        interface_of(self.interface_addrs(), from)
+    }
+
+    /// Returns the networks of the interface owning the given address.
+    fn networks_of(&self, ip: &IpAddr) -> SmallVec<[IpNet; 4]> {
+        networks_of(self.interface_addrs(), ip)
+    }
+
+    /// Receives a packet on any of the sockets, if one is available.
+    fn recv(&mut self) -> Option<(usize, SocketAddr)> {
+        let recv_buffer = &mut self.recv_buffer;
+        let sockets = self.socket_v4.iter_mut()
+            .chain(self.socket_v6.iter_mut())
+            .chain(self.interfaces.values_mut().map(|i| &mut i.socket));
+        for socket in sockets {
+            match socket.poll_recv_from(recv_buffer) {
+                Ok(Async::Ready(received)) => return Some(received),
+                Ok(Async::NotReady) => (),
                 Err(_) => {
-                    // Errors are non-fatal because they can happen for example if we lose
-                    // connection to the network.
-                    self.query_send_buffers.clear();
-                    break;
+                    // Error are non-fatal and can happen if we get disconnected from example.
+                    // The query interval will wake up the task at some point so that we can try
+                    // again.
                 }
             }
         }
+        None
+    }
 
-        // Check for any incoming packet.
-        match self.socket.poll_recv_from(&mut self.recv_buffer) {
-            Ok(Async::Ready((len, from))) => {
-                match Packet::parse(&self.recv_buffer[..len]) {
-                    Ok(packet) => {
-                        if packet.header.query {
-                            if packet
-                                .questions
-                                .iter()
-                                .any(|q| q.qname.to_string().as_bytes() == SERVICE_NAME)
-                            {
-                                return Async::Ready(MdnsPacket::Query(MdnsQuery {
-                                    from,
-                                    query_id: packet.header.id,
-                                    send_buffers: &mut self.send_buffers,
-                                }));
-                            } else if packet
-                                .questions
-                                .iter()
-                                .any(|q| q.qname.to_string().as_bytes() == META_QUERY_SERVICE)
-                            {
-                                // TODO: what if multiple questions, one with SERVICE_NAME and one with META_QUERY_SERVICE?
-                                return Async::Ready(MdnsPacket::ServiceDiscovery(
-                                    MdnsServiceDiscovery {
-                                        from,
-                                        query_id: packet.header.id,
-                                        send_buffers: &mut self.send_buffers,
-                                    },
-                                ));
-                            } else {
-                                // Note that ideally we would use a loop instead. However as of the
-                                // writing of this code non-lexical lifetimes haven't been merged
-                                // yet, and I can't manage to write this code without having borrow
-                                // issues.
-                                task::current().notify();
-                                return Async::NotReady;
-                            }
-                        } else {
-                            return Async::Ready(MdnsPacket::Response(MdnsResponse {
-                                packet,
-                                from,
-                            }));
-                        }
-                    }
-                    Err(_) => {
-                        // Ignore errors while parsing the packet. We need to poll again for the
-                        // next packet.
-                        // Note that ideally we would use a loop instead. However as of the writing
-                        // of this code non-lexical lifetimes haven't been merged yet, and I can't
-                        // manage to write this code without having borrow issues.
-                        task::current().notify();
-                        return Async::NotReady;
+    /// Polls the service for packets.
+    pub fn poll(&mut self) -> Async<MdnsPacket<'_>> {
+        // Check for interfaces going up or down.
+        match self.if_watch.poll() {
+            Ok(Async::Ready(_)) => self.refresh_interfaces(),
+            Ok(Async::NotReady) => (),
+            _ => unreachable!("A wasm_timer::Interval never errors"),
+        };
+
+        // Send a query every time `query_interval` fires.
+        // Note that we don't use a loop here—it is pretty unlikely that we need it, and there is
+        // no point in sending multiple requests in a row.
+        match self.query_interval.poll() {
+            Ok(Async::Ready(_)) => {
+                if !self.silent {
+                    let query = dns::build_query(&self.service_name);
+                    for iface in self.interfaces.values_mut() {
+                        iface.send_buffers.push(query.clone());
                     }
                 }
             }
             Ok(Async::NotReady) => (),
+            _ => unreachable!("A wasm_timer::Interval never errors"), // TODO: is that true?
+        };
+
+        // Flush the send buffers of the interfaces.
+        for iface in self.interfaces.values_mut() {
+            iface.flush();
+        }
+
+        // Check for any incoming packet.
+        let (len, from) = match self.recv() {
+            Some(received) => received,
+            None => return Async::NotReady,
+        };
+
+        let packet = match Packet::parse(&self.recv_buffer[..len]) {
+            Ok(packet) => packet,
             Err(_) => {
-                // Error are non-fatal and can happen if we get disconnected from example.
-                // The query interval will wake up the task at some point so that we can try again.
+                // Ignore errors while parsing the packet. We need to poll again for the
+                // next packet.
+                task::current().notify();
+                return Async::NotReady;
             }
         };
 
-        Async::NotReady
+        if !packet.header.query {
+            return Async::Ready(MdnsPacket::Response(MdnsResponse {
+                packet,
+                from,
+                service_name: &self.service_name,
+            }));
+        }
+
+        if !self.respond {
+            task::current().notify();
+            return Async::NotReady;
+        }
+
+        // Queries are answered on the interface they arrived on.
+        let iface_ip = match self.interface_of(&from) {
+            Some(ip) => ip,
+            None => {
+                debug!("Ignoring mDNS query from {}, which is not on any of our interfaces", from);
+                task::current().notify();
+                return Async::NotReady;
+            }
+        };
+        let networks = self.networks_of(&iface_ip);
+        let send_buffers = match self.interfaces.get_mut(&iface_ip) {
+            Some(iface) => &mut iface.send_buffers,
+            None => unreachable!("interface_of only returns addresses of known interfaces; qed"),
+        };
+
+        if packet
+            .questions
+            .iter()
+            .any(|q| q.qname.to_string().as_bytes() == &self.service_name[..])
+        {
+            Async::Ready(MdnsPacket::Query(MdnsQuery {
+                from,
+                query_id: packet.header.id,
+                service_name: &self.service_name,
+                networks,
+                send_buffers,
+            }))
+        } else if packet
+            .questions
+            .iter()
+            .any(|q| q.qname.to_string().as_bytes() == META_QUERY_SERVICE)
+        {
+            // TODO: what if multiple questions, one with the service name and one with META_QUERY_SERVICE?
+            Async::Ready(MdnsPacket::ServiceDiscovery(
+                MdnsServiceDiscovery {
+                    from,
+                    query_id: packet.header.id,
+                    service_name: &self.service_name,
+                    send_buffers,
+                },
+            ))
+        } else {
+            task::current().notify();
+            Async::NotReady
+        }
     }
 }
 
 impl fmt::Debug for MdnsService {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("MdnsService")
+            .field("service_name", &String::from_utf8_lossy(&self.service_name))
+            .field("respond", &self.respond)
             .field("silent", &self.silent)
+            .field("interfaces", &self.interfaces.keys().collect::<Vec<_>>())
             .finish()
     }
 }
 
+/// Enables `SO_REUSEPORT` on platforms that support it, so that several services can share the
+/// mDNS port.
+#[cfg(unix)]
+fn reuse_port(builder: &net2::UdpBuilder) -> io::Result<()> {
+    net2::unix::UnixUdpBuilderExt::reuse_port(builder, true)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reuse_port(_: &net2::UdpBuilder) -> io::Result<()> {
+    Ok(())
+}
+
+/// Returns the index of the interface with the given name, or 0 if unknown.
+#[cfg(unix)]
+fn interface_index(name: &str) -> u32 {
+    match CString::new(name) {
+        Ok(name) => unsafe { libc::if_nametoindex(name.as_ptr()) },
+        Err(_) => 0,
+    }
+}
+
+#[cfg(not(unix))]
+fn interface_index(_: &str) -> u32 {
+    0
+}
+
+/// Returns `true` if `addr` is an IP address belonging to one of `networks`.
+fn is_reachable(networks: &[IpNet], addr: &Multiaddr) -> bool {
+    let ip = match addr.iter().next() {
+        Some(Protocol::Ip4(ip)) => IpAddr::V4(ip),
+        Some(Protocol::Ip6(ip)) => IpAddr::V6(ip),
+        _ => return false,
+    };
+    networks.iter().any(|net| net.contains(&ip))
+}
+
+/// Returns the interface address, among `interfaces`, that a packet sent by `from` arrived on.
+///
+/// A packet with an IPv6 scope arrived on the interface of that index, otherwise on one whose
+/// network contains the sender. If several addresses match, the one whose network contains the
+/// sender with the longest prefix is picked, then the lowest address, so that the result does not
+/// depend on the order of `interfaces`.
+fn interface_of<'a>(
+    interfaces: impl Iterator<Item = (&'a str, IpNet, u32)>,
+    from: &SocketAddr
+) -> Option<IpAddr> {
+    let scope_id = match from {
+        SocketAddr::V6(from) => from.scope_id(),
+        SocketAddr::V4(_) => 0,
+    };
+    let from_ip = from.ip();
+
+    interfaces
+        .filter(|(_, net, index)| {
+            if scope_id != 0 {
+                net.addr().is_ipv6() && *index == scope_id
+            } else {
+                net.contains(&from_ip)
+            }
+        })
+        .map(|(_, net, _)| net)
+        .max_by(|a, b| {
+            a.contains(&from_ip).cmp(&b.contains(&from_ip))
+                .then(a.prefix_len().cmp(&b.prefix_len()))
+                .then(b.addr().cmp(&a.addr()))
+        })
+        .map(|net| net.addr())
+}
+
+/// Returns the networks of the interface owning `ip`, among `interfaces`.
+fn networks_of<'a>(
+    interfaces: impl Iterator<Item = (&'a str, IpNet, u32)> + Clone,
+    ip: &IpAddr
+) -> SmallVec<[IpNet; 4]> {
+    let name = match interfaces.clone().find(|(_, net, _)| &net.addr() == ip) {
+        Some((name, _, _)) => name,
+        None => return SmallVec::new(),
+    };
+    interfaces
+        .filter(|(n, _, _)| *n == name)
+        .map(|(_, net, _)| net)
+        .collect()
+}
+
 /// A valid mDNS packet received by the service.
 #[derive(Debug)]
 pub enum MdnsPacket<'a> {
@@ -310,14 +747,20 @@ pub struct MdnsQuery<'a> {
     from: SocketAddr,
     /// Id of the received DNS query. We need to pass this ID back in the results.
     query_id: u16,
-    /// Queue of pending buffers.
+    /// Name of the service we advertise.
+    service_name: &'a [u8],
+    /// Networks of the interface the query arrived on.
+    networks: SmallVec<[IpNet; 4]>,
+    /// Queue of pending buffers of the interface the query arrived on.
     send_buffers: &'a mut Vec<Vec<u8>>,
 }
 
 impl<'a> MdnsQuery<'a> {
     /// Respond to the query.
     ///
-    /// Pass the ID of the local peer, and the list of addresses we're listening on.
+    /// Pass the ID of the local peer, and the list of addresses we're listening on. Only the
+    /// addresses that belong to the networks of the interface the query arrived on are sent,
+    /// since the other ones are not reachable by the remote.
     ///
     /// If there are more than 2^16-1 addresses, ignores the others.
     ///
@@ -331,10 +774,13 @@ impl<'a> MdnsQuery<'a> {
     ) -> Result<(), MdnsResponseError>
     where
         TAddresses: IntoIterator<Item = Multiaddr>,
-        TAddresses::IntoIter: ExactSizeIterator,
     {
+        let networks = &self.networks;
+        let addresses = addresses.into_iter()
+            .filter(|addr| is_reachable(networks, addr))
+            .collect::<Vec<_>>();
         let response =
-            dns::build_query_response(self.query_id, peer_id, addresses.into_iter(), ttl)?;
+            dns::build_query_response(self.query_id, self.service_name, peer_id, addresses.into_iter(), ttl)?;
         self.send_buffers.push(response);
         Ok(())
     }
@@ -351,6 +797,7 @@ impl<'a> fmt::Debug for MdnsQuery<'a> {
         f.debug_struct("MdnsQuery")
             .field("from", self.remote_addr())
             .field("query_id", &self.query_id)
+            .field("networks", &self.networks)
             .finish()
     }
 }
@@ -361,7 +808,9 @@ pub struct MdnsServiceDiscovery<'a> {
     from: SocketAddr,
     /// Id of the received DNS query. We need to pass this ID back in the results.
     query_id: u16,
-    /// Queue of pending buffers.
+    /// Name of the service we advertise.
+    service_name: &'a [u8],
+    /// Queue of pending buffers of the interface the query arrived on.
     send_buffers: &'a mut Vec<Vec<u8>>,
 }
 
@@ -369,7 +818,7 @@ impl<'a> MdnsServiceDiscovery<'a> {
     /// Respond to the query.
     #[inline]
     pub fn respond(self, ttl: Duration) {
-        let response = dns::build_service_discovery_response(self.query_id, ttl);
+        let response = dns::build_service_discovery_response(self.query_id, self.service_name, ttl);
         self.send_buffers.push(response);
     }
 
@@ -393,6 +842,8 @@ impl<'a> fmt::Debug for MdnsServiceDiscovery<'a> {
 pub struct MdnsResponse<'a> {
     packet: Packet<'a>,
     from: SocketAddr,
+    /// Name of the service we are looking for.
+    service_name: &'a [u8],
 }
 
 impl<'a> MdnsResponse<'a> {
@@ -401,8 +852,9 @@ impl<'a> MdnsResponse<'a> {
     /// > **Note**: Keep in mind that this will also contain the responses we sent ourselves.
     pub fn discovered_peers<'b>(&'b self) -> impl Iterator<Item = MdnsPeer<'b>> {
         let packet = &self.packet;
+        let service_name = self.service_name;
         self.packet.answers.iter().filter_map(move |record| {
-            if record.name.to_string().as_bytes() != SERVICE_NAME {
+            if record.name.to_string().as_bytes() != service_name {
                 return None;
             }
 
@@ -417,7 +869,7 @@ impl<'a> MdnsResponse<'a> {
                     Some(n) => n.to_owned(),
                     None => return None,
                 };
-                if iter.next().map(|v| v.as_bytes()) != Some(SERVICE_NAME) {
+                if iter.next().map(|v| v.as_bytes()) != Some(service_name) {
                     return None;
                 }
                 name
@@ -537,14 +989,32 @@ impl<'a> fmt::Debug for MdnsPeer<'a> {
 
 #[cfg(test)]
 mod tests {
+    use ipnet::IpNet;
     use libp2p_core::PeerId;
-    use std::{io, time::Duration};
+    use std::{io, net::{SocketAddr, SocketAddrV6}, time::Duration};
     use tokio::{self, prelude::*};
-    use crate::service::{MdnsPacket, MdnsService};
+    use crate::service::{MdnsConfig, MdnsPacket, MdnsService, interface_of, is_reachable, networks_of};
 
     #[test]
     fn discover_ourselves() {
-        let mut service = MdnsService::new().unwrap();
+        discover(MdnsService::new().unwrap());
+    }
+
+    #[test]
+    fn discover_ourselves_custom_service_name() {
+        let config = MdnsConfig::new()
+            .with_service_name("_libp2p-mdns-test._udp.local")
+            .with_query_interval(Duration::from_secs(5));
+        discover(MdnsService::with_config(config).unwrap());
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_service_name() {
+        MdnsConfig::new().with_service_name("_p2p.._udp.local");
+    }
+
+    fn discover(mut service: MdnsService) {
         let peer_id = PeerId::random();
         let stream = stream::poll_fn(move || -> Poll<Option<()>, io::Error> {
             loop {
@@ -575,4 +1045,68 @@ mod tests {
                 .for_each(|_| Ok(())),
         );
     }
+
+    #[test]
+    fn only_reachable_addresses() {
+        let networks: [IpNet; 2] = ["192.168.1.12/24".parse().unwrap(), "fe80::1/64".parse().unwrap()];
+
+        let reachable = ["/ip4/192.168.1.12/tcp/4001", "/ip6/fe80::1/tcp/4001"];
+        for addr in reachable.iter() {
+            assert!(is_reachable(&networks, &addr.parse().unwrap()));
+        }
+
+        let unreachable = ["/ip4/10.0.0.3/tcp/4001", "/ip6/2001:db8::1/tcp/4001", "/dns4/example.com/tcp/4001"];
+        for addr in unreachable.iter() {
+            assert!(!is_reachable(&networks, &addr.parse().unwrap()));
+        }
+    }
+
+    #[test]
+    fn packets_are_attributed_to_their_interface() {
+        let eth0 = ["192.168.1.12/24".parse::<IpNet>().unwrap(), "fe80::1/64".parse().unwrap()];
+        let eth1 = ["10.0.0.2/8".parse::<IpNet>().unwrap(), "10.1.0.2/16".parse().unwrap()];
+        let interfaces = eth0.iter().map(|net| ("eth0", *net, 2))
+            .chain(eth1.iter().map(|net| ("eth1", *net, 3)))
+            .collect::<Vec<_>>();
+
+        let cases: [(SocketAddr, Option<&str>); 5] = [
+            ("192.168.1.30:5353".parse().unwrap(), Some("192.168.1.12")),
+            // The most specific network wins.
+            ("10.1.4.4:5353".parse().unwrap(), Some("10.1.0.2")),
+            ("10.2.4.4:5353".parse().unwrap(), Some("10.0.0.2")),
+            (SocketAddrV6::new("fe80::2".parse().unwrap(), 5353, 0, 2).into(), Some("fe80::1")),
+            ("172.16.0.1:5353".parse().unwrap(), None),
+        ];
+
+        for (from, expected) in cases.iter() {
+            let expected = expected.map(|ip| ip.parse().unwrap());
+            assert_eq!(interface_of(interfaces.iter().cloned(), from), expected);
+            // The order of the interfaces doesn't matter.
+            assert_eq!(interface_of(interfaces.iter().rev().cloned(), from), expected);
+        }
+
+        // A scoped IPv6 packet arrived on the interface of that index, even if no network of
+        // the interface contains the sender.
+        let from = SocketAddrV6::new("fe80:1::2".parse().unwrap(), 5353, 0, 2).into();
+        assert_eq!(interface_of(interfaces.iter().cloned(), &from), Some("fe80::1".parse().unwrap()));
+        let from = SocketAddrV6::new("fe80::2".parse().unwrap(), 5353, 0, 5).into();
+        assert_eq!(interface_of(interfaces.iter().cloned(), &from), None);
+    }
+
+    #[test]
+    fn networks_are_tracked_per_interface() {
+        let interfaces = [
+            ("eth0", "192.168.1.12/24".parse::<IpNet>().unwrap(), 2),
+            ("eth0", "fe80::1/64".parse().unwrap(), 2),
+            ("eth1", "10.0.0.2/8".parse().unwrap(), 3),
+        ];
+
+        let networks = networks_of(interfaces.iter().cloned(), &"fe80::1".parse().unwrap());
+        assert_eq!(&networks[..], &[interfaces[0].1, interfaces[1].1][..]);
+
+        let networks = networks_of(interfaces.iter().cloned(), &"10.0.0.2".parse().unwrap());
+        assert_eq!(&networks[..], &[interfaces[2].1][..]);
+
+        assert!(networks_of(interfaces.iter().cloned(), &"10.0.0.3".parse().unwrap()).is_empty());
+    }
 }
@@ -0,0 +1,132 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Encoding of the metrics of a `Registry` in the Prometheus text format.
+
+use crate::Registry;
+use std::fmt::Write;
+
+/// The type of a metric, as reported on the `# TYPE` line of the text format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    Counter,
+    Histogram,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Histogram => "histogram",
+        }
+    }
+}
+
+/// A metric that can be encoded in the Prometheus text format.
+pub trait EncodeMetric: Send + Sync {
+    /// Returns the type of the metric.
+    fn metric_type(&self) -> MetricType;
+
+    /// Appends the samples of the metric to `out`, one per line, with the given name and labels.
+    fn encode(&self, name: &str, labels: &[(&str, &str)], out: &mut String);
+}
+
+/// Renders all the metrics of the registry in the Prometheus text format.
+pub fn encode(registry: &Registry) -> String {
+    let mut out = String::new();
+    for descriptor in registry.metrics.lock().iter() {
+        let _ = writeln!(out, "# HELP {} {}", descriptor.name, escape(&descriptor.help, false));
+        let _ = writeln!(out, "# TYPE {} {}", descriptor.name, descriptor.metric.metric_type().as_str());
+        descriptor.metric.encode(&descriptor.name, &[], &mut out);
+    }
+    out
+}
+
+/// Appends one sample line to `out`.
+pub(crate) fn encode_sample(
+    name: &str,
+    labels: &[(&str, &str)],
+    extra_label: Option<(&str, &str)>,
+    value: &str,
+    out: &mut String
+) {
+    out.push_str(name);
+    let mut labels = labels.iter().cloned().chain(extra_label).peekable();
+    if labels.peek().is_some() {
+        out.push('{');
+        for (i, (label, label_value)) in labels.enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}=\"{}\"", label, escape(label_value, true));
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+/// Escapes a help text or a label value.
+fn escape(s: &str, label_value: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if label_value => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Counter, Family, Registry, encode};
+
+    #[test]
+    fn encode_family_with_escaped_labels() {
+        let registry = Registry::new();
+        let family = Family::<Counter>::new(&["protocol", "direction"]);
+        registry.register("negotiations_total", "Negotiated\nsubstreams", family.clone());
+
+        family.get_or_create(&["/ipfs/ping/1.0.0", "inbound"]).inc();
+        family.get_or_create(&["/ipfs/ping/1.0.0", "inbound"]).inc();
+        family.get_or_create(&["/a\"b", "outbound"]).inc_by(3);
+
+        assert_eq!(encode(&registry), "# HELP negotiations_total Negotiated\\nsubstreams\n\
+            # TYPE negotiations_total counter\n\
+            negotiations_total{protocol=\"/a\\\"b\",direction=\"outbound\"} 3\n\
+            negotiations_total{protocol=\"/ipfs/ping/1.0.0\",direction=\"inbound\"} 2\n");
+    }
+
+    #[test]
+    fn registering_replaces_existing_metric() {
+        let registry = Registry::new();
+        let first = Counter::new();
+        first.inc();
+        registry.register("requests_total", "Requests", first);
+        registry.register("requests_total", "Requests", Counter::new());
+
+        assert_eq!(encode(&registry), "# HELP requests_total Requests\n\
+            # TYPE requests_total counter\n\
+            requests_total 0\n");
+    }
+}
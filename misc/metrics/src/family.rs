@@ -0,0 +1,100 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use crate::encoding::{EncodeMetric, MetricType};
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+/// A set of metrics of the same type, each identified by the values of a fixed set of labels.
+///
+/// For example, a family of counters with a `protocol` label counts events per protocol.
+#[derive(Clone)]
+pub struct Family<M> {
+    label_names: &'static [&'static str],
+    metrics: Arc<Mutex<BTreeMap<Vec<String>, M>>>,
+    constructor: Arc<dyn Fn() -> M + Send + Sync>,
+}
+
+impl<M: Default + 'static> Family<M> {
+    /// Creates an empty family with the given label names, whose metrics are created with
+    /// `M::default()`.
+    pub fn new(label_names: &'static [&'static str]) -> Self {
+        Family::with_constructor(label_names, M::default)
+    }
+}
+
+impl<M> Family<M> {
+    /// Creates an empty family with the given label names, whose metrics are created with the
+    /// given function, e.g. to create histograms with specific buckets.
+    pub fn with_constructor(
+        label_names: &'static [&'static str],
+        constructor: impl Fn() -> M + Send + Sync + 'static
+    ) -> Self {
+        Family {
+            label_names,
+            metrics: Arc::new(Mutex::new(BTreeMap::new())),
+            constructor: Arc::new(constructor),
+        }
+    }
+}
+
+impl<M: Clone> Family<M> {
+    /// Returns the metric identified by the given label values, creating it if necessary.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the number of label values doesn't match the number of label names.
+    pub fn get_or_create(&self, label_values: &[&str]) -> M {
+        assert_eq!(label_values.len(), self.label_names.len(),
+            "Expected one value for each of the labels {:?}", self.label_names);
+
+        let key = label_values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        self.metrics.lock()
+            .entry(key)
+            .or_insert_with(|| (self.constructor)())
+            .clone()
+    }
+}
+
+impl<M> fmt::Debug for Family<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Family")
+            .field("label_names", &self.label_names)
+            .finish()
+    }
+}
+
+impl<M> EncodeMetric for Family<M>
+where
+    M: EncodeMetric + Send
+{
+    fn metric_type(&self) -> MetricType {
+        (self.constructor)().metric_type()
+    }
+
+    fn encode(&self, name: &str, labels: &[(&str, &str)], out: &mut String) {
+        for (values, metric) in self.metrics.lock().iter() {
+            let mut all_labels = labels.to_vec();
+            all_labels.extend(self.label_names.iter().cloned().zip(values.iter().map(|v| v.as_str())));
+            metric.encode(name, &all_labels, out);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use crate::encoding::{EncodeMetric, MetricType, encode_sample};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// A histogram, which counts observed values in buckets.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// The upper bounds of the buckets, in increasing order.
+    bounds: Vec<f64>,
+    /// For each bucket, the number of observed values that fall in it and not in the previous
+    /// buckets. The last element counts the values greater than all the bounds.
+    counts: Vec<u64>,
+    /// The sum of the observed values.
+    sum: f64,
+}
+
+impl Histogram {
+    /// Creates a histogram with the given bucket upper bounds.
+    ///
+    /// The bounds are sorted and deduplicated. A final `+Inf` bucket is implicit.
+    pub fn new(bounds: impl IntoIterator<Item = f64>) -> Self {
+        let mut bounds: Vec<f64> = bounds.into_iter().filter(|b| !b.is_nan()).collect();
+        bounds.sort_by(|a, b| a.partial_cmp(b).expect("NaN have been filtered out; QED"));
+        bounds.dedup();
+        let counts = vec![0; bounds.len() + 1];
+
+        Histogram {
+            inner: Arc::new(Mutex::new(Inner { bounds, counts, sum: 0.0 })),
+        }
+    }
+
+    /// Records an observed value.
+    pub fn observe(&self, value: f64) {
+        let mut inner = self.inner.lock();
+        let bucket = inner.bounds.iter()
+            .position(|b| value <= *b)
+            .unwrap_or(inner.bounds.len());
+        inner.counts[bucket] += 1;
+        inner.sum += value;
+    }
+
+    /// Returns the number of observed values.
+    pub fn count(&self) -> u64 {
+        self.inner.lock().counts.iter().sum()
+    }
+
+    /// Returns the sum of the observed values.
+    pub fn sum(&self) -> f64 {
+        self.inner.lock().sum
+    }
+}
+
+/// Returns `count` bucket bounds, the first one being `start` and each other being `factor`
+/// times the previous one.
+pub fn exponential_buckets(start: f64, factor: f64, count: usize) -> Vec<f64> {
+    let mut bounds = Vec::with_capacity(count);
+    let mut bound = start;
+    for _ in 0 .. count {
+        bounds.push(bound);
+        bound *= factor;
+    }
+    bounds
+}
+
+impl EncodeMetric for Histogram {
+    fn metric_type(&self) -> MetricType {
+        MetricType::Histogram
+    }
+
+    fn encode(&self, name: &str, labels: &[(&str, &str)], out: &mut String) {
+        let inner = self.inner.lock();
+        let bucket_name = format!("{}_bucket", name);
+
+        let mut cumulative = 0;
+        for (bound, count) in inner.bounds.iter().zip(inner.counts.iter()) {
+            cumulative += count;
+            let le = bound.to_string();
+            encode_sample(&bucket_name, labels, Some(("le", le.as_str())), &cumulative.to_string(), out);
+        }
+        cumulative += inner.counts.last().expect("There is always the +Inf bucket; QED");
+        encode_sample(&bucket_name, labels, Some(("le", "+Inf")), &cumulative.to_string(), out);
+
+        encode_sample(&format!("{}_sum", name), labels, None, &inner.sum.to_string(), out);
+        encode_sample(&format!("{}_count", name), labels, None, &cumulative.to_string(), out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn observe_in_buckets() {
+        let histogram = Histogram::new(vec![1.0, 0.5, 2.0]);
+        for value in &[0.25, 0.5, 0.75, 3.0] {
+            histogram.observe(*value);
+        }
+        assert_eq!(histogram.count(), 4);
+
+        let mut out = String::new();
+        histogram.encode("latency", &[], &mut out);
+        assert_eq!(out, "latency_bucket{le=\"0.5\"} 2\n\
+                         latency_bucket{le=\"1\"} 3\n\
+                         latency_bucket{le=\"2\"} 3\n\
+                         latency_bucket{le=\"+Inf\"} 4\n\
+                         latency_sum 4.5\n\
+                         latency_count 4\n");
+    }
+
+    #[test]
+    fn exponential() {
+        assert_eq!(exponential_buckets(0.5, 2.0, 4), vec![0.5, 1.0, 2.0, 4.0]);
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Metrics of libp2p, and their encoding in the [Prometheus text format].
+//!
+//! A [`Registry`] holds named metrics. The components of libp2p that support metrics, such as
+//! the `Swarm`, `Kademlia` or `Floodsub`, register their metrics in a registry they are given
+//! and update them as they run. The application can register its own metrics in the same
+//! registry, and renders all of them with [`encode`], e.g. to serve them from an HTTP endpoint
+//! scraped by Prometheus.
+//!
+//! The metrics are cheap handles that can be cloned: all the clones of a metric share the same
+//! value.
+//!
+//! # Example
+//!
+//! ```
+//! use libp2p_metrics::{Counter, Registry, encode};
+//!
+//! let registry = Registry::new();
+//! let counter = Counter::new();
+//! registry.register("requests_total", "Number of requests", counter.clone());
+//! counter.inc();
+//!
+//! assert_eq!(encode(&registry), "# HELP requests_total Number of requests\n\
+//!                                # TYPE requests_total counter\n\
+//!                                requests_total 1\n");
+//! ```
+//!
+//! [Prometheus text format]: https://prometheus.io/docs/instrumenting/exposition_formats/
+
+mod counter;
+mod encoding;
+mod family;
+mod histogram;
+
+pub use counter::Counter;
+pub use encoding::{EncodeMetric, MetricType, encode};
+pub use family::Family;
+pub use histogram::{Histogram, exponential_buckets};
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// A set of named metrics.
+///
+/// Cloning a `Registry` is cheap, and all the clones share the same metrics.
+#[derive(Clone, Default)]
+pub struct Registry {
+    metrics: Arc<Mutex<Vec<Descriptor>>>,
+}
+
+/// A metric registered in a `Registry`.
+struct Descriptor {
+    name: String,
+    help: String,
+    metric: Box<dyn EncodeMetric>,
+}
+
+impl Registry {
+    /// Creates an empty `Registry`.
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Registers a metric under the given name, with the given description.
+    ///
+    /// A metric previously registered under the same name is replaced. As a consequence, a
+    /// registry should only be given to one instance of each component of libp2p.
+    pub fn register<M>(&self, name: impl Into<String>, help: impl Into<String>, metric: M)
+    where
+        M: EncodeMetric + 'static
+    {
+        let descriptor = Descriptor {
+            name: name.into(),
+            help: help.into(),
+            metric: Box::new(metric),
+        };
+
+        let mut metrics = self.metrics.lock();
+        if let Some(existing) = metrics.iter_mut().find(|d| d.name == descriptor.name) {
+            *existing = descriptor;
+        } else {
+            metrics.push(descriptor);
+        }
+    }
+}
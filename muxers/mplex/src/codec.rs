@@ -38,46 +38,6 @@ pub enum Elem {
     Reset { substream_id: u32, endpoint: Endpoint },
 }
 
-impl Elem {
-    /// Returns the ID of the substream of the message.
-    pub fn substream_id(&self) -> u32 {
-        match *self {
-            Elem::Open { substream_id } => substream_id,
-            Elem::Data { substream_id, .. } => substream_id,
-            Elem::Close { substream_id, .. } => substream_id,
-            Elem::Reset { substream_id, .. } => substream_id,
-        }
-    }
-
-    pub fn endpoint(&self) -> Option<Endpoint> {
-        match *self {
-            Elem::Open { .. } => None,
-            Elem::Data { endpoint, .. } => Some(endpoint),
-            Elem::Close { endpoint, .. } => Some(endpoint),
-            Elem::Reset { endpoint, .. } => Some(endpoint)
-        }
-    }
-
-    /// Returns true if this message is `Close` or `Reset`.
-    #[inline]
-    pub fn is_close_or_reset_msg(&self) -> bool {
-        match self {
-            Elem::Close { .. } | Elem::Reset { .. } => true,
-            _ => false,
-        }
-    }
-
-    /// Returns true if this message is `Open`.
-    #[inline]
-    pub fn is_open_msg(&self) -> bool {
-        if let Elem::Open { .. } = self {
-            true
-        } else {
-            false
-        }
-    }
-}
-
 pub struct Codec {
     varint_decoder: codec::Uvi<u32>,
     decoder_state: CodecDecodeState,
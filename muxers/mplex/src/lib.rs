@@ -21,6 +21,7 @@
 mod codec;
 
 use std::{cmp, iter, mem};
+use std::collections::VecDeque;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 use std::sync::{atomic::AtomicUsize, atomic::Ordering, Arc};
 use bytes::Bytes;
@@ -31,7 +32,7 @@ use libp2p_core::{
 };
 use log::{debug, trace};
 use parking_lot::Mutex;
-use fnv::{FnvHashMap, FnvHashSet};
+use fnv::FnvHashMap;
 use futures::{prelude::*, executor, future, stream::Fuse, task, task_local, try_ready};
 use tokio_codec::Framed;
 use tokio_io::{AsyncRead, AsyncWrite};
@@ -41,7 +42,7 @@ use tokio_io::{AsyncRead, AsyncWrite};
 pub struct MplexConfig {
     /// Maximum number of simultaneously-open substreams.
     max_substreams: usize,
-    /// Maximum number of elements in the internal buffer.
+    /// Maximum number of frames buffered per substream.
     max_buffer_len: usize,
     /// Behaviour when the buffer size limit is reached.
     max_buffer_behaviour: MaxBufferBehaviour,
@@ -57,8 +58,8 @@ impl MplexConfig {
         Default::default()
     }
 
-    /// Sets the maximum number of simultaneously opened substreams, after which an error is
-    /// generated and the connection closes.
+    /// Sets the maximum number of simultaneously opened substreams, after which the new
+    /// substreams opened by the remote are reset.
     ///
     /// A limit is necessary in order to avoid DoS attacks.
     #[inline]
@@ -67,7 +68,8 @@ impl MplexConfig {
         self
     }
 
-    /// Sets the maximum number of pending incoming messages.
+    /// Sets the maximum number of data frames buffered for each substream, i.e. received from
+    /// the remote but not yet read.
     ///
     /// A limit is necessary in order to avoid DoS attacks.
     #[inline]
@@ -76,7 +78,7 @@ impl MplexConfig {
         self
     }
 
-    /// Sets the behaviour when the maximum buffer length has been reached.
+    /// Sets the behaviour when the maximum buffer length of a substream has been reached.
     ///
     /// See the documentation of `MaxBufferBehaviour`.
     #[inline]
@@ -98,15 +100,18 @@ impl MplexConfig {
     where
         C: AsyncRead + AsyncWrite
     {
-        let max_buffer_len = self.max_buffer_len;
         Multiplex {
             inner: Mutex::new(MultiplexInner {
                 error: Ok(()),
                 inner: executor::spawn(Framed::new(i, codec::Codec::new()).fuse()),
                 config: self,
-                buffer: Vec::with_capacity(cmp::min(max_buffer_len, 512)),
-                opened_substreams: Default::default(),
+                substreams: Default::default(),
+                pending_inbound: VecDeque::new(),
+                inbound_task: None,
+                pending_frames: VecDeque::new(),
+                blocked_tasks: Default::default(),
                 next_outbound_stream_id: 0,
+                reader: None,
                 notifier_read: Arc::new(Notifier {
                     to_notify: Mutex::new(Default::default()),
                 }),
@@ -126,18 +131,20 @@ impl Default for MplexConfig {
         MplexConfig {
             max_substreams: 128,
             max_buffer_len: 4096,
-            max_buffer_behaviour: MaxBufferBehaviour::CloseAll,
+            max_buffer_behaviour: MaxBufferBehaviour::ResetStream,
             split_send_size: 1024,
         }
     }
 }
 
-/// Behaviour when the maximum length of the buffer is reached.
+/// Behaviour when the maximum length of the buffer of a substream is reached.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MaxBufferBehaviour {
-    /// Produce an error on all the substreams.
-    CloseAll,
-    /// No new message will be read from the underlying connection if the buffer is full.
+    /// Reset the substream whose buffer is full, both locally and towards the remote. Reading
+    /// from the substream then produces an error, while the other substreams are unaffected.
+    ResetStream,
+    /// No new message will be read from the underlying connection while the buffer of a
+    /// substream is full.
     ///
     /// This can potentially introduce a deadlock if you are waiting for a message from a substream
     /// before processing the messages received on another substream.
@@ -193,16 +200,28 @@ struct MultiplexInner<C> {
     inner: executor::Spawn<Fuse<Framed<C, codec::Codec>>>,
     /// The original configuration.
     config: MplexConfig,
-    // Buffer of elements pulled from the stream but not processed yet.
-    buffer: Vec<codec::Elem>,
-    // List of Ids of opened substreams. Used to filter out messages that don't belong to any
-    // substream. Note that this is handled exclusively by `next_match`.
+    // State of the opened substreams, including the data received on each of them. Messages
+    // that don't belong to any substream are ignored.
     // The `Endpoint` value denotes who initiated the substream from our point of view
     // (see note [StreamId]).
-    opened_substreams: FnvHashSet<(u32, Endpoint)>,
+    substreams: FnvHashMap<(u32, Endpoint), SubstreamState>,
+    /// Substreams opened by the remote that haven't been returned by `poll_inbound` yet.
+    pending_inbound: VecDeque<u32>,
+    /// Task to notify when a substream is opened by the remote.
+    inbound_task: Option<task::Task>,
+    /// Frames to send before any other one, i.e. the resets of the substreams that we refuse or
+    /// whose buffer is full. No frame is read from the connection while this isn't empty.
+    pending_frames: VecDeque<codec::Elem>,
+    /// Tasks waiting for the buffer of a substream to no longer be full, with
+    /// `MaxBufferBehaviour::Block`.
+    blocked_tasks: FnvHashMap<usize, task::Task>,
     // Id of the next outgoing substream.
     next_outbound_stream_id: u32,
-    /// List of tasks to notify when a read event happens on the underlying stream.
+    /// The reader whose task is notified when a read event happens on the underlying stream, if
+    /// any. The other tasks wait for their substream to receive a frame.
+    reader: Option<Reader>,
+    /// Task to notify when a read event happens on the underlying stream, i.e. the task of
+    /// `reader`.
     notifier_read: Arc<Notifier>,
     /// List of tasks to notify when a write event happens on the underlying stream.
     notifier_write: Arc<Notifier>,
@@ -213,6 +232,38 @@ struct MultiplexInner<C> {
     is_acknowledged: bool,
 }
 
+/// Reader of the underlying connection, see `MultiplexInner::reader`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Reader {
+    /// The task waiting for a substream opened by the remote.
+    Inbound,
+    /// The task reading the given substream.
+    Substream(u32, Endpoint),
+}
+
+/// State of a substream, shared between the `Substream` and the data read from the underlying
+/// connection.
+#[derive(Default)]
+struct SubstreamState {
+    /// Data received on the substream and not yet read.
+    buffer: VecDeque<Bytes>,
+    /// If true, the remote has closed or reset its writing side.
+    remote_closed: bool,
+    /// If true, we have reset the substream because its buffer was full.
+    reset: bool,
+    /// Task to notify when data is received on the substream.
+    task: Option<task::Task>,
+}
+
+impl SubstreamState {
+    /// Notifies the task reading the substream, if any.
+    fn notify(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.notify();
+        }
+    }
+}
+
 struct Notifier {
     /// List of tasks to notify.
     to_notify: Mutex<FnvHashMap<usize, task::Task>>,
@@ -243,85 +294,170 @@ task_local!{
 // entry has been stored as `(<u32>, Dialer)`. So, when looking up streams based on frames
 // received, we have to invert the `Endpoint`, except for `Open`.
 
-/// Processes elements in `inner` until one matching `filter` is found.
+/// Reads the next frame from the underlying connection and dispatches it to its substream,
+/// notifying only the task reading that substream.
+///
+/// Only one task at a time, the one of the first `reader` to call this function, is notified
+/// when the underlying connection becomes readable. The callers must therefore have registered
+/// the current task in `inbound_task` or in the state of their substream beforehand, and must
+/// call `release_read` once they stop reading.
 ///
 /// If `NotReady` is returned, the current task is scheduled for later, just like with any `Poll`.
-/// `Ready(Some())` is almost always returned. An error is returned if the stream is EOF.
-fn next_match<C, F, O>(inner: &mut MultiplexInner<C>, mut filter: F) -> Poll<O, IoError>
-where C: AsyncRead + AsyncWrite,
-      F: FnMut(&codec::Elem) -> Option<O>,
+/// An error is returned if the stream is EOF.
+fn read_frame<C>(inner: &mut MultiplexInner<C>, reader: Reader) -> Poll<(), IoError>
+where C: AsyncRead + AsyncWrite
 {
     // If an error happened earlier, immediately return it.
     if let Err(ref err) = inner.error {
         return Err(IoError::new(err.kind(), err.to_string()));
     }
 
-    if let Some((offset, out)) = inner.buffer.iter().enumerate().filter_map(|(n, v)| filter(v).map(|v| (n, v))).next() {
-        // The buffer was full and no longer is, so let's notify everything.
-        if inner.buffer.len() == inner.config.max_buffer_len {
-            executor::Notify::notify(&*inner.notifier_read, 0);
-        }
-
-        inner.buffer.remove(offset);
-        return Ok(Async::Ready(out));
+    // Stop reading while the resets we owe the remote can't be sent, otherwise a remote that
+    // doesn't read could make `pending_frames` grow forever. We are notified by `notifier_write`.
+    send_pending_frames(inner)?;
+    if !inner.pending_frames.is_empty() {
+        return Ok(Async::NotReady);
     }
 
-    loop {
-        // Check if we reached max buffer length first.
-        debug_assert!(inner.buffer.len() <= inner.config.max_buffer_len);
-        if inner.buffer.len() == inner.config.max_buffer_len {
+    if inner.config.max_buffer_behaviour == MaxBufferBehaviour::Block {
+        let max_buffer_len = inner.config.max_buffer_len;
+        if inner.substreams.values().any(|s| s.buffer.len() >= max_buffer_len) {
             debug!("Reached mplex maximum buffer length");
-            match inner.config.max_buffer_behaviour {
-                MaxBufferBehaviour::CloseAll => {
-                    inner.error = Err(IoError::new(IoErrorKind::Other, "reached maximum buffer length"));
-                    return Err(IoError::new(IoErrorKind::Other, "reached maximum buffer length"));
-                },
-                MaxBufferBehaviour::Block => {
-                    inner.notifier_read.to_notify.lock().insert(TASK_ID.with(|&t| t), task::current());
-                    return Ok(Async::NotReady);
-                },
-            }
+            inner.blocked_tasks.insert(TASK_ID.with(|&t| t), task::current());
+            return Ok(Async::NotReady);
         }
+    }
 
-        inner.notifier_read.to_notify.lock().insert(TASK_ID.with(|&t| t), task::current());
-        let elem = match inner.inner.poll_stream_notify(&inner.notifier_read, 0) {
-            Ok(Async::Ready(Some(item))) => item,
-            Ok(Async::Ready(None)) => return Err(IoErrorKind::BrokenPipe.into()),
-            Ok(Async::NotReady) => return Ok(Async::NotReady),
-            Err(err) => {
-                let err2 = IoError::new(err.kind(), err.to_string());
-                inner.error = Err(err);
-                return Err(err2);
-            },
-        };
-
-        trace!("Received message: {:?}", elem);
-        inner.is_acknowledged = true;
+    if inner.reader.map_or(true, |r| r == reader) {
+        inner.reader = Some(reader);
+        let mut to_notify = inner.notifier_read.to_notify.lock();
+        to_notify.clear();
+        to_notify.insert(0, task::current());
+    }
 
-        // Handle substreams opening/closing.
-        match elem {
-            codec::Elem::Open { substream_id } => {
-                if !inner.opened_substreams.insert((substream_id, Endpoint::Listener)) {
-                    debug!("Received open message for substream {} which was already open", substream_id)
+    let elem = match inner.inner.poll_stream_notify(&inner.notifier_read, 0) {
+        Ok(Async::Ready(Some(item))) => item,
+        Ok(Async::Ready(None)) => {
+            notify_all_readers(inner);
+            return Err(IoErrorKind::BrokenPipe.into())
+        },
+        Ok(Async::NotReady) => return Ok(Async::NotReady),
+        Err(err) => {
+            let err2 = IoError::new(err.kind(), err.to_string());
+            inner.error = Err(err);
+            notify_all_readers(inner);
+            return Err(err2);
+        },
+    };
+
+    trace!("Received message: {:?}", elem);
+    inner.is_acknowledged = true;
+
+    match elem {
+        codec::Elem::Open { substream_id } => {
+            if inner.substreams.contains_key(&(substream_id, Endpoint::Listener)) {
+                debug!("Received open message for substream {} which was already open", substream_id)
+            } else if inner.substreams.len() >= inner.config.max_substreams {
+                debug!("Refused substream {}; reached maximum number of substreams {}",
+                    substream_id, inner.config.max_substreams);
+                inner.pending_frames.push_back(codec::Elem::Reset {
+                    substream_id,
+                    endpoint: Endpoint::Listener,
+                });
+            } else {
+                inner.substreams.insert((substream_id, Endpoint::Listener), SubstreamState::default());
+                inner.pending_inbound.push_back(substream_id);
+                if let Some(task) = inner.inbound_task.take() {
+                    task.notify();
                 }
             }
-            codec::Elem::Close { substream_id, endpoint, .. } | codec::Elem::Reset { substream_id, endpoint, .. } => {
-                inner.opened_substreams.remove(&(substream_id, !endpoint));
+        }
+        codec::Elem::Data { substream_id, endpoint, data } => {
+            // See note [StreamId].
+            match inner.substreams.get_mut(&(substream_id, !endpoint)) {
+                // Data received after a reset is dropped.
+                Some(ref state) if state.reset => {}
+                Some(state) => {
+                    if state.buffer.len() >= inner.config.max_buffer_len {
+                        debug!("Reset substream {}; reached maximum buffer length", substream_id);
+                        state.buffer.clear();
+                        state.reset = true;
+                        inner.pending_frames.push_back(codec::Elem::Reset {
+                            substream_id,
+                            endpoint: !endpoint,
+                        });
+                    } else {
+                        state.buffer.push_back(data);
+                    }
+                    state.notify();
+                }
+                None => debug!("Ignored data for substream {} because it wasn't open", substream_id),
             }
-            _ => ()
         }
-
-        if let Some(out) = filter(&elem) {
-            return Ok(Async::Ready(out));
-        } else {
-            let endpoint = elem.endpoint().unwrap_or(Endpoint::Dialer);
-            if inner.opened_substreams.contains(&(elem.substream_id(), !endpoint)) || elem.is_open_msg() {
-                inner.buffer.push(elem);
-            } else if !elem.is_close_or_reset_msg() {
-                debug!("Ignored message {:?} because the substream wasn't open", elem);
+        codec::Elem::Close { substream_id, endpoint } | codec::Elem::Reset { substream_id, endpoint } => {
+            if let Some(state) = inner.substreams.get_mut(&(substream_id, !endpoint)) {
+                state.remote_closed = true;
+                state.notify();
             }
         }
     }
+
+    Ok(Async::Ready(()))
+}
+
+/// Must be called when `reader` no longer waits for a frame, from any task. If it was the
+/// reader notified when the underlying connection becomes readable, wakes up one of the tasks
+/// waiting for a frame so that it reads from the connection instead.
+fn release_read<C>(inner: &mut MultiplexInner<C>, reader: Reader) {
+    if inner.reader != Some(reader) {
+        return;
+    }
+    inner.reader = None;
+    inner.notifier_read.to_notify.lock().clear();
+
+    if let Some(state) = inner.substreams.values_mut().find(|s| s.task.is_some()) {
+        state.notify();
+    } else if let Some(task) = inner.inbound_task.take() {
+        task.notify();
+    }
+}
+
+/// Notifies all the tasks waiting for a frame, for example because the connection is closed.
+fn notify_all_readers<C>(inner: &mut MultiplexInner<C>) {
+    for state in inner.substreams.values_mut() {
+        state.notify();
+    }
+    if let Some(task) = inner.inbound_task.take() {
+        task.notify();
+    }
+}
+
+/// Sends the frames of `pending_frames`, as long as the underlying connection accepts them.
+fn send_pending_frames<C>(inner: &mut MultiplexInner<C>) -> Result<(), IoError>
+where C: AsyncRead + AsyncWrite
+{
+    if inner.is_shutdown {
+        inner.pending_frames.clear();
+        return Ok(());
+    }
+    while let Some(elem) = inner.pending_frames.pop_front() {
+        inner.notifier_write.to_notify.lock().insert(TASK_ID.with(|&t| t), task::current());
+        if let AsyncSink::NotReady(elem) = inner.inner.start_send_notify(elem, &inner.notifier_write, 0)? {
+            inner.pending_frames.push_front(elem);
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Notifies the tasks blocked on a full buffer, if the buffer of a substream of `len` frames
+/// was full.
+fn unblock_if_full<C>(inner: &mut MultiplexInner<C>, len: usize) {
+    if len >= inner.config.max_buffer_len {
+        for (_, task) in inner.blocked_tasks.drain() {
+            task.notify();
+        }
+    }
 }
 
 // Small convenience function that tries to write `elem` to the stream.
@@ -331,6 +467,7 @@ where C: AsyncRead + AsyncWrite
     if inner.is_shutdown {
         return Err(IoError::new(IoErrorKind::Other, "connection is shut down"))
     }
+    send_pending_frames(inner)?;
     inner.notifier_write.to_notify.lock().insert(TASK_ID.with(|&t| t), task::current());
     match inner.inner.start_send_notify(elem, &inner.notifier_write, 0) {
         Ok(AsyncSink::Ready) => Ok(Async::Ready(())),
@@ -349,18 +486,15 @@ where C: AsyncRead + AsyncWrite
     fn poll_inbound(&self) -> Poll<Self::Substream, IoError> {
         let mut inner = self.inner.lock();
 
-        if inner.opened_substreams.len() >= inner.config.max_substreams {
-            debug!("Refused substream; reached maximum number of substreams {}", inner.config.max_substreams);
-            return Err(IoError::new(IoErrorKind::ConnectionRefused,
-                                    "exceeded maximum number of open substreams"));
-        }
-
-        let num = try_ready!(next_match(&mut inner, |elem| {
-            match elem {
-                codec::Elem::Open { substream_id } => Some(*substream_id),
-                _ => None,
+        let num = loop {
+            if let Some(num) = inner.pending_inbound.pop_front() {
+                inner.inbound_task = None;
+                release_read(&mut inner, Reader::Inbound);
+                break num;
             }
-        }));
+            inner.inbound_task = Some(task::current());
+            try_ready!(read_frame(&mut inner, Reader::Inbound));
+        };
 
         debug!("Successfully opened inbound substream {}", num);
         Ok(Async::Ready(Substream {
@@ -383,7 +517,7 @@ where C: AsyncRead + AsyncWrite
             n
         };
 
-        inner.opened_substreams.insert((substream_id, Endpoint::Dialer));
+        inner.substreams.insert((substream_id, Endpoint::Dialer), SubstreamState::default());
 
         OutboundSubstream {
             num: substream_id,
@@ -419,9 +553,7 @@ where C: AsyncRead + AsyncWrite
                 },
                 Err(err) => {
                     debug!("Failed to open outbound substream {}", substream.num);
-                    inner.buffer.retain(|elem| {
-                        elem.substream_id() != substream.num || elem.endpoint() == Some(Endpoint::Dialer)
-                    });
+                    inner.substreams.remove(&(substream.num, Endpoint::Dialer));
                     return Err(err)
                 },
             };
@@ -450,8 +582,12 @@ where C: AsyncRead + AsyncWrite
     }
 
     #[inline]
-    fn destroy_outbound(&self, _substream: Self::OutboundSubstream) {
-        // Nothing to do.
+    fn destroy_outbound(&self, substream: Self::OutboundSubstream) {
+        // If the substream has been opened, it is now owned by the corresponding `Substream`.
+        if let OutboundSubstreamState::Done = substream.state {
+            return;
+        }
+        self.inner.lock().substreams.remove(&(substream.num, Endpoint::Dialer));
     }
 
     unsafe fn prepare_uninitialized_buffer(&self, _: &mut [u8]) -> bool {
@@ -472,42 +608,44 @@ where C: AsyncRead + AsyncWrite
                 return Ok(Async::Ready(0));
             }
 
-            // Try to find a packet of data in the buffer.
+            // Try to find a packet of data in the buffer of the substream.
             let mut inner = self.inner.lock();
-            let next_data_poll = next_match(&mut inner, |elem| {
-                match elem {
-                    codec::Elem::Data { substream_id, endpoint, data, .. }
-                        if *substream_id == substream.num && *endpoint != substream.endpoint => // see note [StreamId]
-                    {
-                        Some(Some(data.clone()))
+            let inner = &mut *inner; // Avoids borrow errors
+            let reader = Reader::Substream(substream.num, substream.endpoint);
+            match inner.substreams.get_mut(&(substream.num, substream.endpoint)) {
+                Some(state) => {
+                    if state.reset {
+                        state.task = None;
+                        release_read(inner, reader);
+                        return Err(IoError::new(IoErrorKind::ConnectionReset,
+                            "substream reset after reaching maximum buffer length"));
                     }
-                    codec::Elem::Close { substream_id, endpoint }
-                        if *substream_id == substream.num && *endpoint != substream.endpoint => // see note [StreamId]
-                    {
-                        Some(None)
+                    let len = state.buffer.len();
+                    if let Some(data) = state.buffer.pop_front() {
+                        state.task = None;
+                        substream.current_data = data;
+                        unblock_if_full(inner, len);
+                        release_read(inner, reader);
+                        continue;
                     }
-                    _ => None
-                }
-            });
-
-            // We're in a loop, so all we need to do is set `substream.current_data` to the data we
-            // just read and wait for the next iteration.
-            match next_data_poll? {
-                Async::Ready(Some(data)) => substream.current_data = data,
-                Async::Ready(None) => {
-                    substream.remote_open = false;
-                    return Ok(Async::Ready(0));
-                },
-                Async::NotReady => {
-                    // There was no data packet in the buffer about this substream; maybe it's
-                    // because it has been closed.
-                    if inner.opened_substreams.contains(&(substream.num, substream.endpoint)) {
-                        return Ok(Async::NotReady)
-                    } else {
-                        return Ok(Async::Ready(0))
+                    if state.remote_closed {
+                        state.task = None;
+                        substream.remote_open = false;
+                        release_read(inner, reader);
+                        return Ok(Async::Ready(0));
                     }
+                    state.task = Some(task::current());
+                }
+                // The substream is no longer known.
+                None => {
+                    release_read(inner, reader);
+                    return Ok(Async::Ready(0))
                 },
             }
+
+            // Read the next frame from the connection, which may or may not be for this
+            // substream. We're in a loop, so all we need to do is to check the buffer again.
+            try_ready!(read_frame(inner, reader));
         }
     }
 
@@ -562,9 +700,11 @@ where C: AsyncRead + AsyncWrite
     }
 
     fn destroy_substream(&self, sub: Self::Substream) {
-        self.inner.lock().buffer.retain(|elem| {
-            elem.substream_id() != sub.num || elem.endpoint() == Some(sub.endpoint)
-        })
+        let mut inner = self.inner.lock();
+        if let Some(state) = inner.substreams.remove(&(sub.num, sub.endpoint)) {
+            unblock_if_full(&mut inner, state.buffer.len());
+        }
+        release_read(&mut inner, Reader::Substream(sub.num, sub.endpoint));
     }
 
     fn is_remote_acknowledged(&self) -> bool {
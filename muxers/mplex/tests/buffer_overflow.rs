@@ -0,0 +1,125 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use bytes::Bytes;
+use libp2p_core::{muxing, Transport, transport::ListenerEvent};
+use libp2p_tcp::TcpConfig;
+use futures::{prelude::*, stream};
+use std::io;
+use std::sync::{Arc, mpsc};
+use std::thread;
+use tokio::{
+    codec::length_delimited::Builder,
+    runtime::current_thread::Runtime
+};
+
+#[test]
+fn overflowing_substream_is_reset() {
+    // The client floods a first substream that the server doesn't read, then sends a message on
+    // a second substream. Only the first substream must be reset.
+
+    let (tx, rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let bg_thread = thread::spawn(move || {
+        let mut config = libp2p_mplex::MplexConfig::new();
+        config.max_buffer_len(2);
+        let transport = TcpConfig::new().with_upgrade(config);
+
+        let mut listener = transport
+            .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+
+        let addr = listener.by_ref().wait()
+            .next()
+            .expect("some event")
+            .expect("no error")
+            .into_new_address()
+            .expect("listen address");
+
+        tx.send(addr).unwrap();
+
+        let future = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(err, _)| panic!("{:?}", err))
+            .and_then(|(client, _)| client.unwrap().0)
+            .map_err(|err| panic!("{:?}", err))
+            .and_then(|client| {
+                let client = Arc::new(client);
+                muxing::inbound_from_ref_and_wrap(client.clone())
+                    .and_then(move |bulk| {
+                        muxing::inbound_from_ref_and_wrap(client)
+                            .map(move |other| (bulk, other))
+                    })
+            })
+            .and_then(|(bulk, other)| {
+                Builder::new()
+                    .new_read(other)
+                    .into_future()
+                    .map_err(|(err, _)| err)
+                    .map(move |(msg, _)| {
+                        assert_eq!(msg.unwrap(), "hello world");
+                        bulk
+                    })
+            })
+            .and_then(|bulk| {
+                tokio::io::read_to_end(bulk, Vec::new()).then(|result| {
+                    match result {
+                        Err(ref err) if err.kind() == io::ErrorKind::ConnectionReset => Ok(()),
+                        _ => panic!("expected the substream to be reset"),
+                    }
+                })
+            });
+
+        let mut rt = Runtime::new().unwrap();
+        let _ = rt.block_on(future).unwrap();
+        done_tx.send(()).unwrap();
+    });
+
+    let transport = TcpConfig::new().with_upgrade(libp2p_mplex::MplexConfig::new());
+
+    let future = transport
+        .dial(rx.recv().unwrap())
+        .unwrap()
+        .map_err(|err| panic!("{:?}", err))
+        .and_then(|client| {
+            let client = Arc::new(client);
+            muxing::outbound_from_ref_and_wrap(client.clone())
+                .map(|bulk| Builder::new().new_write(bulk))
+                .and_then(|bulk| {
+                    // Every message is sent in its own frame.
+                    stream::iter_ok::<_, io::Error>(0 .. 4)
+                        .fold(bulk, |bulk, _| bulk.send(Bytes::from("bulk data")))
+                })
+                .and_then(move |bulk| {
+                    muxing::outbound_from_ref_and_wrap(client)
+                        .map(|other| Builder::new().new_write(other))
+                        .and_then(|other| other.send("hello world".into()))
+                        .map(move |other| (bulk, other))
+                })
+        });
+
+    let mut rt = Runtime::new().unwrap();
+    // Keep the connection open until the server is done.
+    let _substreams = rt.block_on(future).unwrap();
+    done_rx.recv().unwrap();
+    bg_thread.join().unwrap();
+}
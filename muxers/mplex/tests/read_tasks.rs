@@ -0,0 +1,223 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use bytes::Bytes;
+use libp2p_core::{muxing, Transport, transport::ListenerEvent};
+use libp2p_tcp::TcpConfig;
+use futures::{future, prelude::*};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use tokio::{
+    codec::length_delimited::Builder,
+    io::AsyncRead,
+    runtime::current_thread::Runtime
+};
+
+#[test]
+fn substreams_read_from_different_tasks() {
+    // The server reads each of its two substreams from a different task. Only one of these tasks
+    // is notified when the connection is readable, and the other one must take over once the
+    // first one has received its message.
+
+    let (tx, rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let bg_thread = thread::spawn(move || {
+        let transport = TcpConfig::new().with_upgrade(libp2p_mplex::MplexConfig::new());
+
+        let mut listener = transport
+            .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+
+        let addr = listener.by_ref().wait()
+            .next()
+            .expect("some event")
+            .expect("no error")
+            .into_new_address()
+            .expect("listen address");
+
+        tx.send(addr).unwrap();
+
+        let future = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(err, _)| panic!("{:?}", err))
+            .and_then(|(client, _)| client.unwrap().0)
+            .map_err(|err| panic!("{:?}", err))
+            .and_then(|client| {
+                let client = Arc::new(client);
+                muxing::inbound_from_ref_and_wrap(client.clone())
+                    .and_then(move |first| {
+                        muxing::inbound_from_ref_and_wrap(client)
+                            .map(move |second| (first, second))
+                    })
+            });
+
+        let mut rt = Runtime::new().unwrap();
+        let (first, second) = rt.block_on(future).unwrap();
+
+        let read = |substream, expected: &'static str| {
+            Builder::new()
+                .new_read(substream)
+                .into_future()
+                .map_err(|(err, _)| panic!("{:?}", err))
+                .map(move |(msg, _)| assert_eq!(msg.unwrap(), expected))
+        };
+        rt.spawn(read(first, "first"));
+        rt.spawn(read(second, "second"));
+        rt.spawn(future::lazy(move || {
+            ready_tx.send(()).unwrap();
+            Ok(())
+        }));
+
+        rt.run().unwrap();
+        done_tx.send(()).unwrap();
+    });
+
+    let transport = TcpConfig::new().with_upgrade(libp2p_mplex::MplexConfig::new());
+
+    let future = transport
+        .dial(rx.recv().unwrap())
+        .unwrap()
+        .map_err(|err| panic!("{:?}", err))
+        .and_then(|client| {
+            let client = Arc::new(client);
+            muxing::outbound_from_ref_and_wrap(client.clone())
+                .and_then(move |first| {
+                    muxing::outbound_from_ref_and_wrap(client)
+                        .map(move |second| (first, second))
+                })
+        });
+
+    let mut rt = Runtime::new().unwrap();
+    let (first, second) = rt.block_on(future).unwrap();
+
+    // Only send the messages once both tasks of the server are waiting for them.
+    ready_rx.recv().unwrap();
+    let future = Builder::new()
+        .new_write(first)
+        .send(Bytes::from("first"))
+        .join(Builder::new().new_write(second).send(Bytes::from("second")));
+    // Keep the connection open until the server is done.
+    let _substreams = rt.block_on(future).unwrap();
+    done_rx.recv().unwrap();
+    bg_thread.join().unwrap();
+}
+
+#[test]
+fn dropping_the_reader_from_another_thread_wakes_up_the_other_tasks() {
+    // The server's first substream becomes the reader of the connection and is then dropped on
+    // another thread, without its task ever reading it again. The task reading the second
+    // substream must still receive its message.
+
+    let (tx, rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let (reader_tx, reader_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let bg_thread = thread::spawn(move || {
+        let transport = TcpConfig::new().with_upgrade(libp2p_mplex::MplexConfig::new());
+
+        let mut listener = transport
+            .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+
+        let addr = listener.by_ref().wait()
+            .next()
+            .expect("some event")
+            .expect("no error")
+            .into_new_address()
+            .expect("listen address");
+
+        tx.send(addr).unwrap();
+
+        let future = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(err, _)| panic!("{:?}", err))
+            .and_then(|(client, _)| client.unwrap().0)
+            .map_err(|err| panic!("{:?}", err))
+            .and_then(|client| {
+                let client = Arc::new(client);
+                muxing::inbound_from_ref_and_wrap(client.clone())
+                    .and_then(move |first| {
+                        muxing::inbound_from_ref_and_wrap(client)
+                            .map(move |second| (first, second))
+                    })
+            });
+
+        let mut rt = Runtime::new().unwrap();
+        let (first, second) = rt.block_on(future).unwrap();
+
+        // Poll the first substream once, which makes its task the reader, and hand it over.
+        let mut first = Some(first);
+        rt.spawn(future::poll_fn(move || {
+            let mut buf = [0; 1];
+            match first.as_mut().unwrap().poll_read(&mut buf) {
+                Ok(Async::NotReady) => {}
+                other => panic!("unexpected read result: {:?}", other),
+            }
+            reader_tx.send(first.take().unwrap()).unwrap();
+            Ok(Async::Ready(()))
+        }));
+        rt.spawn(Builder::new()
+            .new_read(second)
+            .into_future()
+            .map_err(|(err, _)| panic!("{:?}", err))
+            .map(|(msg, _)| assert_eq!(msg.unwrap(), "second")));
+        rt.spawn(future::lazy(move || {
+            ready_tx.send(()).unwrap();
+            Ok(())
+        }));
+
+        rt.run().unwrap();
+        done_tx.send(()).unwrap();
+    });
+
+    let transport = TcpConfig::new().with_upgrade(libp2p_mplex::MplexConfig::new());
+
+    let future = transport
+        .dial(rx.recv().unwrap())
+        .unwrap()
+        .map_err(|err| panic!("{:?}", err))
+        .and_then(|client| {
+            let client = Arc::new(client);
+            muxing::outbound_from_ref_and_wrap(client.clone())
+                .and_then(move |first| {
+                    muxing::outbound_from_ref_and_wrap(client)
+                        .map(move |second| (first, second))
+                })
+        });
+
+    let mut rt = Runtime::new().unwrap();
+    let (first, second) = rt.block_on(future).unwrap();
+
+    // Drop the reader on this thread once both tasks of the server have polled their substream.
+    ready_rx.recv().unwrap();
+    drop(reader_rx.recv().unwrap());
+
+    let future = Builder::new().new_write(second).send(Bytes::from("second"));
+    // Keep the connection open until the server is done.
+    let _substreams = (first, rt.block_on(future).unwrap());
+    done_rx.recv().unwrap();
+    bg_thread.join().unwrap();
+}
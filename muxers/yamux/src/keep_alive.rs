@@ -0,0 +1,358 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Keep-alive of Yamux connections.
+//!
+//! The `yamux` crate answers the ping frames sent by the remote but doesn't send any itself.
+//! `KeepAlive` wraps the socket of a connection and, when nothing has been received for the
+//! keep-alive interval, inserts a ping frame between the frames written by `yamux`. The remote
+//! answers with a pong, and reading from the socket fails if nothing at all is received during
+//! the following interval.
+
+use futures::{Async, Future, Poll};
+use std::{cmp, io, io::{Read, Write}, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Size of the header of a Yamux frame.
+const HEADER_SIZE: usize = 12;
+
+/// Type of the data frames, the only ones with a body.
+const TYPE_DATA: u8 = 0;
+
+/// Type of the ping frames.
+const TYPE_PING: u8 = 2;
+
+/// Flag of the frames opening a stream or, for ping frames, asking for a pong.
+const FLAG_SYN: u16 = 1;
+
+/// Socket of a Yamux connection, sending ping frames when the connection is idle.
+pub struct KeepAlive<C> {
+    /// The underlying socket.
+    inner: C,
+    /// The keep-alive timer, or `None` if keep-alive is disabled.
+    timer: Option<Timer>,
+    /// Ping frame that remains to be written.
+    pending: Vec<u8>,
+    /// Whether a ping frame was written without the socket being flushed since.
+    unflushed: bool,
+    /// The frames written by `yamux` so far.
+    frames: FrameTracker,
+}
+
+/// State of the keep-alive of a connection.
+struct Timer {
+    /// Duration without receiving anything after which a ping is sent.
+    interval: Duration,
+    /// Fires when the next ping is due or when the pong is overdue.
+    delay: Delay,
+    /// Last time data was received.
+    last_read: Instant,
+    /// Whether a ping was sent since the last time data was received.
+    ping_sent: bool,
+    /// Nonce of the last ping sent.
+    nonce: u32,
+}
+
+impl<C> KeepAlive<C> {
+    /// Wraps `inner`, sending a ping when nothing has been received for `interval`, or never if
+    /// `interval` is `None`.
+    pub(crate) fn new(inner: C, interval: Option<Duration>) -> Self {
+        let timer = interval.map(|interval| {
+            let now = Instant::now();
+            Timer {
+                interval,
+                delay: Delay::new(now + interval),
+                last_read: now,
+                ping_sent: false,
+                nonce: 0,
+            }
+        });
+        KeepAlive {
+            inner,
+            timer,
+            pending: Vec::new(),
+            unflushed: false,
+            frames: FrameTracker::default(),
+        }
+    }
+
+    /// Queues a ping frame with the given nonce, to be written after the current frame.
+    fn queue_ping(&mut self, nonce: u32) {
+        self.pending.clear();
+        self.pending.push(0);
+        self.pending.push(TYPE_PING);
+        self.pending.extend_from_slice(&FLAG_SYN.to_be_bytes());
+        self.pending.extend_from_slice(&0u32.to_be_bytes());
+        self.pending.extend_from_slice(&nonce.to_be_bytes());
+    }
+}
+
+impl<C: AsyncWrite> KeepAlive<C> {
+    /// Writes the pending ping frame, unless `yamux` is in the middle of writing a frame.
+    fn write_pending(&mut self) -> io::Result<()> {
+        if !self.frames.at_boundary() {
+            return Ok(());
+        }
+        while !self.pending.is_empty() {
+            let n = self.inner.write(&self.pending)?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.pending.drain(.. n);
+            self.unflushed = true;
+        }
+        Ok(())
+    }
+
+    /// Sends a ping if one is due, and fails if the remote didn't answer the previous one.
+    ///
+    /// Registers the current task to be woken up when the timer fires.
+    fn poll_keep_alive(&mut self) -> io::Result<()> {
+        loop {
+            let nonce = match self.timer {
+                Some(ref mut timer) => {
+                    if let Async::NotReady = timer.delay.poll()? {
+                        break;
+                    }
+                    let now = Instant::now();
+                    let deadline = timer.last_read + timer.interval;
+                    if deadline > now {
+                        timer.delay.reset(deadline);
+                        continue;
+                    }
+                    if timer.ping_sent {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut,
+                            "Nothing received in response to a keep-alive ping"));
+                    }
+                    timer.ping_sent = true;
+                    timer.nonce = timer.nonce.wrapping_add(1);
+                    timer.delay.reset(now + timer.interval);
+                    timer.nonce
+                }
+                None => return Ok(()),
+            };
+            self.queue_ping(nonce);
+        }
+
+        // Nothing is written while the connection is idle, so we send the ping ourselves.
+        let result = self.write_pending().and_then(|()| {
+            if self.unflushed {
+                self.inner.flush()?;
+                self.unflushed = false;
+            }
+            Ok(())
+        });
+        match result {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl<C: AsyncRead + AsyncWrite> Read for KeepAlive<C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner.read(buf) {
+            Ok(n) => {
+                if let Some(ref mut timer) = self.timer {
+                    timer.last_read = Instant::now();
+                    timer.ping_sent = false;
+                }
+                Ok(n)
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.poll_keep_alive()?;
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<C: AsyncRead + AsyncWrite> AsyncRead for KeepAlive<C> {}
+
+impl<C: AsyncWrite> Write for KeepAlive<C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_pending()?;
+        let n = self.inner.write(buf)?;
+        self.frames.advance(&buf[.. n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.inner.flush()?;
+        self.unflushed = false;
+        Ok(())
+    }
+}
+
+impl<C: AsyncWrite> AsyncWrite for KeepAlive<C> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self.write_pending() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Async::NotReady),
+            Err(e) => return Err(e),
+            Ok(()) => {}
+        }
+        self.inner.shutdown()
+    }
+}
+
+/// Tracks the boundaries of the frames written by `yamux`, so that ping frames are only
+/// inserted between two frames.
+#[derive(Default)]
+struct FrameTracker {
+    /// The header of the current frame.
+    header: [u8; HEADER_SIZE],
+    /// Number of bytes of the header written so far.
+    header_len: usize,
+    /// Number of bytes of the body of the current frame that remain to be written.
+    body_remaining: usize,
+}
+
+impl FrameTracker {
+    /// Returns true if the frames written so far are complete.
+    fn at_boundary(&self) -> bool {
+        self.header_len == 0 && self.body_remaining == 0
+    }
+
+    /// Takes into account the bytes written by `yamux`.
+    fn advance(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.body_remaining > 0 {
+                let n = cmp::min(self.body_remaining, data.len());
+                self.body_remaining -= n;
+                data = &data[n ..];
+                continue;
+            }
+            let n = cmp::min(HEADER_SIZE - self.header_len, data.len());
+            self.header[self.header_len .. self.header_len + n].copy_from_slice(&data[.. n]);
+            self.header_len += n;
+            data = &data[n ..];
+            if self.header_len == HEADER_SIZE {
+                self.header_len = 0;
+                if self.header[1] == TYPE_DATA {
+                    let mut len = [0; 4];
+                    len.copy_from_slice(&self.header[8 ..]);
+                    self.body_remaining = u32::from_be_bytes(len) as usize;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+
+    /// Socket on which nothing is ever received and which records what is written.
+    #[derive(Default)]
+    struct SilentSocket {
+        written: Vec<u8>,
+    }
+
+    impl Read for SilentSocket {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    impl AsyncRead for SilentSocket {}
+
+    impl Write for SilentSocket {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for SilentSocket {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    /// Returns a data frame carrying `body`.
+    fn data_frame(body: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0, TYPE_DATA, 0, 0, 0, 0, 0, 1];
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    /// Returns a ping frame with the given nonce.
+    fn ping_frame(nonce: u32) -> Vec<u8> {
+        let mut frame = vec![0, TYPE_PING, 0, 1, 0, 0, 0, 0];
+        frame.extend_from_slice(&nonce.to_be_bytes());
+        frame
+    }
+
+    #[test]
+    fn ping_is_written_between_frames() {
+        let mut socket = KeepAlive::new(SilentSocket::default(), None);
+        let first = data_frame(b"hello world");
+        let second = data_frame(b"bye");
+
+        socket.write_all(&first[.. 5]).unwrap();
+        socket.queue_ping(7);
+        socket.write_all(&first[5 .. 15]).unwrap();
+        socket.write_all(&first[15 ..]).unwrap();
+        socket.write_all(&second).unwrap();
+
+        let mut expected = first.clone();
+        expected.extend(ping_frame(7));
+        expected.extend(second);
+        assert_eq!(socket.inner.written, expected);
+    }
+
+    #[test]
+    fn silent_remote_times_out() {
+        let interval = Duration::from_millis(50);
+        let mut socket = KeepAlive::new(SilentSocket::default(), Some(interval));
+        let start = Instant::now();
+
+        let error = future::poll_fn(|| {
+            match socket.read(&mut [0; 16]) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(Async::NotReady),
+                Err(e) => Ok(Async::Ready(e)),
+                Ok(_) => panic!("nothing is ever received"),
+            }
+        }).wait().unwrap_or_else(|()| unreachable!());
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= 2 * interval);
+        assert_eq!(socket.inner.written, ping_frame(1));
+    }
+
+    #[test]
+    fn no_ping_without_keep_alive() {
+        let mut socket = KeepAlive::new(SilentSocket::default(), None);
+        for _ in 0 .. 3 {
+            assert_eq!(socket.read(&mut [0; 16]).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        }
+        assert!(socket.inner.written.is_empty());
+    }
+}
@@ -21,23 +21,113 @@
 //! Implements the Yamux multiplexing protocol for libp2p, see also the
 //! [specification](https://github.com/hashicorp/yamux/blob/master/spec.md).
 
+mod keep_alive;
+
 use futures::{future::{self, FutureResult}, prelude::*};
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, Negotiated};
 use log::debug;
-use std::{io, iter, sync::atomic};
+use self::keep_alive::KeepAlive;
+use std::{io, iter, sync::{Arc, atomic::{self, AtomicBool, AtomicU64, AtomicUsize}}, time::Duration};
 use std::io::{Error as IoError};
 use tokio_io::{AsyncRead, AsyncWrite};
 
-// TODO: add documentation and field names
-pub struct Yamux<C>(yamux::Connection<C>, atomic::AtomicBool);
+pub use yamux::WindowUpdateMode;
+
+/// A Yamux connection, implementing `StreamMuxer`.
+pub struct Yamux<C> {
+    /// The underlying Yamux connection.
+    connection: yamux::Connection<KeepAlive<C>>,
+    /// Whether the remote has acknowledged the connection, i.e. opened a substream or sent data.
+    acknowledged: AtomicBool,
+    /// Statistics about the substreams whose writes are stalled.
+    stalls: Arc<StallMetrics>,
+}
 
 impl<C> Yamux<C>
 where
     C: AsyncRead + AsyncWrite + 'static
 {
+    /// Creates a Yamux connection on top of `c` with the given configuration, except that the
+    /// substreams can't be read after the connection has been closed, and without keep-alive.
+    ///
+    /// Upgrading with a `Config` uses its settings as is.
     pub fn new(c: C, mut cfg: yamux::Config, mode: yamux::Mode) -> Self {
         cfg.set_read_after_close(false);
-        Yamux(yamux::Connection::new(c, cfg, mode), atomic::AtomicBool::new(false))
+        Self::with_config(c, cfg, mode, None, Arc::new(StallMetrics::default()))
+    }
+
+    /// Creates a Yamux connection that sends pings after `keep_alive` without receiving
+    /// anything, if any, and records its stalled substreams in `stalls`.
+    fn with_config(
+        c: C,
+        cfg: yamux::Config,
+        mode: yamux::Mode,
+        keep_alive: Option<Duration>,
+        stalls: Arc<StallMetrics>
+    ) -> Self {
+        Yamux {
+            connection: yamux::Connection::new(KeepAlive::new(c, keep_alive), cfg, mode),
+            acknowledged: AtomicBool::new(false),
+            stalls,
+        }
+    }
+
+    /// Returns the statistics about the substreams whose writes are stalled.
+    ///
+    /// For a connection created by upgrading with a `Config`, these are the statistics shared
+    /// by all the connections of that `Config`, see `Config::stall_metrics`.
+    pub fn stall_metrics(&self) -> &Arc<StallMetrics> {
+        &self.stalls
+    }
+}
+
+/// Statistics about the substreams of `Yamux` connections whose writes are stalled, i.e.
+/// can't make progress because the send window granted by the remote is exhausted or the
+/// connection is congested.
+#[derive(Debug, Default)]
+pub struct StallMetrics {
+    /// Number of substreams currently stalled.
+    stalled: AtomicUsize,
+    /// Number of times a substream became stalled.
+    stalls: AtomicU64,
+}
+
+impl StallMetrics {
+    /// Returns the number of substreams whose last write attempt didn't make progress.
+    pub fn stalled_substreams(&self) -> usize {
+        self.stalled.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of times a substream became stalled since the statistics were
+    /// created.
+    pub fn total_stalls(&self) -> u64 {
+        self.stalls.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Records the outcome of a write attempt on a substream, given whether its previous
+    /// write attempt was stalled.
+    fn on_write(&self, was_stalled: &mut bool, stalled: bool) {
+        if stalled && !*was_stalled {
+            self.stalled.fetch_add(1, atomic::Ordering::Relaxed);
+            self.stalls.fetch_add(1, atomic::Ordering::Relaxed);
+        } else if !stalled && *was_stalled {
+            self.stalled.fetch_sub(1, atomic::Ordering::Relaxed);
+        }
+        *was_stalled = stalled;
+    }
+}
+
+/// A substream of a `Yamux` connection.
+pub struct YamuxSubstream<C> {
+    /// The underlying Yamux stream.
+    inner: yamux::StreamHandle<KeepAlive<C>>,
+    /// Whether the last write attempt didn't make progress.
+    stalled: bool,
+}
+
+impl<C> YamuxSubstream<C> {
+    fn new(inner: yamux::StreamHandle<KeepAlive<C>>) -> Self {
+        YamuxSubstream { inner, stalled: false }
     }
 }
 
@@ -45,12 +135,12 @@ impl<C> libp2p_core::StreamMuxer for Yamux<C>
 where
     C: AsyncRead + AsyncWrite + 'static
 {
-    type Substream = yamux::StreamHandle<C>;
-    type OutboundSubstream = FutureResult<Option<Self::Substream>, io::Error>;
+    type Substream = YamuxSubstream<C>;
+    type OutboundSubstream = FutureResult<Option<yamux::StreamHandle<KeepAlive<C>>>, io::Error>;
     type Error = IoError;
 
     fn poll_inbound(&self) -> Poll<Self::Substream, IoError> {
-        match self.0.poll() {
+        match self.connection.poll() {
             Err(e) => {
                 debug!("connection error: {}", e);
                 Err(io::Error::new(io::ErrorKind::Other, e))
@@ -58,20 +148,20 @@ where
             Ok(Async::NotReady) => Ok(Async::NotReady),
             Ok(Async::Ready(None)) => Err(io::ErrorKind::BrokenPipe.into()),
             Ok(Async::Ready(Some(stream))) => {
-                self.1.store(true, atomic::Ordering::Release);
-                Ok(Async::Ready(stream))
+                self.acknowledged.store(true, atomic::Ordering::Release);
+                Ok(Async::Ready(YamuxSubstream::new(stream)))
             }
         }
     }
 
     fn open_outbound(&self) -> Self::OutboundSubstream {
-        let stream = self.0.open_stream().map_err(|e| io::Error::new(io::ErrorKind::Other, e));
+        let stream = self.connection.open_stream().map_err(|e| io::Error::new(io::ErrorKind::Other, e));
         future::result(stream)
     }
 
     fn poll_outbound(&self, substream: &mut Self::OutboundSubstream) -> Poll<Self::Substream, IoError> {
         match substream.poll()? {
-            Async::Ready(Some(s)) => Ok(Async::Ready(s)),
+            Async::Ready(Some(s)) => Ok(Async::Ready(YamuxSubstream::new(s))),
             Async::Ready(None) => Err(io::ErrorKind::BrokenPipe.into()),
             Async::NotReady => Ok(Async::NotReady),
         }
@@ -85,53 +175,132 @@ where
     }
 
     fn read_substream(&self, sub: &mut Self::Substream, buf: &mut [u8]) -> Poll<usize, IoError> {
-        let result = sub.poll_read(buf);
+        let result = sub.inner.poll_read(buf);
         if let Ok(Async::Ready(_)) = result {
-            self.1.store(true, atomic::Ordering::Release);
+            self.acknowledged.store(true, atomic::Ordering::Release);
         }
         result
     }
 
     fn write_substream(&self, sub: &mut Self::Substream, buf: &[u8]) -> Poll<usize, IoError> {
-        sub.poll_write(buf)
+        let result = sub.inner.poll_write(buf);
+        match result {
+            Ok(Async::NotReady) => self.stalls.on_write(&mut sub.stalled, true),
+            Ok(Async::Ready(_)) => self.stalls.on_write(&mut sub.stalled, false),
+            Err(_) => {}
+        }
+        result
     }
 
     fn flush_substream(&self, sub: &mut Self::Substream) -> Poll<(), IoError> {
-        sub.poll_flush()
+        sub.inner.poll_flush()
     }
 
     fn shutdown_substream(&self, sub: &mut Self::Substream) -> Poll<(), IoError> {
-        sub.shutdown()
+        sub.inner.shutdown()
     }
 
-    fn destroy_substream(&self, _: Self::Substream) {
+    fn destroy_substream(&self, mut sub: Self::Substream) {
+        self.stalls.on_write(&mut sub.stalled, false);
     }
 
     fn is_remote_acknowledged(&self) -> bool {
-        self.1.load(atomic::Ordering::Acquire)
+        self.acknowledged.load(atomic::Ordering::Acquire)
     }
 
     fn close(&self) -> Poll<(), IoError> {
-        self.0.close().map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        self.connection.close().map_err(|e| io::Error::new(io::ErrorKind::Other, e))
     }
 
     fn flush_all(&self) -> Poll<(), IoError> {
-        self.0.flush().map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        self.connection.flush().map_err(|e| io::Error::new(io::ErrorKind::Other, e))
     }
 }
 
+/// Configuration of the Yamux multiplexing protocol.
+///
+/// The connections created with a `Config`, and with its clones, share their `StallMetrics`.
 #[derive(Clone)]
-pub struct Config(yamux::Config);
+pub struct Config {
+    inner: yamux::Config,
+    keep_alive: Option<Duration>,
+    stalls: Arc<StallMetrics>,
+}
 
 impl Config {
+    /// Creates a configuration from a `yamux::Config`, which is used as is, without keep-alive.
     pub fn new(cfg: yamux::Config) -> Self {
-        Config(cfg)
+        Config { inner: cfg, keep_alive: None, stalls: Arc::new(StallMetrics::default()) }
+    }
+
+    /// Returns the statistics about the stalled substreams of all the connections created with
+    /// this configuration and its clones.
+    ///
+    /// Unlike `Yamux::stall_metrics`, they remain accessible once the connections have been
+    /// boxed, e.g. in a `StreamMuxerBox`.
+    pub fn stall_metrics(&self) -> Arc<StallMetrics> {
+        self.stalls.clone()
+    }
+
+    /// Sets the receive window of the substreams, i.e. the number of bytes the remote may send
+    /// on a substream before waiting for a window update.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `num_bytes` is smaller than the initial window of 256 KiB mandated by the
+    /// Yamux specification.
+    pub fn with_receive_window(mut self, num_bytes: u32) -> Self {
+        assert!(num_bytes >= 256 * 1024, "The receive window must be at least 256 KiB");
+        self.inner.set_receive_window(num_bytes);
+        self
+    }
+
+    /// Sets the maximum number of bytes buffered for a substream. The connection is closed if
+    /// the remote sends more data than that on a substream that isn't read.
+    pub fn with_max_buffer_size(mut self, num_bytes: usize) -> Self {
+        self.inner.set_max_buffer_size(num_bytes);
+        self
+    }
+
+    /// Sets the maximum number of substreams that can be open at the same time.
+    pub fn with_max_num_streams(mut self, num_streams: usize) -> Self {
+        self.inner.set_max_num_streams(num_streams);
+        self
+    }
+
+    /// Sets when window updates are sent to the remote.
+    ///
+    /// With `WindowUpdateMode::OnReceive`, the receive window is replenished as soon as data is
+    /// received, regardless of whether it is read. With `WindowUpdateMode::OnRead`, it is only
+    /// replenished once the data has been read, which applies back-pressure to the remote.
+    pub fn with_window_update_mode(mut self, mode: WindowUpdateMode) -> Self {
+        self.inner.set_window_update_mode(mode);
+        self
+    }
+
+    /// Sets whether the data received on a substream can still be read after the connection
+    /// has been closed.
+    pub fn with_read_after_close(mut self, read_after_close: bool) -> Self {
+        self.inner.set_read_after_close(read_after_close);
+        self
+    }
+
+    /// Sets the keep-alive interval, or `None` to disable keep-alive, which is the default.
+    ///
+    /// When nothing has been received from the remote during the interval, a ping frame is
+    /// sent, which the remote answers. The connection is closed with an error if nothing is
+    /// received during the following interval either.
+    pub fn with_keep_alive(mut self, interval: Option<Duration>) -> Self {
+        self.keep_alive = interval;
+        self
     }
 }
 
 impl Default for Config {
+    /// Creates a configuration with the default settings of `yamux::Config`, except that the
+    /// substreams can't be read after the connection has been closed.
     fn default() -> Self {
-        Config(yamux::Config::default())
+        Config::new(yamux::Config::default()).with_read_after_close(false)
     }
 }
 
@@ -153,7 +322,7 @@ where
     type Future = FutureResult<Yamux<Negotiated<C>>, io::Error>;
 
     fn upgrade_inbound(self, i: Negotiated<C>, _: Self::Info) -> Self::Future {
-        future::ok(Yamux::new(i, self.0, yamux::Mode::Server))
+        future::ok(Yamux::with_config(i, self.inner, yamux::Mode::Server, self.keep_alive, self.stalls))
     }
 }
 
@@ -166,7 +335,57 @@ where
     type Future = FutureResult<Yamux<Negotiated<C>>, io::Error>;
 
     fn upgrade_outbound(self, i: Negotiated<C>, _: Self::Info) -> Self::Future {
-        future::ok(Yamux::new(i, self.0, yamux::Mode::Client))
+        future::ok(Yamux::with_config(i, self.inner, yamux::Mode::Client, self.keep_alive, self.stalls))
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receive_window_of_spec_is_accepted() {
+        Config::default().with_receive_window(256 * 1024).with_receive_window(u32::max_value());
+    }
+
+    #[test]
+    #[should_panic(expected = "The receive window must be at least 256 KiB")]
+    fn receive_window_below_spec_is_rejected() {
+        Config::default().with_receive_window(256 * 1024 - 1);
+    }
+
+    #[test]
+    fn stalls_are_counted_once_per_stall() {
+        let stalls = StallMetrics::default();
+        let (mut first, mut second) = (false, false);
+
+        stalls.on_write(&mut first, true);
+        stalls.on_write(&mut first, true);
+        assert_eq!(stalls.stalled_substreams(), 1);
+        assert_eq!(stalls.total_stalls(), 1);
+
+        stalls.on_write(&mut second, true);
+        assert_eq!(stalls.stalled_substreams(), 2);
+        assert_eq!(stalls.total_stalls(), 2);
+
+        stalls.on_write(&mut first, false);
+        stalls.on_write(&mut first, false);
+        assert_eq!(stalls.stalled_substreams(), 1);
+        assert_eq!(stalls.total_stalls(), 2);
+
+        stalls.on_write(&mut first, true);
+        stalls.on_write(&mut second, false);
+        assert_eq!(stalls.stalled_substreams(), 1);
+        assert_eq!(stalls.total_stalls(), 3);
+    }
+
+    #[test]
+    fn stall_metrics_are_shared_by_config_clones() {
+        let config = Config::default();
+        let stalls = config.stall_metrics();
+        let mut stalled = false;
+        config.clone().stall_metrics().on_write(&mut stalled, true);
+        assert!(Arc::ptr_eq(&stalls, &config.clone().stall_metrics()));
+        assert_eq!(stalls.stalled_substreams(), 1);
+    }
+}
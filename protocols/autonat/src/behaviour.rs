@@ -0,0 +1,643 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::handler::{AutoNatHandler, AutoNatHandlerEvent};
+use crate::protocol::{DialBackRequest, DialResponse, ProbeRequest};
+use futures::{future::{self, Either, Loop}, prelude::*};
+use libp2p_core::{
+    ConnectedPoint,
+    Multiaddr,
+    PeerId,
+    Transport,
+    multiaddr::Protocol,
+    upgrade::{Negotiated, UpgradeError}
+};
+use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, ProtocolsHandlerUpgrErr};
+use log::debug;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    marker::PhantomData,
+    net::IpAddr,
+    sync::Arc,
+    time::Duration
+};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Maximum number of addresses of a remote we dial back for a single request.
+const MAX_DIAL_BACK_ADDRS: usize = 16;
+
+/// Configuration for the `AutoNat` network behaviour.
+#[derive(Debug, Clone)]
+pub struct AutoNatConfig {
+    /// Delay before the first probe.
+    boot_delay: Duration,
+    /// Interval between probes while the status is uncertain.
+    retry_interval: Duration,
+    /// Interval between probes once the status is established.
+    refresh_interval: Duration,
+    /// Minimum duration between two probes sent to the same peer.
+    peer_throttle: Duration,
+    /// Number of consecutive probes contradicting the status before it changes.
+    confidence_threshold: usize,
+    /// Timeout of a dial back.
+    dial_back_timeout: Duration,
+    /// Maximum number of remotes we dial back at the same time.
+    max_concurrent_dial_backs: usize,
+}
+
+impl AutoNatConfig {
+    /// Creates a new `AutoNatConfig` with the following default settings:
+    ///
+    ///   * [`AutoNatConfig::with_boot_delay`] 15s
+    ///   * [`AutoNatConfig::with_retry_interval`] 90s
+    ///   * [`AutoNatConfig::with_refresh_interval`] 15min
+    ///   * [`AutoNatConfig::with_peer_throttle`] 90s
+    ///   * [`AutoNatConfig::with_confidence_threshold`] 3
+    ///   * [`AutoNatConfig::with_dial_back_timeout`] 15s
+    ///   * [`AutoNatConfig::with_max_concurrent_dial_backs`] 4
+    pub fn new() -> Self {
+        AutoNatConfig {
+            boot_delay: Duration::from_secs(15),
+            retry_interval: Duration::from_secs(90),
+            refresh_interval: Duration::from_secs(15 * 60),
+            peer_throttle: Duration::from_secs(90),
+            confidence_threshold: 3,
+            dial_back_timeout: Duration::from_secs(15),
+            max_concurrent_dial_backs: 4,
+        }
+    }
+
+    /// Sets the delay before the first probe.
+    pub fn with_boot_delay(mut self, d: Duration) -> Self {
+        self.boot_delay = d;
+        self
+    }
+
+    /// Sets the interval between probes while the status is unknown or being contradicted.
+    pub fn with_retry_interval(mut self, d: Duration) -> Self {
+        self.retry_interval = d;
+        self
+    }
+
+    /// Sets the interval between probes once the status is established.
+    pub fn with_refresh_interval(mut self, d: Duration) -> Self {
+        self.refresh_interval = d;
+        self
+    }
+
+    /// Sets the minimum duration between two probes sent to the same peer.
+    pub fn with_peer_throttle(mut self, d: Duration) -> Self {
+        self.peer_throttle = d;
+        self
+    }
+
+    /// Sets the number of consecutive probes that must contradict the current status before
+    /// it changes. The first conclusive probe always determines the status.
+    pub fn with_confidence_threshold(mut self, n: usize) -> Self {
+        self.confidence_threshold = n;
+        self
+    }
+
+    /// Sets the timeout of a dial back, when acting as a server.
+    pub fn with_dial_back_timeout(mut self, d: Duration) -> Self {
+        self.dial_back_timeout = d;
+        self
+    }
+
+    /// Sets the maximum number of remotes dialed back at the same time, when acting as a
+    /// server. Further requests are refused.
+    pub fn with_max_concurrent_dial_backs(mut self, n: usize) -> Self {
+        self.max_concurrent_dial_backs = n;
+        self
+    }
+}
+
+impl Default for AutoNatConfig {
+    fn default() -> Self {
+        AutoNatConfig::new()
+    }
+}
+
+/// Reachability of the local node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NatStatus {
+    /// Other nodes can dial us on at least one of our addresses.
+    Public,
+    /// Other nodes fail to dial us on any of our addresses.
+    Private,
+    /// No conclusive probe has been made yet.
+    Unknown,
+}
+
+/// Event produced by the `AutoNat` network behaviour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AutoNatEvent {
+    /// Our reachability changed.
+    StatusChanged {
+        /// The previous status.
+        old: NatStatus,
+        /// The new status.
+        new: NatStatus,
+    },
+    /// A remote managed to dial us on the given address. The address has been added to the
+    /// external addresses of the `Swarm`.
+    AddressConfirmed(Multiaddr),
+    /// Our status changed to `NatStatus::Private`, and the given address, which a remote
+    /// previously managed to dial us on, has been removed from the external addresses of the
+    /// `Swarm`.
+    AddressExpired(Multiaddr),
+}
+
+/// Dials an address and resolves to the identity of the node reached.
+type DialBack = Arc<dyn Fn(Multiaddr) -> Box<dyn Future<Item = PeerId, Error = ()> + Send> + Send + Sync>;
+
+/// Network behaviour that determines whether we are publicly reachable, by asking the peers
+/// we are connected to to dial us back on our addresses.
+///
+/// The candidate addresses are the external addresses of the `Swarm`, for example the ones
+/// reported by identify, and the addresses we listen on. The addresses on which a remote
+/// manages to dial us are added to the external addresses of the `Swarm`. When our status
+/// changes to `NatStatus::Private`, these confirmed addresses are removed from them again. The
+/// other external addresses are left untouched.
+///
+/// If created with `AutoNat::with_dial_back`, the behaviour also dials back the peers that ask
+/// us to. Only the addresses sharing the IP address of the connection with the remote are
+/// dialed.
+pub struct AutoNat<TSubstream> {
+    /// The configuration of the behaviour.
+    config: AutoNatConfig,
+    /// Used to dial back remotes, if we act as a server.
+    dial_back: Option<DialBack>,
+    /// Peers we are connected to, with the address of the connection.
+    connected_peers: HashMap<PeerId, Multiaddr>,
+    /// Peers that don't support the protocol.
+    unsupported_peers: HashSet<PeerId>,
+    /// When each peer has last been sent a probe.
+    last_probed: HashMap<PeerId, Instant>,
+    /// The probe in progress, if any, with the peer it was sent to and the candidate addresses.
+    pending_probe: Option<(PeerId, Vec<Multiaddr>)>,
+    /// When to send the next probe.
+    next_probe: Delay,
+    /// Our current reachability.
+    status: NatStatus,
+    /// Number of consecutive probes that contradicted `status`.
+    contradictions: usize,
+    /// The addresses remotes managed to dial us on.
+    confirmed_addrs: Vec<Multiaddr>,
+    /// The remotes being dialed back, along with the future dialing them and responding.
+    dial_backs: Vec<(PeerId, Box<dyn Future<Item = (), Error = ()> + Send>)>,
+    /// Events waiting to be returned by `poll`.
+    events: VecDeque<NetworkBehaviourAction<ProbeRequest, AutoNatEvent>>,
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> AutoNat<TSubstream> {
+    /// Creates an `AutoNat` behaviour that probes our reachability but doesn't dial back other
+    /// peers.
+    pub fn new(config: AutoNatConfig) -> Self {
+        AutoNat {
+            next_probe: Delay::new(Instant::now() + config.boot_delay),
+            config,
+            dial_back: None,
+            connected_peers: HashMap::new(),
+            unsupported_peers: HashSet::new(),
+            last_probed: HashMap::new(),
+            pending_probe: None,
+            status: NatStatus::Unknown,
+            contradictions: 0,
+            confirmed_addrs: Vec::new(),
+            dial_backs: Vec::new(),
+            events: VecDeque::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates an `AutoNat` behaviour that also dials back the peers asking for it, using
+    /// `transport`.
+    ///
+    /// The transport must authenticate the remote, so that we only report a successful dial
+    /// back if we reached the peer that asked for it.
+    pub fn with_dial_back<TTransport, TMuxer>(config: AutoNatConfig, transport: TTransport) -> Self
+    where
+        TTransport: Transport<Output = (PeerId, TMuxer)> + Clone + Send + Sync + 'static,
+        TTransport::Dial: Send + 'static,
+        TMuxer: Send + 'static,
+    {
+        let dial_back = move |addr: Multiaddr| -> Box<dyn Future<Item = PeerId, Error = ()> + Send> {
+            match transport.clone().dial(addr) {
+                Ok(dial) => Box::new(dial.map(|(peer_id, _)| peer_id).map_err(|_| ())),
+                Err(_) => Box::new(future::err(())),
+            }
+        };
+
+        let mut autonat = AutoNat::new(config);
+        autonat.dial_back = Some(Arc::new(dial_back));
+        autonat
+    }
+
+    /// Returns our current reachability.
+    pub fn nat_status(&self) -> NatStatus {
+        self.status
+    }
+
+    /// Returns the addresses remotes managed to dial us on.
+    pub fn confirmed_addresses(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.confirmed_addrs.iter()
+    }
+
+    /// Sends a probe to one of the peers we are connected to, if possible.
+    fn start_probe(&mut self, params: &mut impl PollParameters) {
+        let now = Instant::now();
+
+        let mut addrs = params.external_addresses().collect::<Vec<_>>();
+        for addr in params.listened_addresses() {
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+        if addrs.is_empty() {
+            debug!("No address to probe");
+            return;
+        }
+
+        let peer_throttle = self.config.peer_throttle;
+        let last_probed = &self.last_probed;
+        let server = self.connected_peers.keys()
+            .filter(|p| !self.unsupported_peers.contains(p))
+            .filter(|p| last_probed.get(p).map_or(true, |t| now.duration_since(*t) >= peer_throttle))
+            .min_by_key(|p| last_probed.get(p).cloned())
+            .cloned();
+        let server = match server {
+            Some(server) => server,
+            None => {
+                debug!("No peer to send a probe to");
+                return;
+            }
+        };
+
+        debug!("Asking {:?} to dial us back on {:?}", server, addrs);
+        self.last_probed.insert(server.clone(), now);
+        self.pending_probe = Some((server.clone(), addrs.clone()));
+        self.events.push_back(NetworkBehaviourAction::SendEvent {
+            peer_id: server,
+            event: ProbeRequest {
+                peer_id: params.local_peer_id().clone(),
+                addrs,
+            },
+        });
+    }
+
+    /// Processes the response to our probe, which was sent with the candidate addresses `addrs`.
+    fn on_probe_response(&mut self, addrs: Vec<Multiaddr>, response: DialResponse) {
+        match response {
+            DialResponse::Ok(addr) => {
+                if !addrs.contains(&addr) {
+                    debug!("Ignoring probe response with an address we didn't send: {}", addr);
+                    return;
+                }
+                if !self.confirmed_addrs.contains(&addr) {
+                    self.confirmed_addrs.push(addr.clone());
+                    self.events.push_back(NetworkBehaviourAction::AddExternalAddr {
+                        address: addr.clone(),
+                    });
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                        AutoNatEvent::AddressConfirmed(addr),
+                    ));
+                }
+                self.update_status(NatStatus::Public);
+            }
+            DialResponse::DialError => {
+                self.update_status(NatStatus::Private);
+                if self.status != NatStatus::Private {
+                    return;
+                }
+                // Only forget the addresses we confirmed ourselves, the other external
+                // addresses of the `Swarm` aren't ours to remove.
+                for addr in self.confirmed_addrs.drain(..) {
+                    self.events.push_back(NetworkBehaviourAction::RemoveExternalAddr {
+                        address: addr.clone(),
+                    });
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                        AutoNatEvent::AddressExpired(addr),
+                    ));
+                }
+            }
+            DialResponse::DialRefused | DialResponse::BadRequest | DialResponse::InternalError => {
+                debug!("Inconclusive probe: {:?}", response);
+            }
+        }
+    }
+
+    /// Takes the outcome of a conclusive probe into account.
+    fn update_status(&mut self, observed: NatStatus) {
+        if observed == self.status {
+            self.contradictions = 0;
+            return;
+        }
+
+        self.contradictions += 1;
+        if self.status == NatStatus::Unknown || self.contradictions >= self.config.confidence_threshold {
+            let old = self.status;
+            self.status = observed;
+            self.contradictions = 0;
+            self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                AutoNatEvent::StatusChanged { old, new: observed },
+            ));
+        }
+    }
+
+    /// Returns the interval before the next probe.
+    fn probe_interval(&self) -> Duration {
+        if self.status == NatStatus::Unknown || self.contradictions > 0 {
+            self.config.retry_interval
+        } else {
+            self.config.refresh_interval
+        }
+    }
+
+    /// Processes a request from `peer_id` to be dialed back.
+    fn on_dial_back_request(&mut self, peer_id: PeerId, request: DialBackRequest<Negotiated<TSubstream>>)
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let observed_ip = self.connected_peers.get(&peer_id).and_then(ip_addr);
+        let addrs = request.addrs()
+            .iter()
+            .filter(|a| observed_ip.is_some() && ip_addr(a) == observed_ip)
+            .take(MAX_DIAL_BACK_ADDRS)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let dial_back = match self.dial_back.clone() {
+            Some(dial_back) => dial_back,
+            None => return self.respond(peer_id, request, DialResponse::DialRefused),
+        };
+        if request.peer_id() != &peer_id {
+            return self.respond(peer_id, request, DialResponse::BadRequest);
+        }
+        if addrs.is_empty()
+            || self.dial_backs.len() >= self.config.max_concurrent_dial_backs
+            || self.dial_backs.iter().any(|(p, _)| p == &peer_id)
+        {
+            return self.respond(peer_id, request, DialResponse::DialRefused);
+        }
+
+        debug!("Dialing back {:?} on {:?}", peer_id, addrs);
+        let future = dial_back_any(dial_back, addrs, peer_id.clone(), self.config.dial_back_timeout)
+            .then(move |result| {
+                let response = match result {
+                    Ok(addr) => DialResponse::Ok(addr),
+                    Err(()) => DialResponse::DialError,
+                };
+                request.respond(response)
+            })
+            .map(|_| ())
+            .map_err(|err| debug!("Failed to send dial back response: {:?}", err));
+        self.dial_backs.push((peer_id, Box::new(future)));
+    }
+
+    /// Sends a response to a dial back request without dialing the remote.
+    fn respond(&mut self, peer_id: PeerId, request: DialBackRequest<Negotiated<TSubstream>>, response: DialResponse)
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        debug!("Responding {:?} to {:?} without dialing back", response, peer_id);
+        let future = request.respond(response)
+            .map(|_| ())
+            .map_err(|err| debug!("Failed to send dial back response: {:?}", err));
+        self.dial_backs.push((peer_id, Box::new(future)));
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for AutoNat<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type ProtocolsHandler = AutoNatHandler<TSubstream>;
+    type OutEvent = AutoNatEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        AutoNatHandler::new()
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        let addr = match endpoint {
+            ConnectedPoint::Dialer { address } => address,
+            ConnectedPoint::Listener { send_back_addr, .. } => send_back_addr,
+        };
+        self.connected_peers.insert(peer_id, addr);
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.connected_peers.remove(peer_id);
+        if self.pending_probe.as_ref().map_or(false, |(p, _)| p == peer_id) {
+            self.pending_probe = None;
+        }
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: AutoNatHandlerEvent<TSubstream>) {
+        match event {
+            AutoNatHandlerEvent::DialBackRequest(request) => {
+                self.on_dial_back_request(peer_id, request);
+            }
+            AutoNatHandlerEvent::ProbeResponse(response) => {
+                if self.pending_probe.as_ref().map_or(true, |(p, _)| p != &peer_id) {
+                    return;
+                }
+                if let Some((_, addrs)) = self.pending_probe.take() {
+                    self.on_probe_response(addrs, response);
+                    self.next_probe.reset(Instant::now() + self.probe_interval());
+                }
+            }
+            AutoNatHandlerEvent::ProbeFailed(error) => {
+                debug!("Probe sent to {:?} failed: {:?}", peer_id, error);
+                if let ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(_)) = error {
+                    self.unsupported_peers.insert(peer_id.clone());
+                }
+                if self.pending_probe.as_ref().map_or(false, |(p, _)| p == &peer_id) {
+                    self.pending_probe = None;
+                }
+            }
+        }
+    }
+
+    fn poll(
+        &mut self,
+        params: &mut impl PollParameters,
+    ) -> Async<NetworkBehaviourAction<ProbeRequest, Self::OutEvent>> {
+        match self.next_probe.poll() {
+            Ok(Async::NotReady) => {}
+            Ok(Async::Ready(())) => {
+                // Retry later in case the probe can't be sent or gets no response.
+                self.next_probe.reset(Instant::now() + self.config.retry_interval);
+                if self.pending_probe.is_none() {
+                    self.start_probe(params);
+                }
+            }
+            Err(err) => {
+                debug!("Probe timer errored: {:?}", err);
+                self.next_probe.reset(Instant::now() + self.config.retry_interval);
+            }
+        }
+
+        let mut n = 0;
+        while n < self.dial_backs.len() {
+            match self.dial_backs[n].1.poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) | Err(()) => {
+                    self.dial_backs.swap_remove(n);
+                }
+            }
+        }
+
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(event);
+        }
+
+        Async::NotReady
+    }
+}
+
+/// Dials the addresses one after the other until we reach `peer_id`. Resolves to the address
+/// on which `peer_id` was reached.
+fn dial_back_any(dial_back: DialBack, addrs: Vec<Multiaddr>, peer_id: PeerId, timeout: Duration)
+    -> impl Future<Item = Multiaddr, Error = ()> + Send
+{
+    future::loop_fn(addrs.into_iter(), move |mut addrs| {
+        let addr = match addrs.next() {
+            Some(addr) => addr,
+            None => return Either::A(future::err(())),
+        };
+
+        let expected = peer_id.clone();
+        let dial = (*dial_back)(addr.clone())
+            .select2(Delay::new(Instant::now() + timeout))
+            .then(move |result| match result {
+                Ok(Either::A((reached, _))) if reached == expected => Ok(Loop::Break(addr)),
+                _ => Ok(Loop::Continue(addrs)),
+            });
+        Either::B(dial)
+    })
+}
+
+/// Returns the IP address of a multiaddress, if it starts with one.
+fn ip_addr(addr: &Multiaddr) -> Option<IpAddr> {
+    match addr.iter().next()? {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn autonat() -> AutoNat<()> {
+        AutoNat::new(AutoNatConfig::new().with_confidence_threshold(2))
+    }
+
+    fn status_changes(autonat: &mut AutoNat<()>) -> Vec<(NatStatus, NatStatus)> {
+        autonat.events.drain(..).filter_map(|e| match e {
+            NetworkBehaviourAction::GenerateEvent(AutoNatEvent::StatusChanged { old, new }) => Some((old, new)),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn first_conclusive_probe_sets_status() {
+        let mut autonat = autonat();
+        autonat.on_probe_response(Vec::new(), DialResponse::DialRefused);
+        assert_eq!(autonat.nat_status(), NatStatus::Unknown);
+
+        autonat.on_probe_response(Vec::new(), DialResponse::DialError);
+        assert_eq!(autonat.nat_status(), NatStatus::Private);
+        assert_eq!(status_changes(&mut autonat), vec![(NatStatus::Unknown, NatStatus::Private)]);
+    }
+
+    #[test]
+    fn status_changes_after_confidence_threshold() {
+        let mut autonat = autonat();
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/5".parse().unwrap();
+        autonat.on_probe_response(vec![addr.clone()], DialResponse::Ok(addr.clone()));
+        assert_eq!(autonat.nat_status(), NatStatus::Public);
+        assert_eq!(autonat.confirmed_addresses().collect::<Vec<_>>(), vec![&addr]);
+        status_changes(&mut autonat);
+
+        // A single failure doesn't make us forget the confirmed address.
+        autonat.on_probe_response(vec![addr.clone()], DialResponse::DialError);
+        assert_eq!(autonat.nat_status(), NatStatus::Public);
+        assert_eq!(autonat.confirmed_addresses().collect::<Vec<_>>(), vec![&addr]);
+        assert!(autonat.events.is_empty());
+
+        autonat.on_probe_response(vec![addr.clone()], DialResponse::DialError);
+        assert_eq!(autonat.nat_status(), NatStatus::Private);
+        assert_eq!(autonat.confirmed_addresses().count(), 0);
+        assert!(autonat.events.iter().any(|e| match e {
+            NetworkBehaviourAction::RemoveExternalAddr { address } => address == &addr,
+            _ => false,
+        }));
+        assert_eq!(status_changes(&mut autonat), vec![(NatStatus::Public, NatStatus::Private)]);
+    }
+
+    #[test]
+    fn address_not_probed_is_ignored() {
+        let mut autonat = autonat();
+        let probed: Multiaddr = "/ip4/1.2.3.4/tcp/5".parse().unwrap();
+        let other: Multiaddr = "/ip4/5.6.7.8/tcp/5".parse().unwrap();
+        autonat.on_probe_response(vec![probed], DialResponse::Ok(other));
+        assert_eq!(autonat.nat_status(), NatStatus::Unknown);
+        assert_eq!(autonat.confirmed_addresses().count(), 0);
+        assert!(autonat.events.is_empty());
+    }
+
+    #[test]
+    fn only_confirmed_addresses_are_removed() {
+        let mut autonat = autonat();
+        let confirmed: Multiaddr = "/ip4/1.2.3.4/tcp/5".parse().unwrap();
+        let listened: Multiaddr = "/ip4/10.0.0.1/tcp/5".parse().unwrap();
+        autonat.on_probe_response(vec![confirmed.clone(), listened.clone()], DialResponse::Ok(confirmed.clone()));
+        autonat.events.clear();
+
+        for _ in 0 .. 2 {
+            autonat.on_probe_response(vec![confirmed.clone(), listened.clone()], DialResponse::DialError);
+        }
+        assert_eq!(autonat.nat_status(), NatStatus::Private);
+        let removed = autonat.events.iter().filter_map(|e| match e {
+            NetworkBehaviourAction::RemoveExternalAddr { address } => Some(address.clone()),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(removed, vec![confirmed]);
+    }
+
+    #[test]
+    fn ip_of_address() {
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/5".parse().unwrap();
+        assert_eq!(ip_addr(&addr), Some("1.2.3.4".parse().unwrap()));
+        let addr: Multiaddr = "/dns4/example.com/tcp/5".parse().unwrap();
+        assert_eq!(ip_addr(&addr), None);
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::protocol::{AutoNatError, AutoNatProtocol, DialBackRequest, DialResponse, ProbeRequest};
+use futures::prelude::*;
+use libp2p_core::upgrade::{InboundUpgrade, Negotiated, OutboundUpgrade};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use smallvec::SmallVec;
+use std::{marker::PhantomData, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::Instant;
+
+/// Duration after which an idle connection is closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Duration during which the connection is kept alive after a remote asked to be dialed back,
+/// so that the response can be delivered.
+const DIAL_BACK_KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Protocol handler for the AutoNAT protocol.
+///
+/// Sends the probes requested by the `AutoNat` behaviour and hands the requests of the remote
+/// over to it.
+pub struct AutoNatHandler<TSubstream> {
+    /// Probes waiting for a substream to be opened.
+    queued_probes: SmallVec<[ProbeRequest; 2]>,
+    /// Number of probes being sent.
+    pending_probes: usize,
+    /// Events waiting to be reported to the behaviour.
+    pending_events: SmallVec<[AutoNatHandlerEvent<TSubstream>; 2]>,
+    /// Until when the connection is kept alive if there is no pending probe.
+    keep_alive: KeepAlive,
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+/// Event produced by an `AutoNatHandler`.
+pub enum AutoNatHandlerEvent<TSubstream> {
+    /// The remote asks to be dialed back.
+    DialBackRequest(DialBackRequest<Negotiated<TSubstream>>),
+    /// The remote answered one of our probes.
+    ProbeResponse(DialResponse),
+    /// One of our probes failed.
+    ProbeFailed(ProtocolsHandlerUpgrErr<AutoNatError>),
+}
+
+impl<TSubstream> AutoNatHandler<TSubstream> {
+    /// Builds a new `AutoNatHandler`.
+    pub fn new() -> Self {
+        AutoNatHandler {
+            queued_probes: SmallVec::new(),
+            pending_probes: 0,
+            pending_events: SmallVec::new(),
+            keep_alive: KeepAlive::Until(Instant::now() + IDLE_TIMEOUT),
+            marker: PhantomData,
+        }
+    }
+
+    /// Extends the duration during which the connection is kept alive, if needed.
+    fn keep_alive_for(&mut self, duration: Duration) {
+        let keep_alive = KeepAlive::Until(Instant::now() + duration);
+        if keep_alive > self.keep_alive {
+            self.keep_alive = keep_alive;
+        }
+    }
+}
+
+impl<TSubstream> Default for AutoNatHandler<TSubstream> {
+    fn default() -> Self {
+        AutoNatHandler::new()
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for AutoNatHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type InEvent = ProbeRequest;
+    type OutEvent = AutoNatHandlerEvent<TSubstream>;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = AutoNatProtocol;
+    type OutboundProtocol = ProbeRequest;
+    type OutboundOpenInfo = ();
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        SubstreamProtocol::new(AutoNatProtocol)
+    }
+
+    fn inject_fully_negotiated_inbound(
+        &mut self,
+        request: <Self::InboundProtocol as InboundUpgrade<TSubstream>>::Output
+    ) {
+        self.keep_alive_for(DIAL_BACK_KEEP_ALIVE);
+        self.pending_events.push(AutoNatHandlerEvent::DialBackRequest(request));
+    }
+
+    fn inject_fully_negotiated_outbound(
+        &mut self,
+        response: <Self::OutboundProtocol as OutboundUpgrade<TSubstream>>::Output,
+        _: Self::OutboundOpenInfo
+    ) {
+        self.pending_probes -= 1;
+        self.keep_alive_for(IDLE_TIMEOUT);
+        self.pending_events.push(AutoNatHandlerEvent::ProbeResponse(response));
+    }
+
+    fn inject_event(&mut self, probe: Self::InEvent) {
+        self.queued_probes.push(probe);
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        _: Self::OutboundOpenInfo,
+        error: ProtocolsHandlerUpgrErr<
+            <Self::OutboundProtocol as OutboundUpgrade<Self::Substream>>::Error
+        >
+    ) {
+        self.pending_probes -= 1;
+        self.keep_alive_for(IDLE_TIMEOUT);
+        self.pending_events.push(AutoNatHandlerEvent::ProbeFailed(error));
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        if self.pending_probes > 0 || !self.queued_probes.is_empty() {
+            KeepAlive::Yes
+        } else {
+            self.keep_alive
+        }
+    }
+
+    fn poll(
+        &mut self,
+    ) -> Poll<
+        ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent>,
+        Self::Error,
+    > {
+        if !self.pending_events.is_empty() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(
+                self.pending_events.remove(0),
+            )));
+        }
+
+        if !self.queued_probes.is_empty() {
+            self.pending_probes += 1;
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+                protocol: SubstreamProtocol::new(self.queued_probes.remove(0)),
+                info: (),
+            }));
+        }
+
+        Ok(Async::NotReady)
+    }
+}
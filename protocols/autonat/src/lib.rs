@@ -0,0 +1,47 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the AutoNAT protocol (`/libp2p/autonat/1.0.0`), which lets a node find out
+//! whether it is reachable from the outside.
+//!
+//! A node sends its candidate addresses to one of the peers it is connected to, which tries to
+//! dial them back and reports whether it succeeded. Depending on the answers, the node
+//! considers itself `Public` or `Private`.
+//!
+//! # Usage
+//!
+//! The `AutoNat` network behaviour periodically probes the peers it is connected to and
+//! produces an `AutoNatEvent` whenever the status changes or an address is confirmed. Confirmed
+//! addresses are added to the external addresses of the `Swarm`, and addresses that remotes
+//! fail to dial are removed from them.
+//!
+//! A node created with `AutoNat::with_dial_back` also dials back the peers that ask it to. The
+//! dial backs go through a separate transport, since the `Swarm` keeps a single connection per
+//! peer and dialing through it would replace the connection the request came from.
+
+pub mod protocol;
+
+mod behaviour;
+mod handler;
+mod structs_proto;
+
+pub use self::behaviour::{AutoNat, AutoNatConfig, AutoNatEvent, NatStatus};
+pub use self::handler::{AutoNatHandler, AutoNatHandlerEvent};
+pub use self::protocol::{AutoNatError, DialResponse};
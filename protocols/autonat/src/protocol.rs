@@ -0,0 +1,320 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::structs_proto;
+use libp2p_core::{
+    Multiaddr,
+    PeerId,
+    upgrade::{self, InboundUpgrade, OutboundUpgrade, UpgradeInfo, Negotiated}
+};
+use protobuf::{Message as ProtobufMessage, ProtobufError, RepeatedField};
+use std::{convert::TryFrom, error, fmt, iter};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Maximum size of an AutoNAT message, in bytes.
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Upgrade that accepts requests from remotes to be dialed back.
+#[derive(Debug, Clone, Default)]
+pub struct AutoNatProtocol;
+
+impl UpgradeInfo for AutoNatProtocol {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/libp2p/autonat/1.0.0")
+    }
+}
+
+impl<C> InboundUpgrade<C> for AutoNatProtocol
+where
+    C: AsyncRead + AsyncWrite,
+{
+    type Output = DialBackRequest<Negotiated<C>>;
+    type Error = AutoNatError;
+    type Future = upgrade::ReadRespond<
+        Negotiated<C>,
+        (),
+        fn(Negotiated<C>, Vec<u8>, ()) -> Result<Self::Output, AutoNatError>
+    >;
+
+    fn upgrade_inbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        upgrade::read_respond(socket, MAX_MESSAGE_SIZE, (), |socket, packet, ()| {
+            let (peer_id, addrs) = decode_request(&packet)?;
+            Ok(DialBackRequest { socket, peer_id, addrs })
+        })
+    }
+}
+
+/// Request from a remote to be dialed back on the given addresses.
+///
+/// The remote waits for the response, which must be sent with `respond`.
+pub struct DialBackRequest<TSubstream> {
+    socket: TSubstream,
+    peer_id: PeerId,
+    addrs: Vec<Multiaddr>,
+}
+
+impl<TSubstream> DialBackRequest<TSubstream> {
+    /// Returns the identity the remote claims to have.
+    pub fn peer_id(&self) -> &PeerId {
+        &self.peer_id
+    }
+
+    /// Returns the addresses the remote wants us to dial.
+    pub fn addrs(&self) -> &[Multiaddr] {
+        &self.addrs
+    }
+}
+
+impl<TSubstream> DialBackRequest<TSubstream>
+where
+    TSubstream: AsyncWrite,
+{
+    /// Sends the response to the remote.
+    pub fn respond(self, response: DialResponse) -> upgrade::WriteOne<TSubstream> {
+        upgrade::write_one(self.socket, encode_response(response))
+    }
+}
+
+impl<TSubstream> fmt::Debug for DialBackRequest<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DialBackRequest")
+            .field("peer_id", &self.peer_id)
+            .field("addrs", &self.addrs)
+            .finish()
+    }
+}
+
+/// Upgrade that asks the remote to dial us back on the given addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeRequest {
+    /// Our own identity.
+    pub peer_id: PeerId,
+    /// The addresses on which we want to be dialed.
+    pub addrs: Vec<Multiaddr>,
+}
+
+impl UpgradeInfo for ProbeRequest {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/libp2p/autonat/1.0.0")
+    }
+}
+
+impl<C> OutboundUpgrade<C> for ProbeRequest
+where
+    C: AsyncRead + AsyncWrite,
+{
+    type Output = DialResponse;
+    type Error = AutoNatError;
+    type Future = upgrade::RequestResponse<
+        Negotiated<C>,
+        (),
+        fn(Vec<u8>, ()) -> Result<DialResponse, AutoNatError>
+    >;
+
+    fn upgrade_outbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        let bytes = encode_request(self);
+        upgrade::request_response(socket, bytes, MAX_MESSAGE_SIZE, (), |packet, ()| {
+            decode_response(&packet)
+        })
+    }
+}
+
+/// Response to a `ProbeRequest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DialResponse {
+    /// The remote reached us on the given address.
+    Ok(Multiaddr),
+    /// The remote failed to reach us on any of the addresses.
+    DialError,
+    /// The remote refused to dial us back.
+    DialRefused,
+    /// The remote considered the request invalid.
+    BadRequest,
+    /// The remote failed to process the request.
+    InternalError,
+}
+
+/// Error that can happen when receiving or sending an AutoNAT message.
+#[derive(Debug)]
+pub enum AutoNatError {
+    /// Error when reading the packet from the socket.
+    ReadError(upgrade::ReadOneError),
+    /// Error when decoding the raw buffer into a protobuf.
+    ProtobufError(ProtobufError),
+    /// The message is not of the expected type or lacks a required field.
+    InvalidMessage,
+}
+
+impl From<upgrade::ReadOneError> for AutoNatError {
+    fn from(err: upgrade::ReadOneError) -> Self {
+        AutoNatError::ReadError(err)
+    }
+}
+
+impl From<ProtobufError> for AutoNatError {
+    fn from(err: ProtobufError) -> Self {
+        AutoNatError::ProtobufError(err)
+    }
+}
+
+impl fmt::Display for AutoNatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutoNatError::ReadError(err) => write!(f, "Error while reading from socket: {}", err),
+            AutoNatError::ProtobufError(err) => write!(f, "Error while decoding protobuf: {}", err),
+            AutoNatError::InvalidMessage => write!(f, "Invalid AutoNAT message"),
+        }
+    }
+}
+
+impl error::Error for AutoNatError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            AutoNatError::ReadError(err) => Some(err),
+            AutoNatError::ProtobufError(err) => Some(err),
+            AutoNatError::InvalidMessage => None,
+        }
+    }
+}
+
+/// Turns a `ProbeRequest` into the bytes of a `DIAL` message.
+fn encode_request(request: ProbeRequest) -> Vec<u8> {
+    let mut peer = structs_proto::Message_PeerInfo::new();
+    peer.set_id(request.peer_id.into_bytes());
+    peer.set_addrs(RepeatedField::from_vec(request.addrs.into_iter().map(|a| a.to_vec()).collect()));
+
+    let mut dial = structs_proto::Message_Dial::new();
+    dial.set_peer(peer);
+
+    let mut message = structs_proto::Message::new();
+    message.set_field_type(structs_proto::Message_MessageType::DIAL);
+    message.set_dial(dial);
+    message
+        .write_to_bytes()
+        .expect("writing protobuf failed; should never happen")
+}
+
+/// Decodes a `DIAL` message. Invalid addresses are ignored.
+fn decode_request(packet: &[u8]) -> Result<(PeerId, Vec<Multiaddr>), AutoNatError> {
+    let message = protobuf::parse_from_bytes::<structs_proto::Message>(packet)?;
+    if message.get_field_type() != structs_proto::Message_MessageType::DIAL || !message.has_dial() {
+        return Err(AutoNatError::InvalidMessage);
+    }
+
+    let peer = message.get_dial().get_peer();
+    let peer_id = PeerId::from_bytes(peer.get_id().to_vec())
+        .map_err(|_| AutoNatError::InvalidMessage)?;
+    let addrs = peer.get_addrs()
+        .iter()
+        .filter_map(|a| Multiaddr::try_from(a.clone()).ok())
+        .collect();
+    Ok((peer_id, addrs))
+}
+
+/// Turns a `DialResponse` into the bytes of a `DIAL_RESPONSE` message.
+fn encode_response(response: DialResponse) -> Vec<u8> {
+    use structs_proto::Message_ResponseStatus as Status;
+
+    let mut dial_response = structs_proto::Message_DialResponse::new();
+    match response {
+        DialResponse::Ok(addr) => {
+            dial_response.set_status(Status::OK);
+            dial_response.set_addr(addr.to_vec());
+        }
+        DialResponse::DialError => dial_response.set_status(Status::E_DIAL_ERROR),
+        DialResponse::DialRefused => dial_response.set_status(Status::E_DIAL_REFUSED),
+        DialResponse::BadRequest => dial_response.set_status(Status::E_BAD_REQUEST),
+        DialResponse::InternalError => dial_response.set_status(Status::E_INTERNAL_ERROR),
+    }
+
+    let mut message = structs_proto::Message::new();
+    message.set_field_type(structs_proto::Message_MessageType::DIAL_RESPONSE);
+    message.set_dialResponse(dial_response);
+    message
+        .write_to_bytes()
+        .expect("writing protobuf failed; should never happen")
+}
+
+/// Decodes a `DIAL_RESPONSE` message.
+fn decode_response(packet: &[u8]) -> Result<DialResponse, AutoNatError> {
+    use structs_proto::Message_ResponseStatus as Status;
+
+    let message = protobuf::parse_from_bytes::<structs_proto::Message>(packet)?;
+    if message.get_field_type() != structs_proto::Message_MessageType::DIAL_RESPONSE
+        || !message.has_dialResponse()
+    {
+        return Err(AutoNatError::InvalidMessage);
+    }
+
+    let dial_response = message.get_dialResponse();
+    match dial_response.get_status() {
+        Status::OK => {
+            let addr = Multiaddr::try_from(dial_response.get_addr().to_vec())
+                .map_err(|_| AutoNatError::InvalidMessage)?;
+            Ok(DialResponse::Ok(addr))
+        }
+        Status::E_DIAL_ERROR => Ok(DialResponse::DialError),
+        Status::E_DIAL_REFUSED => Ok(DialResponse::DialRefused),
+        Status::E_BAD_REQUEST => Ok(DialResponse::BadRequest),
+        Status::E_INTERNAL_ERROR => Ok(DialResponse::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_round_trip() {
+        let request = ProbeRequest {
+            peer_id: PeerId::random(),
+            addrs: vec!["/ip4/1.2.3.4/tcp/5".parse().unwrap(), "/ip6/::1/tcp/6".parse().unwrap()],
+        };
+        let (peer_id, addrs) = decode_request(&encode_request(request.clone())).unwrap();
+        assert_eq!(peer_id, request.peer_id);
+        assert_eq!(addrs, request.addrs);
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let responses = vec![
+            DialResponse::Ok("/ip4/1.2.3.4/tcp/5".parse().unwrap()),
+            DialResponse::DialError,
+            DialResponse::DialRefused,
+            DialResponse::BadRequest,
+            DialResponse::InternalError,
+        ];
+        for response in responses {
+            assert_eq!(decode_response(&encode_response(response.clone())).unwrap(), response);
+        }
+    }
+
+    #[test]
+    fn reject_unexpected_message_type() {
+        let request = ProbeRequest { peer_id: PeerId::random(), addrs: Vec::new() };
+        assert!(decode_response(&encode_request(request)).is_err());
+    }
+}
@@ -0,0 +1,592 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Discovery of the peers subscribed to a topic through Kademlia provider records.
+//!
+//! The `TopicDiscovery` network behaviour combines a `Floodsub` and a `Kademlia` behaviour.
+//! Subscribing to a topic through `TopicDiscovery::subscribe` advertises the local node as a
+//! provider of a key derived from the hash of the topic, see `topic_key`, and periodically
+//! looks up the other providers of that key. The providers that are found are added to the
+//! partial view of floodsub, which connects to them and exchanges messages with them.
+//!
+//! This module is only available with the `kad` feature.
+
+use crate::layer::{Floodsub, FloodsubEvent};
+use crate::topic::{Topic, TopicHash};
+use fnv::FnvHashMap;
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, either::EitherOutput};
+use libp2p_core::nodes::ListenerId;
+use libp2p_kad::{
+    GetProvidersError,
+    GetProvidersOk,
+    Kademlia,
+    KademliaEvent,
+    record::{self, store::RecordStore}
+};
+use libp2p_swarm::{
+    IntoProtocolsHandler,
+    NetworkBehaviour,
+    NetworkBehaviourAction,
+    PollParameters,
+    ProtocolsHandler,
+    ProtocolsHandlerSelect
+};
+use smallvec::SmallVec;
+use std::{collections::VecDeque, error, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Instant, Interval};
+
+/// Configuration of a `TopicDiscovery` behaviour.
+#[derive(Debug, Clone)]
+pub struct TopicDiscoveryConfig {
+    /// Interval between two lookups of the providers of the topics we are subscribed to.
+    lookup_interval: Duration,
+    /// Maximum number of subscribers whose addresses we remember.
+    max_subscriber_addresses: usize,
+}
+
+impl TopicDiscoveryConfig {
+    /// Creates a new configuration with the following default settings:
+    ///
+    ///   * lookup of the providers of each topic every 5 minutes
+    ///   * addresses of up to 256 subscribers remembered
+    pub fn new() -> Self {
+        TopicDiscoveryConfig {
+            lookup_interval: Duration::from_secs(5 * 60),
+            max_subscriber_addresses: 256,
+        }
+    }
+
+    /// Sets the interval between two lookups of the providers of the topics we are subscribed
+    /// to. A lookup is also started immediately when subscribing to a topic.
+    pub fn with_lookup_interval(mut self, interval: Duration) -> Self {
+        self.lookup_interval = interval;
+        self
+    }
+
+    /// Sets the maximum number of subscribers whose addresses, as reported by Kademlia, we
+    /// remember in order to dial them. Beyond that, the addresses of the subscribers found the
+    /// longest ago are forgotten, and these subscribers can only be dialed if their addresses
+    /// are known otherwise, e.g. by the routing table of Kademlia.
+    pub fn with_max_subscriber_addresses(mut self, max: usize) -> Self {
+        self.max_subscriber_addresses = max;
+        self
+    }
+}
+
+impl Default for TopicDiscoveryConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Prefix of the Kademlia keys derived from topics.
+const TOPIC_KEY_PREFIX: &[u8] = b"/floodsub/";
+
+/// Returns the Kademlia key under which the subscribers of a topic advertise themselves.
+pub fn topic_key(topic: &TopicHash) -> record::Key {
+    let mut key = TOPIC_KEY_PREFIX.to_vec();
+    key.extend_from_slice(topic.clone().into_string().as_bytes());
+    record::Key::from(key)
+}
+
+/// Network behaviour that combines `Floodsub` with `Kademlia` in order to discover the peers
+/// subscribed to the same topics as us.
+pub struct TopicDiscovery<TSubstream, TStore> {
+    /// The floodsub behaviour.
+    floodsub: Floodsub<TSubstream>,
+
+    /// The Kademlia behaviour, used to advertise our topics and to find the other subscribers.
+    kademlia: Kademlia<TSubstream, TStore>,
+
+    /// The topics we are subscribed to, by Kademlia key.
+    topics: FnvHashMap<record::Key, TopicHash>,
+
+    /// Interval at which we look up the providers of our topics.
+    lookup_interval: Interval,
+
+    /// Number of ongoing lookups of providers of topics.
+    pending_lookups: usize,
+
+    /// Addresses of the peers reported by Kademlia during the ongoing lookups.
+    discovered_addresses: FnvHashMap<PeerId, SmallVec<[Multiaddr; 4]>>,
+
+    /// Addresses of the subscribers we found, which are not necessarily in the routing table
+    /// of Kademlia.
+    subscriber_addresses: FnvHashMap<PeerId, SmallVec<[Multiaddr; 4]>>,
+
+    /// The keys of `subscriber_addresses`, from the least to the most recently found.
+    subscribers_order: VecDeque<PeerId>,
+
+    /// Maximum number of entries in `subscriber_addresses`.
+    max_subscriber_addresses: usize,
+
+    /// Events that need to be yielded to the outside when polling.
+    events: VecDeque<TopicDiscoveryEvent>,
+}
+
+/// Event that can happen on the `TopicDiscovery` behaviour.
+#[derive(Debug)]
+pub enum TopicDiscoveryEvent {
+    /// An event of the floodsub behaviour.
+    Floodsub(FloodsubEvent),
+
+    /// An event of the Kademlia behaviour.
+    Kademlia(KademliaEvent),
+
+    /// A subscriber of a topic has been found and added to the partial view of floodsub.
+    SubscriberFound {
+        /// The subscriber.
+        peer_id: PeerId,
+        /// The topic it is subscribed to.
+        topic: TopicHash,
+    },
+}
+
+impl<TSubstream, TStore> TopicDiscovery<TSubstream, TStore>
+where
+    for<'a> TStore: RecordStore<'a>
+{
+    /// Creates a `TopicDiscovery` with the default configuration.
+    pub fn new(floodsub: Floodsub<TSubstream>, kademlia: Kademlia<TSubstream, TStore>) -> Self {
+        Self::with_config(floodsub, kademlia, TopicDiscoveryConfig::default())
+    }
+
+    /// Creates a `TopicDiscovery` with the given configuration.
+    pub fn with_config(
+        floodsub: Floodsub<TSubstream>,
+        kademlia: Kademlia<TSubstream, TStore>,
+        config: TopicDiscoveryConfig
+    ) -> Self {
+        let interval = config.lookup_interval;
+        TopicDiscovery {
+            floodsub,
+            kademlia,
+            topics: FnvHashMap::default(),
+            lookup_interval: Interval::new(Instant::now() + interval, interval),
+            pending_lookups: 0,
+            discovered_addresses: FnvHashMap::default(),
+            subscriber_addresses: FnvHashMap::default(),
+            subscribers_order: VecDeque::new(),
+            max_subscriber_addresses: config.max_subscriber_addresses,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Subscribes to a topic, advertises the local node as a subscriber of the topic and starts
+    /// looking for the other subscribers.
+    ///
+    /// Returns true if the subscription worked. Returns false if we were already subscribed.
+    pub fn subscribe(&mut self, topic: Topic) -> bool {
+        let hash = topic.hash().clone();
+        if !self.floodsub.subscribe(topic) {
+            return false;
+        }
+
+        let key = topic_key(&hash);
+        self.kademlia.start_providing(key.clone());
+        self.kademlia.get_providers(key.clone());
+        self.pending_lookups += 1;
+        self.topics.insert(key, hash);
+        true
+    }
+
+    /// Unsubscribes from a topic and stops advertising the local node as a subscriber.
+    ///
+    /// Returns true if we were subscribed to this topic.
+    pub fn unsubscribe(&mut self, topic: impl AsRef<TopicHash>) -> bool {
+        let topic = topic.as_ref();
+        if !self.floodsub.unsubscribe(topic) {
+            return false;
+        }
+
+        let key = topic_key(topic);
+        self.kademlia.stop_providing(&key);
+        self.topics.remove(&key);
+        true
+    }
+
+    /// Returns the floodsub behaviour.
+    pub fn floodsub(&self) -> &Floodsub<TSubstream> {
+        &self.floodsub
+    }
+
+    /// Returns the floodsub behaviour mutably, e.g. in order to publish messages.
+    ///
+    /// > **Note**: The topics subscribed to directly through `Floodsub::subscribe` are not
+    /// >           advertised through Kademlia.
+    pub fn floodsub_mut(&mut self) -> &mut Floodsub<TSubstream> {
+        &mut self.floodsub
+    }
+
+    /// Returns the Kademlia behaviour.
+    pub fn kademlia(&self) -> &Kademlia<TSubstream, TStore> {
+        &self.kademlia
+    }
+
+    /// Returns the Kademlia behaviour mutably, e.g. in order to add the addresses of bootstrap
+    /// nodes.
+    pub fn kademlia_mut(&mut self) -> &mut Kademlia<TSubstream, TStore> {
+        &mut self.kademlia
+    }
+
+    /// Starts a lookup of the providers of each topic we are subscribed to.
+    fn lookup_all(&mut self) {
+        for key in self.topics.keys() {
+            self.kademlia.get_providers(key.clone());
+            self.pending_lookups += 1;
+        }
+    }
+
+    /// Processes an event of the Kademlia behaviour before it is reported to the user.
+    fn on_kademlia_event(&mut self, local_peer_id: &PeerId, event: &KademliaEvent) {
+        let (key, providers) = match event {
+            KademliaEvent::Discovered { peer_id, addresses, .. } => {
+                if self.pending_lookups > 0 {
+                    let known = self.discovered_addresses.entry(peer_id.clone())
+                        .or_insert_with(SmallVec::new);
+                    for addr in addresses {
+                        if !known.contains(addr) {
+                            known.push(addr.clone());
+                        }
+                    }
+                }
+                return
+            }
+            KademliaEvent::GetProvidersResult(Ok(GetProvidersOk { key, providers, .. })) =>
+                (key, providers),
+            KademliaEvent::GetProvidersResult(Err(GetProvidersError::Timeout { key, providers, .. })) =>
+                (key, providers),
+            _ => return,
+        };
+
+        if !key.as_ref().starts_with(TOPIC_KEY_PREFIX) {
+            return
+        }
+
+        self.pending_lookups = self.pending_lookups.saturating_sub(1);
+        if let Some(topic) = self.topics.get(key).cloned() {
+            self.on_subscribers_found(local_peer_id, topic, providers);
+        }
+        if self.pending_lookups == 0 {
+            self.discovered_addresses.clear();
+        }
+    }
+
+    /// Adds the subscribers of a topic found through Kademlia to the partial view of floodsub.
+    fn on_subscribers_found(&mut self, local_peer_id: &PeerId, topic: TopicHash, providers: &[PeerId]) {
+
+        for provider in providers {
+            if provider == local_peer_id {
+                continue;
+            }
+
+            if let Some(addrs) = self.discovered_addresses.get(provider).cloned() {
+                self.insert_subscriber_addresses(provider.clone(), addrs);
+            }
+            self.floodsub.add_node_to_partial_view(provider.clone());
+            self.events.push_back(TopicDiscoveryEvent::SubscriberFound {
+                peer_id: provider.clone(),
+                topic: topic.clone(),
+            });
+        }
+    }
+
+    /// Remembers the addresses of a subscriber, forgetting the ones of the subscribers found
+    /// the longest ago if there are too many.
+    fn insert_subscriber_addresses(&mut self, peer_id: PeerId, addrs: SmallVec<[Multiaddr; 4]>) {
+        if self.subscriber_addresses.insert(peer_id.clone(), addrs).is_some() {
+            self.subscribers_order.retain(|p| p != &peer_id);
+        }
+        self.subscribers_order.push_back(peer_id);
+
+        while self.subscribers_order.len() > self.max_subscriber_addresses {
+            if let Some(evicted) = self.subscribers_order.pop_front() {
+                self.subscriber_addresses.remove(&evicted);
+            }
+        }
+    }
+
+    /// Forgets the addresses of a subscriber.
+    fn remove_subscriber_addresses(&mut self, peer_id: &PeerId) {
+        if self.subscriber_addresses.remove(peer_id).is_some() {
+            self.subscribers_order.retain(|p| p != peer_id);
+        }
+    }
+}
+
+impl<TSubstream, TStore> NetworkBehaviour for TopicDiscovery<TSubstream, TStore>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+    for<'a> TStore: RecordStore<'a>,
+{
+    type ProtocolsHandler = ProtocolsHandlerSelect<
+        <Floodsub<TSubstream> as NetworkBehaviour>::ProtocolsHandler,
+        <Kademlia<TSubstream, TStore> as NetworkBehaviour>::ProtocolsHandler,
+    >;
+    type OutEvent = TopicDiscoveryEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        ProtocolsHandler::select(self.floodsub.new_handler(), self.kademlia.new_handler())
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        let mut addrs = self.kademlia.addresses_of_peer(peer_id);
+        addrs.extend(self.floodsub.addresses_of_peer(peer_id));
+        if let Some(known) = self.subscriber_addresses.get(peer_id) {
+            for addr in known {
+                if !addrs.contains(addr) {
+                    addrs.push(addr.clone());
+                }
+            }
+        }
+        addrs
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        self.floodsub.inject_connected(peer_id.clone(), endpoint.clone());
+        self.kademlia.inject_connected(peer_id, endpoint);
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
+        self.floodsub.inject_disconnected(peer_id, endpoint.clone());
+        self.kademlia.inject_disconnected(peer_id, endpoint);
+    }
+
+    fn inject_replaced(&mut self, peer_id: PeerId, closed_endpoint: ConnectedPoint, new_endpoint: ConnectedPoint) {
+        self.floodsub.inject_replaced(peer_id.clone(), closed_endpoint.clone(), new_endpoint.clone());
+        self.kademlia.inject_replaced(peer_id, closed_endpoint, new_endpoint);
+    }
+
+    fn inject_node_event(
+        &mut self,
+        peer_id: PeerId,
+        event: <<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutEvent
+    ) {
+        match event {
+            EitherOutput::First(event) => self.floodsub.inject_node_event(peer_id, event),
+            EitherOutput::Second(event) => self.kademlia.inject_node_event(peer_id, event),
+        }
+    }
+
+    fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error) {
+        self.floodsub.inject_addr_reach_failure(peer_id, addr, error);
+        self.kademlia.inject_addr_reach_failure(peer_id, addr, error);
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        // The addresses we knew didn't work.
+        self.remove_subscriber_addresses(peer_id);
+        self.floodsub.inject_dial_failure(peer_id);
+        self.kademlia.inject_dial_failure(peer_id);
+    }
+
+    fn inject_incoming_connection_error(&mut self, local_addr: &Multiaddr, send_back_addr: &Multiaddr, error: &dyn error::Error) {
+        self.floodsub.inject_incoming_connection_error(local_addr, send_back_addr, error);
+        self.kademlia.inject_incoming_connection_error(local_addr, send_back_addr, error);
+    }
+
+    fn inject_new_listen_addr(&mut self, addr: &Multiaddr) {
+        self.floodsub.inject_new_listen_addr(addr);
+        self.kademlia.inject_new_listen_addr(addr);
+    }
+
+    fn inject_expired_listen_addr(&mut self, addr: &Multiaddr) {
+        self.floodsub.inject_expired_listen_addr(addr);
+        self.kademlia.inject_expired_listen_addr(addr);
+    }
+
+    fn inject_new_external_addr(&mut self, addr: &Multiaddr) {
+        self.floodsub.inject_new_external_addr(addr);
+        self.kademlia.inject_new_external_addr(addr);
+    }
+
+    fn inject_expired_external_addr(&mut self, addr: &Multiaddr) {
+        self.floodsub.inject_expired_external_addr(addr);
+        self.kademlia.inject_expired_external_addr(addr);
+    }
+
+    fn inject_listener_error(&mut self, id: ListenerId, err: &(dyn error::Error + 'static)) {
+        self.floodsub.inject_listener_error(id, err);
+        self.kademlia.inject_listener_error(id, err);
+    }
+
+    fn inject_listener_closed(&mut self, id: ListenerId) {
+        self.floodsub.inject_listener_closed(id);
+        self.kademlia.inject_listener_closed(id);
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<<<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent, Self::OutEvent>>
+    {
+        // Periodically look up the other subscribers of our topics.
+        loop {
+            match self.lookup_interval.poll() {
+                Ok(Async::Ready(Some(_))) => self.lookup_all(),
+                Ok(Async::Ready(None)) | Ok(Async::NotReady) => break,
+                Err(_) => break,
+            }
+        }
+
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(NetworkBehaviourAction::GenerateEvent(event));
+        }
+
+        if let Async::Ready(action) = self.floodsub.poll(params) {
+            return Async::Ready(map_action(action, EitherOutput::First, TopicDiscoveryEvent::Floodsub));
+        }
+
+        match self.kademlia.poll(params) {
+            Async::Ready(NetworkBehaviourAction::GenerateEvent(event)) => {
+                let local_peer_id = params.local_peer_id().clone();
+                self.on_kademlia_event(&local_peer_id, &event);
+                Async::Ready(NetworkBehaviourAction::GenerateEvent(TopicDiscoveryEvent::Kademlia(event)))
+            }
+            Async::Ready(action) =>
+                Async::Ready(map_action(action, EitherOutput::Second, TopicDiscoveryEvent::Kademlia)),
+            Async::NotReady => Async::NotReady,
+        }
+    }
+}
+
+/// Maps the events of an action of one of the inner behaviours to the ones of `TopicDiscovery`.
+fn map_action<TInEvent, TOutEvent, TInEvent2, TOutEvent2>(
+    action: NetworkBehaviourAction<TInEvent, TOutEvent>,
+    map_in: impl FnOnce(TInEvent) -> TInEvent2,
+    map_out: impl FnOnce(TOutEvent) -> TOutEvent2,
+) -> NetworkBehaviourAction<TInEvent2, TOutEvent2> {
+    match action {
+        NetworkBehaviourAction::GenerateEvent(event) =>
+            NetworkBehaviourAction::GenerateEvent(map_out(event)),
+        NetworkBehaviourAction::DialAddress { address } =>
+            NetworkBehaviourAction::DialAddress { address },
+        NetworkBehaviourAction::DialPeer { peer_id } =>
+            NetworkBehaviourAction::DialPeer { peer_id },
+        NetworkBehaviourAction::SendEvent { peer_id, event } =>
+            NetworkBehaviourAction::SendEvent { peer_id, event: map_in(event) },
+        NetworkBehaviourAction::ReportObservedAddr { address } =>
+            NetworkBehaviourAction::ReportObservedAddr { address },
+        NetworkBehaviourAction::AddExternalAddr { address } =>
+            NetworkBehaviourAction::AddExternalAddr { address },
+        NetworkBehaviourAction::RemoveExternalAddr { address } =>
+            NetworkBehaviourAction::RemoveExternalAddr { address },
+        NetworkBehaviourAction::TagPeer { peer_id, tag, value } =>
+            NetworkBehaviourAction::TagPeer { peer_id, tag, value },
+        NetworkBehaviourAction::UntagPeer { peer_id, tag } =>
+            NetworkBehaviourAction::UntagPeer { peer_id, tag },
+        NetworkBehaviourAction::ProtectPeer { peer_id, tag } =>
+            NetworkBehaviourAction::ProtectPeer { peer_id, tag },
+        NetworkBehaviourAction::UnprotectPeer { peer_id, tag } =>
+            NetworkBehaviourAction::UnprotectPeer { peer_id, tag },
+        NetworkBehaviourAction::DisconnectPeer { peer_id } =>
+            NetworkBehaviourAction::DisconnectPeer { peer_id },
+        NetworkBehaviourAction::BanPeer { peer_id } =>
+            NetworkBehaviourAction::BanPeer { peer_id },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topic::TopicBuilder;
+    use libp2p_core::transport::memory::Channel;
+    use libp2p_kad::{KadConnectionType, record::store::MemoryStore};
+
+    type TestDiscovery = TopicDiscovery<Channel<Vec<u8>>, MemoryStore>;
+
+    fn build(config: TopicDiscoveryConfig) -> (PeerId, TestDiscovery) {
+        let local_peer_id = PeerId::random();
+        let floodsub = Floodsub::new(local_peer_id.clone());
+        let kademlia = Kademlia::new(local_peer_id.clone(), MemoryStore::new(local_peer_id.clone()));
+        (local_peer_id, TopicDiscovery::with_config(floodsub, kademlia, config))
+    }
+
+    fn find_subscriber(discovery: &mut TestDiscovery, local_peer_id: &PeerId, topic: &Topic, addr: Multiaddr) -> PeerId {
+        // The peers discovered by Kademlia are only remembered during lookups.
+        discovery.pending_lookups += 1;
+        let peer_id = PeerId::random();
+        discovery.on_kademlia_event(local_peer_id, &KademliaEvent::Discovered {
+            peer_id: peer_id.clone(),
+            addresses: vec![addr],
+            ty: KadConnectionType::NotConnected,
+        });
+        discovery.on_kademlia_event(local_peer_id, &KademliaEvent::GetProvidersResult(Ok(GetProvidersOk {
+            key: topic_key(topic.hash()),
+            providers: vec![peer_id.clone()],
+            closest_peers: Vec::new(),
+        })));
+        peer_id
+    }
+
+    #[test]
+    fn topic_keys_are_distinct() {
+        let a = TopicHash::from_raw("a".to_string());
+        let b = TopicHash::from_raw("b".to_string());
+        assert_eq!(topic_key(&a), topic_key(&a));
+        assert_ne!(topic_key(&a), topic_key(&b));
+        assert!(topic_key(&a).as_ref().starts_with(TOPIC_KEY_PREFIX));
+    }
+
+    #[test]
+    fn providers_are_reported_and_dialable() {
+        let (local_peer_id, mut discovery) = build(TopicDiscoveryConfig::new());
+        let topic = TopicBuilder::new("topic").build();
+        assert!(discovery.subscribe(topic.clone()));
+        discovery.events.clear();
+
+        let addr: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+        let peer_id = find_subscriber(&mut discovery, &local_peer_id, &topic, addr.clone());
+
+        match discovery.events.pop_front() {
+            Some(TopicDiscoveryEvent::SubscriberFound { peer_id: p, topic: t }) => {
+                assert_eq!(p, peer_id);
+                assert_eq!(&t, topic.hash());
+            }
+            e => panic!("Unexpected event: {:?}", e),
+        }
+        assert!(discovery.addresses_of_peer(&peer_id).contains(&addr));
+
+        // The addresses are forgotten if dialing fails.
+        discovery.inject_dial_failure(&peer_id);
+        assert!(!discovery.addresses_of_peer(&peer_id).contains(&addr));
+    }
+
+    #[test]
+    fn subscriber_addresses_are_bounded() {
+        let config = TopicDiscoveryConfig::new().with_max_subscriber_addresses(2);
+        let (local_peer_id, mut discovery) = build(config);
+        let topic = TopicBuilder::new("topic").build();
+        assert!(discovery.subscribe(topic.clone()));
+
+        let addrs = (1 ..= 3)
+            .map(|i| format!("/ip4/10.0.0.{}/tcp/4001", i).parse::<Multiaddr>().unwrap())
+            .collect::<Vec<_>>();
+        let peers = addrs.iter()
+            .map(|addr| find_subscriber(&mut discovery, &local_peer_id, &topic, addr.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(discovery.subscriber_addresses.len(), 2);
+        assert!(!discovery.addresses_of_peer(&peers[0]).contains(&addrs[0]));
+        assert!(discovery.addresses_of_peer(&peers[1]).contains(&addrs[1]));
+        assert!(discovery.addresses_of_peer(&peers[2]).contains(&addrs[2]));
+    }
+}
@@ -18,12 +18,15 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use crate::metrics::FloodsubMetrics;
 use crate::protocol::{FloodsubConfig, FloodsubMessage, FloodsubRpc, FloodsubSubscription, FloodsubSubscriptionAction};
 use crate::topic::{Topic, TopicHash};
+use crate::validation::{MessageAcceptance, MessageValidator, Penalty, PenaltyConfig, SignatureVerification};
 use cuckoofilter::CuckooFilter;
 use fnv::FnvHashSet;
 use futures::prelude::*;
-use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, identity::Keypair};
+use libp2p_metrics::Registry;
 use libp2p_swarm::{
     NetworkBehaviour,
     NetworkBehaviourAction,
@@ -31,6 +34,7 @@ use libp2p_swarm::{
     ProtocolsHandler,
     OneShotHandler
 };
+use log::warn;
 use rand;
 use smallvec::SmallVec;
 use std::{collections::VecDeque, iter, marker::PhantomData};
@@ -46,6 +50,12 @@ pub struct Floodsub<TSubstream> {
     /// Peer id of the local node. Used for the source of the messages that we publish.
     local_peer_id: PeerId,
 
+    /// Keypair of the local node, used to sign the messages that we publish, if any.
+    keypair: Option<Keypair>,
+
+    /// How the signatures of the messages we receive are verified.
+    signature_verification: SignatureVerification,
+
     /// List of peers to send messages to.
     target_peers: FnvHashSet<PeerId>,
 
@@ -62,20 +72,56 @@ pub struct Floodsub<TSubstream> {
     // we don't dispatch the same message twice if we receive it twice on the network.
     received: CuckooFilter<DefaultHasher>,
 
+    /// The validator for received messages, if any.
+    message_validator: Option<Box<dyn MessageValidator>>,
+
+    /// Penalties applied to the peers sending invalid messages.
+    penalty_config: PenaltyConfig,
+
+    /// Number of invalid messages received from each connected peer since it was last penalized.
+    invalid_messages: HashMap<PeerId, u32>,
+
+    /// The metrics of the messages, if enabled with `register_metrics`.
+    metrics: Option<FloodsubMetrics>,
+
     /// Marker to pin the generics.
     marker: PhantomData<TSubstream>,
 }
 
 impl<TSubstream> Floodsub<TSubstream> {
-    /// Creates a `Floodsub`.
+    /// Creates a `Floodsub` that neither signs the messages it publishes nor verifies the
+    /// signatures of the messages it receives.
     pub fn new(local_peer_id: PeerId) -> Self {
+        Self::new_inner(local_peer_id, None, SignatureVerification::None)
+    }
+
+    /// Creates a `Floodsub` that signs the messages it publishes with the given keypair, and
+    /// drops the received messages that are unsigned or have an invalid signature.
+    ///
+    /// The verification can be relaxed with `set_signature_verification`.
+    pub fn new_signed(keypair: Keypair) -> Self {
+        let local_peer_id = keypair.public().into_peer_id();
+        Self::new_inner(local_peer_id, Some(keypair), SignatureVerification::Strict)
+    }
+
+    fn new_inner(
+        local_peer_id: PeerId,
+        keypair: Option<Keypair>,
+        signature_verification: SignatureVerification
+    ) -> Self {
         Floodsub {
             events: VecDeque::new(),
             local_peer_id,
+            keypair,
+            signature_verification,
             target_peers: FnvHashSet::default(),
             connected_peers: HashMap::new(),
             subscribed_topics: SmallVec::new(),
             received: CuckooFilter::new(),
+            message_validator: None,
+            penalty_config: PenaltyConfig::default(),
+            invalid_messages: HashMap::new(),
+            metrics: None,
             marker: PhantomData,
         }
     }
@@ -109,6 +155,76 @@ impl<TSubstream> Floodsub<TSubstream> {
     pub fn remove_node_from_partial_view(&mut self, peer_id: &PeerId) {
         self.target_peers.remove(&peer_id);
     }
+
+    /// Sets the validator for received messages.
+    ///
+    /// Every message received from a remote is validated before being dispatched to the user
+    /// and propagated to the other peers. Only accepted messages are dispatched and propagated,
+    /// and rejected messages count against the peer they were received from, as configured
+    /// with `set_penalty_config`.
+    pub fn set_message_validator<V>(&mut self, validator: V)
+    where
+        V: MessageValidator
+    {
+        self.message_validator = Some(Box::new(validator));
+    }
+
+    /// Sets how the signatures of the received messages are verified.
+    pub fn set_signature_verification(&mut self, verification: SignatureVerification) {
+        self.signature_verification = verification;
+    }
+
+    /// Sets the penalties applied to the peers sending invalid messages.
+    pub fn set_penalty_config(&mut self, config: PenaltyConfig) {
+        self.penalty_config = config;
+    }
+
+    /// Registers the metrics of the messages published, forwarded and received more than once
+    /// in the given registry.
+    pub fn register_metrics(&mut self, registry: &Registry) {
+        self.metrics = Some(FloodsubMetrics::new(registry));
+    }
+
+    /// Returns the number of invalid messages received from the given peer since it was last
+    /// penalized.
+    pub fn invalid_messages(&self, peer_id: &PeerId) -> u32 {
+        self.invalid_messages.get(peer_id).cloned().unwrap_or(0)
+    }
+
+    /// Records an invalid message received from the given peer, and penalizes the peer if it
+    /// has sent too many of them.
+    fn on_invalid_message(&mut self, peer_id: &PeerId) {
+        let count = {
+            let count = self.invalid_messages.entry(peer_id.clone()).or_insert(0);
+            *count += 1;
+            *count
+        };
+
+        let penalty = match self.penalty_config.penalty_for(count) {
+            Some(penalty) => penalty,
+            None => return,
+        };
+
+        self.invalid_messages.remove(peer_id);
+        // Don't try to reconnect to a penalized peer.
+        self.target_peers.remove(peer_id);
+        match penalty {
+            Penalty::Disconnect => {
+                self.events.push_back(NetworkBehaviourAction::DisconnectPeer {
+                    peer_id: peer_id.clone(),
+                });
+            }
+            Penalty::Ban => {
+                self.events.push_back(NetworkBehaviourAction::BanPeer {
+                    peer_id: peer_id.clone(),
+                });
+            }
+        }
+        self.events.push_back(NetworkBehaviourAction::GenerateEvent(FloodsubEvent::PeerPenalized {
+            peer_id: peer_id.clone(),
+            penalty,
+        }));
+    }
 }
 
 impl<TSubstream> Floodsub<TSubstream> {
@@ -191,7 +307,7 @@ impl<TSubstream> Floodsub<TSubstream> {
     }
 
     fn publish_many_inner(&mut self, topic: impl IntoIterator<Item = impl Into<TopicHash>>, data: impl Into<Vec<u8>>, check_self_subscriptions: bool) {
-        let message = FloodsubMessage {
+        let mut message = FloodsubMessage {
             source: self.local_peer_id.clone(),
             data: data.into(),
             // If the sequence numbers are predictable, then an attacker could flood the network
@@ -199,8 +315,17 @@ impl<TSubstream> Floodsub<TSubstream> {
             // messages. We therefore use a random number.
             sequence_number: rand::random::<[u8; 20]>().to_vec(),
             topics: topic.into_iter().map(|t| t.into().clone()).collect(),
+            signature: None,
+            key: None,
         };
 
+        if let Some(keypair) = &self.keypair {
+            if let Err(err) = message.sign(keypair) {
+                warn!("Failed to sign floodsub message: {:?}", err);
+                return
+            }
+        }
+
         let self_subscribed = self.subscribed_topics.iter().any(|t| message.topics.iter().any(|u| t.hash() == u));
         if self_subscribed {
             self.received.add(&message);
@@ -211,6 +336,10 @@ impl<TSubstream> Floodsub<TSubstream> {
             return
         }
 
+        if let Some(metrics) = &self.metrics {
+            metrics.published.inc();
+        }
+
         // Send to peers we know are subscribed to the topic.
         for (peer_id, sub_topic) in self.connected_peers.iter() {
             if !sub_topic.iter().any(|t| message.topics.iter().any(|u| t == u)) {
@@ -266,6 +395,7 @@ where
     fn inject_disconnected(&mut self, id: &PeerId, _: ConnectedPoint) {
         let was_in = self.connected_peers.remove(id);
         debug_assert!(was_in.is_some());
+        self.invalid_messages.remove(id);
 
         // We can be disconnected by the remote in case of inactivity for example, so we always
         // try to reconnect.
@@ -319,9 +449,28 @@ where
             // Use `self.received` to skip the messages that we have already received in the past.
             // Note that this can false positive.
             if !self.received.test_and_add(&message) {
+                if let Some(metrics) = &self.metrics {
+                    metrics.duplicated.inc();
+                }
                 continue;
             }
 
+            // Check the signature, then let the application decide whether the message should be
+            // dispatched and propagated.
+            let acceptance = match self.signature_verification.verify(&message) {
+                MessageAcceptance::Accept => self.message_validator.as_ref()
+                    .map_or(MessageAcceptance::Accept, |v| v.validate(&propagation_source, &message)),
+                acceptance => acceptance,
+            };
+            match acceptance {
+                MessageAcceptance::Accept => {}
+                MessageAcceptance::Ignore => continue,
+                MessageAcceptance::Reject => {
+                    self.on_invalid_message(&propagation_source);
+                    continue;
+                }
+            }
+
             // Add the message to be dispatched to the user.
             if self.subscribed_topics.iter().any(|t| message.topics.iter().any(|u| t.hash() == u)) {
                 let event = FloodsubEvent::Message(message.clone());
@@ -350,6 +499,9 @@ where
         }
 
         for (peer_id, rpc) in rpcs_to_dispatch {
+            if let Some(metrics) = &self.metrics {
+                metrics.forwarded.inc_by(rpc.messages.len() as u64);
+            }
             self.events.push_back(NetworkBehaviourAction::SendEvent {
                 peer_id,
                 event: rpc,
@@ -417,4 +569,131 @@ pub enum FloodsubEvent {
         /// The topic it has subscribed from.
         topic: TopicHash,
     },
+
+    /// A remote has been penalized for sending too many invalid messages.
+    PeerPenalized {
+        /// Remote that has been penalized.
+        peer_id: PeerId,
+        /// The penalty applied to the remote.
+        penalty: Penalty,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topic::TopicBuilder;
+    use libp2p_core::transport::memory::Channel;
+
+    fn connect(floodsub: &mut Floodsub<Channel<Vec<u8>>>, peer_id: &PeerId) {
+        let endpoint = ConnectedPoint::Dialer { address: "/ip4/127.0.0.1/tcp/1234".parse().unwrap() };
+        floodsub.add_node_to_partial_view(peer_id.clone());
+        floodsub.inject_connected(peer_id.clone(), endpoint);
+    }
+
+    fn message(topic: &Topic) -> FloodsubMessage {
+        FloodsubMessage {
+            source: PeerId::random(),
+            data: b"hello".to_vec(),
+            sequence_number: rand::random::<[u8; 20]>().to_vec(),
+            topics: vec![topic.hash().clone()],
+            signature: None,
+            key: None,
+        }
+    }
+
+    #[test]
+    fn rejected_messages_are_neither_dispatched_nor_forwarded() {
+        let topic = TopicBuilder::new("topic").build();
+        let mut floodsub = Floodsub::new(PeerId::random());
+        floodsub.subscribe(topic.clone());
+        floodsub.set_message_validator(|_: &PeerId, message: &FloodsubMessage| {
+            if message.data == b"invalid" {
+                MessageAcceptance::Reject
+            } else {
+                MessageAcceptance::Accept
+            }
+        });
+        floodsub.set_penalty_config(PenaltyConfig::new().with_max_invalid_messages(1));
+
+        let sender = PeerId::random();
+        let other = PeerId::random();
+        connect(&mut floodsub, &sender);
+        connect(&mut floodsub, &other);
+        floodsub.inject_node_event(other.clone(), InnerMessage::Rx(FloodsubRpc {
+            messages: Vec::new(),
+            subscriptions: vec![FloodsubSubscription {
+                topic: topic.hash().clone(),
+                action: FloodsubSubscriptionAction::Subscribe,
+            }],
+        }));
+        floodsub.events.clear();
+
+        // An accepted message is dispatched and forwarded.
+        floodsub.inject_node_event(sender.clone(), InnerMessage::Rx(FloodsubRpc {
+            messages: vec![message(&topic)],
+            subscriptions: Vec::new(),
+        }));
+        assert!(floodsub.events.iter().any(|e| match e {
+            NetworkBehaviourAction::GenerateEvent(FloodsubEvent::Message(_)) => true,
+            _ => false,
+        }));
+        assert!(floodsub.events.iter().any(|e| match e {
+            NetworkBehaviourAction::SendEvent { peer_id, .. } => peer_id == &other,
+            _ => false,
+        }));
+        floodsub.events.clear();
+
+        // A rejected message is neither.
+        let mut invalid = message(&topic);
+        invalid.data = b"invalid".to_vec();
+        floodsub.inject_node_event(sender.clone(), InnerMessage::Rx(FloodsubRpc {
+            messages: vec![invalid],
+            subscriptions: Vec::new(),
+        }));
+        assert!(floodsub.events.is_empty());
+        assert_eq!(floodsub.invalid_messages(&sender), 1);
+
+        // Once penalized, the peer is disconnected and not dialed again.
+        let mut invalid = message(&topic);
+        invalid.data = b"invalid".to_vec();
+        floodsub.inject_node_event(sender.clone(), InnerMessage::Rx(FloodsubRpc {
+            messages: vec![invalid],
+            subscriptions: Vec::new(),
+        }));
+        assert!(floodsub.events.iter().any(|e| match e {
+            NetworkBehaviourAction::DisconnectPeer { peer_id } => peer_id == &sender,
+            _ => false,
+        }));
+        assert!(floodsub.events.iter().all(|e| match e {
+            NetworkBehaviourAction::GenerateEvent(FloodsubEvent::Message(_)) => false,
+            NetworkBehaviourAction::SendEvent { .. } => false,
+            _ => true,
+        }));
+        floodsub.events.clear();
+
+        let endpoint = ConnectedPoint::Dialer { address: "/ip4/127.0.0.1/tcp/1234".parse().unwrap() };
+        floodsub.inject_disconnected(&sender, endpoint);
+        assert!(floodsub.events.is_empty());
+    }
+
+    #[test]
+    fn invalid_messages_are_forgotten_on_disconnect() {
+        let topic = TopicBuilder::new("topic").build();
+        let mut floodsub = Floodsub::new(PeerId::random());
+        floodsub.subscribe(topic.clone());
+        floodsub.set_message_validator(|_: &PeerId, _: &FloodsubMessage| MessageAcceptance::Reject);
+
+        let sender = PeerId::random();
+        connect(&mut floodsub, &sender);
+        floodsub.inject_node_event(sender.clone(), InnerMessage::Rx(FloodsubRpc {
+            messages: vec![message(&topic)],
+            subscriptions: Vec::new(),
+        }));
+        assert_eq!(floodsub.invalid_messages(&sender), 1);
+
+        let endpoint = ConnectedPoint::Dialer { address: "/ip4/127.0.0.1/tcp/1234".parse().unwrap() };
+        floodsub.inject_disconnected(&sender, endpoint);
+        assert_eq!(floodsub.invalid_messages(&sender), 0);
+    }
 }
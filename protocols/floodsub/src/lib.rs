@@ -22,11 +22,16 @@
 //! [spec](https://github.com/libp2p/specs/tree/master/pubsub).
 
 pub mod protocol;
+pub mod validation;
+#[cfg(feature = "kad")]
+pub mod discovery;
 
 mod layer;
+mod metrics;
 mod rpc_proto;
 mod topic;
 
 pub use self::layer::{Floodsub, FloodsubEvent};
 pub use self::protocol::{FloodsubMessage, FloodsubRpc};
 pub use self::topic::{Topic, TopicBuilder, TopicHash};
+pub use self::validation::{MessageAcceptance, MessageValidator, Penalty, PenaltyConfig, SignatureVerification};
@@ -0,0 +1,60 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Metrics of the `Floodsub` behaviour, enabled with `Floodsub::register_metrics`.
+
+use libp2p_metrics::{Counter, Registry};
+
+/// The metrics of the messages handled by a `Floodsub` behaviour.
+pub(crate) struct FloodsubMetrics {
+    /// Messages published by the local node.
+    pub(crate) published: Counter,
+    /// Messages received from a peer and forwarded to another one, counted once per recipient.
+    pub(crate) forwarded: Counter,
+    /// Messages received that had already been received before.
+    pub(crate) duplicated: Counter,
+}
+
+impl FloodsubMetrics {
+    /// Creates the floodsub metrics and registers them in the registry.
+    pub(crate) fn new(registry: &Registry) -> Self {
+        let metrics = FloodsubMetrics {
+            published: Counter::new(),
+            forwarded: Counter::new(),
+            duplicated: Counter::new(),
+        };
+
+        registry.register(
+            "libp2p_floodsub_messages_published_total",
+            "Number of messages published by the local node",
+            metrics.published.clone());
+        registry.register(
+            "libp2p_floodsub_messages_forwarded_total",
+            "Number of messages forwarded to other peers",
+            metrics.forwarded.clone());
+        registry.register(
+            "libp2p_floodsub_messages_duplicated_total",
+            "Number of messages received more than once",
+            metrics.duplicated.clone());
+
+        metrics
+    }
+}
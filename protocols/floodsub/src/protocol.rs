@@ -21,6 +21,7 @@
 use crate::rpc_proto;
 use crate::topic::TopicHash;
 use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, PeerId, upgrade};
+use libp2p_core::identity::{Keypair, PublicKey, error::SigningError};
 use protobuf::{ProtobufError, Message as ProtobufMessage};
 use std::{error, fmt, io, iter};
 use tokio_io::{AsyncRead, AsyncWrite};
@@ -62,6 +63,16 @@ where
 
             let mut messages = Vec::with_capacity(rpc.get_publish().len());
             for mut publish in rpc.take_publish().into_iter() {
+                let signature = if publish.has_signature() {
+                    Some(publish.take_signature())
+                } else {
+                    None
+                };
+                let key = if publish.has_key() {
+                    Some(publish.take_key())
+                } else {
+                    None
+                };
                 messages.push(FloodsubMessage {
                     source: PeerId::from_bytes(publish.take_from()).map_err(|_| {
                         FloodsubDecodeError::InvalidPeerId
@@ -73,6 +84,8 @@ where
                         .into_iter()
                         .map(TopicHash::from_raw)
                         .collect(),
+                    signature,
+                    key,
                 });
             }
 
@@ -183,17 +196,13 @@ impl FloodsubRpc {
         let mut proto = rpc_proto::RPC::new();
 
         for message in self.messages {
-            let mut msg = rpc_proto::Message::new();
-            msg.set_from(message.source.into_bytes());
-            msg.set_data(message.data);
-            msg.set_seqno(message.sequence_number);
-            msg.set_topicIDs(
-                message
-                    .topics
-                    .into_iter()
-                    .map(TopicHash::into_string)
-                    .collect(),
-            );
+            let mut msg = message.unsigned_proto();
+            if let Some(signature) = message.signature {
+                msg.set_signature(signature);
+            }
+            if let Some(key) = message.key {
+                msg.set_key(key);
+            }
             proto.mut_publish().push(msg);
         }
 
@@ -226,6 +235,83 @@ pub struct FloodsubMessage {
     ///
     /// Each message can belong to multiple topics at once.
     pub topics: Vec<TopicHash>,
+
+    /// Signature of the message by the key of `source`, if the message is signed.
+    pub signature: Option<Vec<u8>>,
+
+    /// Protobuf encoding of the public key of `source`, if the message is signed.
+    pub key: Option<Vec<u8>>,
+}
+
+/// Prefix of the bytes signed by the author of a message, as per the libp2p pubsub
+/// specification.
+const SIGNING_PREFIX: &[u8] = b"libp2p-pubsub:";
+
+impl FloodsubMessage {
+    /// Signs the message with the given keypair, which must be the one of `source`, filling
+    /// `signature` and `key`.
+    ///
+    /// The signature covers the protobuf encoding of the message without the `signature` and
+    /// `key` fields, prefixed with `libp2p-pubsub:`.
+    pub fn sign(&mut self, keypair: &Keypair) -> Result<(), SigningError> {
+        let signature = keypair.sign(&self.signed_bytes())?;
+        self.signature = Some(signature);
+        self.key = Some(keypair.public().into_protobuf_encoding());
+        Ok(())
+    }
+
+    /// Returns `true` if the message is signed.
+    pub fn is_signed(&self) -> bool {
+        self.signature.is_some()
+    }
+
+    /// Verifies the signature of the message.
+    ///
+    /// Returns `true` if the message is signed, its key matches `source` and the signature is
+    /// valid.
+    pub fn verify(&self) -> bool {
+        let (signature, key) = match (&self.signature, &self.key) {
+            (Some(signature), Some(key)) => (signature, key),
+            _ => return false,
+        };
+
+        let key = match PublicKey::from_protobuf_encoding(key) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+
+        if self.source.is_public_key(&key) != Some(true) {
+            return false;
+        }
+
+        key.verify(&self.signed_bytes(), signature)
+    }
+
+    /// Returns the protobuf representation of the message, without the signature and key.
+    fn unsigned_proto(&self) -> rpc_proto::Message {
+        let mut msg = rpc_proto::Message::new();
+        msg.set_from(self.source.as_bytes().to_vec());
+        msg.set_data(self.data.clone());
+        msg.set_seqno(self.sequence_number.clone());
+        msg.set_topicIDs(
+            self.topics
+                .iter()
+                .map(|t| t.clone().into_string())
+                .collect(),
+        );
+        msg
+    }
+
+    /// Returns the bytes covered by the signature of the message.
+    fn signed_bytes(&self) -> Vec<u8> {
+        let proto = self.unsigned_proto()
+            .write_to_bytes()
+            .expect("there is no situation in which the protobuf message can be invalid");
+        let mut bytes = Vec::with_capacity(SIGNING_PREFIX.len() + proto.len());
+        bytes.extend_from_slice(SIGNING_PREFIX);
+        bytes.extend_from_slice(&proto);
+        bytes
+    }
 }
 
 /// A subscription received by the floodsub system.
@@ -245,3 +331,54 @@ pub enum FloodsubSubscriptionAction {
     /// The remote wants to unsubscribe from the given topic.
     Unsubscribe,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::identity;
+
+    fn message(keypair: &identity::Keypair) -> FloodsubMessage {
+        FloodsubMessage {
+            source: keypair.public().into_peer_id(),
+            data: b"hello".to_vec(),
+            sequence_number: vec![1, 2, 3],
+            topics: vec![TopicHash::from_raw("topic".to_string())],
+            signature: None,
+            key: None,
+        }
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let mut msg = message(&keypair);
+        assert!(!msg.verify());
+        msg.sign(&keypair).unwrap();
+        assert!(msg.is_signed());
+        assert!(msg.verify());
+
+        // The signature survives the encoding.
+        let rpc = FloodsubRpc { messages: vec![msg.clone()], subscriptions: Vec::new() };
+        let proto: rpc_proto::RPC = protobuf::parse_from_bytes(&rpc.into_bytes()).unwrap();
+        assert_eq!(proto.get_publish()[0].get_signature(), &msg.signature.unwrap()[..]);
+    }
+
+    #[test]
+    fn tampered_message_is_rejected() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let mut msg = message(&keypair);
+        msg.sign(&keypair).unwrap();
+        msg.data = b"goodbye".to_vec();
+        assert!(!msg.verify());
+    }
+
+    #[test]
+    fn forged_source_is_rejected() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let mut msg = message(&keypair);
+        msg.sign(&keypair).unwrap();
+        msg.source = PeerId::random();
+        msg.sign(&keypair).unwrap();
+        assert!(!msg.verify());
+    }
+}
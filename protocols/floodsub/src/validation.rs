@@ -0,0 +1,172 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Validation of the messages received from remotes, and penalties for the peers that send
+//! invalid messages.
+//!
+//! A `MessageValidator` decides whether a message received from a remote is dispatched to the
+//! user and propagated to the other peers. Each rejected message counts against the peer we
+//! received it from; once a peer has sent more than the configured number of invalid messages,
+//! the `Floodsub` behaviour applies the configured `Penalty`.
+//!
+//! Before being passed to the validator, the signatures of the messages are checked according to
+//! the configured `SignatureVerification`. Messages with an invalid signature are rejected.
+
+use crate::protocol::FloodsubMessage;
+use libp2p_core::PeerId;
+
+/// Outcome of the validation of a message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MessageAcceptance {
+    /// The message is valid. It is dispatched to the user and propagated to the other peers.
+    Accept,
+    /// The message is invalid. It is dropped, and counts against the peer we received it from.
+    Reject,
+    /// The message is dropped, but without penalizing the peer we received it from. This can
+    /// be used for messages that are not harmful but not worth propagating either, such as
+    /// outdated ones.
+    Ignore,
+}
+
+/// Validates the messages received from remotes.
+pub trait MessageValidator: Send + 'static {
+    /// Checks the given message, received from `propagation_source`.
+    ///
+    /// Note that `propagation_source` is the peer that forwarded the message to us, which isn't
+    /// necessarily the author of the message, `message.source`.
+    fn validate(&self, propagation_source: &PeerId, message: &FloodsubMessage) -> MessageAcceptance;
+}
+
+impl<F> MessageValidator for F
+where
+    F: Fn(&PeerId, &FloodsubMessage) -> MessageAcceptance + Send + 'static
+{
+    fn validate(&self, propagation_source: &PeerId, message: &FloodsubMessage) -> MessageAcceptance {
+        self(propagation_source, message)
+    }
+}
+
+/// How the signatures of the messages received from remotes are verified.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureVerification {
+    /// Signatures are not verified.
+    None,
+    /// Messages with an invalid signature are rejected, but unsigned messages are accepted.
+    Permissive,
+    /// Messages with an invalid signature are rejected, and unsigned messages are dropped.
+    Strict,
+}
+
+impl SignatureVerification {
+    /// Returns the outcome of the verification of the signature of the given message.
+    pub(crate) fn verify(&self, message: &FloodsubMessage) -> MessageAcceptance {
+        match self {
+            SignatureVerification::None => MessageAcceptance::Accept,
+            SignatureVerification::Permissive if !message.is_signed() => MessageAcceptance::Accept,
+            SignatureVerification::Strict if !message.is_signed() => MessageAcceptance::Ignore,
+            _ if message.verify() => MessageAcceptance::Accept,
+            _ => MessageAcceptance::Reject,
+        }
+    }
+}
+
+/// Action taken against a peer that sent too many invalid messages.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Penalty {
+    /// Close the connection to the peer, and stop dialing it. The peer may connect again.
+    Disconnect,
+    /// Ban the peer from the `Swarm`, close the connection to it and stop dialing it.
+    Ban,
+}
+
+/// Configuration of the penalties applied to the peers sending invalid messages.
+#[derive(Debug, Clone)]
+pub struct PenaltyConfig {
+    /// Number of invalid messages a peer may send before it is penalized.
+    max_invalid_messages: u32,
+    /// Penalty to apply, if any.
+    penalty: Option<Penalty>,
+}
+
+impl PenaltyConfig {
+    /// Creates a new configuration with the following default settings:
+    ///
+    ///   * 10 invalid messages allowed per peer
+    ///   * peers sending more are disconnected
+    pub fn new() -> Self {
+        PenaltyConfig {
+            max_invalid_messages: 10,
+            penalty: Some(Penalty::Disconnect),
+        }
+    }
+
+    /// Sets the number of invalid messages a peer may send before it is penalized.
+    pub fn with_max_invalid_messages(mut self, max: u32) -> Self {
+        self.max_invalid_messages = max;
+        self
+    }
+
+    /// Sets the penalty applied to the peers sending too many invalid messages.
+    pub fn with_penalty(mut self, penalty: Penalty) -> Self {
+        self.penalty = Some(penalty);
+        self
+    }
+
+    /// Only counts the invalid messages, without ever penalizing peers.
+    pub fn without_penalty(mut self) -> Self {
+        self.penalty = None;
+        self
+    }
+
+    /// Returns the penalty for a peer that has sent `invalid_messages` invalid messages, if it
+    /// must be penalized.
+    pub(crate) fn penalty_for(&self, invalid_messages: u32) -> Option<Penalty> {
+        if invalid_messages > self.max_invalid_messages {
+            self.penalty
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for PenaltyConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn penalty_after_max_invalid_messages() {
+        let config = PenaltyConfig::new()
+            .with_max_invalid_messages(2)
+            .with_penalty(Penalty::Ban);
+        assert_eq!(config.penalty_for(1), None);
+        assert_eq!(config.penalty_for(2), None);
+        assert_eq!(config.penalty_for(3), Some(Penalty::Ban));
+
+        let config = config.without_penalty();
+        assert_eq!(config.penalty_for(3), None);
+    }
+}
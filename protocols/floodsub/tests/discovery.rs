@@ -0,0 +1,147 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Integration tests for the discovery of the subscribers of a topic through Kademlia.
+
+#![cfg(feature = "kad")]
+
+use futures::{future, prelude::*};
+use libp2p_core::{
+    Multiaddr,
+    PeerId,
+    Transport,
+    identity,
+    multiaddr::Protocol,
+    muxing::StreamMuxerBox,
+    nodes::Substream,
+    transport::{MemoryTransport, boxed::Boxed},
+    upgrade,
+};
+use libp2p_floodsub::{
+    Floodsub,
+    FloodsubEvent,
+    TopicBuilder,
+    discovery::{TopicDiscovery, TopicDiscoveryConfig, TopicDiscoveryEvent},
+};
+use libp2p_kad::{Kademlia, record::store::MemoryStore};
+use libp2p_secio::SecioConfig;
+use libp2p_swarm::Swarm;
+use libp2p_yamux as yamux;
+use rand::random;
+use std::{io, time::Duration};
+use tokio::runtime::current_thread;
+
+type TestSwarm = Swarm<
+    Boxed<(PeerId, StreamMuxerBox), io::Error>,
+    TopicDiscovery<Substream<StreamMuxerBox>, MemoryStore>
+>;
+
+fn build_node(addr: Multiaddr) -> TestSwarm {
+    let local_key = identity::Keypair::generate_ed25519();
+    let local_id = local_key.public().into_peer_id();
+    let transport = MemoryTransport::default()
+        .with_upgrade(SecioConfig::new(local_key))
+        .and_then(move |out, endpoint| {
+            let peer_id = out.remote_key.into_peer_id();
+            let yamux = yamux::Config::default();
+            upgrade::apply(out.stream, yamux, endpoint)
+                .map(|muxer| (peer_id, StreamMuxerBox::new(muxer)))
+        })
+        .map_err(|e| panic!("Failed to create transport: {:?}", e))
+        .boxed();
+
+    let floodsub = Floodsub::new(local_id.clone());
+    let kademlia = Kademlia::new(local_id.clone(), MemoryStore::new(local_id.clone()));
+    let config = TopicDiscoveryConfig::new().with_lookup_interval(Duration::from_millis(200));
+    let behaviour = TopicDiscovery::with_config(floodsub, kademlia, config);
+    let mut swarm = Swarm::new(transport, behaviour, local_id);
+    Swarm::listen_on(&mut swarm, addr).unwrap();
+    swarm
+}
+
+#[test]
+fn provider_is_dialed_by_floodsub() {
+    let port_base = 1 + random::<u64>() % (u64::max_value() - 3);
+    let addrs = (0 .. 3)
+        .map(|i| Multiaddr::from(Protocol::Memory(port_base + i)))
+        .collect::<Vec<_>>();
+
+    // The subscriber advertises itself on the bootstrap node, which is the only node the
+    // searcher knows.
+    let mut subscriber = build_node(addrs[0].clone());
+    let mut bootstrap = build_node(addrs[1].clone());
+    let mut searcher = build_node(addrs[2].clone());
+
+    let subscriber_id = Swarm::local_peer_id(&subscriber).clone();
+    let bootstrap_id = Swarm::local_peer_id(&bootstrap).clone();
+    let searcher_id = Swarm::local_peer_id(&searcher).clone();
+
+    let topic = TopicBuilder::new("topic").build();
+    subscriber.kademlia_mut().add_address(&bootstrap_id, addrs[1].clone());
+    searcher.kademlia_mut().add_address(&bootstrap_id, addrs[1].clone());
+    assert!(subscriber.subscribe(topic.clone()));
+    assert!(searcher.subscribe(topic.clone()));
+
+    let mut found = false;
+    let future = future::poll_fn(move || -> Result<_, io::Error> {
+        loop {
+            let mut progress = false;
+
+            match searcher.poll()? {
+                Async::Ready(Some(TopicDiscoveryEvent::SubscriberFound { peer_id, topic: t })) => {
+                    assert_eq!(peer_id, subscriber_id);
+                    assert_eq!(&t, topic.hash());
+                    found = true;
+                    progress = true;
+                }
+                Async::Ready(Some(_)) => progress = true,
+                Async::Ready(None) => panic!("Swarm ended"),
+                Async::NotReady => {}
+            }
+
+            // The subscriber learns about the subscription of the searcher once the latter has
+            // added it to its floodsub partial view and connected to it.
+            match subscriber.poll()? {
+                Async::Ready(Some(TopicDiscoveryEvent::Floodsub(FloodsubEvent::Subscribed { peer_id, topic: t }))) => {
+                    if found && peer_id == searcher_id && &t == topic.hash() {
+                        return Ok(Async::Ready(()));
+                    }
+                    progress = true;
+                }
+                Async::Ready(Some(_)) => progress = true,
+                Async::Ready(None) => panic!("Swarm ended"),
+                Async::NotReady => {}
+            }
+
+            match bootstrap.poll()? {
+                Async::Ready(Some(_)) => progress = true,
+                Async::Ready(None) => panic!("Swarm ended"),
+                Async::NotReady => {}
+            }
+
+            if !progress {
+                return Ok(Async::NotReady);
+            }
+        }
+    });
+
+    current_thread::run(future.map_err(|e| panic!("{:?}", e)));
+}
@@ -0,0 +1,755 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use crate::config::GossipsubConfig;
+use crate::mcache::MessageCache;
+use crate::protocol::{
+    GossipsubControlAction,
+    GossipsubMessage,
+    GossipsubRpc,
+    GossipsubSubscription,
+    GossipsubSubscriptionAction,
+    MessageId,
+    ProtocolConfig,
+};
+use cuckoofilter::CuckooFilter;
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_floodsub::{Topic, TopicHash};
+use libp2p_swarm::{
+    NetworkBehaviour,
+    NetworkBehaviourAction,
+    PollParameters,
+    ProtocolsHandler,
+    OneShotHandler,
+    SubstreamProtocol,
+};
+use log::{debug, trace};
+use rand::seq::SliceRandom;
+use smallvec::SmallVec;
+use std::{collections::VecDeque, iter, marker::PhantomData, time::Duration};
+use std::collections::hash_map::{DefaultHasher, HashMap};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Instant, Interval};
+
+mod tests;
+
+/// Network behaviour that implements the gossipsub protocol.
+///
+/// Messages of a topic are eagerly forwarded to a bounded number of peers, the mesh of the topic,
+/// and lazily advertised to some of the other peers subscribed to the topic through `IHAVE`
+/// gossip. Topics that we publish to without being subscribed use a fanout set of peers instead.
+///
+/// The peers of a mesh are protected from being disconnected by the connection manager of the
+/// `Swarm`, under the tag returned by `mesh_tag`.
+pub struct Gossipsub<TSubstream> {
+    /// Configuration of the behaviour.
+    config: GossipsubConfig,
+
+    /// Events that need to be yielded to the outside when polling.
+    events: VecDeque<NetworkBehaviourAction<GossipsubRpc, GossipsubEvent>>,
+
+    /// Control messages to piggyback to the next heartbeat's RPCs, per peer.
+    control_pool: HashMap<PeerId, Vec<GossipsubControlAction>>,
+
+    /// Peer id of the local node. Used for the source of the messages that we publish.
+    local_peer_id: PeerId,
+
+    /// The peers subscribed to each topic.
+    topic_peers: HashMap<TopicHash, Vec<PeerId>>,
+
+    /// The peers the network is connected to, and the topics that they're subscribed to.
+    peer_topics: HashMap<PeerId, SmallVec<[TopicHash; 8]>>,
+
+    /// The mesh of each topic we're subscribed to.
+    ///
+    /// The keys of this map are the topics we're subscribed to.
+    mesh: HashMap<TopicHash, Vec<PeerId>>,
+
+    /// The peers we publish to for the topics we're not subscribed to.
+    fanout: HashMap<TopicHash, Vec<PeerId>>,
+
+    /// The last time we published to each topic of `fanout`.
+    fanout_last_pub: HashMap<TopicHash, Instant>,
+
+    /// Messages of the last heartbeats, used to answer `IWANT` requests and to emit gossip.
+    mcache: MessageCache,
+
+    // We keep track of the identifiers of the messages we received so that we don't dispatch
+    // the same message twice if we receive it twice on the network.
+    received: CuckooFilter<DefaultHasher>,
+
+    /// Fires at every heartbeat.
+    heartbeat: Interval,
+
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> Gossipsub<TSubstream> {
+    /// Creates a `Gossipsub` with the given configuration.
+    pub fn new(local_peer_id: PeerId, config: GossipsubConfig) -> Self {
+        Gossipsub {
+            mcache: MessageCache::new(config.history_gossip, config.history_length, config.message_id_fn),
+            heartbeat: Interval::new(
+                Instant::now() + config.heartbeat_initial_delay,
+                config.heartbeat_interval,
+            ),
+            config,
+            events: VecDeque::new(),
+            control_pool: HashMap::new(),
+            local_peer_id,
+            topic_peers: HashMap::new(),
+            peer_topics: HashMap::new(),
+            mesh: HashMap::new(),
+            fanout: HashMap::new(),
+            fanout_last_pub: HashMap::new(),
+            received: CuckooFilter::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Subscribes to a topic.
+    ///
+    /// Returns true if the subscription worked. Returns false if we were already subscribed.
+    pub fn subscribe(&mut self, topic: Topic) -> bool {
+        let topic_hash = topic.hash().clone();
+        if self.mesh.contains_key(&topic_hash) {
+            return false;
+        }
+
+        for peer in self.peer_topics.keys() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer.clone(),
+                event: GossipsubRpc {
+                    messages: Vec::new(),
+                    subscriptions: vec![GossipsubSubscription {
+                        topic: topic_hash.clone(),
+                        action: GossipsubSubscriptionAction::Subscribe,
+                    }],
+                    control_msgs: Vec::new(),
+                },
+            });
+        }
+
+        self.join(topic_hash);
+        true
+    }
+
+    /// Unsubscribes from a topic.
+    ///
+    /// Note that this only requires a `TopicHash` and not a full `Topic`.
+    ///
+    /// Returns true if we were subscribed to this topic.
+    pub fn unsubscribe(&mut self, topic: impl AsRef<TopicHash>) -> bool {
+        let topic = topic.as_ref();
+        if !self.mesh.contains_key(topic) {
+            return false;
+        }
+
+        for peer in self.peer_topics.keys() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer.clone(),
+                event: GossipsubRpc {
+                    messages: Vec::new(),
+                    subscriptions: vec![GossipsubSubscription {
+                        topic: topic.clone(),
+                        action: GossipsubSubscriptionAction::Unsubscribe,
+                    }],
+                    control_msgs: Vec::new(),
+                },
+            });
+        }
+
+        self.leave(topic);
+        true
+    }
+
+    /// Publishes a message to the network.
+    ///
+    /// If we're not subscribed to the topic, the message is sent to the fanout peers of the topic.
+    pub fn publish(&mut self, topic: impl Into<TopicHash>, data: impl Into<Vec<u8>>) {
+        self.publish_many(iter::once(topic), data)
+    }
+
+    /// Publishes a message with multiple topics to the network.
+    pub fn publish_many(&mut self, topic: impl IntoIterator<Item = impl Into<TopicHash>>, data: impl Into<Vec<u8>>) {
+        let message = GossipsubMessage {
+            source: self.local_peer_id.clone(),
+            data: data.into(),
+            // If the sequence numbers are predictable, then an attacker could flood the network
+            // with packets with the predetermined sequence numbers and absorb our legitimate
+            // messages. We therefore use a random number.
+            sequence_number: rand::random::<[u8; 20]>().to_vec(),
+            topics: topic.into_iter().map(|t| t.into()).collect(),
+        };
+
+        let msg_id = (self.config.message_id_fn)(&message);
+        self.received.add(&msg_id);
+        self.mcache.put(message.clone());
+
+        let mut recipients: Vec<PeerId> = Vec::new();
+        for topic_hash in &message.topics {
+            let peers = if let Some(mesh_peers) = self.mesh.get(topic_hash) {
+                mesh_peers.clone()
+            } else {
+                let mesh_n = self.config.mesh_n;
+                let topic_peers = &self.topic_peers;
+                let fanout_peers = self.fanout
+                    .entry(topic_hash.clone())
+                    .or_insert_with(|| random_peers(topic_peers, topic_hash, mesh_n, |_| true));
+                self.fanout_last_pub.insert(topic_hash.clone(), Instant::now());
+                fanout_peers.clone()
+            };
+
+            for peer in peers {
+                if !recipients.contains(&peer) {
+                    recipients.push(peer);
+                }
+            }
+        }
+
+        for peer_id in recipients {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id,
+                event: GossipsubRpc {
+                    subscriptions: Vec::new(),
+                    messages: vec![message.clone()],
+                    control_msgs: Vec::new(),
+                },
+            });
+        }
+    }
+
+    /// Builds the mesh of a topic we're now subscribed to and sends `GRAFT`s to its members.
+    ///
+    /// The peers of the fanout of the topic, if any, are reused first.
+    fn join(&mut self, topic_hash: TopicHash) {
+        debug!("Joining the mesh of topic {:?}", topic_hash);
+
+        let mut peers = self.fanout.remove(&topic_hash).unwrap_or_default();
+        self.fanout_last_pub.remove(&topic_hash);
+        peers.truncate(self.config.mesh_n);
+
+        if peers.len() < self.config.mesh_n {
+            let more = random_peers(
+                &self.topic_peers,
+                &topic_hash,
+                self.config.mesh_n - peers.len(),
+                |p| !peers.contains(p),
+            );
+            peers.extend(more);
+        }
+
+        for peer_id in &peers {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: GossipsubRpc {
+                    subscriptions: Vec::new(),
+                    messages: Vec::new(),
+                    control_msgs: vec![GossipsubControlAction::Graft {
+                        topic_hash: topic_hash.clone(),
+                    }],
+                },
+            });
+            self.events.push_back(protect_mesh_peer(peer_id.clone(), &topic_hash));
+        }
+
+        self.mesh.insert(topic_hash, peers);
+    }
+
+    /// Removes the mesh of a topic we're no longer subscribed to and sends `PRUNE`s to its
+    /// members.
+    fn leave(&mut self, topic_hash: &TopicHash) {
+        debug!("Leaving the mesh of topic {:?}", topic_hash);
+
+        for peer_id in self.mesh.remove(topic_hash).unwrap_or_default() {
+            self.events.push_back(unprotect_mesh_peer(peer_id.clone(), topic_hash));
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id,
+                event: GossipsubRpc {
+                    subscriptions: Vec::new(),
+                    messages: Vec::new(),
+                    control_msgs: vec![GossipsubControlAction::Prune {
+                        topic_hash: topic_hash.clone(),
+                    }],
+                },
+            });
+        }
+    }
+
+    /// Removes a peer from the mesh of a topic, if it is part of it.
+    fn remove_from_mesh(&mut self, topic_hash: &TopicHash, peer_id: &PeerId) {
+        if let Some(peers) = self.mesh.get_mut(topic_hash) {
+            if let Some(pos) = peers.iter().position(|p| p == peer_id) {
+                peers.remove(pos);
+                self.events.push_back(unprotect_mesh_peer(peer_id.clone(), topic_hash));
+            }
+        }
+    }
+
+    /// Handles a message received from `propagation_source`.
+    fn handle_received_message(&mut self, message: GossipsubMessage, propagation_source: &PeerId) {
+        let msg_id = (self.config.message_id_fn)(&message);
+
+        // Use `self.received` to skip the messages that we have already received in the past.
+        // Note that this can false positive.
+        if !self.received.test_and_add(&msg_id) {
+            trace!("Ignoring duplicate message {}", msg_id);
+            return;
+        }
+
+        self.mcache.put(message.clone());
+
+        // Add the message to be dispatched to the user.
+        if message.topics.iter().any(|t| self.mesh.contains_key(t)) {
+            let event = GossipsubEvent::Message(propagation_source.clone(), message.clone());
+            self.events.push_back(NetworkBehaviourAction::GenerateEvent(event));
+        }
+
+        // Forward the message to the mesh of each of its topics.
+        let mut recipients: Vec<PeerId> = Vec::new();
+        for topic_hash in &message.topics {
+            if let Some(mesh_peers) = self.mesh.get(topic_hash) {
+                for peer in mesh_peers {
+                    if peer != propagation_source && *peer != message.source && !recipients.contains(peer) {
+                        recipients.push(peer.clone());
+                    }
+                }
+            }
+        }
+
+        for peer_id in recipients {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id,
+                event: GossipsubRpc {
+                    subscriptions: Vec::new(),
+                    messages: vec![message.clone()],
+                    control_msgs: Vec::new(),
+                },
+            });
+        }
+    }
+
+    /// Handles the control messages received from `peer_id`, and answers them in a single RPC.
+    fn handle_control_msgs(&mut self, peer_id: &PeerId, control_msgs: Vec<GossipsubControlAction>) {
+        let mut answer = GossipsubRpc {
+            subscriptions: Vec::new(),
+            messages: Vec::new(),
+            control_msgs: Vec::new(),
+        };
+        let mut iwant_ids = Vec::new();
+
+        for action in control_msgs {
+            match action {
+                GossipsubControlAction::IHave { topic_hash, message_ids } => {
+                    // We only care about the messages of the topics we're subscribed to.
+                    if !self.mesh.contains_key(&topic_hash) {
+                        continue;
+                    }
+                    for id in message_ids {
+                        if !self.received.contains(&id) && !iwant_ids.contains(&id) {
+                            iwant_ids.push(id);
+                        }
+                    }
+                }
+                GossipsubControlAction::IWant { message_ids } => {
+                    for id in message_ids {
+                        if let Some(message) = self.mcache.get(&id) {
+                            if !answer.messages.contains(message) {
+                                answer.messages.push(message.clone());
+                            }
+                        }
+                    }
+                }
+                GossipsubControlAction::Graft { topic_hash } => {
+                    match self.mesh.get_mut(&topic_hash) {
+                        Some(peers) => {
+                            if peers.contains(peer_id) {
+                                continue;
+                            }
+                            if peers.len() >= self.config.mesh_n_high {
+                                // The mesh is full; let the remote know that we don't add it.
+                                debug!("Rejecting GRAFT from {:?}; the mesh of topic {:?} is full",
+                                    peer_id, topic_hash);
+                                answer.control_msgs.push(GossipsubControlAction::Prune { topic_hash });
+                            } else {
+                                peers.push(peer_id.clone());
+                                self.events.push_back(protect_mesh_peer(peer_id.clone(), &topic_hash));
+                            }
+                        }
+                        None => {
+                            // We're not subscribed to this topic; let the remote know.
+                            answer.control_msgs.push(GossipsubControlAction::Prune { topic_hash });
+                        }
+                    }
+                }
+                GossipsubControlAction::Prune { topic_hash } => {
+                    self.remove_from_mesh(&topic_hash, peer_id);
+                }
+            }
+        }
+
+        if !iwant_ids.is_empty() {
+            answer.control_msgs.push(GossipsubControlAction::IWant { message_ids: iwant_ids });
+        }
+
+        if !answer.is_empty() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: answer,
+            });
+        }
+    }
+
+    /// Maintains the meshes and the fanouts, emits gossip and shifts the message cache.
+    fn heartbeat(&mut self) {
+        trace!("Gossipsub heartbeat");
+
+        let mut to_graft: Vec<(PeerId, TopicHash)> = Vec::new();
+        let mut to_prune: Vec<(PeerId, TopicHash)> = Vec::new();
+
+        for (topic_hash, peers) in self.mesh.iter_mut() {
+            if peers.len() < self.config.mesh_n_low {
+                let more = random_peers(
+                    &self.topic_peers,
+                    topic_hash,
+                    self.config.mesh_n - peers.len(),
+                    |p| !peers.contains(p),
+                );
+                for peer in more {
+                    to_graft.push((peer.clone(), topic_hash.clone()));
+                    peers.push(peer);
+                }
+            }
+
+            if peers.len() > self.config.mesh_n_high {
+                peers.shuffle(&mut rand::thread_rng());
+                for peer in peers.drain(self.config.mesh_n..) {
+                    to_prune.push((peer, topic_hash.clone()));
+                }
+            }
+        }
+
+        // Forget the fanout of the topics we haven't published to for a while.
+        let fanout_ttl = self.config.fanout_ttl;
+        let now = Instant::now();
+        let expired: Vec<TopicHash> = self.fanout_last_pub
+            .iter()
+            .filter(|(_, last_pub)| **last_pub + fanout_ttl < now)
+            .map(|(topic_hash, _)| topic_hash.clone())
+            .collect();
+        for topic_hash in expired {
+            self.fanout.remove(&topic_hash);
+            self.fanout_last_pub.remove(&topic_hash);
+        }
+
+        for (topic_hash, peers) in self.fanout.iter_mut() {
+            if peers.len() < self.config.mesh_n {
+                let more = random_peers(
+                    &self.topic_peers,
+                    topic_hash,
+                    self.config.mesh_n - peers.len(),
+                    |p| !peers.contains(p),
+                );
+                peers.extend(more);
+            }
+        }
+
+        self.emit_gossip();
+
+        for (peer_id, topic_hash) in to_graft {
+            self.events.push_back(protect_mesh_peer(peer_id.clone(), &topic_hash));
+            self.control_pool
+                .entry(peer_id)
+                .or_insert_with(Vec::new)
+                .push(GossipsubControlAction::Graft { topic_hash });
+        }
+        for (peer_id, topic_hash) in to_prune {
+            self.events.push_back(unprotect_mesh_peer(peer_id.clone(), &topic_hash));
+            self.control_pool
+                .entry(peer_id)
+                .or_insert_with(Vec::new)
+                .push(GossipsubControlAction::Prune { topic_hash });
+        }
+
+        for (peer_id, control_msgs) in self.control_pool.drain() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id,
+                event: GossipsubRpc {
+                    subscriptions: Vec::new(),
+                    messages: Vec::new(),
+                    control_msgs,
+                },
+            });
+        }
+
+        self.mcache.shift();
+    }
+
+    /// Queues `IHAVE` gossip about the recent messages of our meshes and fanouts to peers that
+    /// aren't part of them.
+    fn emit_gossip(&mut self) {
+        for (topic_hash, peers) in self.mesh.iter().chain(self.fanout.iter()) {
+            let message_ids = self.mcache.get_gossip_ids(topic_hash);
+            if message_ids.is_empty() {
+                continue;
+            }
+
+            let gossip_peers = random_peers(
+                &self.topic_peers,
+                topic_hash,
+                self.config.gossip_lazy,
+                |p| !peers.contains(p),
+            );
+            for peer_id in gossip_peers {
+                self.control_pool
+                    .entry(peer_id)
+                    .or_insert_with(Vec::new)
+                    .push(GossipsubControlAction::IHave {
+                        topic_hash: topic_hash.clone(),
+                        message_ids: message_ids.clone(),
+                    });
+            }
+        }
+    }
+}
+
+/// Returns the tag under which the peers of the mesh of a topic are protected from being
+/// disconnected by the connection manager of the `Swarm`.
+pub fn mesh_tag(topic_hash: &TopicHash) -> String {
+    format!("gossipsub-mesh:{}", topic_hash.clone().into_string())
+}
+
+/// Returns the action protecting a peer that joined the mesh of a topic.
+fn protect_mesh_peer(peer_id: PeerId, topic_hash: &TopicHash)
+    -> NetworkBehaviourAction<GossipsubRpc, GossipsubEvent>
+{
+    NetworkBehaviourAction::ProtectPeer { peer_id, tag: mesh_tag(topic_hash) }
+}
+
+/// Returns the action removing the protection of a peer that left the mesh of a topic.
+fn unprotect_mesh_peer(peer_id: PeerId, topic_hash: &TopicHash)
+    -> NetworkBehaviourAction<GossipsubRpc, GossipsubEvent>
+{
+    NetworkBehaviourAction::UnprotectPeer { peer_id, tag: mesh_tag(topic_hash) }
+}
+
+/// Returns up to `n` random peers subscribed to `topic_hash` that match `filter`.
+fn random_peers(
+    topic_peers: &HashMap<TopicHash, Vec<PeerId>>,
+    topic_hash: &TopicHash,
+    n: usize,
+    mut filter: impl FnMut(&PeerId) -> bool,
+) -> Vec<PeerId> {
+    let mut peers: Vec<PeerId> = topic_peers
+        .get(topic_hash)
+        .map(|peers| peers.iter().filter(|p| filter(p)).cloned().collect())
+        .unwrap_or_default();
+    peers.shuffle(&mut rand::thread_rng());
+    peers.truncate(n);
+    peers
+}
+
+impl<TSubstream> NetworkBehaviour for Gossipsub<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = OneShotHandler<TSubstream, ProtocolConfig, GossipsubRpc, InnerMessage>;
+    type OutEvent = GossipsubEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        let listen_protocol = SubstreamProtocol::new(ProtocolConfig::new(self.config.max_transmit_size));
+        OneShotHandler::new(listen_protocol, Duration::from_secs(10))
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, id: PeerId, _: ConnectedPoint) {
+        // We need to send our subscriptions to the newly-connected node.
+        let subscriptions: Vec<_> = self.mesh
+            .keys()
+            .map(|topic_hash| GossipsubSubscription {
+                topic: topic_hash.clone(),
+                action: GossipsubSubscriptionAction::Subscribe,
+            })
+            .collect();
+
+        if !subscriptions.is_empty() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: id.clone(),
+                event: GossipsubRpc {
+                    messages: Vec::new(),
+                    subscriptions,
+                    control_msgs: Vec::new(),
+                },
+            });
+        }
+
+        self.peer_topics.insert(id, SmallVec::new());
+    }
+
+    fn inject_disconnected(&mut self, id: &PeerId, _: ConnectedPoint) {
+        let topics = self.peer_topics.remove(id);
+        debug_assert!(topics.is_some());
+
+        for topic_hash in topics.unwrap_or_default() {
+            if let Some(peers) = self.topic_peers.get_mut(&topic_hash) {
+                peers.retain(|p| p != id);
+            }
+            self.remove_from_mesh(&topic_hash, id);
+            if let Some(peers) = self.fanout.get_mut(&topic_hash) {
+                peers.retain(|p| p != id);
+            }
+        }
+
+        self.control_pool.remove(id);
+    }
+
+    fn inject_node_event(
+        &mut self,
+        propagation_source: PeerId,
+        event: InnerMessage,
+    ) {
+        // We ignore successful sends event.
+        let event = match event {
+            InnerMessage::Rx(event) => event,
+            InnerMessage::Sent => return,
+        };
+
+        // Update connected peers topics
+        for subscription in event.subscriptions {
+            let remote_peer_topics = self.peer_topics
+                .get_mut(&propagation_source)
+                .expect("peer_topics is kept in sync with the peers we are connected to; we are guaranteed to only receive events from connected peers; QED");
+            let topic_peers = self.topic_peers
+                .entry(subscription.topic.clone())
+                .or_insert_with(Vec::new);
+            match subscription.action {
+                GossipsubSubscriptionAction::Subscribe => {
+                    if !remote_peer_topics.contains(&subscription.topic) {
+                        remote_peer_topics.push(subscription.topic.clone());
+                    }
+                    if !topic_peers.contains(&propagation_source) {
+                        topic_peers.push(propagation_source.clone());
+                    }
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Subscribed {
+                        peer_id: propagation_source.clone(),
+                        topic: subscription.topic,
+                    }));
+                }
+                GossipsubSubscriptionAction::Unsubscribe => {
+                    if let Some(pos) = remote_peer_topics.iter().position(|t| t == &subscription.topic) {
+                        remote_peer_topics.remove(pos);
+                    }
+                    topic_peers.retain(|p| p != &propagation_source);
+                    self.remove_from_mesh(&subscription.topic, &propagation_source);
+                    if let Some(peers) = self.fanout.get_mut(&subscription.topic) {
+                        peers.retain(|p| p != &propagation_source);
+                    }
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Unsubscribed {
+                        peer_id: propagation_source.clone(),
+                        topic: subscription.topic,
+                    }));
+                }
+            }
+        }
+
+        for message in event.messages {
+            self.handle_received_message(message, &propagation_source);
+        }
+
+        if !event.control_msgs.is_empty() {
+            self.handle_control_msgs(&propagation_source, event.control_msgs);
+        }
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut impl PollParameters,
+    ) -> Async<
+        NetworkBehaviourAction<
+            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            Self::OutEvent,
+        >,
+    > {
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(event);
+        }
+
+        while let Ok(Async::Ready(Some(_))) = self.heartbeat.poll() {
+            self.heartbeat();
+        }
+
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(event);
+        }
+
+        Async::NotReady
+    }
+}
+
+/// Transmission between the `OneShotHandler` and the `Gossipsub` behaviour.
+pub enum InnerMessage {
+    /// We received an RPC from a remote.
+    Rx(GossipsubRpc),
+    /// We successfully sent an RPC request.
+    Sent,
+}
+
+impl From<GossipsubRpc> for InnerMessage {
+    #[inline]
+    fn from(rpc: GossipsubRpc) -> InnerMessage {
+        InnerMessage::Rx(rpc)
+    }
+}
+
+impl From<()> for InnerMessage {
+    #[inline]
+    fn from(_: ()) -> InnerMessage {
+        InnerMessage::Sent
+    }
+}
+
+/// Event that can happen on the gossipsub behaviour.
+#[derive(Debug)]
+pub enum GossipsubEvent {
+    /// A message has been received. Contains the peer that propagated the message to us.
+    Message(PeerId, GossipsubMessage),
+
+    /// A remote subscribed to a topic.
+    Subscribed {
+        /// Remote that has subscribed.
+        peer_id: PeerId,
+        /// The topic it has subscribed to.
+        topic: TopicHash,
+    },
+
+    /// A remote unsubscribed from a topic.
+    Unsubscribed {
+        /// Remote that has unsubscribed.
+        peer_id: PeerId,
+        /// The topic it has subscribed from.
+        topic: TopicHash,
+    },
+}
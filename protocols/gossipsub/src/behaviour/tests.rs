@@ -0,0 +1,307 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+#![cfg(test)]
+
+use super::*;
+use libp2p_core::transport::memory::Channel;
+use libp2p_floodsub::TopicBuilder;
+
+type TestGossipsub = Gossipsub<Channel<Vec<u8>>>;
+
+fn new_topic(name: &str) -> Topic {
+    TopicBuilder::new(name).build()
+}
+
+fn rpc(
+    subscriptions: Vec<GossipsubSubscription>,
+    control_msgs: Vec<GossipsubControlAction>,
+) -> InnerMessage {
+    InnerMessage::Rx(GossipsubRpc {
+        messages: Vec::new(),
+        subscriptions,
+        control_msgs,
+    })
+}
+
+/// Connects a new peer subscribed to the given topics.
+fn add_peer(gs: &mut TestGossipsub, topics: &[TopicHash]) -> PeerId {
+    let peer_id = PeerId::random();
+    let endpoint = ConnectedPoint::Dialer { address: "/ip4/127.0.0.1/tcp/1234".parse().unwrap() };
+    gs.inject_connected(peer_id.clone(), endpoint);
+    let subscriptions = topics
+        .iter()
+        .map(|topic| GossipsubSubscription {
+            topic: topic.clone(),
+            action: GossipsubSubscriptionAction::Subscribe,
+        })
+        .collect();
+    gs.inject_node_event(peer_id.clone(), rpc(subscriptions, Vec::new()));
+    peer_id
+}
+
+/// Drains the pending events and returns the RPCs sent to `peer_id`.
+fn sent_to(gs: &mut TestGossipsub, peer_id: &PeerId) -> Vec<GossipsubRpc> {
+    let mut rpcs = Vec::new();
+    let mut others = VecDeque::new();
+    for event in gs.events.drain(..) {
+        match event {
+            NetworkBehaviourAction::SendEvent { peer_id: ref p, ref event } if p == peer_id => {
+                rpcs.push(event.clone())
+            }
+            event => others.push_back(event),
+        }
+    }
+    gs.events = others;
+    rpcs
+}
+
+/// Returns the control messages sent to `peer_id`.
+fn control_sent_to(gs: &mut TestGossipsub, peer_id: &PeerId) -> Vec<GossipsubControlAction> {
+    sent_to(gs, peer_id).into_iter().flat_map(|rpc| rpc.control_msgs).collect()
+}
+
+fn message(topic_hash: &TopicHash) -> GossipsubMessage {
+    GossipsubMessage {
+        source: PeerId::random(),
+        data: b"data".to_vec(),
+        sequence_number: rand::random::<[u8; 20]>().to_vec(),
+        topics: vec![topic_hash.clone()],
+    }
+}
+
+#[test]
+fn graft_accepted_for_subscribed_topic() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut gs = TestGossipsub::new(PeerId::random(), GossipsubConfig::default());
+    gs.subscribe(topic.clone());
+    let peer = add_peer(&mut gs, &[t.clone()]);
+
+    gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![GossipsubControlAction::Graft { topic_hash: t.clone() }]));
+
+    assert!(gs.mesh[&t].contains(&peer));
+    assert!(control_sent_to(&mut gs, &peer).is_empty());
+}
+
+#[test]
+fn graft_rejected_for_unsubscribed_topic() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut gs = TestGossipsub::new(PeerId::random(), GossipsubConfig::default());
+    let peer = add_peer(&mut gs, &[t.clone()]);
+
+    gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![GossipsubControlAction::Graft { topic_hash: t.clone() }]));
+
+    assert!(!gs.mesh.contains_key(&t));
+    assert_eq!(control_sent_to(&mut gs, &peer), vec![GossipsubControlAction::Prune { topic_hash: t }]);
+}
+
+#[test]
+fn graft_rejected_when_mesh_full() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut config = GossipsubConfig::default();
+    config.set_mesh_n(2, 1, 2);
+    let mut gs = TestGossipsub::new(PeerId::random(), config);
+    gs.subscribe(topic.clone());
+
+    let peers = (0 .. 3).map(|_| add_peer(&mut gs, &[t.clone()])).collect::<Vec<_>>();
+    for peer in &peers {
+        gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![GossipsubControlAction::Graft { topic_hash: t.clone() }]));
+    }
+
+    assert_eq!(gs.mesh[&t], peers[.. 2].to_vec());
+    assert!(control_sent_to(&mut gs, &peers[0]).is_empty());
+    assert_eq!(control_sent_to(&mut gs, &peers[2]), vec![GossipsubControlAction::Prune { topic_hash: t }]);
+}
+
+#[test]
+fn prune_removes_peer_from_mesh() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut gs = TestGossipsub::new(PeerId::random(), GossipsubConfig::default());
+    gs.subscribe(topic.clone());
+    let peer = add_peer(&mut gs, &[t.clone()]);
+    gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![GossipsubControlAction::Graft { topic_hash: t.clone() }]));
+    assert!(gs.mesh[&t].contains(&peer));
+
+    gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![GossipsubControlAction::Prune { topic_hash: t.clone() }]));
+    assert!(!gs.mesh[&t].contains(&peer));
+}
+
+#[test]
+fn mesh_peers_are_protected() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut gs = TestGossipsub::new(PeerId::random(), GossipsubConfig::default());
+    gs.subscribe(topic.clone());
+    let peer = add_peer(&mut gs, &[t.clone()]);
+    gs.events.clear();
+
+    gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![GossipsubControlAction::Graft { topic_hash: t.clone() }]));
+    let protected = gs.events.iter().any(|e| match e {
+        NetworkBehaviourAction::ProtectPeer { peer_id, tag } => peer_id == &peer && *tag == mesh_tag(&t),
+        _ => false,
+    });
+    assert!(protected);
+
+    gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![GossipsubControlAction::Prune { topic_hash: t.clone() }]));
+    let unprotected = gs.events.iter().any(|e| match e {
+        NetworkBehaviourAction::UnprotectPeer { peer_id, tag } => peer_id == &peer && *tag == mesh_tag(&t),
+        _ => false,
+    });
+    assert!(unprotected);
+}
+
+#[test]
+fn heartbeat_grafts_peers_when_mesh_too_small() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut gs = TestGossipsub::new(PeerId::random(), GossipsubConfig::default());
+    // Subscribe before knowing any peer, so that the mesh starts empty.
+    gs.subscribe(topic.clone());
+    let peers = (0 .. 10).map(|_| add_peer(&mut gs, &[t.clone()])).collect::<Vec<_>>();
+    assert!(gs.mesh[&t].is_empty());
+
+    gs.heartbeat();
+
+    assert_eq!(gs.mesh[&t].len(), gs.config.mesh_n);
+    for peer in &peers {
+        let grafted = control_sent_to(&mut gs, peer)
+            .contains(&GossipsubControlAction::Graft { topic_hash: t.clone() });
+        assert_eq!(grafted, gs.mesh[&t].contains(peer));
+    }
+}
+
+#[test]
+fn heartbeat_prunes_peers_when_mesh_too_large() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut gs = TestGossipsub::new(PeerId::random(), GossipsubConfig::default());
+    gs.subscribe(topic.clone());
+    let peers = (0 .. 15).map(|_| add_peer(&mut gs, &[t.clone()])).collect::<Vec<_>>();
+    gs.mesh.insert(t.clone(), peers.clone());
+
+    gs.heartbeat();
+
+    assert_eq!(gs.mesh[&t].len(), gs.config.mesh_n);
+    for peer in &peers {
+        let pruned = control_sent_to(&mut gs, peer)
+            .contains(&GossipsubControlAction::Prune { topic_hash: t.clone() });
+        assert_eq!(pruned, !gs.mesh[&t].contains(peer));
+    }
+}
+
+#[test]
+fn fanout_expires_after_ttl() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut gs = TestGossipsub::new(PeerId::random(), GossipsubConfig::default());
+    let peer = add_peer(&mut gs, &[t.clone()]);
+
+    gs.publish(t.clone(), "data");
+    assert_eq!(gs.fanout[&t], vec![peer.clone()]);
+    assert_eq!(sent_to(&mut gs, &peer).len(), 1);
+
+    // A recent publication keeps the fanout.
+    gs.heartbeat();
+    assert!(gs.fanout.contains_key(&t));
+
+    let expired = Instant::now() - gs.config.fanout_ttl - Duration::from_secs(1);
+    gs.fanout_last_pub.insert(t.clone(), expired);
+    gs.heartbeat();
+    assert!(!gs.fanout.contains_key(&t));
+    assert!(!gs.fanout_last_pub.contains_key(&t));
+}
+
+#[test]
+fn ihave_answered_with_iwant_for_unknown_messages() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut gs = TestGossipsub::new(PeerId::random(), GossipsubConfig::default());
+    gs.subscribe(topic.clone());
+    let peer = add_peer(&mut gs, &[t.clone()]);
+
+    let known = message(&t);
+    let known_id = (gs.config.message_id_fn)(&known);
+    gs.handle_received_message(known, &peer);
+    let unknown_id = MessageId("unknown".to_owned());
+
+    let ihave = GossipsubControlAction::IHave {
+        topic_hash: t.clone(),
+        message_ids: vec![known_id, unknown_id.clone()],
+    };
+    gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![ihave]));
+    assert_eq!(
+        control_sent_to(&mut gs, &peer),
+        vec![GossipsubControlAction::IWant { message_ids: vec![unknown_id] }]
+    );
+
+    // Gossip about topics we're not subscribed to is ignored.
+    let ihave = GossipsubControlAction::IHave {
+        topic_hash: new_topic("other").hash().clone(),
+        message_ids: vec![MessageId("other".to_owned())],
+    };
+    gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![ihave]));
+    assert!(sent_to(&mut gs, &peer).is_empty());
+}
+
+#[test]
+fn iwant_answered_with_cached_messages() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut gs = TestGossipsub::new(PeerId::random(), GossipsubConfig::default());
+    let peer = add_peer(&mut gs, &[t.clone()]);
+
+    gs.publish(t.clone(), "data");
+    let published = sent_to(&mut gs, &peer).remove(0).messages.remove(0);
+    let id = (gs.config.message_id_fn)(&published);
+
+    let iwant = GossipsubControlAction::IWant {
+        message_ids: vec![id, MessageId("unknown".to_owned())],
+    };
+    gs.inject_node_event(peer.clone(), rpc(Vec::new(), vec![iwant]));
+    let answers = sent_to(&mut gs, &peer);
+    assert_eq!(answers.len(), 1);
+    assert_eq!(answers[0].messages, vec![published]);
+}
+
+#[test]
+fn heartbeat_emits_gossip_to_peers_outside_mesh() {
+    let topic = new_topic("t");
+    let t = topic.hash().clone();
+    let mut config = GossipsubConfig::default();
+    config.set_mesh_n(1, 1, 2);
+    let mut gs = TestGossipsub::new(PeerId::random(), config);
+    let peers = (0 .. 3).map(|_| add_peer(&mut gs, &[t.clone()])).collect::<Vec<_>>();
+    gs.subscribe(topic.clone());
+    assert_eq!(gs.mesh[&t].len(), 1);
+
+    gs.publish(t.clone(), "data");
+    let id = gs.mcache.get_gossip_ids(&t).remove(0);
+    gs.heartbeat();
+
+    for peer in &peers {
+        let ihave = GossipsubControlAction::IHave { topic_hash: t.clone(), message_ids: vec![id.clone()] };
+        let gossiped = control_sent_to(&mut gs, peer).contains(&ihave);
+        assert_eq!(gossiped, !gs.mesh[&t].contains(peer));
+    }
+}
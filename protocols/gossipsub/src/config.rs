@@ -0,0 +1,179 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use crate::protocol::{GossipsubMessage, MessageId};
+use std::time::Duration;
+
+/// The configuration for the `Gossipsub` behaviour.
+///
+/// The configuration is consumed by [`Gossipsub::new`](crate::Gossipsub::new).
+#[derive(Clone)]
+pub struct GossipsubConfig {
+    pub(crate) history_length: usize,
+    pub(crate) history_gossip: usize,
+    pub(crate) mesh_n: usize,
+    pub(crate) mesh_n_low: usize,
+    pub(crate) mesh_n_high: usize,
+    pub(crate) gossip_lazy: usize,
+    pub(crate) heartbeat_initial_delay: Duration,
+    pub(crate) heartbeat_interval: Duration,
+    pub(crate) fanout_ttl: Duration,
+    pub(crate) max_transmit_size: usize,
+    pub(crate) message_id_fn: fn(&GossipsubMessage) -> MessageId,
+}
+
+impl Default for GossipsubConfig {
+    fn default() -> Self {
+        GossipsubConfig {
+            history_length: 5,
+            history_gossip: 3,
+            mesh_n: 6,
+            mesh_n_low: 4,
+            mesh_n_high: 12,
+            gossip_lazy: 6,
+            heartbeat_initial_delay: Duration::from_secs(5),
+            heartbeat_interval: Duration::from_secs(1),
+            fanout_ttl: Duration::from_secs(60),
+            max_transmit_size: 2048,
+            message_id_fn: default_message_id,
+        }
+    }
+}
+
+impl GossipsubConfig {
+    /// Sets the number of heartbeats for which messages are kept in the message cache.
+    ///
+    /// The default is 5.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `history_length` is smaller than the number of heartbeats advertised in gossip,
+    /// see [`set_history_gossip`](GossipsubConfig::set_history_gossip).
+    pub fn set_history_length(&mut self, history_length: usize) -> &mut Self {
+        assert!(history_length >= self.history_gossip);
+        self.history_length = history_length;
+        self
+    }
+
+    /// Sets the number of past heartbeats whose messages are advertised in `IHAVE` gossip.
+    ///
+    /// The default is 3.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `history_gossip` is larger than the history length, as we would advertise
+    /// messages that are no longer in the cache.
+    pub fn set_history_gossip(&mut self, history_gossip: usize) -> &mut Self {
+        assert!(history_gossip <= self.history_length);
+        self.history_gossip = history_gossip;
+        self
+    }
+
+    /// Sets the target number of peers in the mesh of a topic, together with the bounds outside
+    /// of which the mesh is repaired during the heartbeat.
+    ///
+    /// The defaults are 6, 4 and 12 respectively.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the condition `mesh_n_low <= mesh_n <= mesh_n_high` doesn't hold.
+    pub fn set_mesh_n(&mut self, mesh_n: usize, mesh_n_low: usize, mesh_n_high: usize) -> &mut Self {
+        assert!(mesh_n_low <= mesh_n && mesh_n <= mesh_n_high);
+        self.mesh_n = mesh_n;
+        self.mesh_n_low = mesh_n_low;
+        self.mesh_n_high = mesh_n_high;
+        self
+    }
+
+    /// Sets the number of peers outside of the mesh to which `IHAVE` gossip is emitted.
+    ///
+    /// The default is 6.
+    pub fn set_gossip_lazy(&mut self, gossip_lazy: usize) -> &mut Self {
+        self.gossip_lazy = gossip_lazy;
+        self
+    }
+
+    /// Sets the delay before the first heartbeat.
+    ///
+    /// The default is 5 seconds.
+    pub fn set_heartbeat_initial_delay(&mut self, delay: Duration) -> &mut Self {
+        self.heartbeat_initial_delay = delay;
+        self
+    }
+
+    /// Sets the interval between two heartbeats.
+    ///
+    /// The default is 1 second.
+    pub fn set_heartbeat_interval(&mut self, interval: Duration) -> &mut Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// Sets the time after which the fanout of a topic we have stopped publishing to is
+    /// forgotten.
+    ///
+    /// The default is 60 seconds.
+    pub fn set_fanout_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.fanout_ttl = ttl;
+        self
+    }
+
+    /// Sets the maximum size, in bytes, of an RPC received over a substream.
+    ///
+    /// The default is 2048 bytes.
+    pub fn set_max_transmit_size(&mut self, max_transmit_size: usize) -> &mut Self {
+        self.max_transmit_size = max_transmit_size;
+        self
+    }
+
+    /// Sets the function used to derive the identifier of a message.
+    ///
+    /// Message identifiers are used for de-duplication and in `IHAVE`/`IWANT` gossip. The default
+    /// concatenates the base58 encoding of the source and of the sequence number.
+    pub fn set_message_id_fn(&mut self, message_id_fn: fn(&GossipsubMessage) -> MessageId) -> &mut Self {
+        self.message_id_fn = message_id_fn;
+        self
+    }
+}
+
+impl std::fmt::Debug for GossipsubConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GossipsubConfig")
+            .field("history_length", &self.history_length)
+            .field("history_gossip", &self.history_gossip)
+            .field("mesh_n", &self.mesh_n)
+            .field("mesh_n_low", &self.mesh_n_low)
+            .field("mesh_n_high", &self.mesh_n_high)
+            .field("gossip_lazy", &self.gossip_lazy)
+            .field("heartbeat_initial_delay", &self.heartbeat_initial_delay)
+            .field("heartbeat_interval", &self.heartbeat_interval)
+            .field("fanout_ttl", &self.fanout_ttl)
+            .field("max_transmit_size", &self.max_transmit_size)
+            .finish()
+    }
+}
+
+/// The default way of computing a `MessageId`, from the source and the sequence number.
+fn default_message_id(message: &GossipsubMessage) -> MessageId {
+    let mut id = message.source.to_base58();
+    id.push_str(&bs58::encode(&message.sequence_number).into_string());
+    MessageId(id)
+}
@@ -0,0 +1,43 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Implements the gossipsub protocol, see also the:
+//! [spec](https://github.com/libp2p/specs/tree/master/pubsub/gossipsub).
+//!
+//! Gossipsub is a pubsub protocol that can be used in place of floodsub. Instead of forwarding
+//! every message to every peer subscribed to its topics, each node maintains a bounded mesh of
+//! peers per topic that messages are forwarded to, and periodically gossips about the messages
+//! it has recently seen to the other peers so that they can request the ones they missed.
+//!
+//! The `Topic` and `TopicHash` types are shared with floodsub, which makes it possible to move
+//! an application from one protocol to the other incrementally.
+
+pub mod protocol;
+
+mod behaviour;
+mod config;
+mod mcache;
+mod rpc_proto;
+
+pub use self::behaviour::{Gossipsub, GossipsubEvent, mesh_tag};
+pub use self::config::GossipsubConfig;
+pub use self::protocol::{GossipsubMessage, GossipsubRpc, MessageId};
+pub use libp2p_floodsub::{Topic, TopicBuilder, TopicHash};
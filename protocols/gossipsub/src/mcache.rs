@@ -0,0 +1,158 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use crate::protocol::{GossipsubMessage, MessageId};
+use fnv::FnvHashMap;
+use libp2p_floodsub::TopicHash;
+
+/// An entry of the history, pointing to a message of the cache.
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    mid: MessageId,
+    topics: Vec<TopicHash>,
+}
+
+/// Cache of the messages seen during the last few heartbeats.
+///
+/// The history is split into windows, one per heartbeat. Messages are gossiped about during the
+/// first `gossip` windows, and can be retrieved for as long as they are part of the history.
+pub struct MessageCache {
+    msgs: FnvHashMap<MessageId, GossipsubMessage>,
+    history: Vec<Vec<CacheEntry>>,
+    gossip: usize,
+    id_fn: fn(&GossipsubMessage) -> MessageId,
+}
+
+impl MessageCache {
+    /// Creates a new cache holding `history_capacity` windows, of which the most recent
+    /// `gossip` are advertised.
+    pub fn new(gossip: usize, history_capacity: usize, id_fn: fn(&GossipsubMessage) -> MessageId) -> MessageCache {
+        MessageCache {
+            msgs: FnvHashMap::default(),
+            history: vec![Vec::new(); history_capacity],
+            gossip,
+            id_fn,
+        }
+    }
+
+    /// Puts a message into the current window of the cache.
+    pub fn put(&mut self, msg: GossipsubMessage) {
+        let mid = (self.id_fn)(&msg);
+        let cache_entry = CacheEntry {
+            mid: mid.clone(),
+            topics: msg.topics.clone(),
+        };
+
+        if self.msgs.insert(mid, msg).is_none() {
+            if let Some(window) = self.history.get_mut(0) {
+                window.push(cache_entry);
+            }
+        }
+    }
+
+    /// Returns the message with the given identifier, if it is in the cache.
+    pub fn get(&self, mid: &MessageId) -> Option<&GossipsubMessage> {
+        self.msgs.get(mid)
+    }
+
+    /// Returns the identifiers of the messages of the given topic that are in the gossip windows.
+    pub fn get_gossip_ids(&self, topic: &TopicHash) -> Vec<MessageId> {
+        self.history[..self.gossip.min(self.history.len())]
+            .iter()
+            .flat_map(|window| window.iter())
+            .filter(|entry| entry.topics.iter().any(|t| t == topic))
+            .map(|entry| entry.mid.clone())
+            .collect()
+    }
+
+    /// Shifts the windows of the cache, dropping the messages of the oldest one.
+    pub fn shift(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        for entry in self.history.pop().expect("history is not empty; QED") {
+            self.msgs.remove(&entry.mid);
+        }
+
+        self.history.insert(0, Vec::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::PeerId;
+
+    fn id_fn(message: &GossipsubMessage) -> MessageId {
+        MessageId(String::from_utf8(message.data.clone()).unwrap())
+    }
+
+    fn message(data: &str, topic: &TopicHash) -> GossipsubMessage {
+        GossipsubMessage {
+            source: PeerId::random(),
+            data: data.as_bytes().to_vec(),
+            sequence_number: Vec::new(),
+            topics: vec![topic.clone()],
+        }
+    }
+
+    #[test]
+    fn put_get() {
+        let topic = TopicHash::from_raw("topic".to_owned());
+        let mut mc = MessageCache::new(3, 5, id_fn);
+        let msg = message("a", &topic);
+        mc.put(msg.clone());
+
+        assert_eq!(mc.get(&MessageId("a".to_owned())), Some(&msg));
+        assert_eq!(mc.get(&MessageId("b".to_owned())), None);
+        assert_eq!(mc.get_gossip_ids(&topic), vec![MessageId("a".to_owned())]);
+        assert!(mc.get_gossip_ids(&TopicHash::from_raw("other".to_owned())).is_empty());
+    }
+
+    #[test]
+    fn gossip_window_and_expiry() {
+        let topic = TopicHash::from_raw("topic".to_owned());
+        let mut mc = MessageCache::new(2, 3, id_fn);
+        mc.put(message("a", &topic));
+
+        mc.shift();
+        assert_eq!(mc.get_gossip_ids(&topic).len(), 1);
+
+        // The message leaves the gossip windows but stays retrievable.
+        mc.shift();
+        assert!(mc.get_gossip_ids(&topic).is_empty());
+        assert!(mc.get(&MessageId("a".to_owned())).is_some());
+
+        // The message leaves the history.
+        mc.shift();
+        assert!(mc.get(&MessageId("a".to_owned())).is_none());
+    }
+
+    #[test]
+    fn duplicates_are_ignored() {
+        let topic = TopicHash::from_raw("topic".to_owned());
+        let mut mc = MessageCache::new(3, 5, id_fn);
+        mc.put(message("a", &topic));
+        mc.put(message("a", &topic));
+        assert_eq!(mc.get_gossip_ids(&topic).len(), 1);
+    }
+}
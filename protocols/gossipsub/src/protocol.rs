@@ -0,0 +1,402 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use crate::rpc_proto;
+use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, PeerId, upgrade};
+use libp2p_floodsub::TopicHash;
+use protobuf::{ProtobufError, Message as ProtobufMessage};
+use std::{error, fmt, io, iter};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Name of the protocol negotiated on the substreams.
+const PROTOCOL_NAME: &[u8] = b"/meshsub/1.0.0";
+
+/// Implementation of `InboundUpgrade` for the gossipsub protocol.
+#[derive(Debug, Clone)]
+pub struct ProtocolConfig {
+    max_transmit_size: usize,
+}
+
+impl ProtocolConfig {
+    /// Builds a new `ProtocolConfig` that rejects RPCs larger than `max_transmit_size` bytes.
+    #[inline]
+    pub fn new(max_transmit_size: usize) -> ProtocolConfig {
+        ProtocolConfig { max_transmit_size }
+    }
+}
+
+impl Default for ProtocolConfig {
+    #[inline]
+    fn default() -> Self {
+        ProtocolConfig::new(2048)
+    }
+}
+
+impl UpgradeInfo for ProtocolConfig {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    #[inline]
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for ProtocolConfig
+where
+    TSocket: AsyncRead + AsyncWrite,
+{
+    type Output = GossipsubRpc;
+    type Error = GossipsubDecodeError;
+    type Future = upgrade::ReadOneThen<upgrade::Negotiated<TSocket>, (), fn(Vec<u8>, ()) -> Result<GossipsubRpc, GossipsubDecodeError>>;
+
+    #[inline]
+    fn upgrade_inbound(self, socket: upgrade::Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        upgrade::read_one_then(socket, self.max_transmit_size, (), |packet, ()| {
+            GossipsubRpc::from_bytes(&packet)
+        })
+    }
+}
+
+/// Error that can happen when decoding an RPC.
+#[derive(Debug)]
+pub enum GossipsubDecodeError {
+    /// Error when reading the packet from the socket.
+    ReadError(upgrade::ReadOneError),
+    /// Error when decoding the raw buffer into a protobuf.
+    ProtobufError(ProtobufError),
+    /// Error when parsing the `PeerId` in the message.
+    InvalidPeerId,
+}
+
+impl From<upgrade::ReadOneError> for GossipsubDecodeError {
+    #[inline]
+    fn from(err: upgrade::ReadOneError) -> Self {
+        GossipsubDecodeError::ReadError(err)
+    }
+}
+
+impl From<ProtobufError> for GossipsubDecodeError {
+    #[inline]
+    fn from(err: ProtobufError) -> Self {
+        GossipsubDecodeError::ProtobufError(err)
+    }
+}
+
+impl fmt::Display for GossipsubDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            GossipsubDecodeError::ReadError(ref err) =>
+                write!(f, "Error while reading from socket: {}", err),
+            GossipsubDecodeError::ProtobufError(ref err) =>
+                write!(f, "Error while decoding protobuf: {}", err),
+            GossipsubDecodeError::InvalidPeerId =>
+                write!(f, "Error while decoding PeerId from message"),
+        }
+    }
+}
+
+impl error::Error for GossipsubDecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            GossipsubDecodeError::ReadError(ref err) => Some(err),
+            GossipsubDecodeError::ProtobufError(ref err) => Some(err),
+            GossipsubDecodeError::InvalidPeerId => None,
+        }
+    }
+}
+
+/// An RPC exchanged by the gossipsub system.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GossipsubRpc {
+    /// List of messages that were part of this RPC query.
+    pub messages: Vec<GossipsubMessage>,
+    /// List of subscriptions.
+    pub subscriptions: Vec<GossipsubSubscription>,
+    /// List of gossipsub control messages.
+    pub control_msgs: Vec<GossipsubControlAction>,
+}
+
+impl GossipsubRpc {
+    /// Returns true if the RPC doesn't contain anything.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty() && self.subscriptions.is_empty() && self.control_msgs.is_empty()
+    }
+
+    /// Turns this `GossipsubRpc` into a message that can be sent to a substream.
+    fn into_bytes(self) -> Vec<u8> {
+        let mut proto = rpc_proto::RPC::new();
+
+        for message in self.messages {
+            let mut msg = rpc_proto::Message::new();
+            msg.set_from(message.source.into_bytes());
+            msg.set_data(message.data);
+            msg.set_seqno(message.sequence_number);
+            msg.set_topicIDs(
+                message
+                    .topics
+                    .into_iter()
+                    .map(TopicHash::into_string)
+                    .collect(),
+            );
+            proto.mut_publish().push(msg);
+        }
+
+        for topic in self.subscriptions {
+            let mut subscription = rpc_proto::RPC_SubOpts::new();
+            subscription.set_subscribe(topic.action == GossipsubSubscriptionAction::Subscribe);
+            subscription.set_topicid(topic.topic.into_string());
+            proto.mut_subscriptions().push(subscription);
+        }
+
+        if !self.control_msgs.is_empty() {
+            let control = proto.mut_control();
+            for action in self.control_msgs {
+                match action {
+                    GossipsubControlAction::IHave { topic_hash, message_ids } => {
+                        let mut ihave = rpc_proto::ControlIHave::new();
+                        ihave.set_topicID(topic_hash.into_string());
+                        ihave.set_messageIDs(message_ids.into_iter().map(|id| id.0).collect());
+                        control.mut_ihave().push(ihave);
+                    }
+                    GossipsubControlAction::IWant { message_ids } => {
+                        let mut iwant = rpc_proto::ControlIWant::new();
+                        iwant.set_messageIDs(message_ids.into_iter().map(|id| id.0).collect());
+                        control.mut_iwant().push(iwant);
+                    }
+                    GossipsubControlAction::Graft { topic_hash } => {
+                        let mut graft = rpc_proto::ControlGraft::new();
+                        graft.set_topicID(topic_hash.into_string());
+                        control.mut_graft().push(graft);
+                    }
+                    GossipsubControlAction::Prune { topic_hash } => {
+                        let mut prune = rpc_proto::ControlPrune::new();
+                        prune.set_topicID(topic_hash.into_string());
+                        control.mut_prune().push(prune);
+                    }
+                }
+            }
+        }
+
+        proto
+            .write_to_bytes()
+            .expect("there is no situation in which the protobuf message can be invalid")
+    }
+
+    /// Parses a `GossipsubRpc` from the bytes received on a substream.
+    fn from_bytes(packet: &[u8]) -> Result<GossipsubRpc, GossipsubDecodeError> {
+        let mut rpc: rpc_proto::RPC = protobuf::parse_from_bytes(packet)?;
+
+        let mut messages = Vec::with_capacity(rpc.get_publish().len());
+        for mut publish in rpc.take_publish().into_iter() {
+            messages.push(GossipsubMessage {
+                source: PeerId::from_bytes(publish.take_from()).map_err(|_| {
+                    GossipsubDecodeError::InvalidPeerId
+                })?,
+                data: publish.take_data(),
+                sequence_number: publish.take_seqno(),
+                topics: publish
+                    .take_topicIDs()
+                    .into_iter()
+                    .map(TopicHash::from_raw)
+                    .collect(),
+            });
+        }
+
+        let mut control = rpc.take_control();
+        let mut control_msgs = Vec::new();
+        for mut ihave in control.take_ihave().into_iter() {
+            control_msgs.push(GossipsubControlAction::IHave {
+                topic_hash: TopicHash::from_raw(ihave.take_topicID()),
+                message_ids: ihave.take_messageIDs().into_iter().map(MessageId).collect(),
+            });
+        }
+        for mut iwant in control.take_iwant().into_iter() {
+            control_msgs.push(GossipsubControlAction::IWant {
+                message_ids: iwant.take_messageIDs().into_iter().map(MessageId).collect(),
+            });
+        }
+        for mut graft in control.take_graft().into_iter() {
+            control_msgs.push(GossipsubControlAction::Graft {
+                topic_hash: TopicHash::from_raw(graft.take_topicID()),
+            });
+        }
+        for mut prune in control.take_prune().into_iter() {
+            control_msgs.push(GossipsubControlAction::Prune {
+                topic_hash: TopicHash::from_raw(prune.take_topicID()),
+            });
+        }
+
+        Ok(GossipsubRpc {
+            messages,
+            subscriptions: rpc
+                .take_subscriptions()
+                .into_iter()
+                .map(|mut sub| GossipsubSubscription {
+                    action: if sub.get_subscribe() {
+                        GossipsubSubscriptionAction::Subscribe
+                    } else {
+                        GossipsubSubscriptionAction::Unsubscribe
+                    },
+                    topic: TopicHash::from_raw(sub.take_topicid()),
+                })
+                .collect(),
+            control_msgs,
+        })
+    }
+}
+
+impl UpgradeInfo for GossipsubRpc {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    #[inline]
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for GossipsubRpc
+where
+    TSocket: AsyncWrite + AsyncRead,
+{
+    type Output = ();
+    type Error = io::Error;
+    type Future = upgrade::WriteOne<upgrade::Negotiated<TSocket>>;
+
+    #[inline]
+    fn upgrade_outbound(self, socket: upgrade::Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        let bytes = self.into_bytes();
+        upgrade::write_one(socket, bytes)
+    }
+}
+
+/// Identifier of a message, as derived by [`GossipsubConfig::set_message_id_fn`](crate::GossipsubConfig::set_message_id_fn).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MessageId(pub String);
+
+impl fmt::Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A message received by the gossipsub system.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GossipsubMessage {
+    /// Id of the peer that published this message.
+    pub source: PeerId,
+
+    /// Content of the message. Its meaning is out of scope of this library.
+    pub data: Vec<u8>,
+
+    /// A random sequence number.
+    pub sequence_number: Vec<u8>,
+
+    /// List of topics this message belongs to.
+    ///
+    /// Each message can belong to multiple topics at once.
+    pub topics: Vec<TopicHash>,
+}
+
+/// A subscription received by the gossipsub system.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GossipsubSubscription {
+    /// Action to perform.
+    pub action: GossipsubSubscriptionAction,
+    /// The topic from which to subscribe or unsubscribe.
+    pub topic: TopicHash,
+}
+
+/// Action that a subscription wants to perform.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GossipsubSubscriptionAction {
+    /// The remote wants to subscribe to the given topic.
+    Subscribe,
+    /// The remote wants to unsubscribe from the given topic.
+    Unsubscribe,
+}
+
+/// A control message exchanged by the gossipsub system.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GossipsubControlAction {
+    /// The node has messages in its cache for the given topic that the remote may be missing.
+    IHave {
+        /// The topic of the messages.
+        topic_hash: TopicHash,
+        /// Identifiers of the messages the node has in its cache.
+        message_ids: Vec<MessageId>,
+    },
+    /// The node requests the given messages, following an `IHave`.
+    IWant {
+        /// Identifiers of the requested messages.
+        message_ids: Vec<MessageId>,
+    },
+    /// The node has added the remote to its mesh for the given topic.
+    Graft {
+        /// The topic of the mesh.
+        topic_hash: TopicHash,
+    },
+    /// The node has removed the remote from its mesh for the given topic.
+    Prune {
+        /// The topic of the mesh.
+        topic_hash: TopicHash,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpc_round_trip() {
+        let rpc = GossipsubRpc {
+            messages: vec![GossipsubMessage {
+                source: PeerId::random(),
+                data: b"hello".to_vec(),
+                sequence_number: vec![1, 2, 3],
+                topics: vec![TopicHash::from_raw("a".to_owned()), TopicHash::from_raw("b".to_owned())],
+            }],
+            subscriptions: vec![GossipsubSubscription {
+                action: GossipsubSubscriptionAction::Unsubscribe,
+                topic: TopicHash::from_raw("c".to_owned()),
+            }],
+            control_msgs: vec![
+                GossipsubControlAction::IHave {
+                    topic_hash: TopicHash::from_raw("a".to_owned()),
+                    message_ids: vec![MessageId("1".to_owned()), MessageId("2".to_owned())],
+                },
+                GossipsubControlAction::IWant {
+                    message_ids: vec![MessageId("3".to_owned())],
+                },
+                GossipsubControlAction::Graft {
+                    topic_hash: TopicHash::from_raw("b".to_owned()),
+                },
+                GossipsubControlAction::Prune {
+                    topic_hash: TopicHash::from_raw("c".to_owned()),
+                },
+            ],
+        };
+
+        let bytes = rpc.clone().into_bytes();
+        assert_eq!(GossipsubRpc::from_bytes(&bytes).unwrap(), rpc);
+    }
+}
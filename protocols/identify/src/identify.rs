@@ -20,15 +20,18 @@
 
 use crate::listen_handler::IdentifyListenHandler;
 use crate::periodic_id_handler::{PeriodicIdHandler, PeriodicIdHandlerEvent};
-use crate::protocol::{IdentifyInfo, IdentifySender, IdentifySenderFuture};
+use crate::protocol::{IdentifyInfo, IdentifyPush, IdentifySender, IdentifySenderFuture};
+use crate::push_handler::IdentifyPushHandler;
 use futures::prelude::*;
 use libp2p_core::{
     ConnectedPoint,
     Multiaddr,
     PeerId,
+    PeerRecord,
     PublicKey,
+    identity::Keypair,
     either::EitherOutput,
-    upgrade::Negotiated
+    upgrade::{Negotiated, UpgradeError}
 };
 use libp2p_swarm::{
     NetworkBehaviour,
@@ -38,6 +41,7 @@ use libp2p_swarm::{
     ProtocolsHandlerSelect,
     ProtocolsHandlerUpgrErr
 };
+use log::warn;
 use smallvec::SmallVec;
 use std::{collections::HashMap, collections::VecDeque, io};
 use tokio_io::{AsyncRead, AsyncWrite};
@@ -45,6 +49,9 @@ use void::Void;
 
 /// Network behaviour that automatically identifies nodes periodically, returns information
 /// about them, and answers identify queries from other nodes.
+///
+/// Whenever our listen or external addresses change, the updated information is pushed to all
+/// the nodes we're connected to.
 pub struct Identify<TSubstream> {
     /// Protocol version to send back to remotes.
     protocol_version: String,
@@ -52,6 +59,8 @@ pub struct Identify<TSubstream> {
     agent_version: String,
     /// The public key of the local node. To report on the wire.
     local_public_key: PublicKey,
+    /// The keypair of the local node, if we sign a record of our addresses.
+    local_keypair: Option<Keypair>,
     /// For each peer we're connected to, the observed address to send back to it.
     observed_addresses: HashMap<PeerId, Multiaddr>,
     /// List of senders to answer, with the observed multiaddr.
@@ -59,20 +68,68 @@ pub struct Identify<TSubstream> {
     /// List of futures that send back information back to remotes.
     futures: SmallVec<[(PeerId, IdentifySenderFuture<Negotiated<TSubstream>>); 4]>,
     /// Events that need to be produced outside when polling..
-    events: VecDeque<NetworkBehaviourAction<EitherOutput<Void, Void>, IdentifyEvent>>,
+    events: VecDeque<NetworkBehaviourAction<EitherOutput<EitherOutput<Void, Void>, IdentifyPush>, IdentifyEvent>>,
+    /// If true, our information has changed and must be pushed to the nodes we're connected to.
+    pending_push: bool,
 }
 
 impl<TSubstream> Identify<TSubstream> {
     /// Creates a `Identify`.
     pub fn new(protocol_version: String, agent_version: String, local_public_key: PublicKey) -> Self {
+        Self::new_inner(protocol_version, agent_version, local_public_key, None)
+    }
+
+    /// Creates a `Identify` that also sends to remotes a record of our addresses signed with
+    /// `local_keypair`, which lets them authenticate these addresses.
+    pub fn new_signed(protocol_version: String, agent_version: String, local_keypair: Keypair) -> Self {
+        let local_public_key = local_keypair.public();
+        Self::new_inner(protocol_version, agent_version, local_public_key, Some(local_keypair))
+    }
+
+    fn new_inner(
+        protocol_version: String,
+        agent_version: String,
+        local_public_key: PublicKey,
+        local_keypair: Option<Keypair>,
+    ) -> Self {
         Identify {
             protocol_version,
             agent_version,
             local_public_key,
+            local_keypair,
             observed_addresses: HashMap::new(),
             to_answer: SmallVec::new(),
             futures: SmallVec::new(),
             events: VecDeque::new(),
+            pending_push: false,
+        }
+    }
+
+    /// Builds the information about the local node to send to remotes.
+    fn local_info(&self, params: &mut impl PollParameters) -> IdentifyInfo {
+        // The protocol names can be bytes, but the identify protocol except UTF-8 strings.
+        // There's not much we can do to solve this conflict except strip non-UTF-8 characters.
+        let protocols = params
+            .supported_protocols()
+            .map(|p| String::from_utf8_lossy(&p).to_string())
+            .collect();
+
+        let mut listen_addrs: Vec<_> = params.external_addresses().collect();
+        listen_addrs.extend(params.listened_addresses());
+
+        let signed_peer_record = self.local_keypair.as_ref().and_then(|keypair| {
+            PeerRecord::new(keypair, listen_addrs.clone())
+                .map_err(|err| warn!("Failed to sign peer record: {:?}", err))
+                .ok()
+        });
+
+        IdentifyInfo {
+            public_key: self.local_public_key.clone(),
+            protocol_version: self.protocol_version.clone(),
+            agent_version: self.agent_version.clone(),
+            listen_addrs,
+            protocols,
+            signed_peer_record,
         }
     }
 }
@@ -81,11 +138,16 @@ impl<TSubstream> NetworkBehaviour for Identify<TSubstream>
 where
     TSubstream: AsyncRead + AsyncWrite,
 {
-    type ProtocolsHandler = ProtocolsHandlerSelect<IdentifyListenHandler<TSubstream>, PeriodicIdHandler<TSubstream>>;
+    type ProtocolsHandler = ProtocolsHandlerSelect<
+        ProtocolsHandlerSelect<IdentifyListenHandler<TSubstream>, PeriodicIdHandler<TSubstream>>,
+        IdentifyPushHandler<TSubstream>
+    >;
     type OutEvent = IdentifyEvent;
 
     fn new_handler(&mut self) -> Self::ProtocolsHandler {
-        IdentifyListenHandler::new().select(PeriodicIdHandler::new())
+        IdentifyListenHandler::new()
+            .select(PeriodicIdHandler::new())
+            .select(IdentifyPushHandler::new())
     }
 
     fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
@@ -105,13 +167,29 @@ where
         self.observed_addresses.remove(peer_id);
     }
 
+    fn inject_new_listen_addr(&mut self, _: &Multiaddr) {
+        self.pending_push = true;
+    }
+
+    fn inject_expired_listen_addr(&mut self, _: &Multiaddr) {
+        self.pending_push = true;
+    }
+
+    fn inject_new_external_addr(&mut self, _: &Multiaddr) {
+        self.pending_push = true;
+    }
+
+    fn inject_expired_external_addr(&mut self, _: &Multiaddr) {
+        self.pending_push = true;
+    }
+
     fn inject_node_event(
         &mut self,
         peer_id: PeerId,
         event: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent,
     ) {
         match event {
-            EitherOutput::Second(PeriodicIdHandlerEvent::Identified(remote)) => {
+            EitherOutput::First(EitherOutput::Second(PeriodicIdHandlerEvent::Identified(remote))) => {
                 self.events
                     .push_back(NetworkBehaviourAction::GenerateEvent(IdentifyEvent::Identified {
                         peer_id,
@@ -123,20 +201,31 @@ where
                         address: remote.observed_addr,
                     });
             }
-            EitherOutput::First(sender) => {
+            EitherOutput::First(EitherOutput::First(sender)) => {
                 let observed = self.observed_addresses.get(&peer_id)
                     .expect("We only receive events from nodes we're connected to. We insert \
                              into the hashmap when we connect to a node and remove only when we \
                              disconnect; QED");
                 self.to_answer.push((peer_id, sender, observed.clone()));
             }
-            EitherOutput::Second(PeriodicIdHandlerEvent::IdentificationError(err)) => {
+            EitherOutput::First(EitherOutput::Second(PeriodicIdHandlerEvent::IdentificationError(err))) => {
                 self.events
                     .push_back(NetworkBehaviourAction::GenerateEvent(IdentifyEvent::Error {
                         peer_id,
                         error: err,
                     }));
             }
+            EitherOutput::Second(info) => {
+                // A remote may only push its own information.
+                let event = match info.verify_sender(&peer_id) {
+                    Ok(()) => IdentifyEvent::Pushed { peer_id, info },
+                    Err(err) => IdentifyEvent::Error {
+                        peer_id,
+                        error: ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(err)),
+                    },
+                };
+                self.events.push_back(NetworkBehaviourAction::GenerateEvent(event));
+            }
         }
     }
 
@@ -153,27 +242,29 @@ where
             return Async::Ready(event);
         }
 
-        for (peer_id, sender, observed) in self.to_answer.drain() {
-            // The protocol names can be bytes, but the identify protocol except UTF-8 strings.
-            // There's not much we can do to solve this conflict except strip non-UTF-8 characters.
-            let protocols = params
-                .supported_protocols()
-                .map(|p| String::from_utf8_lossy(&p).to_string())
-                .collect();
-
-            let mut listen_addrs: Vec<_> = params.external_addresses().collect();
-            listen_addrs.extend(params.listened_addresses());
-
-            let send_back_info = IdentifyInfo {
-                public_key: self.local_public_key.clone(),
-                protocol_version: self.protocol_version.clone(),
-                agent_version: self.agent_version.clone(),
-                listen_addrs,
-                protocols,
-            };
-
-            let future = sender.send(send_back_info, &observed);
-            self.futures.push((peer_id, future));
+        if self.pending_push {
+            self.pending_push = false;
+            let info = self.local_info(params);
+            for (peer_id, observed_addr) in &self.observed_addresses {
+                self.events.push_back(NetworkBehaviourAction::SendEvent {
+                    peer_id: peer_id.clone(),
+                    event: EitherOutput::Second(IdentifyPush {
+                        info: info.clone(),
+                        observed_addr: observed_addr.clone(),
+                    }),
+                });
+            }
+            if let Some(event) = self.events.pop_front() {
+                return Async::Ready(event);
+            }
+        }
+
+        if !self.to_answer.is_empty() {
+            let send_back_info = self.local_info(params);
+            for (peer_id, sender, observed) in self.to_answer.drain() {
+                let future = sender.send(send_back_info.clone(), &observed);
+                self.futures.push((peer_id, future));
+            }
         }
 
         // Removes each future one by one, and pushes them back if they're not ready.
@@ -221,6 +312,13 @@ pub enum IdentifyEvent {
         /// The error that happened.
         error: ProtocolsHandlerUpgrErr<io::Error>,
     },
+    /// The remote pushed updated identification information to us.
+    Pushed {
+        /// Peer that pushed its information.
+        peer_id: PeerId,
+        /// The updated information of the remote.
+        info: IdentifyInfo,
+    },
     /// Finished sending back our identification information to a remote.
     SendBack {
         /// Peer that we sent our identification info to.
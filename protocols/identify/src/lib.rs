@@ -64,6 +64,14 @@
 //! will contain the information sent by the remote. If we are the listener, then it will contain
 //! a `IdentifySender` struct that can be used to transmit back to the remote the information about
 //! it.
+//!
+//! ## Identify push
+//!
+//! The `/ipfs/id/push/1.0.0` protocol lets a node push its information to the nodes it is
+//! connected to whenever it changes, for example when it starts listening on a new address.
+//! The `Identify` behaviour does so automatically through the `IdentifyPushHandler`, and reports
+//! the information pushed by remotes as `IdentifyEvent::Pushed`. Pushed information whose public
+//! key isn't the one of the remote is rejected and reported as `IdentifyEvent::Error`.
 
 pub use self::identify::{Identify, IdentifyEvent};
 pub use self::protocol::IdentifyInfo;
@@ -71,6 +79,7 @@ pub use self::protocol::IdentifyInfo;
 pub mod listen_handler;
 pub mod periodic_id_handler;
 pub mod protocol;
+pub mod push_handler;
 
 mod identify;
 mod structs_proto;
@@ -20,11 +20,14 @@
 
 use bytes::BytesMut;
 use crate::structs_proto;
-use futures::{future::{self, FutureResult}, Async, AsyncSink, Future, Poll, Sink, Stream};
+use futures::{future::{self, FutureResult, Map}, Async, AsyncSink, Future, Poll, Sink, Stream};
 use futures::try_ready;
 use libp2p_core::{
     Multiaddr,
+    PeerId,
+    PeerRecord,
     PublicKey,
+    SignedEnvelope,
     upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, Negotiated}
 };
 use log::{debug, trace};
@@ -42,6 +45,20 @@ use unsigned_varint::codec;
 #[derive(Debug, Clone)]
 pub struct IdentifyProtocolConfig;
 
+/// Configuration for an inbound upgrade to the identify push protocol, with which a remote
+/// pushes its information to us whenever it changes.
+#[derive(Debug, Clone)]
+pub struct IdentifyPushProtocolConfig;
+
+/// Outbound upgrade to the identify push protocol, which pushes our information to the remote.
+#[derive(Debug, Clone)]
+pub struct IdentifyPush {
+    /// Our information.
+    pub info: IdentifyInfo,
+    /// Address we observe the remote as.
+    pub observed_addr: Multiaddr,
+}
+
 #[derive(Debug, Clone)]
 pub struct RemoteInfo {
     /// Information about the remote.
@@ -64,32 +81,38 @@ impl<T> IdentifySender<T> where T: AsyncWrite {
         debug!("Sending identify info to client");
         trace!("Sending: {:?}", info);
 
-        let listen_addrs = info.listen_addrs
-            .into_iter()
-            .map(|addr| addr.to_vec())
-            .collect();
-
-        let pubkey_bytes = info.public_key.into_protobuf_encoding();
-
-        let mut message = structs_proto::Identify::new();
-        message.set_agentVersion(info.agent_version);
-        message.set_protocolVersion(info.protocol_version);
-        message.set_publicKey(pubkey_bytes);
-        message.set_listenAddrs(listen_addrs);
-        message.set_observedAddr(observed_addr.to_vec());
-        message.set_protocols(RepeatedField::from_vec(info.protocols));
-
-        let bytes = message
-            .write_to_bytes()
-            .expect("writing protobuf failed; should never happen");
-
         IdentifySenderFuture {
             inner: self.inner,
-            item: Some(bytes),
+            item: Some(encode_info(info, observed_addr)),
         }
     }
 }
 
+/// Turns an `IdentifyInfo` and the address we observe the remote as into a protobuf message.
+fn encode_info(info: IdentifyInfo, observed_addr: &Multiaddr) -> Vec<u8> {
+    let listen_addrs = info.listen_addrs
+        .into_iter()
+        .map(|addr| addr.to_vec())
+        .collect();
+
+    let pubkey_bytes = info.public_key.into_protobuf_encoding();
+
+    let mut message = structs_proto::Identify::new();
+    message.set_agentVersion(info.agent_version);
+    message.set_protocolVersion(info.protocol_version);
+    message.set_publicKey(pubkey_bytes);
+    message.set_listenAddrs(listen_addrs);
+    message.set_observedAddr(observed_addr.to_vec());
+    message.set_protocols(RepeatedField::from_vec(info.protocols));
+    if let Some(record) = info.signed_peer_record {
+        message.set_signedPeerRecord(record.into_signed_envelope().into_protobuf_encoding());
+    }
+
+    message
+        .write_to_bytes()
+        .expect("writing protobuf failed; should never happen")
+}
+
 /// Future returned by `IdentifySender::send()`. Must be processed to the end in order to send
 /// the information to the remote.
 // Note: we don't use a `futures::sink::Sink` because it requires `T` to implement `Sink`, which
@@ -137,6 +160,24 @@ pub struct IdentifyInfo {
     pub listen_addrs: Vec<Multiaddr>,
     /// Protocols supported by the node, e.g. `/ipfs/ping/1.0.0`.
     pub protocols: Vec<String>,
+    /// Record of the addresses of the node, signed by the node.
+    ///
+    /// Unlike `listen_addrs`, these addresses are authenticated. When receiving information, a
+    /// record that can't be decoded, whose signature is invalid or that doesn't describe the
+    /// node identified by `public_key` is discarded.
+    pub signed_peer_record: Option<PeerRecord>,
+}
+
+impl IdentifyInfo {
+    /// Checks that the information describes `peer_id`, i.e. that `public_key` is the key of
+    /// `peer_id`. Information pushed by a remote must describe that remote.
+    pub fn verify_sender(&self, peer_id: &PeerId) -> Result<(), IoError> {
+        if &self.public_key.clone().into_peer_id() == peer_id {
+            Ok(())
+        } else {
+            Err(IoError::new(IoErrorKind::InvalidData, "public key doesn't match the peer ID of the sender"))
+        }
+    }
 }
 
 impl UpgradeInfo for IdentifyProtocolConfig {
@@ -148,6 +189,24 @@ impl UpgradeInfo for IdentifyProtocolConfig {
     }
 }
 
+impl UpgradeInfo for IdentifyPushProtocolConfig {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/ipfs/id/push/1.0.0")
+    }
+}
+
+impl UpgradeInfo for IdentifyPush {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/ipfs/id/push/1.0.0")
+    }
+}
+
 impl<C> InboundUpgrade<C> for IdentifyProtocolConfig
 where
     C: AsyncRead + AsyncWrite,
@@ -180,6 +239,41 @@ where
     }
 }
 
+impl<C> InboundUpgrade<C> for IdentifyPushProtocolConfig
+where
+    C: AsyncRead + AsyncWrite,
+{
+    type Output = IdentifyInfo;
+    type Error = IoError;
+    type Future = Map<IdentifyOutboundFuture<Negotiated<C>>, fn(RemoteInfo) -> IdentifyInfo>;
+
+    fn upgrade_inbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        trace!("Upgrading inbound identify push");
+        // Receiving pushed information is the same as receiving the answer to an identify
+        // request, except that the observed address is irrelevant.
+        let future = IdentifyOutboundFuture {
+            inner: Framed::new(socket, codec::UviBytes::<BytesMut>::default()),
+            shutdown: false,
+        };
+        future.map((|remote: RemoteInfo| remote.info) as fn(RemoteInfo) -> IdentifyInfo)
+    }
+}
+
+impl<C> OutboundUpgrade<C> for IdentifyPush
+where
+    C: AsyncRead + AsyncWrite,
+{
+    type Output = ();
+    type Error = IoError;
+    type Future = IdentifySenderFuture<Negotiated<C>>;
+
+    fn upgrade_outbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        trace!("Pushing identify info");
+        let sender = IdentifySender { inner: Framed::new(socket, codec::UviBytes::default()) };
+        sender.send(self.info, &self.observed_addr)
+    }
+}
+
 /// Future returned by `OutboundUpgrade::upgrade_outbound`.
 pub struct IdentifyOutboundFuture<T> {
     inner: Framed<T, codec::UviBytes<BytesMut>>,
@@ -251,6 +345,12 @@ fn parse_proto_msg(msg: BytesMut) -> Result<(IdentifyInfo, Multiaddr), IoError>
             let public_key = PublicKey::from_protobuf_encoding(msg.get_publicKey())
                 .map_err(|e| IoError::new(IoErrorKind::InvalidData, e))?;
 
+            let signed_peer_record = if msg.has_signedPeerRecord() {
+                parse_peer_record(msg.get_signedPeerRecord(), &public_key)
+            } else {
+                None
+            };
+
             let observed_addr = bytes_to_multiaddr(msg.take_observedAddr())?;
             let info = IdentifyInfo {
                 public_key,
@@ -258,6 +358,7 @@ fn parse_proto_msg(msg: BytesMut) -> Result<(IdentifyInfo, Multiaddr), IoError>
                 agent_version: msg.take_agentVersion(),
                 listen_addrs,
                 protocols: msg.take_protocols().into_vec(),
+                signed_peer_record,
             };
 
             Ok((info, observed_addr))
@@ -267,14 +368,36 @@ fn parse_proto_msg(msg: BytesMut) -> Result<(IdentifyInfo, Multiaddr), IoError>
     }
 }
 
+/// Decodes the signed peer record of a remote, whose public key is `public_key`.
+///
+/// Returns `None` if the record is invalid or describes another node. A bad record doesn't
+/// invalidate the rest of the message, as the record is only an addition to it.
+fn parse_peer_record(bytes: &[u8], public_key: &PublicKey) -> Option<PeerRecord> {
+    let record = SignedEnvelope::from_protobuf_encoding(bytes)
+        .map_err(|err| debug!("Failed to decode signed envelope: {:?}", err))
+        .ok()
+        .and_then(|envelope| PeerRecord::from_signed_envelope(envelope)
+            .map_err(|err| debug!("Invalid signed peer record: {:?}", err))
+            .ok())?;
+
+    if record.peer_id().is_public_key(public_key) != Some(true) {
+        debug!("Discarding signed peer record of {:?} received from another node", record.peer_id());
+        return None;
+    }
+
+    Some(record)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::protocol::{IdentifyInfo, RemoteInfo, IdentifyProtocolConfig};
+    use crate::protocol::{IdentifyInfo, IdentifyPush, IdentifyPushProtocolConfig, RemoteInfo, IdentifyProtocolConfig};
     use tokio::runtime::current_thread::Runtime;
     use libp2p_tcp::TcpConfig;
     use futures::{Future, Stream};
     use libp2p_core::{
         identity,
+        PeerId,
+        PeerRecord,
         Transport,
         transport::ListenerEvent,
         upgrade::{apply_outbound, apply_inbound}
@@ -285,7 +408,8 @@ mod tests {
     fn correct_transfer() {
         // We open a server and a client, send info from the server to the client, and check that
         // they were successfully received.
-        let send_pubkey = identity::Keypair::generate_ed25519().public();
+        let send_keypair = identity::Keypair::generate_ed25519();
+        let send_pubkey = send_keypair.public();
         let recv_pubkey = send_pubkey.clone();
 
         let (tx, rx) = mpsc::channel();
@@ -327,6 +451,10 @@ mod tests {
                                 "/ip6/::1/udp/1000".parse().unwrap(),
                             ],
                             protocols: vec!["proto1".to_string(), "proto2".to_string()],
+                            signed_peer_record: Some(PeerRecord::new(
+                                &send_keypair,
+                                vec!["/ip4/80.81.82.83/tcp/500".parse().unwrap()]
+                            ).unwrap()),
                         },
                         &"/ip4/100.101.102.103/tcp/5000".parse().unwrap(),
                     )
@@ -352,6 +480,9 @@ mod tests {
                     &["/ip4/80.81.82.83/tcp/500".parse().unwrap(),
                       "/ip6/::1/udp/1000".parse().unwrap()]);
                 assert_eq!(info.protocols, &["proto1".to_string(), "proto2".to_string()]);
+                let record = info.signed_peer_record.expect("the signed peer record is valid");
+                assert_eq!(record.peer_id(), &recv_pubkey.clone().into_peer_id());
+                assert_eq!(record.addresses(), &["/ip4/80.81.82.83/tcp/500".parse().unwrap()]);
                 Ok(())
             });
 
@@ -359,4 +490,148 @@ mod tests {
         let _ = rt.block_on(future).unwrap();
         bg_thread.join().unwrap();
     }
+
+    #[test]
+    fn correct_push() {
+        // We open a server and a client, push info from the client to the server, and check that
+        // it was successfully received.
+        let send_pubkey = identity::Keypair::generate_ed25519().public();
+        let recv_pubkey = send_pubkey.clone();
+
+        let (tx, rx) = mpsc::channel();
+
+        let bg_thread = thread::spawn(move || {
+            let transport = TcpConfig::new();
+
+            let mut listener = transport
+                .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+                .unwrap();
+
+            let addr = listener.by_ref().wait()
+                .next()
+                .expect("some event")
+                .expect("no error")
+                .into_new_address()
+                .expect("listen address");
+
+            tx.send(addr).unwrap();
+
+            let future = listener
+                .filter_map(ListenerEvent::into_upgrade)
+                .into_future()
+                .map_err(|(err, _)| err)
+                .and_then(|(client, _)| client.unwrap().0)
+                .and_then(|socket| {
+                    apply_inbound(socket, IdentifyPushProtocolConfig)
+                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+                })
+                .and_then(|info| {
+                    assert_eq!(info.public_key, recv_pubkey);
+                    assert_eq!(info.protocol_version, "proto_version");
+                    assert_eq!(info.agent_version, "agent_version");
+                    assert_eq!(info.listen_addrs, &["/ip4/80.81.82.83/tcp/500".parse().unwrap()]);
+                    assert_eq!(info.protocols, &["proto1".to_string()]);
+                    assert!(info.signed_peer_record.is_none());
+                    Ok(())
+                });
+            let mut rt = Runtime::new().unwrap();
+            let _ = rt.block_on(future).unwrap();
+        });
+
+        let transport = TcpConfig::new();
+
+        let push = IdentifyPush {
+            info: IdentifyInfo {
+                public_key: send_pubkey,
+                protocol_version: "proto_version".to_owned(),
+                agent_version: "agent_version".to_owned(),
+                listen_addrs: vec!["/ip4/80.81.82.83/tcp/500".parse().unwrap()],
+                protocols: vec!["proto1".to_string()],
+                signed_peer_record: None,
+            },
+            observed_addr: "/ip4/100.101.102.103/tcp/5000".parse().unwrap(),
+        };
+
+        let future = transport.dial(rx.recv().unwrap())
+            .unwrap()
+            .and_then(|socket| {
+                apply_outbound(socket, push)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            });
+
+        let mut rt = Runtime::new().unwrap();
+        let _ = rt.block_on(future).unwrap();
+        bg_thread.join().unwrap();
+    }
+
+    #[test]
+    fn push_of_other_peer() {
+        // We push the info of a node from a different node, and check that the server detects
+        // that it doesn't describe the sender.
+        let pushed_pubkey = identity::Keypair::generate_ed25519().public();
+        let pushed_peer_id = pushed_pubkey.clone().into_peer_id();
+        let sender_peer_id = PeerId::random();
+
+        let (tx, rx) = mpsc::channel();
+
+        let bg_thread = thread::spawn(move || {
+            let transport = TcpConfig::new();
+
+            let mut listener = transport
+                .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+                .unwrap();
+
+            let addr = listener.by_ref().wait()
+                .next()
+                .expect("some event")
+                .expect("no error")
+                .into_new_address()
+                .expect("listen address");
+
+            tx.send(addr).unwrap();
+
+            let future = listener
+                .filter_map(ListenerEvent::into_upgrade)
+                .into_future()
+                .map_err(|(err, _)| err)
+                .and_then(|(client, _)| client.unwrap().0)
+                .and_then(|socket| {
+                    apply_inbound(socket, IdentifyPushProtocolConfig)
+                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+                })
+                .and_then(move |info| {
+                    let err = info.verify_sender(&sender_peer_id).unwrap_err();
+                    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+                    assert!(info.verify_sender(&pushed_peer_id).is_ok());
+                    Ok(())
+                });
+            let mut rt = Runtime::new().unwrap();
+            let _ = rt.block_on(future).unwrap();
+        });
+
+        let transport = TcpConfig::new();
+
+        let push = IdentifyPush {
+            info: IdentifyInfo {
+                public_key: pushed_pubkey,
+                protocol_version: "proto_version".to_owned(),
+                agent_version: "agent_version".to_owned(),
+                listen_addrs: Vec::new(),
+                protocols: Vec::new(),
+                signed_peer_record: None,
+            },
+            observed_addr: "/ip4/100.101.102.103/tcp/5000".parse().unwrap(),
+        };
+
+        let future = transport.dial(rx.recv().unwrap())
+            .unwrap()
+            .and_then(|socket| {
+                apply_outbound(socket, push)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            });
+
+        let mut rt = Runtime::new().unwrap();
+        let _ = rt.block_on(future).unwrap();
+        bg_thread.join().unwrap();
+    }
 }
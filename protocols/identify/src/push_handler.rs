@@ -0,0 +1,145 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use crate::protocol::{IdentifyInfo, IdentifyPush, IdentifyPushProtocolConfig};
+use futures::prelude::*;
+use libp2p_core::upgrade::InboundUpgrade;
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use log::debug;
+use smallvec::SmallVec;
+use std::{io, marker::PhantomData};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+
+/// Protocol handler that pushes our information to the remote when it changes, and receives the
+/// information pushed by the remote.
+pub struct IdentifyPushHandler<TSubstream> {
+    /// Information pushed by the remote, to yield to the user.
+    pending_result: SmallVec<[IdentifyInfo; 2]>,
+
+    /// Information to push to the remote.
+    pending_push: SmallVec<[IdentifyPush; 2]>,
+
+    /// Number of pushes for which we requested a substream and that aren't finished yet.
+    ongoing_pushes: usize,
+
+    /// Marker for strong typing.
+    marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> IdentifyPushHandler<TSubstream> {
+    /// Builds a new `IdentifyPushHandler`.
+    #[inline]
+    pub fn new() -> Self {
+        IdentifyPushHandler {
+            pending_result: SmallVec::new(),
+            pending_push: SmallVec::new(),
+            ongoing_pushes: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for IdentifyPushHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type InEvent = IdentifyPush;
+    type OutEvent = IdentifyInfo;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = IdentifyPushProtocolConfig;
+    type OutboundProtocol = IdentifyPush;
+    type OutboundOpenInfo = ();
+
+    #[inline]
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        SubstreamProtocol::new(IdentifyPushProtocolConfig)
+    }
+
+    fn inject_fully_negotiated_inbound(
+        &mut self,
+        info: <Self::InboundProtocol as InboundUpgrade<TSubstream>>::Output
+    ) {
+        self.pending_result.push(info)
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, _: (), _: Self::OutboundOpenInfo) {
+        self.ongoing_pushes = self.ongoing_pushes.saturating_sub(1);
+    }
+
+    #[inline]
+    fn inject_event(&mut self, push: Self::InEvent) {
+        // Only the most recent information is worth pushing.
+        self.pending_push.clear();
+        self.pending_push.push(push);
+    }
+
+    #[inline]
+    fn inject_dial_upgrade_error(&mut self, _: Self::OutboundOpenInfo, err: ProtocolsHandlerUpgrErr<io::Error>) {
+        // Not all remotes support the push protocol, in which case they learn about our new
+        // information the next time they identify us.
+        debug!("Failed to push identify info: {:?}", err);
+        self.ongoing_pushes = self.ongoing_pushes.saturating_sub(1);
+    }
+
+    #[inline]
+    fn connection_keep_alive(&self) -> KeepAlive {
+        if self.pending_push.is_empty() && self.ongoing_pushes == 0 {
+            KeepAlive::No
+        } else {
+            KeepAlive::Yes
+        }
+    }
+
+    fn poll(
+        &mut self,
+    ) -> Poll<
+        ProtocolsHandlerEvent<
+            Self::OutboundProtocol,
+            Self::OutboundOpenInfo,
+            Self::OutEvent,
+        >,
+        Self::Error,
+    > {
+        if !self.pending_result.is_empty() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(
+                self.pending_result.remove(0),
+            )));
+        }
+
+        if !self.pending_push.is_empty() {
+            self.ongoing_pushes += 1;
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+                protocol: SubstreamProtocol::new(self.pending_push.remove(0)),
+                info: (),
+            }));
+        }
+
+        Ok(Async::NotReady)
+    }
+}
@@ -18,14 +18,19 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use libp2p_core::Multiaddr;
+use libp2p_core::{Multiaddr, PeerRecord};
 use smallvec::SmallVec;
 use std::fmt;
 
 /// A non-empty list of (unique) addresses of a peer in the routing table.
+///
+/// The addresses either come from a signed peer record, in which case they are authenticated,
+/// or have been reported without any proof that they belong to the peer.
 #[derive(Clone)]
 pub struct Addresses {
     addrs: SmallVec<[Multiaddr; 6]>,
+    /// The sequence number of the signed peer record the addresses come from, if any.
+    signed_seq: Option<u64>,
 }
 
 impl Addresses {
@@ -33,7 +38,26 @@ impl Addresses {
     pub fn new(addr: Multiaddr) -> Addresses {
         let mut addrs = SmallVec::new();
         addrs.push(addr);
-        Addresses { addrs }
+        Addresses { addrs, signed_seq: None }
+    }
+
+    /// Creates a new list of addresses from a signed peer record.
+    ///
+    /// Returns `None` if the record doesn't contain any address.
+    pub fn from_record(record: &PeerRecord) -> Option<Addresses> {
+        if record.addresses().is_empty() {
+            return None
+        }
+
+        Some(Addresses {
+            addrs: record.addresses().iter().cloned().collect(),
+            signed_seq: Some(record.seq()),
+        })
+    }
+
+    /// Returns true if the addresses come from a signed peer record.
+    pub fn is_signed(&self) -> bool {
+        self.signed_seq.is_some()
     }
 
     /// Gets a reference to the first address in the list.
@@ -79,6 +103,24 @@ impl Addresses {
         Ok(())
     }
 
+    /// Replaces the addresses with the ones of a signed peer record.
+    ///
+    /// Returns true if the addresses were replaced, false otherwise (i.e. if the record doesn't
+    /// contain any address or isn't more recent than the record the addresses come from).
+    pub fn replace_with_record(&mut self, record: &PeerRecord) -> bool {
+        if self.signed_seq.map_or(false, |seq| seq >= record.seq()) {
+            return false
+        }
+
+        match Addresses::from_record(record) {
+            Some(addresses) => {
+                *self = addresses;
+                true
+            }
+            None => false
+        }
+    }
+
     /// Adds a new address to the end of the list.
     ///
     /// Returns true if the address was added, false otherwise (i.e. if the
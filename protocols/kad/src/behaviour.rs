@@ -27,12 +27,16 @@ use crate::addresses::Addresses;
 use crate::handler::{KademliaHandler, KademliaRequestId, KademliaHandlerEvent, KademliaHandlerIn};
 use crate::jobs::*;
 use crate::kbucket::{self, KBucketsTable, NodeStatus};
+use crate::metrics::QueryMetrics;
 use crate::protocol::{KadConnectionType, KadPeer};
 use crate::query::{Query, QueryId, QueryPool, QueryConfig, QueryPoolState};
 use crate::record::{self, store::{self, RecordStore}, Record, ProviderRecord};
+use crate::record::validator::{RecordValidator, ValidationError};
+use crate::snapshot::RoutingTableSnapshot;
 use fnv::{FnvHashMap, FnvHashSet};
 use futures::prelude::*;
-use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, PeerRecord};
+use libp2p_metrics::Registry;
 use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, ProtocolsHandler};
 use log::{info, debug, warn};
 use smallvec::SmallVec;
@@ -42,6 +46,13 @@ use std::num::NonZeroUsize;
 use tokio_io::{AsyncRead, AsyncWrite};
 use wasm_timer::Instant;
 
+/// The tag set on the peers of the routing table, so that the connection manager of the `Swarm`
+/// closes the connections to other peers first.
+pub const ROUTING_TABLE_TAG: &str = "kad-routing-table";
+
+/// The value of the `ROUTING_TABLE_TAG` of a peer.
+const ROUTING_TABLE_TAG_VALUE: i32 = 10;
+
 /// Network behaviour that handles Kademlia.
 pub struct Kademlia<TSubstream, TStore> {
     /// The Kademlia routing table.
@@ -72,9 +83,15 @@ pub struct Kademlia<TSubstream, TStore> {
     /// The TTL of provider records.
     provider_record_ttl: Option<Duration>,
 
+    /// The validator for (value-)records, if any.
+    record_validator: Option<Box<dyn RecordValidator>>,
+
     /// Queued events to return when the behaviour is being polled.
     queued_events: VecDeque<NetworkBehaviourAction<KademliaHandlerIn<QueryId>, KademliaEvent>>,
 
+    /// The metrics of the queries, if enabled with [`Kademlia::register_metrics`].
+    metrics: Option<QueryMetrics>,
+
     /// Marker to pin the generics.
     marker: PhantomData<TSubstream>,
 
@@ -142,6 +159,22 @@ impl KademliaConfig {
         self
     }
 
+    /// Enables lookups over disjoint paths, as described in the S/Kademlia paper.
+    ///
+    /// When enabled, iterative lookups towards the closest peers to a key are
+    /// split into `num_paths` paths that run in parallel. No peer is contacted by
+    /// more than one path and the peers learned from a response are only used by
+    /// the path that received it. The results of all paths are merged once they
+    /// finished. This makes lookups, in particular record lookups, more robust
+    /// against adversarial nodes returning bogus closer peers, at the cost of
+    /// contacting more peers.
+    ///
+    /// `None` disables disjoint lookups, which is the default.
+    pub fn set_disjoint_query_paths(&mut self, num_paths: Option<NonZeroUsize>) -> &mut Self {
+        self.query_config.disjoint_paths = num_paths;
+        self
+    }
+
     /// Sets the TTL for stored records.
     ///
     /// The TTL should be significantly longer than the (re-)publication
@@ -255,10 +288,97 @@ where
             put_record_job,
             record_ttl: config.record_ttl,
             provider_record_ttl: config.provider_record_ttl,
+            record_validator: None,
+            metrics: None,
             marker: PhantomData,
         }
     }
 
+    /// Creates a new `Kademlia` network behaviour with the given configuration, whose
+    /// routing table is initialised from a snapshot taken with
+    /// [`Kademlia::routing_table_snapshot`].
+    ///
+    /// Since there are no connections yet, all peers are inserted as disconnected.
+    /// The peers that were connected when the snapshot was taken are inserted last,
+    /// i.e. they are considered the most recently seen peers of their bucket.
+    /// A subsequent [`Kademlia::bootstrap`] thus starts from the previous routing
+    /// table instead of an empty one.
+    pub fn from_snapshot(id: PeerId, store: TStore, config: KademliaConfig, snapshot: RoutingTableSnapshot)
+        -> Self
+    {
+        let mut kademlia = Self::with_config(id, store, config);
+
+        let (connected, disconnected): (Vec<_>, Vec<_>) = snapshot.peers
+            .into_iter()
+            .partition(|p| p.connection_ty == KadConnectionType::Connected);
+
+        for peer in disconnected.into_iter().chain(connected) {
+            let mut addrs = peer.multiaddrs.into_iter();
+            let mut addresses = match addrs.next() {
+                Some(addr) => Addresses::new(addr),
+                None => continue,
+            };
+            for addr in addrs {
+                addresses.insert(addr);
+            }
+
+            let key = kbucket::Key::new(peer.node_id);
+            if let kbucket::Entry::Absent(entry) = kademlia.kbuckets.entry(&key) {
+                match entry.insert(addresses, NodeStatus::Disconnected) {
+                    kbucket::InsertResult::Inserted => {},
+                    _ => debug!("Bucket full. Peer not restored to routing table: {}", key.preimage()),
+                }
+            }
+        }
+
+        kademlia
+    }
+
+    /// Takes a snapshot of the Kademlia routing table, with the addresses and the
+    /// connection status of every peer.
+    ///
+    /// The snapshot can be used to later construct a `Kademlia` behaviour with
+    /// the same routing table through [`Kademlia::from_snapshot`].
+    pub fn routing_table_snapshot(&mut self) -> RoutingTableSnapshot {
+        let peers = self.kbuckets.iter()
+            .map(|entry| KadPeer {
+                node_id: entry.node.key.preimage().clone(),
+                multiaddrs: entry.node.value.iter().cloned().collect(),
+                connection_ty: match entry.status {
+                    NodeStatus::Connected => KadConnectionType::Connected,
+                    NodeStatus::Disconnected => KadConnectionType::NotConnected,
+                },
+            })
+            .collect();
+
+        RoutingTableSnapshot { peers }
+    }
+
+    /// Sets the validator for (value-)records.
+    ///
+    /// Once set, records are validated before they are stored, whether
+    /// locally via [`Kademlia::put_record`] or on request of a remote peer,
+    /// and records received in response to [`Kademlia::get_record`] that
+    /// fail validation are ignored. If a lookup yields multiple valid records,
+    /// the validator selects the best record, which becomes the first of
+    /// [`GetRecordOk::records`].
+    ///
+    /// By default, no validation takes place.
+    pub fn set_record_validator<V>(&mut self, validator: V)
+    where
+        V: RecordValidator
+    {
+        self.record_validator = Some(Box::new(validator));
+    }
+
+    /// Registers the metrics of the queries of this behaviour in the given registry.
+    ///
+    /// The number of completed queries and their duration are then recorded by
+    /// type of query and outcome, i.e. whether the query finished or timed out.
+    pub fn register_metrics(&mut self, registry: &Registry) {
+        self.metrics = Some(QueryMetrics::new(registry));
+    }
+
     /// Adds a known listen address of a peer participating in the DHT to the
     /// routing table.
     ///
@@ -274,13 +394,19 @@ where
     ///      from where it can subsequently be discovered by all peers
     ///      in the DHT.
     ///
+    /// If the addresses of the peer in the routing table come from a signed
+    /// peer record (see [`Kademlia::add_peer_record`]), the address is ignored,
+    /// as it is not authenticated.
+    ///
     /// If the routing table has been updated as a result of this operation,
     /// a [`KademliaEvent::RoutingUpdated`] event is emitted.
     pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
         let key = kbucket::Key::new(peer.clone());
         match self.kbuckets.entry(&key) {
             kbucket::Entry::Present(mut entry, _) => {
-                if entry.value().insert(address) {
+                if entry.value().is_signed() {
+                    debug!("Ignoring unsigned address {} of {}", address, peer);
+                } else if entry.value().insert(address) {
                     self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
                         KademliaEvent::RoutingUpdated {
                             peer: peer.clone(),
@@ -291,7 +417,9 @@ where
                 }
             }
             kbucket::Entry::Pending(mut entry, _) => {
-                entry.value().insert(address);
+                if !entry.value().is_signed() {
+                    entry.value().insert(address);
+                }
             }
             kbucket::Entry::Absent(entry) => {
                 let addresses = Addresses::new(address);
@@ -303,6 +431,71 @@ where
                     };
                 match entry.insert(addresses.clone(), status) {
                     kbucket::InsertResult::Inserted => {
+                        self.queued_events.push_back(tag_routing_table_peer(peer.clone()));
+                        self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                            KademliaEvent::RoutingUpdated {
+                                peer: peer.clone(),
+                                addresses,
+                                old_peer: None,
+                            }
+                        ));
+                    },
+                    kbucket::InsertResult::Full => {
+                        debug!("Bucket full. Peer not added to routing table: {}", peer)
+                    },
+                    kbucket::InsertResult::Pending { disconnected } => {
+                        self.queued_events.push_back(NetworkBehaviourAction::DialPeer {
+                            peer_id: disconnected.into_preimage(),
+                        })
+                    },
+                }
+            },
+            kbucket::Entry::SelfEntry => {},
+        }
+    }
+
+    /// Adds the addresses of a signed peer record to the routing table.
+    ///
+    /// Unlike addresses added with [`Kademlia::add_address`] or learned from
+    /// other peers, the addresses of a peer record are authenticated by the
+    /// signature of the peer. They thus replace any unsigned address of the
+    /// peer in the routing table, as well as the addresses of older records.
+    /// Until then, unsigned addresses of the peer are ignored.
+    ///
+    /// If the routing table has been updated as a result of this operation,
+    /// a [`KademliaEvent::RoutingUpdated`] event is emitted.
+    pub fn add_peer_record(&mut self, record: &PeerRecord) {
+        let peer = record.peer_id();
+        let key = kbucket::Key::new(peer.clone());
+        match self.kbuckets.entry(&key) {
+            kbucket::Entry::Present(mut entry, _) => {
+                if entry.value().replace_with_record(record) {
+                    self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                        KademliaEvent::RoutingUpdated {
+                            peer: peer.clone(),
+                            addresses: entry.value().clone(),
+                            old_peer: None,
+                        }
+                    ))
+                }
+            }
+            kbucket::Entry::Pending(mut entry, _) => {
+                entry.value().replace_with_record(record);
+            }
+            kbucket::Entry::Absent(entry) => {
+                let addresses = match Addresses::from_record(record) {
+                    Some(addresses) => addresses,
+                    None => return,
+                };
+                let status =
+                    if self.connected_peers.contains(peer) {
+                        NodeStatus::Connected
+                    } else {
+                        NodeStatus::Disconnected
+                    };
+                match entry.insert(addresses.clone(), status) {
+                    kbucket::InsertResult::Inserted => {
+                        self.queued_events.push_back(tag_routing_table_peer(peer.clone()));
                         self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
                             KademliaEvent::RoutingUpdated {
                                 peer: peer.clone(),
@@ -390,7 +583,16 @@ where
     /// is subject to regular (re-)replication and (re-)publication.
     pub fn put_record(&mut self, mut record: Record, quorum: Quorum) {
         record.publisher = Some(self.kbuckets.local_key().preimage().clone());
-        if let Err(err) = self.store.put(record.clone()) {
+        if let Err(err) = self.validate(&record) {
+            self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                KademliaEvent::PutRecordResult(Err(
+                    PutRecordError::InvalidRecord {
+                        key: record.key,
+                        cause: err,
+                    }
+                ))
+            ));
+        } else if let Err(err) = self.store.put(record.clone()) {
             self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
                 KademliaEvent::PutRecordResult(Err(
                     PutRecordError::LocalStorageError {
@@ -601,7 +803,7 @@ where
         match self.kbuckets.entry(&key) {
             kbucket::Entry::Present(mut entry, old_status) => {
                 if let Some(address) = address {
-                    if entry.value().insert(address) {
+                    if !entry.value().is_signed() && entry.value().insert(address) {
                         self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
                             KademliaEvent::RoutingUpdated {
                                 peer,
@@ -618,7 +820,9 @@ where
 
             kbucket::Entry::Pending(mut entry, old_status) => {
                 if let Some(address) = address {
-                    entry.value().insert(address);
+                    if !entry.value().is_signed() {
+                        entry.value().insert(address);
+                    }
                 }
                 if old_status != new_status {
                     entry.update(new_status);
@@ -632,6 +836,7 @@ where
                         let addresses = Addresses::new(address);
                         match entry.insert(addresses.clone(), new_status) {
                             kbucket::InsertResult::Inserted => {
+                                self.queued_events.push_back(tag_routing_table_peer(peer.clone()));
                                 let event = KademliaEvent::RoutingUpdated {
                                     peer: peer.clone(),
                                     addresses,
@@ -757,8 +962,14 @@ where
                 }
             }
 
-            QueryInfo::GetRecord { key, records, quorum, cache_at } => {
+            QueryInfo::GetRecord { key, mut records, quorum, cache_at } => {
                 let result = if records.len() >= quorum.get() { // [not empty]
+                    if let Some(validator) = &self.record_validator {
+                        let best = validator.select(&key, &records);
+                        if best < records.len() {
+                            records.swap(0, best);
+                        }
+                    }
                     if let Some(cache_key) = cache_at {
                         // Cache the record at the closest node to the key that
                         // did not return the record.
@@ -815,6 +1026,14 @@ where
         }
     }
 
+    /// Records the duration and outcome of a completed query, if metrics are enabled.
+    fn record_query_metrics(&self, query: &Query<QueryInner>, outcome: &str, now: Instant) {
+        if let Some(metrics) = &self.metrics {
+            let elapsed = now - query.started().unwrap_or(now);
+            metrics.query_completed(query.inner.info.name(), outcome, elapsed);
+        }
+    }
+
     /// Handles a query that timed out.
     fn query_timeout(&self, query: Query<QueryInner>) -> Option<KademliaEvent> {
         let result = query.into_result();
@@ -909,8 +1128,25 @@ where
         }
     }
 
+    /// Validates a record with the configured validator, if any.
+    fn validate(&self, record: &Record) -> Result<(), ValidationError> {
+        match &self.record_validator {
+            Some(validator) => validator.validate(record),
+            None => Ok(())
+        }
+    }
+
     /// Processes a record received from a peer.
     fn record_received(&mut self, source: PeerId, request_id: KademliaRequestId, mut record: Record) {
+        if let Err(e) = self.validate(&record) {
+            info!("Record from peer {} rejected: {}", source, e);
+            self.queued_events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: source,
+                event: KademliaHandlerIn::Reset(request_id)
+            });
+            return
+        }
+
         if record.publisher.as_ref() == Some(self.kbuckets.local_key().preimage()) {
             // If the (alleged) publisher is the local node, do nothing. The record of
             // the original publisher should never change as a result of replication
@@ -1032,6 +1268,11 @@ where
             if let kbucket::Entry::Present(mut entry, _) = self.kbuckets.entry(&key) {
                 let addrs = entry.value().iter().cloned().collect::<Vec<_>>();
                 debug_assert!(!addrs.is_empty(), "Empty peer addresses in routing table.");
+                // The addresses of a signed peer record are authenticated, unlike the ones
+                // reported by other peers during queries, which could have been forged.
+                if entry.value().is_signed() {
+                    return addrs
+                }
                 addrs
             } else {
                 Vec::new()
@@ -1254,9 +1495,15 @@ where
                         key, records, quorum, cache_at
                     } = &mut query.inner.info {
                         if let Some(record) = record {
-                            records.push(record);
-                            if records.len() == quorum.get() {
-                                query.finish()
+                            let valid = self.record_validator.as_ref()
+                                .map_or(Ok(()), |v| v.validate(&record));
+                            if let Err(e) = valid {
+                                debug!("Invalid record from peer {} ignored: {}", source, e);
+                            } else {
+                                records.push(record);
+                                if records.len() == quorum.get() {
+                                    query.finish()
+                                }
                             }
                         } else if quorum.get() == 1 {
                             // It is a "standard" Kademlia query, for which the
@@ -1356,10 +1603,18 @@ where
             // Drain applied pending entries from the routing table.
             if let Some(entry) = self.kbuckets.take_applied_pending() {
                 let kbucket::Node { key, value } = entry.inserted;
+                let old_peer = entry.evicted.map(|n| n.key.into_preimage());
+                if let Some(old_peer) = &old_peer {
+                    self.queued_events.push_back(NetworkBehaviourAction::UntagPeer {
+                        peer_id: old_peer.clone(),
+                        tag: ROUTING_TABLE_TAG.to_owned(),
+                    });
+                }
+                self.queued_events.push_back(tag_routing_table_peer(key.preimage().clone()));
                 let event = KademliaEvent::RoutingUpdated {
                     peer: key.into_preimage(),
                     addresses: value,
-                    old_peer: entry.evicted.map(|n| n.key.into_preimage())
+                    old_peer,
                 };
                 return Async::Ready(NetworkBehaviourAction::GenerateEvent(event))
             }
@@ -1368,11 +1623,13 @@ where
             loop {
                 match self.queries.poll(now) {
                     QueryPoolState::Finished(q) => {
+                        self.record_query_metrics(&q, "finished", now);
                         if let Some(event) = self.query_finished(q, parameters) {
                             return Async::Ready(NetworkBehaviourAction::GenerateEvent(event))
                         }
                     }
                     QueryPoolState::Timeout(q) => {
+                        self.record_query_metrics(&q, "timeout", now);
                         if let Some(event) = self.query_timeout(q) {
                             return Async::Ready(NetworkBehaviourAction::GenerateEvent(event))
                         }
@@ -1438,6 +1695,15 @@ impl Quorum {
 //////////////////////////////////////////////////////////////////////////////
 // Events
 
+/// Returns the action tagging a peer that has been inserted into the routing table.
+fn tag_routing_table_peer<TInEvent>(peer_id: PeerId) -> NetworkBehaviourAction<TInEvent, KademliaEvent> {
+    NetworkBehaviourAction::TagPeer {
+        peer_id,
+        tag: ROUTING_TABLE_TAG.to_owned(),
+        value: ROUTING_TABLE_TAG_VALUE,
+    }
+}
+
 /// The events produced by the `Kademlia` behaviour.
 ///
 /// See [`Kademlia::poll`].
@@ -1504,6 +1770,10 @@ pub type GetRecordResult = Result<GetRecordOk, GetRecordError>;
 /// The successful result of [`Kademlia::get_record`].
 #[derive(Debug, Clone)]
 pub struct GetRecordOk {
+    /// The records found.
+    ///
+    /// If a record validator is set, the first record is the one
+    /// selected by the validator.
     pub records: Vec<Record>
 }
 
@@ -1572,6 +1842,10 @@ pub enum PutRecordError {
     LocalStorageError {
         key: record::Key,
         cause: store::Error
+    },
+    InvalidRecord {
+        key: record::Key,
+        cause: ValidationError
     }
 }
 
@@ -1581,7 +1855,8 @@ impl PutRecordError {
         match self {
             PutRecordError::QuorumFailed { key, .. } => key,
             PutRecordError::Timeout { key, .. } => key,
-            PutRecordError::LocalStorageError { key, .. } => key
+            PutRecordError::LocalStorageError { key, .. } => key,
+            PutRecordError::InvalidRecord { key, .. } => key,
         }
     }
 
@@ -1592,6 +1867,7 @@ impl PutRecordError {
             PutRecordError::QuorumFailed { key, .. } => key,
             PutRecordError::Timeout { key, .. } => key,
             PutRecordError::LocalStorageError { key, .. } => key,
+            PutRecordError::InvalidRecord { key, .. } => key,
         }
     }
 }
@@ -1847,6 +2123,20 @@ enum QueryInfo {
 }
 
 impl QueryInfo {
+    /// Returns the name of the type of the query, as reported in the metrics.
+    fn name(&self) -> &'static str {
+        match self {
+            QueryInfo::Bootstrap { .. } => "bootstrap",
+            QueryInfo::GetClosestPeers { .. } => "get_closest_peers",
+            QueryInfo::GetProviders { .. } => "get_providers",
+            QueryInfo::PrepareAddProvider { .. } => "prepare_add_provider",
+            QueryInfo::AddProvider { .. } => "add_provider",
+            QueryInfo::PreparePutRecord { .. } => "prepare_put_record",
+            QueryInfo::PutRecord { .. } => "put_record",
+            QueryInfo::GetRecord { .. } => "get_record",
+        }
+    }
+
     /// Creates an event for a handler to issue an outgoing request in the
     /// context of a query.
     fn to_request(&self, query_id: QueryId) -> KademliaHandlerIn<QueryId> {
@@ -628,3 +628,204 @@ fn add_provider() {
     QuickCheck::new().tests(3).quickcheck(prop as fn(_,_))
 }
 
+
+#[test]
+fn routing_table_snapshot() {
+    let (swarm_ids, mut swarms) = build_connected_nodes(10, 10);
+    let snapshot = swarms[0].routing_table_snapshot();
+    assert_eq!(snapshot.peers.len(), swarm_ids.len() - 1);
+
+    let snapshot = RoutingTableSnapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+    let local_id = swarm_ids[0].clone();
+    let store = MemoryStore::new(local_id.clone());
+    let mut kademlia: Kademlia<Substream<StreamMuxerBox>, MemoryStore> =
+        Kademlia::from_snapshot(local_id, store, Default::default(), snapshot.clone());
+
+    let mut restored = kademlia.kbuckets_entries().cloned().collect::<Vec<_>>();
+    let mut expected = swarm_ids[1..].to_vec();
+    restored.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+    expected.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+    assert_eq!(restored, expected);
+
+    for peer in kademlia.routing_table_snapshot().peers {
+        assert_eq!(peer.connection_ty, KadConnectionType::NotConnected);
+        let original = snapshot.peers.iter().find(|p| p.node_id == peer.node_id).unwrap();
+        assert_eq!(peer.multiaddrs, original.multiaddrs);
+    }
+}
+
+#[test]
+fn get_value_disjoint_paths() {
+    let mut cfg = KademliaConfig::default();
+    cfg.set_disjoint_query_paths(NonZeroUsize::new(2));
+    let (port_base, mut swarms) = build_nodes_with_config(4, cfg);
+
+    let swarm_ids: Vec<_> = swarms.iter().map(Swarm::local_peer_id).cloned().collect();
+
+    // Two disjoint paths from the first node, each leading to the last node.
+    swarms[0].add_address(&swarm_ids[1], Protocol::Memory(port_base + 1).into());
+    swarms[0].add_address(&swarm_ids[2], Protocol::Memory(port_base + 2).into());
+    swarms[1].add_address(&swarm_ids[3], Protocol::Memory(port_base + 3).into());
+    swarms[2].add_address(&swarm_ids[3], Protocol::Memory(port_base + 3).into());
+
+    let record = Record::new(Multihash::random(SHA2256), vec![4,5,6]);
+
+    swarms[3].store.put(record.clone()).unwrap();
+    swarms[0].get_record(&record.key, Quorum::One);
+
+    current_thread::run(
+        future::poll_fn(move || {
+            for swarm in &mut swarms {
+                loop {
+                    match swarm.poll().unwrap() {
+                        Async::Ready(Some(KademliaEvent::GetRecordResult(Ok(ok)))) => {
+                            assert_eq!(ok.records.len(), 1);
+                            assert_eq!(ok.records.first(), Some(&record));
+                            return Ok(Async::Ready(()));
+                        }
+                        Async::Ready(_) => (),
+                        Async::NotReady => break,
+                    }
+                }
+            }
+
+            Ok(Async::NotReady)
+        }))
+}
+
+#[test]
+fn put_record_invalid() {
+    let local_id = PeerId::random();
+    let store = MemoryStore::new(local_id.clone());
+    let mut kademlia: Kademlia<Substream<StreamMuxerBox>, MemoryStore> =
+        Kademlia::new(local_id, store);
+    kademlia.set_record_validator(crate::record::validator::NamespacedValidator::new());
+
+    let record = Record::new(record::Key::new(b"/pk/foo"), vec![1]);
+    kademlia.put_record(record.clone(), Quorum::One);
+
+    assert!(kademlia.store.get(&record.key).is_none());
+    match kademlia.queued_events.pop_front() {
+        Some(NetworkBehaviourAction::GenerateEvent(KademliaEvent::PutRecordResult(Err(
+            PutRecordError::InvalidRecord { key, cause: ValidationError::UnknownNamespace }
+        )))) => assert_eq!(key, record.key),
+        e => panic!("Unexpected event: {:?}", e),
+    }
+}
+
+#[test]
+fn put_record_rejected_by_remote() {
+    let (port_base, mut swarms) = build_nodes(2);
+    let swarm_ids: Vec<_> = swarms.iter().map(Swarm::local_peer_id).cloned().collect();
+
+    // Only the remote validates records, and it knows no namespace.
+    swarms[0].add_address(&swarm_ids[1], Protocol::Memory(port_base + 1).into());
+    swarms[1].set_record_validator(crate::record::validator::NamespacedValidator::new());
+
+    let record = Record::new(record::Key::new(b"/pk/foo"), vec![1]);
+    swarms[0].put_record(record.clone(), Quorum::One);
+
+    current_thread::run(
+        future::poll_fn(move || {
+            for i in 0 .. swarms.len() {
+                loop {
+                    match swarms[i].poll().unwrap() {
+                        Async::Ready(Some(KademliaEvent::PutRecordResult(res))) => {
+                            match res {
+                                Err(PutRecordError::QuorumFailed { key, num_results, .. }) => {
+                                    assert_eq!(key, record.key);
+                                    assert_eq!(num_results, 0);
+                                }
+                                res => panic!("Unexpected result: {:?}", res),
+                            }
+                            assert!(swarms[1].store.get(&record.key).is_none());
+                            return Ok(Async::Ready(()));
+                        }
+                        Async::Ready(_) => (),
+                        Async::NotReady => break,
+                    }
+                }
+            }
+            Ok(Async::NotReady)
+        }))
+}
+
+#[test]
+fn get_record_selects_best() {
+    /// Accepts all records and selects the one with the highest value.
+    struct Highest;
+
+    impl crate::record::validator::RecordValidator for Highest {
+        fn validate(&self, _: &Record) -> Result<(), ValidationError> {
+            Ok(())
+        }
+
+        fn select(&self, _: &record::Key, records: &[Record]) -> usize {
+            (0 .. records.len()).max_by_key(|&i| records[i].value.clone()).unwrap()
+        }
+    }
+
+    let (port_base, mut swarms) = build_nodes(2);
+    let swarm_ids: Vec<_> = swarms.iter().map(Swarm::local_peer_id).cloned().collect();
+
+    swarms[0].set_record_validator(Highest);
+    swarms[0].add_address(&swarm_ids[1], Protocol::Memory(port_base + 1).into());
+
+    // The local record is always the first one found, so the remote one with the higher
+    // value only comes first if the validator selected it.
+    let key = record::Key::from(Multihash::random(SHA2256));
+    swarms[0].store.put(Record::new(key.clone(), vec![1])).unwrap();
+    swarms[1].store.put(Record::new(key.clone(), vec![2])).unwrap();
+
+    let quorum = Quorum::N(NonZeroUsize::new(2).unwrap());
+    swarms[0].get_record(&key, quorum);
+
+    current_thread::run(
+        future::poll_fn(move || {
+            for swarm in &mut swarms {
+                loop {
+                    match swarm.poll().unwrap() {
+                        Async::Ready(Some(KademliaEvent::GetRecordResult(Ok(ok)))) => {
+                            assert_eq!(ok.records.len(), 2);
+                            assert_eq!(ok.records[0].value, vec![2]);
+                            assert_eq!(ok.records[1].value, vec![1]);
+                            return Ok(Async::Ready(()));
+                        }
+                        Async::Ready(_) => (),
+                        Async::NotReady => break,
+                    }
+                }
+            }
+            Ok(Async::NotReady)
+        }))
+}
+
+#[test]
+fn signed_peer_record_preferred_over_unsigned_addresses() {
+    let (_, mut swarms) = build_nodes(1);
+    let swarm = &mut swarms[0];
+
+    let keypair = identity::Keypair::generate_ed25519();
+    let peer = keypair.public().into_peer_id();
+    let unsigned: Multiaddr = Protocol::Udp(10u16).into();
+    let signed: Multiaddr = Protocol::Memory(10).into();
+
+    swarm.add_address(&peer, unsigned.clone());
+    assert_eq!(swarm.addresses_of_peer(&peer), vec![unsigned.clone()]);
+
+    // The signed record replaces the unsigned address.
+    let record = PeerRecord::with_seq(&keypair, 1, vec![signed.clone()]).unwrap();
+    swarm.add_peer_record(&record);
+    assert_eq!(swarm.addresses_of_peer(&peer), vec![signed.clone()]);
+
+    // Unsigned addresses and older records are then ignored.
+    swarm.add_address(&peer, unsigned.clone());
+    let old_record = PeerRecord::with_seq(&keypair, 0, vec![unsigned.clone()]).unwrap();
+    swarm.add_peer_record(&old_record);
+    assert_eq!(swarm.addresses_of_peer(&peer), vec![signed]);
+
+    // A newer record replaces the previous one.
+    let new_record = PeerRecord::with_seq(&keypair, 2, vec![unsigned.clone()]).unwrap();
+    swarm.add_peer_record(&new_record);
+    assert_eq!(swarm.addresses_of_peer(&peer), vec![unsigned]);
+}
@@ -32,11 +32,13 @@ pub mod record;
 mod addresses;
 mod behaviour;
 mod jobs;
+mod metrics;
 mod protobuf_structs;
 mod query;
+mod snapshot;
 
 pub use addresses::Addresses;
-pub use behaviour::{Kademlia, KademliaConfig, KademliaEvent, Quorum};
+pub use behaviour::{Kademlia, KademliaConfig, KademliaEvent, Quorum, ROUTING_TABLE_TAG};
 pub use behaviour::{
     BootstrapResult,
     BootstrapOk,
@@ -63,7 +65,8 @@ pub use behaviour::{
     GetProvidersError,
 };
 pub use protocol::KadConnectionType;
-pub use record::{store, Record, ProviderRecord};
+pub use record::{store, validator, Record, ProviderRecord};
+pub use snapshot::RoutingTableSnapshot;
 
 use std::num::NonZeroUsize;
 
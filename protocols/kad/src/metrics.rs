@@ -0,0 +1,64 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Metrics of the `Kademlia` behaviour, enabled with `Kademlia::register_metrics`.
+
+use libp2p_metrics::{Counter, Family, Histogram, Registry, exponential_buckets};
+use std::time::Duration;
+
+/// The metrics of the queries of a `Kademlia` behaviour.
+pub(crate) struct QueryMetrics {
+    /// Queries completed, by type and outcome.
+    queries: Family<Counter>,
+    /// The duration of the queries, in seconds, by type and outcome.
+    durations: Family<Histogram>,
+}
+
+impl QueryMetrics {
+    /// Creates the query metrics and registers them in the registry.
+    pub(crate) fn new(registry: &Registry) -> Self {
+        let metrics = QueryMetrics {
+            queries: Family::new(&["type", "outcome"]),
+            durations: Family::with_constructor(&["type", "outcome"], || {
+                Histogram::new(exponential_buckets(0.1, 2.0, 10))
+            }),
+        };
+
+        registry.register(
+            "libp2p_kad_queries_total",
+            "Number of completed queries",
+            metrics.queries.clone());
+        registry.register(
+            "libp2p_kad_query_duration_seconds",
+            "Duration of completed queries",
+            metrics.durations.clone());
+
+        metrics
+    }
+
+    /// Records a query of the given type completing with the given outcome.
+    pub(crate) fn query_completed(&self, ty: &str, outcome: &str, duration: Duration) {
+        let labels = [ty, outcome];
+        self.queries.get_or_create(&labels).inc();
+        let secs = duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9;
+        self.durations.get_or_create(&labels).observe(secs);
+    }
+}
@@ -21,7 +21,7 @@
 mod peers;
 
 use peers::PeersIterState;
-use peers::closest::{ClosestPeersIter, ClosestPeersIterConfig};
+use peers::closest::{ClosestPeersIter, ClosestPeersIterConfig, disjoint::ClosestDisjointPeersIter};
 use peers::fixed::FixedPeersIter;
 
 use crate::K_VALUE;
@@ -107,7 +107,11 @@ impl<TInner> QueryPool<TInner> {
             num_results: self.config.replication_factor.get(),
             .. ClosestPeersIterConfig::default()
         };
-        let peer_iter = QueryPeerIter::Closest(ClosestPeersIter::with_config(cfg, target, peers));
+        let peer_iter = match self.config.disjoint_paths {
+            Some(num_paths) => QueryPeerIter::ClosestDisjoint(
+                ClosestDisjointPeersIter::with_config(cfg, target, peers, num_paths)),
+            None => QueryPeerIter::Closest(ClosestPeersIter::with_config(cfg, target, peers)),
+        };
         self.add(peer_iter, inner)
     }
 
@@ -189,13 +193,17 @@ pub struct QueryId(usize);
 pub struct QueryConfig {
     pub timeout: Duration,
     pub replication_factor: NonZeroUsize,
+    /// The number of disjoint paths of iterative lookups towards the
+    /// closest peers to a target, if any.
+    pub disjoint_paths: Option<NonZeroUsize>,
 }
 
 impl Default for QueryConfig {
     fn default() -> Self {
         QueryConfig {
             timeout: Duration::from_secs(60),
-            replication_factor: NonZeroUsize::new(K_VALUE.get()).expect("K_VALUE > 0")
+            replication_factor: NonZeroUsize::new(K_VALUE.get()).expect("K_VALUE > 0"),
+            disjoint_paths: None,
         }
     }
 }
@@ -216,6 +224,7 @@ pub struct Query<TInner> {
 /// The peer selection strategies that can be used by queries.
 enum QueryPeerIter {
     Closest(ClosestPeersIter),
+    ClosestDisjoint(ClosestDisjointPeersIter),
     Fixed(FixedPeersIter)
 }
 
@@ -230,10 +239,16 @@ impl<TInner> Query<TInner> {
         self.id
     }
 
+    /// Gets the instant when the query started, if it has been started.
+    pub fn started(&self) -> Option<Instant> {
+        self.started
+    }
+
     /// Informs the query that the attempt to contact `peer` failed.
     pub fn on_failure(&mut self, peer: &PeerId) {
         match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.on_failure(peer),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.on_failure(peer),
             QueryPeerIter::Fixed(iter) => iter.on_failure(peer)
         }
     }
@@ -247,6 +262,7 @@ impl<TInner> Query<TInner> {
     {
         match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.on_success(peer, new_peers),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.on_success(peer, new_peers),
             QueryPeerIter::Fixed(iter) => iter.on_success(peer)
         }
     }
@@ -255,6 +271,7 @@ impl<TInner> Query<TInner> {
     pub fn is_waiting(&self, peer: &PeerId) -> bool {
         match &self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.is_waiting(peer),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.is_waiting(peer),
             QueryPeerIter::Fixed(iter) => iter.is_waiting(peer)
         }
     }
@@ -263,6 +280,7 @@ impl<TInner> Query<TInner> {
     fn next(&mut self, now: Instant) -> PeersIterState {
         match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.next(now),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.next(now),
             QueryPeerIter::Fixed(iter) => iter.next()
         }
     }
@@ -274,6 +292,7 @@ impl<TInner> Query<TInner> {
     pub fn finish(&mut self) {
         match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.finish(),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.finish(),
             QueryPeerIter::Fixed(iter) => iter.finish()
         }
     }
@@ -281,7 +300,8 @@ impl<TInner> Query<TInner> {
     /// Consumes the query, producing the final `QueryResult`.
     pub fn into_result(self) -> QueryResult<TInner, impl Iterator<Item = PeerId>> {
         let peers = match self.peer_iter {
-            QueryPeerIter::Closest(iter) => Either::Left(iter.into_result()),
+            QueryPeerIter::Closest(iter) => Either::Left(Either::Left(iter.into_result())),
+            QueryPeerIter::ClosestDisjoint(iter) => Either::Left(Either::Right(iter.into_result())),
             QueryPeerIter::Fixed(iter) => Either::Right(iter.into_result())
         };
         QueryResult { inner: self.inner, peers }
@@ -18,6 +18,8 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+pub mod disjoint;
+
 use super::*;
 
 use crate::{K_VALUE, ALPHA_VALUE};
@@ -0,0 +1,289 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use super::*;
+
+use crate::kbucket::{Key, KeyBytes};
+use fnv::FnvHashMap;
+use libp2p_core::PeerId;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::num::NonZeroUsize;
+use wasm_timer::Instant;
+
+/// A peer iterator that runs a number of disjoint lookups towards the closest peers
+/// to a target, as described in the S/Kademlia paper.
+///
+/// Each lookup path is a [`ClosestPeersIter`] and the known closest peers are
+/// initially distributed among them. A peer is only ever contacted by a single path:
+/// if a path wants to contact a peer that has already been contacted by another path,
+/// the peer is reported as failed to the former. The closer peers returned by a peer
+/// are only given to the path that contacted it. An adversary thus has to control a
+/// peer on every path in order to capture a lookup.
+#[derive(Debug, Clone)]
+pub struct ClosestDisjointPeersIter {
+    config: ClosestPeersIterConfig,
+
+    /// The target of the lookup.
+    target: KeyBytes,
+
+    /// The disjoint lookup paths.
+    iters: Vec<ClosestPeersIter>,
+
+    /// The peers contacted so far, together with the index of the path
+    /// that contacted them.
+    contacted_peers: FnvHashMap<PeerId, usize>,
+
+    /// The index of the path to advance first on the next call to `next`,
+    /// so that all paths get to contact peers in a round-robin fashion.
+    next_path: usize,
+}
+
+impl ClosestDisjointPeersIter {
+    /// Creates a new iterator running `num_paths` disjoint lookups with the given configuration.
+    pub fn with_config<I, T>(
+        config: ClosestPeersIterConfig,
+        target: T,
+        known_closest_peers: I,
+        num_paths: NonZeroUsize,
+    ) -> Self
+    where
+        I: IntoIterator<Item = Key<PeerId>>,
+        T: Into<KeyBytes>
+    {
+        let target = target.into();
+        let num_paths = num_paths.get();
+
+        // Distribute the known closest peers among the paths, in order of
+        // increasing distance to the target.
+        let mut known_closest_peers = known_closest_peers.into_iter()
+            .map(|key| (key.distance(&target), key))
+            .collect::<Vec<_>>();
+        known_closest_peers.sort_by(|(d1, _), (d2, _)| d1.cmp(d2));
+        let mut peers_per_path = vec![Vec::new(); num_paths];
+        for (i, (_, key)) in known_closest_peers.into_iter().enumerate() {
+            peers_per_path[i % num_paths].push(key);
+        }
+
+        let iters = peers_per_path.into_iter()
+            .map(|peers| ClosestPeersIter::with_config(config.clone(), target.clone(), peers))
+            .collect();
+
+        ClosestDisjointPeersIter {
+            config,
+            target,
+            iters,
+            contacted_peers: FnvHashMap::default(),
+            next_path: 0,
+        }
+    }
+
+    /// Callback for delivering the result of a successful request to a peer.
+    ///
+    /// The `closer_peers` are only given to the path that contacted `peer`.
+    pub fn on_success<I>(&mut self, peer: &PeerId, closer_peers: I)
+    where
+        I: IntoIterator<Item = PeerId>
+    {
+        if let Some(&i) = self.contacted_peers.get(peer) {
+            self.iters[i].on_success(peer, closer_peers);
+        }
+    }
+
+    /// Callback for informing the iterator about a failed request to a peer.
+    pub fn on_failure(&mut self, peer: &PeerId) {
+        if let Some(&i) = self.contacted_peers.get(peer) {
+            self.iters[i].on_failure(peer);
+        }
+    }
+
+    /// Returns true if the iterator is waiting for a response from the given peer.
+    pub fn is_waiting(&self, peer: &PeerId) -> bool {
+        self.iters.iter().any(|iter| iter.is_waiting(peer))
+    }
+
+    /// Advances the state of the iterator, potentially getting a new peer to contact.
+    pub fn next(&mut self, now: Instant) -> PeersIterState {
+        let num_paths = self.iters.len();
+        let mut waiting = false;
+        let mut at_capacity = false;
+
+        for _ in 0 .. num_paths {
+            let i = self.next_path;
+            self.next_path = (self.next_path + 1) % num_paths;
+
+            loop {
+                match self.iters[i].next(now) {
+                    PeersIterState::Waiting(Some(peer)) => {
+                        let peer = peer.into_owned();
+                        match self.contacted_peers.entry(peer.clone()) {
+                            Entry::Occupied(_) => {
+                                // The peer belongs to another path; this path
+                                // must proceed without it.
+                                self.iters[i].on_failure(&peer);
+                            }
+                            Entry::Vacant(e) => {
+                                e.insert(i);
+                                return PeersIterState::Waiting(Some(Cow::Owned(peer)))
+                            }
+                        }
+                    }
+                    PeersIterState::Waiting(None) => {
+                        waiting = true;
+                        break
+                    }
+                    PeersIterState::WaitingAtCapacity => {
+                        at_capacity = true;
+                        break
+                    }
+                    PeersIterState::Finished => break
+                }
+            }
+        }
+
+        if waiting {
+            PeersIterState::Waiting(None)
+        } else if at_capacity {
+            PeersIterState::WaitingAtCapacity
+        } else {
+            PeersIterState::Finished
+        }
+    }
+
+    /// Immediately transitions the iterator to [`PeersIterState::Finished`].
+    pub fn finish(&mut self) {
+        for iter in &mut self.iters {
+            iter.finish();
+        }
+    }
+
+    /// Checks whether the iterator has finished.
+    pub fn finished(&self) -> bool {
+        self.iters.iter().all(|iter| iter.finished())
+    }
+
+    /// Consumes the iterator, returning the closest peers found by all paths.
+    pub fn into_result(self) -> impl Iterator<Item = PeerId> {
+        let target = self.target;
+        let mut peers = self.iters.into_iter()
+            .flat_map(|iter| iter.into_result())
+            .map(Key::from)
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|k| target.distance(k));
+        peers.dedup();
+        peers.truncate(self.config.num_results);
+        peers.into_iter().map(Key::into_preimage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use multihash::Multihash;
+    use quickcheck::*;
+    use rand::{Rng, thread_rng};
+    use std::collections::HashSet;
+
+    fn random_peers(n: usize) -> Vec<PeerId> {
+        (0 .. n).map(|_| PeerId::random()).collect()
+    }
+
+    fn random_iter<G: Rng>(g: &mut G) -> ClosestDisjointPeersIter {
+        let known_closest_peers = random_peers(g.gen_range(1, 60)).into_iter().map(Key::from);
+        let target = Key::from(Into::<Multihash>::into(PeerId::random()));
+        let config = ClosestPeersIterConfig {
+            parallelism: g.gen_range(1, 10),
+            num_results: g.gen_range(1, 25),
+            .. ClosestPeersIterConfig::default()
+        };
+        let num_paths = NonZeroUsize::new(g.gen_range(1, 5)).unwrap();
+        ClosestDisjointPeersIter::with_config(config, target, known_closest_peers, num_paths)
+    }
+
+    impl Arbitrary for ClosestDisjointPeersIter {
+        fn arbitrary<G: Gen>(g: &mut G) -> ClosestDisjointPeersIter {
+            random_iter(g)
+        }
+    }
+
+    #[test]
+    fn paths_are_disjoint() {
+        fn prop(mut iter: ClosestDisjointPeersIter) {
+            let now = Instant::now();
+            let mut rng = thread_rng();
+            let mut contacted = HashSet::new();
+            // All peers report the same set of closer peers, which every path
+            // would like to contact.
+            let closer = random_peers(10);
+
+            loop {
+                match iter.next(now) {
+                    PeersIterState::Waiting(Some(peer)) => {
+                        let peer = peer.into_owned();
+                        assert!(contacted.insert(peer.clone()), "Peer contacted twice.");
+                        if rng.gen_bool(0.9) {
+                            iter.on_success(&peer, closer.clone());
+                        } else {
+                            iter.on_failure(&peer);
+                        }
+                    }
+                    PeersIterState::Finished => break,
+                    state => panic!("Unexpected iterator state: {:?}", state),
+                }
+            }
+
+            let num_results = iter.config.num_results;
+            let result = iter.into_result().collect::<Vec<_>>();
+            assert!(result.len() <= num_results);
+            assert!(result.iter().all(|p| contacted.contains(p)));
+        }
+
+        QuickCheck::new().tests(10).quickcheck(prop as fn(_))
+    }
+
+    #[test]
+    fn closer_peers_stay_on_their_path() {
+        let target = Key::from(Into::<Multihash>::into(PeerId::random()));
+        let known = random_peers(2).into_iter().map(Key::from);
+        let num_paths = NonZeroUsize::new(2).unwrap();
+        let mut iter = ClosestDisjointPeersIter::with_config(
+            ClosestPeersIterConfig::default(), target, known, num_paths);
+        let now = Instant::now();
+
+        let first = match iter.next(now) {
+            PeersIterState::Waiting(Some(p)) => p.into_owned(),
+            _ => panic!("Expected a peer.")
+        };
+        let path = iter.contacted_peers[&first];
+        let closer = random_peers(3);
+        iter.on_success(&first, closer.clone());
+
+        // The closer peers are only known to the path of the peer that reported them.
+        for (i, path_iter) in iter.iters.iter().enumerate() {
+            let mut path_iter = path_iter.clone();
+            let mut known = HashSet::new();
+            while let PeersIterState::Waiting(Some(p)) = path_iter.next(now) {
+                known.insert(p.into_owned());
+            }
+            assert_eq!(i == path, closer.iter().all(|p| known.contains(p)));
+        }
+    }
+}
@@ -21,6 +21,7 @@
 //! Records and record storage abstraction of the libp2p Kademlia DHT.
 
 pub mod store;
+pub mod validator;
 
 use bytes::Bytes;
 use libp2p_core::PeerId;
@@ -18,13 +18,18 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+#[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
+mod disk;
 mod memory;
 
-pub use memory::MemoryStore;
+#[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
+pub use disk::DiskStore;
+pub use memory::{MemoryStore, MemoryStoreConfig};
 
 use crate::K_VALUE;
 use super::*;
 use std::borrow::Cow;
+use std::io;
 
 /// The result of an operation on a `RecordStore`.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -39,6 +44,8 @@ pub enum Error {
     MaxProvidedKeys,
     /// The value of a record to be stored is too large.
     ValueTooLarge,
+    /// The record could not be persisted by the store.
+    Io(io::Error),
 }
 
 /// Trait for types implementing a record store.
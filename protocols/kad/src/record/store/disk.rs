@@ -0,0 +1,601 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use super::*;
+
+use libp2p_core::PeerId;
+use log::warn;
+use std::borrow::Cow;
+use std::cmp;
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Tag of a log entry storing a (regular) record.
+const TAG_PUT_RECORD: u8 = 0;
+/// Tag of a log entry removing a (regular) record.
+const TAG_REMOVE_RECORD: u8 = 1;
+/// Tag of a log entry storing a provider record.
+const TAG_ADD_PROVIDER: u8 = 2;
+/// Tag of a log entry removing a provider record.
+const TAG_REMOVE_PROVIDER: u8 = 3;
+
+/// The minimum number of entries in the log before it is considered for compaction.
+const MIN_COMPACTION_ENTRIES: usize = 1024;
+
+/// Expirations further in the future than this are stored as if the record never expired, since
+/// they can't be represented as an `Instant` on every platform.
+const MAX_EXPIRATION: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// File-backed implementation of a `RecordStore`.
+///
+/// All records are kept in memory in a [`MemoryStore`], which also enforces the limits of the
+/// [`MemoryStoreConfig`]. Every successful modification of the store is additionally appended to
+/// a log file, which is replayed when the store is opened again. Since entries are only ever
+/// appended, a crash can at worst leave a partially written entry at the end of the log, which is
+/// discarded on the next opening.
+///
+/// Expiration times are written to the log as wall-clock times, so that records keep expiring
+/// at the right moment across restarts. Records that expired while the store was closed are
+/// dropped when the log is replayed. The log is regularly compacted by rewriting it with only
+/// the records currently in the store.
+///
+/// If a removal can't be written to the log, the record is nevertheless removed from memory and
+/// the whole log is rewritten on the next modification of the store.
+pub struct DiskStore {
+    /// The in-memory view of the store.
+    memory: MemoryStore,
+    /// The path of the log file.
+    path: PathBuf,
+    /// The log file, opened in append mode.
+    log: File,
+    /// The number of entries in the log file.
+    log_entries: usize,
+    /// If true, writing to the log failed and the log must be rewritten from `memory` before
+    /// anything is appended to it.
+    stale: bool,
+}
+
+impl DiskStore {
+    /// Opens the `DiskStore` whose log is at the given path, with a default configuration.
+    ///
+    /// The log file is created if it doesn't exist.
+    pub fn open(path: impl Into<PathBuf>, local_id: PeerId) -> io::Result<Self> {
+        Self::open_with_config(path, local_id, Default::default())
+    }
+
+    /// Opens the `DiskStore` whose log is at the given path, with the given configuration.
+    ///
+    /// The log file is created if it doesn't exist.
+    pub fn open_with_config(path: impl Into<PathBuf>, local_id: PeerId, config: MemoryStoreConfig)
+        -> io::Result<Self>
+    {
+        let path = path.into();
+        let mut memory = MemoryStore::with_config(local_id, config);
+
+        let mut bytes = Vec::new();
+        match File::open(&path) {
+            Ok(mut file) => { file.read_to_end(&mut bytes)?; }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let now = Instant::now();
+        let sys_now = SystemTime::now();
+        let mut input = &bytes[..];
+        while let Some(payload) = next_entry(&mut input) {
+            match decode_entry(payload, now, sys_now)? {
+                Entry::PutRecord(record) => {
+                    if !record.is_expired(now) {
+                        let _ = memory.put(record);
+                    }
+                }
+                Entry::RemoveRecord(key) => memory.remove(&key),
+                Entry::AddProvider(record) => {
+                    if !record.is_expired(now) {
+                        let _ = memory.add_provider(record);
+                    }
+                }
+                Entry::RemoveProvider(key, provider) => memory.remove_provider(&key, &provider),
+            }
+        }
+
+        if !input.is_empty() {
+            warn!("Discarding {} bytes of truncated entry at the end of {}", input.len(), path.display());
+        }
+
+        // Rewrite the log from the replayed state, which gets rid of the expired and overwritten
+        // records, as well as of a truncated entry.
+        let log_entries = write_snapshot(&path, &memory)?;
+        let log = OpenOptions::new().append(true).open(&path)?;
+
+        Ok(DiskStore { memory, path, log, log_entries, stale: false })
+    }
+
+    /// Returns the path of the log file of the store.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends an entry to the log, compacting it if it grew too large.
+    ///
+    /// If the log is stale, it is rewritten instead, which includes the entry as `memory` has
+    /// already been modified.
+    fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        if self.stale {
+            return self.compact()
+        }
+
+        let payload = encode_entry(entry, Instant::now(), SystemTime::now());
+        let mut buf = Vec::with_capacity(4 + payload.len());
+        buf.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&payload);
+        if let Err(e) = self.log.write_all(&buf).and_then(|()| self.log.sync_data()) {
+            // A part of the entry may have been written, which would garble the entries
+            // appended after it.
+            self.stale = true;
+            return Err(e)
+        }
+        self.log_entries += 1;
+
+        let live_entries = self.memory.records().count() + self.memory.providers_count();
+        if self.log_entries > MIN_COMPACTION_ENTRIES && self.log_entries > 2 * live_entries {
+            self.compact()?;
+        }
+
+        Ok(())
+    }
+
+    /// Rewrites the log with the records currently in the store.
+    fn compact(&mut self) -> io::Result<()> {
+        self.stale = true;
+        self.log_entries = write_snapshot(&self.path, &self.memory)?;
+        self.log = OpenOptions::new().append(true).open(&self.path)?;
+        self.stale = false;
+        Ok(())
+    }
+
+    /// Restores the provider records of a key to a previous state.
+    fn restore_providers(&mut self, key: &Key, previous: Vec<ProviderRecord>) {
+        for p in self.memory.providers(key) {
+            self.memory.remove_provider(key, &p.provider);
+        }
+        for p in previous {
+            let _ = self.memory.add_provider(p);
+        }
+    }
+}
+
+impl<'a> RecordStore<'a> for DiskStore {
+    type RecordsIter = <MemoryStore as RecordStore<'a>>::RecordsIter;
+    type ProvidedIter = <MemoryStore as RecordStore<'a>>::ProvidedIter;
+
+    fn get(&'a self, k: &Key) -> Option<Cow<Record>> {
+        self.memory.get(k)
+    }
+
+    fn put(&'a mut self, r: Record) -> Result<()> {
+        let previous = self.memory.get(&r.key).map(Cow::into_owned);
+        self.memory.put(r.clone())?;
+        if let Err(e) = self.append(&Entry::PutRecord(r.clone())) {
+            match previous {
+                Some(p) => { let _ = self.memory.put(p); }
+                None => self.memory.remove(&r.key),
+            }
+            return Err(Error::Io(e))
+        }
+        Ok(())
+    }
+
+    fn remove(&'a mut self, k: &Key) {
+        if self.memory.get(k).is_none() {
+            return
+        }
+        self.memory.remove(k);
+        if let Err(e) = self.append(&Entry::RemoveRecord(k.clone())) {
+            warn!("Failed to persist the removal of record {:?}, rewriting the log later: {:?}", k, e);
+        }
+    }
+
+    fn records(&'a self) -> Self::RecordsIter {
+        self.memory.records()
+    }
+
+    fn add_provider(&'a mut self, record: ProviderRecord) -> Result<()> {
+        let previous = self.memory.providers(&record.key);
+        self.memory.add_provider(record.clone())?;
+        if !self.memory.providers(&record.key).contains(&record) {
+            // The provider is farther from the key than all the stored ones.
+            return Ok(())
+        }
+        if let Err(e) = self.append(&Entry::AddProvider(record.clone())) {
+            self.restore_providers(&record.key, previous);
+            return Err(Error::Io(e))
+        }
+        Ok(())
+    }
+
+    fn providers(&'a self, key: &Key) -> Vec<ProviderRecord> {
+        self.memory.providers(key)
+    }
+
+    fn provided(&'a self) -> Self::ProvidedIter {
+        self.memory.provided()
+    }
+
+    fn remove_provider(&'a mut self, key: &Key, provider: &PeerId) {
+        if !self.memory.providers(key).iter().any(|p| &p.provider == provider) {
+            return
+        }
+        self.memory.remove_provider(key, provider);
+        if let Err(e) = self.append(&Entry::RemoveProvider(key.clone(), provider.clone())) {
+            warn!("Failed to persist the removal of provider {:?} of {:?}, rewriting the log later: {:?}",
+                provider, key, e);
+        }
+    }
+}
+
+/// An entry of the log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+    PutRecord(Record),
+    RemoveRecord(Key),
+    AddProvider(ProviderRecord),
+    RemoveProvider(Key, PeerId),
+}
+
+/// Writes the records of `memory` to a fresh log at `path`, returning the number of entries.
+///
+/// The log is first written to a temporary file which then replaces the existing log, so that
+/// a crash in the middle of the operation leaves the previous log intact.
+fn write_snapshot(path: &Path, memory: &MemoryStore) -> io::Result<usize> {
+    let now = Instant::now();
+    let sys_now = SystemTime::now();
+    let mut buf = Vec::new();
+    let mut entries = 0;
+
+    let records = memory.records().map(|r| Entry::PutRecord(r.into_owned()));
+    let providers = memory.all_providers().map(|p| Entry::AddProvider(p.clone()));
+    for entry in records.chain(providers) {
+        let payload = encode_entry(&entry, now, sys_now);
+        buf.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&payload);
+        entries += 1;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&buf)?;
+        tmp.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+
+    Ok(entries)
+}
+
+/// Splits the next entry off the input, returning its payload.
+///
+/// Returns `None` without consuming anything if the input doesn't start with a complete entry.
+fn next_entry<'a>(input: &mut &'a [u8]) -> Option<&'a [u8]> {
+    if input.len() < 4 {
+        return None
+    }
+    let len = u32::from_be_bytes(input[.. 4].try_into().expect("slice has length 4")) as usize;
+    if input.len() < 4 + len {
+        return None
+    }
+    let payload = &input[4 .. 4 + len];
+    *input = &input[4 + len ..];
+    Some(payload)
+}
+
+fn encode_entry(entry: &Entry, now: Instant, sys_now: SystemTime) -> Vec<u8> {
+    let mut buf = Vec::new();
+    match entry {
+        Entry::PutRecord(record) => {
+            buf.push(TAG_PUT_RECORD);
+            put_bytes(&mut buf, record.key.as_ref());
+            put_bytes(&mut buf, &record.value);
+            put_option(&mut buf, record.publisher.as_ref(), |buf, p| put_bytes(buf, p.as_bytes()));
+            put_option(&mut buf, record.expires, |buf, t| put_time(buf, t, now, sys_now));
+        }
+        Entry::RemoveRecord(key) => {
+            buf.push(TAG_REMOVE_RECORD);
+            put_bytes(&mut buf, key.as_ref());
+        }
+        Entry::AddProvider(record) => {
+            buf.push(TAG_ADD_PROVIDER);
+            put_bytes(&mut buf, record.key.as_ref());
+            put_bytes(&mut buf, record.provider.as_bytes());
+            put_option(&mut buf, record.expires, |buf, t| put_time(buf, t, now, sys_now));
+        }
+        Entry::RemoveProvider(key, provider) => {
+            buf.push(TAG_REMOVE_PROVIDER);
+            put_bytes(&mut buf, key.as_ref());
+            put_bytes(&mut buf, provider.as_bytes());
+        }
+    }
+    buf
+}
+
+fn decode_entry(mut payload: &[u8], now: Instant, sys_now: SystemTime) -> io::Result<Entry> {
+    let input = &mut payload;
+    let entry = match get_u8(input)? {
+        TAG_PUT_RECORD => {
+            let key = Key::from(get_bytes(input)?.to_vec());
+            let value = get_bytes(input)?.to_vec();
+            let publisher = get_option(input, get_peer_id)?;
+            let expires = get_option(input, |input| get_time(input, now, sys_now))?.and_then(|t| t);
+            Entry::PutRecord(Record { key, value, publisher, expires })
+        }
+        TAG_REMOVE_RECORD => Entry::RemoveRecord(Key::from(get_bytes(input)?.to_vec())),
+        TAG_ADD_PROVIDER => {
+            let key = Key::from(get_bytes(input)?.to_vec());
+            let provider = get_peer_id(input)?;
+            let expires = get_option(input, |input| get_time(input, now, sys_now))?.and_then(|t| t);
+            Entry::AddProvider(ProviderRecord { key, provider, expires })
+        }
+        TAG_REMOVE_PROVIDER => {
+            let key = Key::from(get_bytes(input)?.to_vec());
+            let provider = get_peer_id(input)?;
+            Entry::RemoveProvider(key, provider)
+        }
+        tag => return Err(invalid_data(format!("unknown entry tag {}", tag))),
+    };
+    if !input.is_empty() {
+        return Err(invalid_data("trailing bytes in entry"))
+    }
+    Ok(entry)
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+fn put_option<T>(buf: &mut Vec<u8>, value: Option<T>, put: impl FnOnce(&mut Vec<u8>, T)) {
+    match value {
+        Some(v) => {
+            buf.push(1);
+            put(buf, v);
+        }
+        None => buf.push(0),
+    }
+}
+
+/// Writes an `Instant` as the number of milliseconds since the UNIX epoch of the corresponding
+/// wall-clock time.
+///
+/// Times that can't be represented as a wall-clock time are written as the farthest or the
+/// earliest time of the log, depending on whether they are in the future or in the past.
+fn put_time(buf: &mut Vec<u8>, t: Instant, now: Instant, sys_now: SystemTime) {
+    let sys_t = if t >= now {
+        sys_now.checked_add(t - now)
+    } else {
+        sys_now.checked_sub(now - t)
+    };
+    let millis = match sys_t {
+        Some(sys_t) => {
+            let millis = sys_t.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+            cmp::min(millis, u128::from(u64::max_value())) as u64
+        }
+        None if t >= now => u64::max_value(),
+        None => 0,
+    };
+    buf.extend_from_slice(&millis.to_be_bytes());
+}
+
+fn get_u8(input: &mut &[u8]) -> io::Result<u8> {
+    let (b, rest) = input.split_first().ok_or_else(|| invalid_data("unexpected end of entry"))?;
+    *input = rest;
+    Ok(*b)
+}
+
+fn get_array<'a>(input: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(invalid_data("unexpected end of entry"))
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn get_bytes<'a>(input: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let len = get_array(input, 4)?;
+    let len = u32::from_be_bytes(len.try_into().expect("slice has length 4")) as usize;
+    get_array(input, len)
+}
+
+fn get_option<T>(input: &mut &[u8], get: impl FnOnce(&mut &[u8]) -> io::Result<T>) -> io::Result<Option<T>> {
+    match get_u8(input)? {
+        0 => Ok(None),
+        1 => get(input).map(Some),
+        _ => Err(invalid_data("invalid option flag")),
+    }
+}
+
+fn get_peer_id(input: &mut &[u8]) -> io::Result<PeerId> {
+    PeerId::from_bytes(get_bytes(input)?.to_vec()).map_err(|_| invalid_data("invalid peer ID"))
+}
+
+/// Reads a wall-clock time written by `put_time` and converts it back to an `Instant`.
+///
+/// Times in the past are mapped to `now`, i.e. the record is considered expired. Times further
+/// than `MAX_EXPIRATION` in the future are mapped to `None`, i.e. the record never expires.
+fn get_time(input: &mut &[u8], now: Instant, sys_now: SystemTime) -> io::Result<Option<Instant>> {
+    let millis = get_array(input, 8)?;
+    let millis = u64::from_be_bytes(millis.try_into().expect("slice has length 8"));
+    let sys_t = match UNIX_EPOCH.checked_add(Duration::from_millis(millis)) {
+        Some(t) => t,
+        None => return Ok(None),
+    };
+    match sys_t.duration_since(sys_now) {
+        Ok(d) if d > MAX_EXPIRATION => Ok(None),
+        Ok(d) => Ok(now.checked_add(d)),
+        Err(_) => Ok(Some(now)),
+    }
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use multihash::{Hash::SHA2256, Multihash};
+    use quickcheck::*;
+    use std::env;
+
+    /// Returns the path of a fresh log file in the temporary directory.
+    fn temp_path() -> PathBuf {
+        let name = format!("libp2p-kad-disk-store-{}.log", PeerId::random().to_base58());
+        env::temp_dir().join(name)
+    }
+
+    #[test]
+    fn records_survive_reopening() {
+        fn prop(r: Record, p: ProviderRecord) {
+            let path = temp_path();
+            let id = PeerId::random();
+            {
+                let mut store = DiskStore::open(&path, id.clone()).unwrap();
+                assert!(store.put(r.clone()).is_ok());
+                assert!(store.add_provider(p.clone()).is_ok());
+            }
+            let store = DiskStore::open(&path, id).unwrap();
+            if !r.is_expired(Instant::now()) {
+                let stored = store.get(&r.key).expect("record to be stored").into_owned();
+                assert_eq!(stored.value, r.value);
+                assert_eq!(stored.publisher, r.publisher);
+                assert_eq!(stored.expires.is_some(), r.expires.is_some());
+            }
+            if !p.is_expired(Instant::now()) {
+                assert!(store.providers(&p.key).iter().any(|q| q.provider == p.provider));
+            }
+            fs::remove_file(&path).unwrap();
+        }
+        quickcheck(prop as fn(_, _))
+    }
+
+    #[test]
+    fn far_future_expiration_never_expires() {
+        let mut payload = vec![TAG_ADD_PROVIDER];
+        put_bytes(&mut payload, Key::from(Multihash::random(SHA2256)).as_ref());
+        put_bytes(&mut payload, PeerId::random().as_bytes());
+        put_option(&mut payload, Some(u64::max_value()), |buf, millis| {
+            buf.extend_from_slice(&millis.to_be_bytes())
+        });
+        match decode_entry(&payload, Instant::now(), SystemTime::now()) {
+            Ok(Entry::AddProvider(record)) => assert_eq!(record.expires, None),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn far_future_expiration_is_written() {
+        let now = Instant::now();
+        let mut buf = Vec::new();
+        put_time(&mut buf, now + MAX_EXPIRATION * 2, now, SystemTime::now());
+        assert_eq!(get_time(&mut &buf[..], now, SystemTime::now()).unwrap(), None);
+    }
+
+    #[test]
+    fn removals_survive_reopening() {
+        let path = temp_path();
+        let id = PeerId::random();
+        let key = Key::from(Multihash::random(SHA2256));
+        {
+            let mut store = DiskStore::open(&path, id.clone()).unwrap();
+            assert!(store.put(Record::new(key.clone(), vec![1, 2, 3])).is_ok());
+            assert!(store.add_provider(ProviderRecord::new(key.clone(), id.clone())).is_ok());
+            store.remove(&key);
+            store.remove_provider(&key, &id);
+        }
+        let store = DiskStore::open(&path, id).unwrap();
+        assert!(store.get(&key).is_none());
+        assert!(store.providers(&key).is_empty());
+        assert_eq!(store.provided().count(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn expiration_is_preserved() {
+        let path = temp_path();
+        let id = PeerId::random();
+        let now = Instant::now();
+        let mut live = Record::new(Key::from(Multihash::random(SHA2256)), vec![1]);
+        live.expires = Some(now + Duration::from_secs(3600));
+        let mut expired = Record::new(Key::from(Multihash::random(SHA2256)), vec![2]);
+        expired.expires = Some(now);
+        {
+            let mut store = DiskStore::open(&path, id.clone()).unwrap();
+            assert!(store.put(live.clone()).is_ok());
+            assert!(store.put(expired.clone()).is_ok());
+        }
+        let store = DiskStore::open(&path, id).unwrap();
+        let expires = store.get(&live.key).unwrap().expires.unwrap();
+        let delta = if expires > live.expires.unwrap() {
+            expires - live.expires.unwrap()
+        } else {
+            live.expires.unwrap() - expires
+        };
+        assert!(delta < Duration::from_secs(1));
+        assert!(store.get(&expired.key).is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_entry_is_discarded() {
+        let path = temp_path();
+        let id = PeerId::random();
+        let key = Key::from(Multihash::random(SHA2256));
+        {
+            let mut store = DiskStore::open(&path, id.clone()).unwrap();
+            assert!(store.put(Record::new(key.clone(), vec![1, 2, 3])).is_ok());
+            assert!(store.put(Record::new(Multihash::random(SHA2256), vec![4, 5, 6])).is_ok());
+        }
+        // Simulate a crash in the middle of writing the last entry.
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 2).unwrap();
+
+        let store = DiskStore::open(&path, id).unwrap();
+        assert!(store.get(&key).is_some());
+        assert_eq!(store.records().count(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn max_records() {
+        let path = temp_path();
+        let config = MemoryStoreConfig { max_records: 1, .. Default::default() };
+        let mut store = DiskStore::open_with_config(&path, PeerId::random(), config).unwrap();
+        assert!(store.put(Record::new(Multihash::random(SHA2256), vec![1])).is_ok());
+        match store.put(Record::new(Multihash::random(SHA2256), vec![2])) {
+            Err(Error::MaxRecords) => {}
+            _ => panic!("Unexpected result"),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -93,6 +93,16 @@ impl MemoryStore {
     {
         self.records.retain(f);
     }
+
+    /// Gets an iterator over all stored provider records.
+    pub(crate) fn all_providers(&self) -> impl Iterator<Item = &ProviderRecord> {
+        self.providers.values().flat_map(|ps| ps.iter())
+    }
+
+    /// Returns the number of stored provider records.
+    pub(crate) fn providers_count(&self) -> usize {
+        self.providers.values().map(|ps| ps.len()).sum()
+    }
 }
 
 impl<'a> RecordStore<'a> for MemoryStore {
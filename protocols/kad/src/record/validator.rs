@@ -0,0 +1,192 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Validation of records and selection among conflicting records.
+//!
+//! A `RecordValidator` decides whether a record is valid before it is stored
+//! in response to a request from a remote, before it is stored locally through
+//! `Kademlia::put_record` and before it is accepted as a result of
+//! `Kademlia::get_record`. When a lookup yields multiple records, the validator
+//! also selects the best one.
+//!
+//! Validators are usually specific to a key namespace, e.g. `/pk/` or `/ipns/`,
+//! and combined with a `NamespacedValidator`.
+
+use super::{Key, Record};
+use fnv::FnvHashMap;
+use std::{error, fmt};
+
+/// Validates records and selects the best among several records for the same key.
+pub trait RecordValidator: Send + 'static {
+    /// Checks whether the given record is valid.
+    fn validate(&self, record: &Record) -> Result<(), ValidationError>;
+
+    /// Selects the best of the given valid records for the given key, returning its index.
+    ///
+    /// `records` is never empty. The default implementation selects the first record.
+    fn select(&self, key: &Key, records: &[Record]) -> usize {
+        let _ = (key, records);
+        0
+    }
+}
+
+/// The error returned when a record is rejected by a `RecordValidator`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// No validator is registered for the namespace of the key of the record.
+    UnknownNamespace,
+    /// The record is invalid, for the given reason.
+    Invalid(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::UnknownNamespace =>
+                write!(f, "No validator for the namespace of the record key"),
+            ValidationError::Invalid(reason) =>
+                write!(f, "Invalid record: {}", reason),
+        }
+    }
+}
+
+impl error::Error for ValidationError {}
+
+/// A `RecordValidator` that dispatches to other validators based on the
+/// namespace of the record key.
+///
+/// The namespace of a key of the form `/<namespace>/<rest>` is `<namespace>`.
+/// Records whose key has no namespace, or a namespace without a registered
+/// validator, are rejected.
+#[derive(Default)]
+pub struct NamespacedValidator {
+    validators: FnvHashMap<Vec<u8>, Box<dyn RecordValidator>>,
+}
+
+impl NamespacedValidator {
+    /// Creates a new `NamespacedValidator` without any namespace.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers the validator for the given namespace, e.g. `"pk"`, replacing the
+    /// previously registered validator, if any.
+    pub fn insert<V>(&mut self, namespace: impl Into<Vec<u8>>, validator: V) -> &mut Self
+    where
+        V: RecordValidator
+    {
+        self.validators.insert(namespace.into(), Box::new(validator));
+        self
+    }
+
+    /// Returns the validator for the namespace of the given key, if any.
+    fn validator(&self, key: &Key) -> Option<&dyn RecordValidator> {
+        namespace(key).and_then(|ns| self.validators.get(ns)).map(|v| &**v)
+    }
+}
+
+impl RecordValidator for NamespacedValidator {
+    fn validate(&self, record: &Record) -> Result<(), ValidationError> {
+        match self.validator(&record.key) {
+            Some(validator) => validator.validate(record),
+            None => Err(ValidationError::UnknownNamespace),
+        }
+    }
+
+    fn select(&self, key: &Key, records: &[Record]) -> usize {
+        self.validator(key).map_or(0, |validator| validator.select(key, records))
+    }
+}
+
+impl fmt::Debug for NamespacedValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.validators.keys().map(|ns| String::from_utf8_lossy(ns)))
+            .finish()
+    }
+}
+
+/// Extracts the namespace of a key of the form `/<namespace>/<rest>`.
+fn namespace(key: &Key) -> Option<&[u8]> {
+    let key = key.as_ref();
+    if key.first() != Some(&b'/') {
+        return None
+    }
+    let len = key[1..].iter().position(|b| *b == b'/')?;
+    Some(&key[1 .. 1 + len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NonEmpty;
+
+    impl RecordValidator for NonEmpty {
+        fn validate(&self, record: &Record) -> Result<(), ValidationError> {
+            if record.value.is_empty() {
+                Err(ValidationError::Invalid("empty value".into()))
+            } else {
+                Ok(())
+            }
+        }
+
+        fn select(&self, _: &Key, records: &[Record]) -> usize {
+            // The longest value wins.
+            records.iter()
+                .enumerate()
+                .max_by_key(|(_, r)| r.value.len())
+                .map_or(0, |(i, _)| i)
+        }
+    }
+
+    #[test]
+    fn namespace_of_key() {
+        assert_eq!(namespace(&Key::new(b"/pk/abc")), Some(&b"pk"[..]));
+        assert_eq!(namespace(&Key::new(b"/ipns/abc/def")), Some(&b"ipns"[..]));
+        assert_eq!(namespace(&Key::new(b"/pk")), None);
+        assert_eq!(namespace(&Key::new(b"pk/abc")), None);
+        assert_eq!(namespace(&Key::new(b"")), None);
+    }
+
+    #[test]
+    fn dispatch_by_namespace() {
+        let mut validator = NamespacedValidator::new();
+        validator.insert("pk", NonEmpty);
+
+        assert!(validator.validate(&Record::new(Key::new(b"/pk/a"), vec![1])).is_ok());
+        assert_eq!(
+            validator.validate(&Record::new(Key::new(b"/pk/a"), Vec::new())),
+            Err(ValidationError::Invalid("empty value".into()))
+        );
+        assert_eq!(
+            validator.validate(&Record::new(Key::new(b"/ipns/a"), vec![1])),
+            Err(ValidationError::UnknownNamespace)
+        );
+
+        let key = Key::new(b"/pk/a");
+        let records = vec![
+            Record::new(key.clone(), vec![1]),
+            Record::new(key.clone(), vec![1, 2, 3]),
+            Record::new(key.clone(), vec![1, 2]),
+        ];
+        assert_eq!(validator.select(&key, &records), 1);
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Snapshots of the Kademlia routing table.
+
+use crate::protobuf_structs::dht as proto;
+use crate::protocol::{KadConnectionType, KadPeer};
+use protobuf::{CodedInputStream, Message};
+use std::convert::TryFrom;
+use std::io;
+
+/// A snapshot of the entries of the Kademlia routing table.
+///
+/// A snapshot is obtained through [`Kademlia::routing_table_snapshot`](crate::Kademlia::routing_table_snapshot)
+/// and can be used to construct a new `Kademlia` behaviour with
+/// [`Kademlia::from_snapshot`](crate::Kademlia::from_snapshot), e.g. after a restart of the
+/// local node. It can be persisted with [`RoutingTableSnapshot::to_bytes`] and
+/// [`RoutingTableSnapshot::from_bytes`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoutingTableSnapshot {
+    /// The peers of the routing table, together with their addresses.
+    ///
+    /// The connection type is either [`KadConnectionType::Connected`] or
+    /// [`KadConnectionType::NotConnected`], depending on the status of the
+    /// peer in the routing table at the time the snapshot was taken.
+    pub peers: Vec<KadPeer>,
+}
+
+impl RoutingTableSnapshot {
+    /// Encodes the snapshot as a sequence of length-delimited protobuf `Peer` messages,
+    /// as defined by the Kademlia wire protocol.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for peer in &self.peers {
+            let peer: proto::Message_Peer = peer.clone().into();
+            peer.write_length_delimited_to_vec(&mut buf)
+                .expect("there is no situation in which the protobuf message can be invalid");
+        }
+        buf
+    }
+
+    /// Decodes a snapshot previously encoded with [`RoutingTableSnapshot::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut input = CodedInputStream::from_bytes(bytes);
+        let mut peers = Vec::new();
+        while !input.eof().map_err(invalid_data)? {
+            let mut peer: proto::Message_Peer = input.read_message().map_err(invalid_data)?;
+            peers.push(KadPeer::try_from(&mut peer)?);
+        }
+        Ok(RoutingTableSnapshot { peers })
+    }
+
+    /// Returns the peers of the snapshot that were connected when it was taken.
+    pub fn connected(&self) -> impl Iterator<Item = &KadPeer> {
+        self.peers.iter().filter(|p| p.connection_ty == KadConnectionType::Connected)
+    }
+}
+
+fn invalid_data(e: protobuf::ProtobufError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::PeerId;
+    use multiaddr::multiaddr;
+
+    #[test]
+    fn bytes_round_trip() {
+        let snapshot = RoutingTableSnapshot {
+            peers: vec![
+                KadPeer {
+                    node_id: PeerId::random(),
+                    multiaddrs: vec![multiaddr![Ip4([127, 0, 0, 1]), Tcp(4001u16)]],
+                    connection_ty: KadConnectionType::Connected,
+                },
+                KadPeer {
+                    node_id: PeerId::random(),
+                    multiaddrs: vec![
+                        multiaddr![Ip4([10, 0, 0, 1]), Tcp(4001u16)],
+                        multiaddr![Ip4([10, 0, 0, 2]), Tcp(4002u16)],
+                    ],
+                    connection_ty: KadConnectionType::NotConnected,
+                },
+            ],
+        };
+
+        let decoded = RoutingTableSnapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+        assert_eq!(decoded, snapshot);
+        assert_eq!(decoded.connected().count(), 1);
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        assert!(RoutingTableSnapshot::from_bytes(&[0xff, 0xff]).is_err());
+    }
+}
@@ -35,6 +35,8 @@ pub enum NoiseError {
     InvalidPayload(protobuf::ProtobufError),
     /// A signature was required and could not be created.
     SigningError(identity::error::SigningError),
+    /// The remote didn't authenticate with its identity key.
+    AuthenticationFailed,
     #[doc(hidden)]
     __Nonexhaustive
 }
@@ -47,6 +49,7 @@ impl fmt::Display for NoiseError {
             NoiseError::InvalidKey => f.write_str("invalid public key"),
             NoiseError::InvalidPayload(e) => write!(f, "{}", e),
             NoiseError::SigningError(e) => write!(f, "{}", e),
+            NoiseError::AuthenticationFailed => f.write_str("remote identity key not authenticated"),
             NoiseError::__Nonexhaustive => f.write_str("__Nonexhaustive")
         }
     }
@@ -60,6 +63,7 @@ impl Error for NoiseError {
             NoiseError::InvalidKey => None,
             NoiseError::InvalidPayload(e) => Some(e),
             NoiseError::SigningError(e) => Some(e),
+            NoiseError::AuthenticationFailed => None,
             NoiseError::__Nonexhaustive => None
         }
     }
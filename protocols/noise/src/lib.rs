@@ -31,6 +31,11 @@
 //! and a `NoiseOutput` which represents the established cryptographic session with the
 //! remote, implementing `tokio_io::AsyncRead` and `tokio_io::AsyncWrite`.
 //!
+//! A `NoiseConfig` can be turned into a `NoiseAuthenticated` upgrade with
+//! `NoiseConfig::into_authenticated`, which instead produces the `PeerId` of the remote
+//! and fails the handshake unless the remote proved ownership of its identity key. Like
+//! secio, `NoiseAuthenticated` can thus be used to secure the connections of a `Swarm`.
+//!
 //! # Usage
 //!
 //! Example:
@@ -49,6 +54,23 @@
 //! # }
 //! ```
 //!
+//! Example with authentication of the libp2p identities:
+//!
+//! ```
+//! use libp2p_core::{identity, Transport};
+//! use libp2p_tcp::TcpConfig;
+//! use libp2p_noise::NoiseAuthenticated;
+//!
+//! # fn main() {
+//! let id_keys = identity::Keypair::generate_ed25519();
+//! let noise = NoiseAuthenticated::xx(&id_keys).unwrap();
+//! let transport = TcpConfig::new()
+//!     .with_upgrade(noise)
+//!     .map(|(_peer_id, stream), _| stream);
+//! // ...
+//! # }
+//! ```
+//!
 //! [noise]: http://noiseprotocol.org/
 
 mod error;
@@ -61,7 +83,8 @@ pub use io::handshake::{Handshake, RemoteIdentity, IdentityExchange};
 pub use protocol::{Keypair, AuthenticKeypair, KeypairIdentity, PublicKey, SecretKey};
 pub use protocol::{Protocol, ProtocolParams, x25519::X25519, IX, IK, XX};
 
-use libp2p_core::{identity, UpgradeInfo, InboundUpgrade, OutboundUpgrade, upgrade::Negotiated};
+use futures::future::{self, Future, FutureResult};
+use libp2p_core::{identity, PeerId, UpgradeInfo, InboundUpgrade, OutboundUpgrade, upgrade::Negotiated};
 use tokio_io::{AsyncRead, AsyncWrite};
 use zeroize::Zeroize;
 
@@ -74,6 +97,15 @@ pub struct NoiseConfig<P, C: Zeroize, R = ()> {
     _marker: std::marker::PhantomData<P>
 }
 
+impl<P, C: Zeroize, R> NoiseConfig<P, C, R> {
+    /// Turns this configuration into a `NoiseAuthenticated` upgrade, which produces the
+    /// `PeerId` of the remote and fails the handshake if the remote doesn't authenticate
+    /// its static DH public key with its identity key.
+    pub fn into_authenticated(self) -> NoiseAuthenticated<P, C, R> {
+        NoiseAuthenticated { config: self }
+    }
+}
+
 impl<C> NoiseConfig<IX, C>
 where
     C: Protocol<C> + Zeroize
@@ -277,3 +309,95 @@ where
     }
 }
 
+
+// Authenticated Upgrades /////////////////////////////////////////////////////
+
+/// A `NoiseAuthenticated` upgrade wraps around any `NoiseConfig` handshake and
+/// verifies that the remote identified with a [`RemoteIdentity::IdentityKey`],
+/// aborting the handshake otherwise.
+///
+/// The remote identifies with its identity key by sending it along with a signature of its
+/// static DH public key, as produced by [`Keypair::into_authentic`]. The upgrade produces the
+/// `PeerId` of the remote, derived from the verified identity key.
+#[derive(Clone)]
+pub struct NoiseAuthenticated<P, C: Zeroize, R> {
+    config: NoiseConfig<P, C, R>
+}
+
+impl NoiseAuthenticated<XX, X25519, ()> {
+    /// Creates a `NoiseAuthenticated` upgrade for the `XX` handshake pattern, using a new
+    /// X25519 DH keypair whose public key is signed with the given identity keypair.
+    pub fn xx(id_keys: &identity::Keypair) -> Result<Self, NoiseError> {
+        let dh_keys = Keypair::<X25519>::new().into_authentic(id_keys)?;
+        Ok(NoiseConfig::xx(dh_keys).into_authenticated())
+    }
+}
+
+impl<P, C: Zeroize, R> UpgradeInfo for NoiseAuthenticated<P, C, R>
+where
+    NoiseConfig<P, C, R>: UpgradeInfo
+{
+    type Info = <NoiseConfig<P, C, R> as UpgradeInfo>::Info;
+    type InfoIter = <NoiseConfig<P, C, R> as UpgradeInfo>::InfoIter;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.config.protocol_info()
+    }
+}
+
+impl<T, P, C, R> InboundUpgrade<T> for NoiseAuthenticated<P, C, R>
+where
+    NoiseConfig<P, C, R>: UpgradeInfo + InboundUpgrade<T,
+        Output = (RemoteIdentity<C>, NoiseOutput<Negotiated<T>>),
+        Error = NoiseError
+    >,
+    T: AsyncRead + AsyncWrite + Send + 'static,
+    C: Protocol<C> + AsRef<[u8]> + Zeroize + Send + 'static,
+{
+    type Output = (PeerId, NoiseOutput<Negotiated<T>>);
+    type Error = NoiseError;
+    type Future = future::AndThen<
+        <NoiseConfig<P, C, R> as InboundUpgrade<T>>::Future,
+        FutureResult<Self::Output, Self::Error>,
+        fn((RemoteIdentity<C>, NoiseOutput<Negotiated<T>>)) -> FutureResult<Self::Output, Self::Error>
+    >;
+
+    fn upgrade_inbound(self, socket: Negotiated<T>, info: Self::Info) -> Self::Future {
+        self.config.upgrade_inbound(socket, info)
+            .and_then(authenticate as fn(_) -> _)
+    }
+}
+
+impl<T, P, C, R> OutboundUpgrade<T> for NoiseAuthenticated<P, C, R>
+where
+    NoiseConfig<P, C, R>: UpgradeInfo + OutboundUpgrade<T,
+        Output = (RemoteIdentity<C>, NoiseOutput<Negotiated<T>>),
+        Error = NoiseError
+    >,
+    T: AsyncRead + AsyncWrite + Send + 'static,
+    C: Protocol<C> + AsRef<[u8]> + Zeroize + Send + 'static,
+{
+    type Output = (PeerId, NoiseOutput<Negotiated<T>>);
+    type Error = NoiseError;
+    type Future = future::AndThen<
+        <NoiseConfig<P, C, R> as OutboundUpgrade<T>>::Future,
+        FutureResult<Self::Output, Self::Error>,
+        fn((RemoteIdentity<C>, NoiseOutput<Negotiated<T>>)) -> FutureResult<Self::Output, Self::Error>
+    >;
+
+    fn upgrade_outbound(self, socket: Negotiated<T>, info: Self::Info) -> Self::Future {
+        self.config.upgrade_outbound(socket, info)
+            .and_then(authenticate as fn(_) -> _)
+    }
+}
+
+/// Turns the result of a handshake into the `PeerId` of the remote, provided that the remote
+/// authenticated with its identity key.
+fn authenticate<C, S>((remote, io): (RemoteIdentity<C>, NoiseOutput<S>))
+    -> FutureResult<(PeerId, NoiseOutput<S>), NoiseError>
+{
+    future::result(match remote {
+        RemoteIdentity::IdentityKey(pk) => Ok((pk.into_peer_id(), io)),
+        _ => Err(NoiseError::AuthenticationFailed)
+    })
+}
@@ -27,6 +27,19 @@ use libp2p_core::identity;
 use rand::FromEntropy;
 use zeroize::Zeroize;
 
+/// Prefix of the payload signed by an identity key in order to authenticate a static DH public
+/// key, as per the libp2p noise specification.
+const STATIC_KEY_DOMAIN: &[u8] = b"noise-libp2p-static-key:";
+
+/// Returns the payload signed by an identity key in order to authenticate the given static DH
+/// public key.
+fn static_key_payload(dh_pk: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(STATIC_KEY_DOMAIN.len() + dh_pk.len());
+    payload.extend_from_slice(STATIC_KEY_DOMAIN);
+    payload.extend_from_slice(dh_pk);
+    payload
+}
+
 /// The parameters of a Noise protocol, consisting of a choice
 /// for a handshake pattern as well as DH, cipher and hash functions.
 #[derive(Clone)]
@@ -84,16 +97,17 @@ pub trait Protocol<C> {
     /// static DH keys.
     ///
     /// If the public keys are [`linked`](Protocol::linked), verification succeeds
-    /// without a signature, otherwise a signature over the static DH public key
-    /// must be given and is verified with the public identity key, establishing
-    /// the authenticity of the static DH public key w.r.t. the public identity key.
+    /// without a signature, otherwise a signature over the static DH public key,
+    /// prefixed with `noise-libp2p-static-key:`, must be given and is verified with
+    /// the public identity key, establishing the authenticity of the static DH
+    /// public key w.r.t. the public identity key.
     fn verify(id_pk: &identity::PublicKey, dh_pk: &PublicKey<C>, sig: &Option<Vec<u8>>) -> bool
     where
         C: AsRef<[u8]>
     {
         Self::linked(id_pk, dh_pk)
             ||
-        sig.as_ref().map_or(false, |s| id_pk.verify(dh_pk.as_ref(), s))
+        sig.as_ref().map_or(false, |s| id_pk.verify(&static_key_payload(dh_pk.as_ref()), s))
     }
 }
 
@@ -148,12 +162,13 @@ impl<T: Zeroize> Keypair<T> {
     }
 
     /// Turn this DH keypair into a [`AuthenticKeypair`], i.e. a DH keypair that
-    /// is authentic w.r.t. the given identity keypair, by signing the DH public key.
+    /// is authentic w.r.t. the given identity keypair, by signing the DH public key
+    /// prefixed with `noise-libp2p-static-key:`.
     pub fn into_authentic(self, id_keys: &identity::Keypair) -> Result<AuthenticKeypair<T>, NoiseError>
     where
         T: AsRef<[u8]>
     {
-        let sig = id_keys.sign(self.public.as_ref())?;
+        let sig = id_keys.sign(&static_key_payload(self.public.as_ref()))?;
 
         let identity = KeypairIdentity {
             public: id_keys.public(),
@@ -314,6 +314,40 @@ mod tests {
         quickcheck(prop as fn() -> _);
     }
 
+    // The static DH public key is authenticated by signing it prefixed with
+    // `noise-libp2p-static-key:`. The expected signature was computed independently
+    // for the ed25519 secret key of RFC 8032 (test 1) and the X25519 key of RFC 7748.
+    #[test]
+    fn static_key_signature_vector() {
+        let mut id_secret = [
+            0x9d, 0x61, 0xb1, 0x9d, 0xef, 0xfd, 0x5a, 0x60, 0xba, 0x84, 0x4a, 0xf4, 0x92, 0xec, 0x2c, 0xc4,
+            0x44, 0x49, 0xc5, 0x69, 0x7b, 0x32, 0x69, 0x19, 0x70, 0x3b, 0xac, 0x03, 0x1c, 0xae, 0x7f, 0x60,
+        ];
+        let dh_secret = [
+            0x77, 0x07, 0x6d, 0x0a, 0x73, 0x18, 0xa5, 0x7d, 0x3c, 0x16, 0xc1, 0x72, 0x51, 0xb2, 0x66, 0x45,
+            0xdf, 0x4c, 0x2f, 0x87, 0xeb, 0xc0, 0x99, 0x2a, 0xb1, 0x77, 0xfb, 0xa5, 0x1d, 0xb9, 0x2c, 0x2a,
+        ];
+        let expected: &[u8] = &[
+            0xe4, 0x9e, 0xaf, 0x43, 0x6e, 0x2f, 0xad, 0x43, 0x31, 0xf7, 0x5d, 0x77, 0x21, 0x50, 0xd6, 0x76,
+            0x65, 0xc3, 0x43, 0x42, 0xba, 0xa8, 0xc0, 0x8a, 0x27, 0xa3, 0xd8, 0xdf, 0xf7, 0x2e, 0x8a, 0xff,
+            0x2a, 0xa5, 0x27, 0x1c, 0x4b, 0xb1, 0x64, 0x2b, 0x89, 0x46, 0x36, 0x2e, 0x4c, 0xac, 0x41, 0x8a,
+            0x19, 0x8d, 0xd5, 0xe5, 0x1e, 0x0e, 0xb3, 0xf3, 0xb3, 0xdf, 0x8f, 0x0a, 0xa2, 0xe3, 0xb6, 0x0f,
+        ];
+
+        let id_keys = identity::Keypair::Ed25519(
+            ed25519::SecretKey::from_bytes(&mut id_secret).unwrap().into());
+        let dh_keys = Keypair::from(SecretKey(X25519(dh_secret)));
+        let dh_public = dh_keys.public().clone();
+
+        let identity = dh_keys.into_authentic(&id_keys).unwrap().into_identity();
+        assert_eq!(identity.signature.as_ref().map(|s| &s[..]), Some(expected));
+        assert!(X25519::verify(&id_keys.public(), &dh_public, &identity.signature));
+
+        // A signature of the bare DH public key is not accepted.
+        let bare = id_keys.sign(dh_public.as_ref()).unwrap();
+        assert!(!X25519::verify(&id_keys.public(), &dh_public, &Some(bare)));
+    }
+
     // Bindings to libsodium's ed25519 to curve25519 key conversions, to check that
     // they agree with the conversions performed in this module.
 
@@ -19,10 +19,10 @@
 // DEALINGS IN THE SOFTWARE.
 
 use futures::{future::{self, Either}, prelude::*};
-use libp2p_core::identity;
+use libp2p_core::{identity, PeerId};
 use libp2p_core::upgrade::{Negotiated, apply_inbound, apply_outbound};
 use libp2p_core::transport::{Transport, ListenerEvent};
-use libp2p_noise::{Keypair, X25519, NoiseConfig, RemoteIdentity, NoiseError, NoiseOutput};
+use libp2p_noise::{Keypair, X25519, NoiseAuthenticated, NoiseConfig, RemoteIdentity, NoiseError, NoiseOutput};
 use libp2p_tcp::{TcpConfig, TcpTransStream};
 use log::info;
 use quickcheck::QuickCheck;
@@ -80,6 +80,30 @@ fn ix() {
     QuickCheck::new().max_tests(30).quickcheck(prop as fn(Vec<u8>) -> bool)
 }
 
+#[test]
+fn xx_authenticated() {
+    let _ = env_logger::try_init();
+    fn prop(message: Vec<u8>) -> bool {
+        let server_id = identity::Keypair::generate_ed25519();
+        let client_id = identity::Keypair::generate_ed25519();
+
+        let server_peer_id = server_id.public().into_peer_id();
+        let client_peer_id = client_id.public().into_peer_id();
+
+        let server_transport = TcpConfig::new()
+            .with_upgrade(NoiseAuthenticated::xx(&server_id).unwrap())
+            .and_then(move |out, _| expect_peer_id(out, &client_peer_id));
+
+        let client_transport = TcpConfig::new()
+            .with_upgrade(NoiseAuthenticated::xx(&client_id).unwrap())
+            .and_then(move |out, _| expect_peer_id(out, &server_peer_id));
+
+        run(server_transport, client_transport, message);
+        true
+    }
+    QuickCheck::new().max_tests(30).quickcheck(prop as fn(Vec<u8>) -> bool)
+}
+
 #[test]
 fn ik_xx() {
     let _ = env_logger::try_init();
@@ -123,13 +147,13 @@ fn ik_xx() {
 
 type Output = (RemoteIdentity<X25519>, NoiseOutput<Negotiated<TcpTransStream>>);
 
-fn run<T, U>(server_transport: T, client_transport: U, message1: Vec<u8>)
+fn run<T, U, I>(server_transport: T, client_transport: U, message1: Vec<u8>)
 where
-    T: Transport<Output = Output>,
+    T: Transport<Output = (I, NoiseOutput<Negotiated<TcpTransStream>>)>,
     T::Dial: Send + 'static,
     T::Listener: Send + 'static,
     T::ListenerUpgrade: Send + 'static,
-    U: Transport<Output = Output>,
+    U: Transport<Output = (I, NoiseOutput<Negotiated<TcpTransStream>>)>,
     U::Dial: Send + 'static,
     U::Listener: Send + 'static,
     U::ListenerUpgrade: Send + 'static,
@@ -182,3 +206,10 @@ fn expect_identity(output: Output, pk: &identity::PublicKey)
         _ => panic!("Unexpected remote identity")
     }
 }
+
+fn expect_peer_id<S>(output: (PeerId, S), peer_id: &PeerId)
+    -> impl Future<Item = (PeerId, S), Error = NoiseError>
+{
+    assert_eq!(&output.0, peer_id, "Unexpected remote peer ID");
+    future::ok(output)
+}
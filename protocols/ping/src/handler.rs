@@ -27,7 +27,7 @@ use libp2p_swarm::{
     ProtocolsHandlerUpgrErr,
     ProtocolsHandlerEvent
 };
-use std::{error::Error, io, fmt, num::NonZeroU32, time::Duration};
+use std::{error::Error, io, fmt, num::{NonZeroU32, NonZeroUsize}, time::Duration};
 use std::collections::VecDeque;
 use tokio_io::{AsyncRead, AsyncWrite};
 use wasm_timer::{Delay, Instant};
@@ -48,6 +48,11 @@ pub struct PingConfig {
     /// Whether the connection should generally be kept alive unless
     /// `max_failures` occur.
     keep_alive: bool,
+    /// The number of most recent round-trip times of a peer its statistics
+    /// are computed from.
+    rtt_window: NonZeroUsize,
+    /// The average round-trip time above which a peer is reported as slow.
+    latency_threshold: Option<Duration>,
 }
 
 impl PingConfig {
@@ -57,6 +62,8 @@ impl PingConfig {
     ///   * [`PingConfig::with_timeout`] 20s
     ///   * [`PingConfig::with_max_failures`] 1
     ///   * [`PingConfig::with_keep_alive`] false
+    ///   * [`PingConfig::with_rtt_window`] 10
+    ///   * [`PingConfig::with_latency_threshold`] none
     ///
     /// These settings have the following effect:
     ///
@@ -68,12 +75,16 @@ impl PingConfig {
     ///   * The connection may be closed at any time as far as the ping protocol
     ///     is concerned, i.e. the ping protocol itself does not keep the
     ///     connection alive.
+    ///   * The round-trip time statistics of a peer are computed from its 10
+    ///     most recent pings, and no latency threshold events are generated.
     pub fn new() -> Self {
         Self {
             timeout: Duration::from_secs(20),
             interval: Duration::from_secs(15),
             max_failures: NonZeroU32::new(1).expect("1 != 0"),
-            keep_alive: false
+            keep_alive: false,
+            rtt_window: NonZeroUsize::new(10).expect("10 != 0"),
+            latency_threshold: None,
         }
     }
 
@@ -110,6 +121,34 @@ impl PingConfig {
         self.keep_alive = b;
         self
     }
+
+    /// Sets the number of most recent round-trip times of a peer its
+    /// [`RttStats`](crate::RttStats) are computed from.
+    pub fn with_rtt_window(mut self, n: NonZeroUsize) -> Self {
+        self.rtt_window = n;
+        self
+    }
+
+    /// Sets the average round-trip time above which a peer is considered slow.
+    ///
+    /// Whenever the average round-trip time of a peer crosses the threshold,
+    /// in either direction, the [`PingEvent`](crate::PingEvent) of the ping
+    /// that caused it reports it.
+    pub fn with_latency_threshold(mut self, d: Duration) -> Self {
+        self.latency_threshold = Some(d);
+        self
+    }
+
+    /// Returns the number of round-trip times of a peer its statistics are
+    /// computed from.
+    pub(crate) fn rtt_window(&self) -> usize {
+        self.rtt_window.get()
+    }
+
+    /// Returns the latency threshold, if any.
+    pub(crate) fn latency_threshold(&self) -> Option<Duration> {
+        self.latency_threshold
+    }
 }
 
 /// The result of an inbound or outbound ping.
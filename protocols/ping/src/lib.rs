@@ -34,6 +34,11 @@
 //! The `Ping` network behaviour produces [`PingEvent`]s, which may be consumed from the `Swarm`
 //! by an application, e.g. to collect statistics.
 //!
+//! The behaviour also maintains rolling round-trip time statistics for every connected peer,
+//! which can be queried with [`Ping::rtt_stats`]. If a latency threshold is configured with
+//! [`PingConfig::with_latency_threshold`], a [`PingEvent::Latency`] is generated when the
+//! average round-trip time of a peer crosses it.
+//!
 //! > **Note**: The ping protocol does not keep otherwise idle connections alive,
 //! > it only adds an additional condition for terminating the connection, namely
 //! > a certain number of failed ping requests.
@@ -43,14 +48,17 @@
 
 pub mod protocol;
 pub mod handler;
+pub mod stats;
 
 pub use handler::{PingConfig, PingResult, PingSuccess, PingFailure};
+pub use stats::{LatencyThreshold, RttStats};
 use handler::PingHandler;
+use stats::RttWindow;
 
 use futures::prelude::*;
 use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
 use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::marker::PhantomData;
 use tokio_io::{AsyncRead, AsyncWrite};
 use void::Void;
@@ -64,16 +72,29 @@ pub struct Ping<TSubstream> {
     config: PingConfig,
     /// Queue of events to yield to the swarm.
     events: VecDeque<PingEvent>,
+    /// The round-trip times of the connected peers.
+    rtts: HashMap<PeerId, RttWindow>,
     _marker: PhantomData<TSubstream>,
 }
 
 /// Event generated by the `Ping` network behaviour.
 #[derive(Debug)]
-pub struct PingEvent {
-    /// The peer ID of the remote.
-    pub peer: PeerId,
-    /// The result of an inbound or outbound ping.
-    pub result: PingResult,
+pub enum PingEvent {
+    /// An inbound or outbound ping was processed.
+    Ping {
+        /// The peer ID of the remote.
+        peer: PeerId,
+        /// The result of the ping.
+        result: PingResult,
+    },
+    /// The average round-trip time of a peer crossed the threshold configured with
+    /// [`PingConfig::with_latency_threshold`].
+    Latency {
+        /// The peer ID of the remote.
+        peer: PeerId,
+        /// The direction of the crossing and the new average round-trip time.
+        threshold: LatencyThreshold,
+    },
 }
 
 impl<TSubstream> Ping<TSubstream> {
@@ -82,9 +103,16 @@ impl<TSubstream> Ping<TSubstream> {
         Ping {
             config,
             events: VecDeque::new(),
+            rtts: HashMap::new(),
             _marker: PhantomData,
         }
     }
+
+    /// Returns the round-trip time statistics of a connected peer, or `None`
+    /// if no outbound ping to that peer succeeded yet.
+    pub fn rtt_stats(&self, peer: &PeerId) -> Option<RttStats> {
+        self.rtts.get(peer).map(RttWindow::stats)
+    }
 }
 
 impl<TSubstream> Default for Ping<TSubstream> {
@@ -110,10 +138,23 @@ where
 
     fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
 
-    fn inject_disconnected(&mut self, _: &PeerId, _: ConnectedPoint) {}
+    fn inject_disconnected(&mut self, peer: &PeerId, _: ConnectedPoint) {
+        self.rtts.remove(peer);
+    }
 
     fn inject_node_event(&mut self, peer: PeerId, result: PingResult) {
-        self.events.push_front(PingEvent { peer, result })
+        let latency = if let Ok(PingSuccess::Ping { rtt }) = &result {
+            let config = &self.config;
+            self.rtts.entry(peer.clone())
+                .or_insert_with(|| RttWindow::new(config.rtt_window()))
+                .push(*rtt, config.latency_threshold())
+        } else {
+            None
+        };
+        self.events.push_front(PingEvent::Ping { peer: peer.clone(), result });
+        if let Some(threshold) = latency {
+            self.events.push_front(PingEvent::Latency { peer, threshold });
+        }
     }
 
     fn poll(&mut self, _: &mut impl PollParameters) -> Async<NetworkBehaviourAction<Void, PingEvent>>
@@ -125,3 +166,40 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Cursor, num::NonZeroUsize, time::Duration};
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn latency_events_follow_the_pings_crossing_the_threshold() {
+        let config = PingConfig::new()
+            .with_rtt_window(NonZeroUsize::new(1).unwrap())
+            .with_latency_threshold(ms(50));
+        let mut ping = Ping::<Cursor<Vec<u8>>>::new(config);
+        let peer = PeerId::random();
+
+        for rtt in &[10, 100, 200, 20] {
+            ping.inject_node_event(peer.clone(), Ok(PingSuccess::Ping { rtt: ms(*rtt) }));
+        }
+
+        let events = ping.events.drain(..).rev().map(|e| match e {
+            PingEvent::Ping { result: Ok(PingSuccess::Ping { rtt }), .. } => format!("ping {:?}", rtt),
+            PingEvent::Latency { threshold, .. } => format!("{:?}", threshold),
+            e => panic!("unexpected event: {:?}", e),
+        }).collect::<Vec<_>>();
+        assert_eq!(events, vec![
+            format!("ping {:?}", ms(10)),
+            format!("ping {:?}", ms(100)),
+            format!("{:?}", LatencyThreshold::Exceeded { avg: ms(100) }),
+            format!("ping {:?}", ms(200)),
+            format!("ping {:?}", ms(20)),
+            format!("{:?}", LatencyThreshold::Recovered { avg: ms(20) }),
+        ]);
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Round-trip time statistics of the peers we ping.
+
+use std::{collections::VecDeque, time::Duration};
+
+/// Round-trip time statistics of a peer, over a window of its most recent
+/// successful outbound pings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RttStats {
+    /// The round-trip time of the most recent ping.
+    last: Duration,
+    /// The smallest round-trip time in the window.
+    min: Duration,
+    /// The average round-trip time in the window.
+    avg: Duration,
+    /// The average difference between the round-trip times of consecutive
+    /// pings in the window.
+    jitter: Duration,
+    /// The number of pings in the window.
+    samples: usize,
+}
+
+impl RttStats {
+    /// Returns the round-trip time of the most recent ping.
+    pub fn last(&self) -> Duration {
+        self.last
+    }
+
+    /// Returns the smallest round-trip time in the window.
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+
+    /// Returns the average round-trip time in the window.
+    pub fn avg(&self) -> Duration {
+        self.avg
+    }
+
+    /// Returns the average difference between the round-trip times of
+    /// consecutive pings in the window.
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Returns the number of pings the statistics are computed from.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+}
+
+/// Whether the average round-trip time of a peer crossed the configured
+/// latency threshold (see [`PingConfig::with_latency_threshold`]).
+///
+/// [`PingConfig::with_latency_threshold`]: crate::PingConfig::with_latency_threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyThreshold {
+    /// The average round-trip time rose above the threshold.
+    Exceeded { avg: Duration },
+    /// The average round-trip time fell back to or below the threshold.
+    Recovered { avg: Duration },
+}
+
+/// The rolling window of the round-trip times of a peer.
+#[derive(Debug)]
+pub(crate) struct RttWindow {
+    /// The most recent round-trip times, oldest first.
+    samples: VecDeque<Duration>,
+    /// The maximum number of round-trip times in the window.
+    capacity: usize,
+    /// Whether the average round-trip time is above the latency threshold.
+    above_threshold: bool,
+}
+
+impl RttWindow {
+    /// Creates an empty window holding up to `capacity` round-trip times.
+    pub(crate) fn new(capacity: usize) -> Self {
+        RttWindow {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            above_threshold: false,
+        }
+    }
+
+    /// Adds a round-trip time to the window, evicting the oldest one if the
+    /// window is full.
+    ///
+    /// Returns the threshold crossing this caused, if any.
+    pub(crate) fn push(&mut self, rtt: Duration, threshold: Option<Duration>) -> Option<LatencyThreshold> {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt);
+
+        let threshold = threshold?;
+        let avg = self.stats().avg;
+        match (self.above_threshold, avg > threshold) {
+            (false, true) => {
+                self.above_threshold = true;
+                Some(LatencyThreshold::Exceeded { avg })
+            }
+            (true, false) => {
+                self.above_threshold = false;
+                Some(LatencyThreshold::Recovered { avg })
+            }
+            _ => None,
+        }
+    }
+
+    /// Computes the statistics of the window.
+    ///
+    /// Must only be called after at least one round-trip time was pushed.
+    pub(crate) fn stats(&self) -> RttStats {
+        let samples = self.samples.len();
+        let last = *self.samples.back().expect("The window is never empty once created; QED");
+        let min = *self.samples.iter().min().expect("The window is not empty; QED");
+        let sum: Duration = self.samples.iter().sum();
+        let avg = sum / samples as u32;
+        let jitter = if samples > 1 {
+            let diffs: Duration = self.samples.iter()
+                .zip(self.samples.iter().skip(1))
+                .map(|(a, b)| if a > b { *a - *b } else { *b - *a })
+                .sum();
+            diffs / (samples - 1) as u32
+        } else {
+            Duration::from_secs(0)
+        };
+
+        RttStats { last, min, avg, jitter, samples }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn rolling_stats() {
+        let mut window = RttWindow::new(3);
+        for rtt in &[ms(100), ms(10), ms(40), ms(20)] {
+            window.push(*rtt, None);
+        }
+
+        // The first round-trip time has been evicted.
+        let stats = window.stats();
+        assert_eq!(stats.samples(), 3);
+        assert_eq!(stats.last(), ms(20));
+        assert_eq!(stats.min(), ms(10));
+        assert_eq!(stats.avg(), ms(70) / 3);
+        assert_eq!(stats.jitter(), ms(25));
+    }
+
+    #[test]
+    fn threshold_crossings() {
+        let mut window = RttWindow::new(2);
+        let threshold = Some(ms(50));
+        assert_eq!(window.push(ms(10), threshold), None);
+        assert_eq!(window.push(ms(200), threshold), Some(LatencyThreshold::Exceeded { avg: ms(105) }));
+        assert_eq!(window.push(ms(100), threshold), None);
+        assert_eq!(window.push(ms(0), threshold), Some(LatencyThreshold::Recovered { avg: ms(50) }));
+    }
+}
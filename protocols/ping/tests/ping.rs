@@ -56,9 +56,13 @@ fn ping() {
     let peer1 = future::poll_fn(move || -> Result<_, ()> {
         loop {
             match swarm1.poll().expect("Error while polling swarm") {
-                Async::Ready(Some(PingEvent { peer, result })) => match result {
-                    Ok(PingSuccess::Ping { rtt }) =>
-                        return Ok(Async::Ready((pid1.clone(), peer, rtt))),
+                Async::Ready(Some(PingEvent::Ping { peer, result })) => match result {
+                    Ok(PingSuccess::Ping { rtt }) => {
+                        let stats = swarm1.rtt_stats(&peer).expect("A ping succeeded");
+                        assert_eq!(stats.last(), rtt);
+                        assert_eq!(stats.samples(), 1);
+                        return Ok(Async::Ready((pid1.clone(), peer, rtt)))
+                    },
                     _ => {}
                 },
                 _ => {
@@ -79,7 +83,7 @@ fn ping() {
     let peer2 = future::poll_fn(move || -> Result<_, ()> {
         loop {
             match swarm2.poll().expect("Error while polling swarm") {
-                Async::Ready(Some(PingEvent { peer, result })) => match result {
+                Async::Ready(Some(PingEvent::Ping { peer, result })) => match result {
                     Ok(PingSuccess::Ping { rtt }) =>
                         return Ok(Async::Ready((pid2.clone(), peer, rtt))),
                     _ => {}
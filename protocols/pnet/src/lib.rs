@@ -0,0 +1,663 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Implementation of the [private networks](https://github.com/libp2p/specs/blob/master/pnet/Private-Networks-PSK-V1.md)
+//! of libp2p, in which nodes only connect to the nodes that share a pre-shared key.
+//!
+//! The `PnetConfig::handshake` method is applied on raw connections, before any other protocol.
+//! Both sides send a random nonce, and all the data that follows is encrypted with XSalsa20,
+//! keyed with the pre-shared key and the nonce of the sender.
+//!
+//! # Mismatched keys
+//!
+//! The specification doesn't include any key confirmation: with a remote that doesn't have the
+//! same key, the data read is garbage, and the protocols that follow either fail when decoding
+//! it or wait for data that the remote never sends. To fail fast instead, once the nonces are
+//! exchanged, both sides send the nonce of the remote through the encrypted stream and check
+//! that they receive their own nonce back. The handshake fails with `PnetError::KeyMismatch`
+//! otherwise, after a single round trip.
+//!
+//! This key confirmation isn't part of the specification, and nodes that don't perform it read
+//! the confirmation as the beginning of the connection. It can be disabled with
+//! `PnetConfig::key_confirmation` to connect to such nodes, in which case the transport should
+//! have a timeout, see `Transport::with_timeout`, so that the connections with the nodes of other
+//! networks are eventually dropped.
+//!
+//! # Usage
+//!
+//! A transport is wrapped in a `PnetTransport`, which applies the handshake to all of its
+//! connections, after which it can be upgraded as usual, e.g. with secio or noise:
+//!
+//! ```
+//! use libp2p_core::Transport;
+//! use libp2p_pnet::{PnetConfig, PnetTransport, PreSharedKey};
+//! use libp2p_tcp::TcpConfig;
+//! use std::time::Duration;
+//!
+//! let psk: PreSharedKey = "/key/swarm/psk/1.0.0/\n/base16/\n\
+//!     6189c5cf0b87fb800c1a9feeda73c6ab5e998db48fb9e6a978575c770ceef683"
+//!     .parse()
+//!     .unwrap();
+//! let transport = PnetTransport::new(TcpConfig::new(), PnetConfig::new(psk))
+//!     .with_timeout(Duration::from_secs(20));
+//! ```
+//!
+//! The handshake can also be applied to individual connections with `PnetConfig::handshake`.
+
+use futures::{Async, Future, Poll, Stream, try_ready};
+use libp2p_core::{
+    Multiaddr,
+    Transport,
+    either::EitherError,
+    transport::{ListenerEvent, TransportError}
+};
+use log::debug;
+use salsa20::XSalsa20;
+use salsa20::stream_cipher::{NewStreamCipher, SyncStreamCipher, generic_array::GenericArray};
+use std::{error, fmt, io, io::{Read, Write}, str::FromStr};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Length of the pre-shared key, in bytes.
+const KEY_SIZE: usize = 32;
+
+/// Length of the nonces exchanged during the handshake, in bytes.
+const NONCE_SIZE: usize = 24;
+
+/// First line of a key file, identifying the type of key.
+const KEY_TYPE: &str = "/key/swarm/psk/1.0.0/";
+
+/// Second line of a key file, identifying the encoding of the key.
+const KEY_ENCODING: &str = "/base16/";
+
+/// A pre-shared key, shared by all the nodes of a private network.
+///
+/// Keys are usually stored in `swarm.key` files, whose content can be parsed with `FromStr`
+/// and produced with `Display`.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct PreSharedKey([u8; KEY_SIZE]);
+
+impl PreSharedKey {
+    /// Creates a pre-shared key from its bytes.
+    pub fn new(data: [u8; KEY_SIZE]) -> Self {
+        PreSharedKey(data)
+    }
+}
+
+impl FromStr for PreSharedKey {
+    type Err = KeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim);
+        if lines.next() != Some(KEY_TYPE) {
+            return Err(KeyParseError::InvalidKeyType);
+        }
+        if lines.next() != Some(KEY_ENCODING) {
+            return Err(KeyParseError::InvalidKeyEncoding);
+        }
+        let encoded = lines.next().ok_or(KeyParseError::InvalidKeyLength)?.as_bytes();
+        if encoded.len() != 2 * KEY_SIZE {
+            return Err(KeyParseError::InvalidKeyLength);
+        }
+        if lines.any(|l| !l.is_empty()) {
+            return Err(KeyParseError::InvalidKeyFile);
+        }
+
+        let mut data = [0; KEY_SIZE];
+        for (byte, digits) in data.iter_mut().zip(encoded.chunks(2)) {
+            let high = (digits[0] as char).to_digit(16).ok_or(KeyParseError::InvalidKey)?;
+            let low = (digits[1] as char).to_digit(16).ok_or(KeyParseError::InvalidKey)?;
+            *byte = (high * 16 + low) as u8;
+        }
+        Ok(PreSharedKey(data))
+    }
+}
+
+impl fmt::Display for PreSharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", KEY_TYPE)?;
+        writeln!(f, "{}", KEY_ENCODING)?;
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PreSharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Don't leak the key in logs.
+        f.write_str("PreSharedKey")
+    }
+}
+
+/// Error while parsing a `PreSharedKey`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyParseError {
+    /// The first line isn't `/key/swarm/psk/1.0.0/`.
+    InvalidKeyType,
+    /// The second line isn't `/base16/`.
+    InvalidKeyEncoding,
+    /// The key doesn't have 32 bytes.
+    InvalidKeyLength,
+    /// The key isn't valid hexadecimal.
+    InvalidKey,
+    /// There is content after the key.
+    InvalidKeyFile,
+}
+
+impl fmt::Display for KeyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyParseError::InvalidKeyType => write!(f, "Invalid key type, expected {}", KEY_TYPE),
+            KeyParseError::InvalidKeyEncoding => write!(f, "Invalid key encoding, expected {}", KEY_ENCODING),
+            KeyParseError::InvalidKeyLength => write!(f, "Invalid key length, expected {} bytes", KEY_SIZE),
+            KeyParseError::InvalidKey => f.write_str("Invalid hexadecimal key"),
+            KeyParseError::InvalidKeyFile => f.write_str("Unexpected content after the key"),
+        }
+    }
+}
+
+impl error::Error for KeyParseError {}
+
+/// Configuration of the private network handshake.
+#[derive(Debug, Copy, Clone)]
+pub struct PnetConfig {
+    /// The pre-shared key of the private network.
+    key: PreSharedKey,
+    /// Whether to check that the remote has the same key during the handshake.
+    key_confirmation: bool,
+}
+
+impl PnetConfig {
+    /// Creates a new configuration for the private network identified by `key`, with key
+    /// confirmation enabled.
+    pub fn new(key: PreSharedKey) -> Self {
+        PnetConfig { key, key_confirmation: true }
+    }
+
+    /// Sets whether the handshake checks that the remote has the same key, see the
+    /// [module-level documentation](index.html#mismatched-keys).
+    pub fn key_confirmation(mut self, value: bool) -> Self {
+        self.key_confirmation = value;
+        self
+    }
+
+    /// Performs the handshake on a raw connection.
+    ///
+    /// The returned future resolves once the nonces have been exchanged and, if key
+    /// confirmation is enabled, once the remote has proven that it has the same key.
+    pub fn handshake<S>(self, socket: S) -> PnetHandshake<S>
+    where
+        S: AsyncRead + AsyncWrite
+    {
+        debug!("Starting private network handshake");
+        let local_nonce = rand::random::<[u8; NONCE_SIZE]>();
+        PnetHandshake {
+            config: self,
+            local_nonce,
+            state: HandshakeState::Writing(tokio_io::io::write_all(socket, local_nonce)),
+        }
+    }
+}
+
+/// Future performing the private network handshake, see `PnetConfig::handshake`.
+pub struct PnetHandshake<S> {
+    config: PnetConfig,
+    local_nonce: [u8; NONCE_SIZE],
+    state: HandshakeState<S>,
+}
+
+/// State of a `PnetHandshake`.
+enum HandshakeState<S> {
+    /// Sending our nonce.
+    Writing(tokio_io::io::WriteAll<S, [u8; NONCE_SIZE]>),
+    /// Flushing our nonce.
+    Flushing(tokio_io::io::Flush<S>),
+    /// Receiving the nonce of the remote.
+    Reading(tokio_io::io::ReadExact<S, [u8; NONCE_SIZE]>),
+    /// Sending the nonce of the remote through the encrypted stream.
+    ConfirmWriting(tokio_io::io::WriteAll<PnetOutput<S>, [u8; NONCE_SIZE]>),
+    /// Flushing the encrypted nonce of the remote.
+    ConfirmFlushing(tokio_io::io::Flush<PnetOutput<S>>),
+    /// Receiving our nonce through the encrypted stream.
+    ConfirmReading(tokio_io::io::ReadExact<PnetOutput<S>, [u8; NONCE_SIZE]>),
+    /// The handshake is over.
+    Done,
+}
+
+impl<S> Future for PnetHandshake<S>
+where
+    S: AsyncRead + AsyncWrite
+{
+    type Item = PnetOutput<S>;
+    type Error = PnetError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            self.state = match self.state {
+                HandshakeState::Writing(ref mut future) => {
+                    let (socket, _) = try_ready!(future.poll().map_err(PnetError::HandshakeError));
+                    HandshakeState::Flushing(tokio_io::io::flush(socket))
+                }
+                HandshakeState::Flushing(ref mut future) => {
+                    let socket = try_ready!(future.poll().map_err(PnetError::HandshakeError));
+                    HandshakeState::Reading(tokio_io::io::read_exact(socket, [0; NONCE_SIZE]))
+                }
+                HandshakeState::Reading(ref mut future) => {
+                    let (socket, remote_nonce) = try_ready!(future.poll().map_err(PnetError::HandshakeError));
+                    let output = PnetOutput::new(socket, &self.config.key, &self.local_nonce, &remote_nonce);
+                    if !self.config.key_confirmation {
+                        self.state = HandshakeState::Done;
+                        return Ok(Async::Ready(output));
+                    }
+                    HandshakeState::ConfirmWriting(tokio_io::io::write_all(output, remote_nonce))
+                }
+                HandshakeState::ConfirmWriting(ref mut future) => {
+                    let (output, _) = try_ready!(future.poll().map_err(PnetError::HandshakeError));
+                    HandshakeState::ConfirmFlushing(tokio_io::io::flush(output))
+                }
+                HandshakeState::ConfirmFlushing(ref mut future) => {
+                    let output = try_ready!(future.poll().map_err(PnetError::HandshakeError));
+                    HandshakeState::ConfirmReading(tokio_io::io::read_exact(output, [0; NONCE_SIZE]))
+                }
+                HandshakeState::ConfirmReading(ref mut future) => {
+                    let (output, nonce) = try_ready!(future.poll().map_err(PnetError::HandshakeError));
+                    self.state = HandshakeState::Done;
+                    if nonce != self.local_nonce {
+                        debug!("Remote doesn't have the same pre-shared key");
+                        return Err(PnetError::KeyMismatch);
+                    }
+                    return Ok(Async::Ready(output));
+                }
+                HandshakeState::Done => panic!("PnetHandshake polled after completion"),
+            };
+        }
+    }
+}
+
+/// Transport wrapping another transport, whose connections are part of a private network.
+///
+/// The private network handshake is performed on every connection of the inner transport.
+#[derive(Debug, Clone)]
+pub struct PnetTransport<T> {
+    /// The underlying transport.
+    inner: T,
+    /// The configuration of the handshake.
+    config: PnetConfig,
+}
+
+impl<T> PnetTransport<T> {
+    /// Wraps around `inner`, whose connections become part of the private network of `config`.
+    pub fn new(inner: T, config: PnetConfig) -> Self {
+        PnetTransport { inner, config }
+    }
+}
+
+impl<T> Transport for PnetTransport<T>
+where
+    T: Transport,
+    T::Output: AsyncRead + AsyncWrite,
+{
+    type Output = PnetOutput<T::Output>;
+    type Error = EitherError<T::Error, PnetError>;
+    type Listener = PnetListener<T::Listener>;
+    type ListenerUpgrade = PnetUpgrade<T::ListenerUpgrade, T::Output>;
+    type Dial = PnetUpgrade<T::Dial, T::Output>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let inner = self.inner.listen_on(addr).map_err(|err| err.map(EitherError::A))?;
+        Ok(PnetListener { inner, config: self.config })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let inner = self.inner.dial(addr).map_err(|err| err.map(EitherError::A))?;
+        Ok(PnetUpgrade::new(inner, self.config))
+    }
+}
+
+/// Listener of a `PnetTransport`.
+pub struct PnetListener<L> {
+    inner: L,
+    config: PnetConfig,
+}
+
+impl<L, F, S> Stream for PnetListener<L>
+where
+    L: Stream<Item = ListenerEvent<F>>,
+    F: Future<Item = S, Error = L::Error>,
+    S: AsyncRead + AsyncWrite,
+{
+    type Item = ListenerEvent<PnetUpgrade<F, S>>;
+    type Error = EitherError<L::Error, PnetError>;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let config = self.config;
+        match try_ready!(self.inner.poll().map_err(EitherError::A)) {
+            Some(event) => Ok(Async::Ready(Some(event.map(|upgrade| PnetUpgrade::new(upgrade, config))))),
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+/// Connection of a `PnetTransport` being established.
+pub struct PnetUpgrade<F, S> {
+    config: PnetConfig,
+    state: UpgradeState<F, S>,
+}
+
+/// State of a `PnetUpgrade`.
+enum UpgradeState<F, S> {
+    /// Establishing the connection with the inner transport.
+    Connecting(F),
+    /// Performing the handshake on the connection.
+    Handshaking(PnetHandshake<S>),
+}
+
+impl<F, S> PnetUpgrade<F, S> {
+    fn new(inner: F, config: PnetConfig) -> Self {
+        PnetUpgrade { config, state: UpgradeState::Connecting(inner) }
+    }
+}
+
+impl<F, S> Future for PnetUpgrade<F, S>
+where
+    F: Future<Item = S>,
+    S: AsyncRead + AsyncWrite,
+{
+    type Item = PnetOutput<S>;
+    type Error = EitherError<F::Error, PnetError>;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            self.state = match self.state {
+                UpgradeState::Connecting(ref mut future) => {
+                    let socket = try_ready!(future.poll().map_err(EitherError::A));
+                    UpgradeState::Handshaking(self.config.handshake(socket))
+                }
+                UpgradeState::Handshaking(ref mut future) => return future.poll().map_err(EitherError::B),
+            };
+        }
+    }
+}
+
+/// Error during the private network handshake.
+#[derive(Debug)]
+pub enum PnetError {
+    /// Failed to exchange the nonces with the remote.
+    HandshakeError(io::Error),
+    /// The remote doesn't have the same pre-shared key.
+    KeyMismatch,
+}
+
+impl fmt::Display for PnetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PnetError::HandshakeError(e) => write!(f, "Private network handshake failed: {}", e),
+            PnetError::KeyMismatch => f.write_str("Remote doesn't have the same pre-shared key"),
+        }
+    }
+}
+
+impl error::Error for PnetError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PnetError::HandshakeError(e) => Some(e),
+            PnetError::KeyMismatch => None,
+        }
+    }
+}
+
+/// A connection of a private network, encrypting and decrypting the data going through the
+/// underlying connection.
+pub struct PnetOutput<S> {
+    /// The underlying connection.
+    inner: S,
+    /// Decrypts the data received from the remote.
+    read_cipher: XSalsa20,
+    /// Encrypts the data sent to the remote.
+    write_cipher: XSalsa20,
+    /// Encrypted data accepted by `write` but not yet written to the underlying connection.
+    write_buffer: Vec<u8>,
+}
+
+impl<S> PnetOutput<S> {
+    fn new(inner: S, key: &PreSharedKey, local_nonce: &[u8; NONCE_SIZE], remote_nonce: &[u8; NONCE_SIZE]) -> Self {
+        let key = GenericArray::from_slice(&key.0);
+        PnetOutput {
+            inner,
+            read_cipher: XSalsa20::new(key, GenericArray::from_slice(remote_nonce)),
+            write_cipher: XSalsa20::new(key, GenericArray::from_slice(local_nonce)),
+            write_buffer: Vec::new(),
+        }
+    }
+}
+
+impl<S: AsyncWrite> PnetOutput<S> {
+    /// Writes the content of `write_buffer` to the underlying connection.
+    fn write_buffered(&mut self) -> io::Result<()> {
+        while !self.write_buffer.is_empty() {
+            let n = self.inner.write(&self.write_buffer)?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.write_buffer.drain(.. n);
+        }
+        Ok(())
+    }
+}
+
+impl<S: AsyncRead> io::Read for PnetOutput<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read_cipher.apply_keystream(&mut buf[.. n]);
+        Ok(n)
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for PnetOutput<S> {}
+
+impl<S: AsyncWrite> io::Write for PnetOutput<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // New data is only accepted once the previously accepted data has been written, which
+        // bounds the size of the buffer. Accepted data is encrypted exactly once, as the
+        // keystream advances with every encrypted byte.
+        self.write_buffered()?;
+        self.write_buffer.extend_from_slice(buf);
+        self.write_cipher.apply_keystream(&mut self.write_buffer);
+        match self.write_buffered() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+            Ok(()) => {}
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buffered()?;
+        self.inner.flush()
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for PnetOutput<S> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self.write_buffered() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Async::NotReady),
+            Err(e) => return Err(e),
+            Ok(()) => {}
+        }
+        self.inner.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Stream;
+    use libp2p_core::{identity, multiaddr::multiaddr, transport::{ListenerEvent, Transport, memory::MemoryTransport}};
+    use libp2p_secio::SecioConfig;
+    use rand::{thread_rng, Rng};
+    use std::time::{Duration, Instant};
+    use tokio::runtime::current_thread::Runtime;
+
+    /// Connects two nodes with the given configurations, sends a message from the dialer to the
+    /// listener and returns the message received by the listener.
+    fn exchange(listener_config: PnetConfig, dialer_config: PnetConfig, message: &'static [u8]) -> Vec<u8> {
+        let addr = multiaddr![Memory(thread_rng().gen::<u64>())];
+        let listener = MemoryTransport
+            .and_then(move |socket, _| listener_config.handshake(socket))
+            .listen_on(addr.clone())
+            .unwrap();
+
+        let server = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(e, _)| panic!("listener error: {:?}", e))
+            .and_then(|(upgrade, _)| {
+                let (upgrade, _) = upgrade.expect("incoming connection");
+                upgrade.map_err(|e| panic!("upgrade error: {:?}", e))
+            })
+            .and_then(move |socket| {
+                tokio_io::io::read_exact(socket, vec![0; message.len()])
+                    .map_err(|e| panic!("read error: {:?}", e))
+            })
+            .map(|(_, buf)| buf);
+
+        let client = MemoryTransport
+            .and_then(move |socket, _| dialer_config.handshake(socket))
+            .dial(addr)
+            .unwrap()
+            .map_err(|e| panic!("dial error: {:?}", e))
+            .and_then(move |socket| {
+                tokio_io::io::write_all(socket, message)
+                    .and_then(|(socket, _)| tokio_io::io::flush(socket))
+                    .map_err(|e| panic!("write error: {:?}", e))
+            });
+
+        let mut rt = Runtime::new().unwrap();
+        let (received, _) = rt.block_on(server.join(client)).unwrap();
+        received
+    }
+
+    #[test]
+    fn key_parsing_round_trip() {
+        let key = PreSharedKey::new(rand::random());
+        let parsed = key.to_string().parse::<PreSharedKey>().unwrap();
+        assert_eq!(parsed, key);
+
+        assert_eq!("/key/swarm/psk/1.0.0/\n/base16/\nabcd".parse::<PreSharedKey>(),
+            Err(KeyParseError::InvalidKeyLength));
+        assert_eq!("/key/swarm/psk/1.0.0/\n/base64/\n".parse::<PreSharedKey>(),
+            Err(KeyParseError::InvalidKeyEncoding));
+    }
+
+    #[test]
+    fn same_key_exchanges_data() {
+        let key = PreSharedKey::new(rand::random());
+        assert_eq!(exchange(PnetConfig::new(key), PnetConfig::new(key), b"hello world"), b"hello world");
+
+        let config = PnetConfig::new(key).key_confirmation(false);
+        assert_eq!(exchange(config, config, b"hello world"), b"hello world");
+    }
+
+    #[test]
+    fn different_keys_garble_data_without_key_confirmation() {
+        let listener_config = PnetConfig::new(PreSharedKey::new(rand::random())).key_confirmation(false);
+        let dialer_config = PnetConfig::new(PreSharedKey::new(rand::random())).key_confirmation(false);
+        assert_ne!(exchange(listener_config, dialer_config, b"hello world"), b"hello world");
+    }
+
+    #[test]
+    fn different_keys_fail_handshake() {
+        let listener_config = PnetConfig::new(PreSharedKey::new(rand::random()));
+        let dialer_config = PnetConfig::new(PreSharedKey::new(rand::random()));
+
+        let addr = multiaddr![Memory(thread_rng().gen::<u64>())];
+        let listener = MemoryTransport.listen_on(addr.clone()).unwrap();
+        let server = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(e, _)| panic!("listener error: {:?}", e))
+            .and_then(|(upgrade, _)| {
+                let (upgrade, _) = upgrade.expect("incoming connection");
+                upgrade.map_err(|e| panic!("upgrade error: {:?}", e))
+            })
+            .and_then(move |socket| listener_config.handshake(socket).then(Ok::<_, ()>));
+        let client = MemoryTransport
+            .dial(addr)
+            .unwrap()
+            .map_err(|e| panic!("dial error: {:?}", e))
+            .and_then(move |socket| dialer_config.handshake(socket).then(Ok::<_, ()>));
+
+        let mut rt = Runtime::new().unwrap();
+        let (server, client) = rt.block_on(server.join(client)).unwrap();
+        match (server, client) {
+            (Err(PnetError::KeyMismatch), Err(PnetError::KeyMismatch)) => {}
+            _ => panic!("the handshake didn't fail with a key mismatch on both sides"),
+        }
+    }
+
+    /// Connects two nodes with the given keys through `PnetTransport`s upgraded with secio, and
+    /// returns the outcome of the upgrade on the listener and on the dialer, along with the time
+    /// it took.
+    fn secio_upgrade(listener_key: PreSharedKey, dialer_key: PreSharedKey) -> ((bool, bool), Duration) {
+        let transport = |psk| PnetTransport::new(MemoryTransport, PnetConfig::new(psk))
+            .with_upgrade(SecioConfig::new(identity::Keypair::generate_ed25519()))
+            .with_timeout(Duration::from_secs(5));
+
+        let addr = multiaddr![Memory(thread_rng().gen::<u64>())];
+        let listener = transport(listener_key).listen_on(addr.clone()).unwrap();
+
+        let server = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(e, _)| panic!("listener error: {:?}", e))
+            .and_then(|(upgrade, _)| {
+                let (upgrade, _) = upgrade.expect("incoming connection");
+                upgrade.then(|result| Ok::<_, ()>(result.is_ok()))
+            });
+
+        let client = transport(dialer_key)
+            .dial(addr)
+            .unwrap()
+            .then(|result| Ok::<_, ()>(result.is_ok()));
+
+        let mut rt = Runtime::new().unwrap();
+        let start = Instant::now();
+        let outcome = rt.block_on(server.join(client)).unwrap();
+        (outcome, start.elapsed())
+    }
+
+    #[test]
+    fn same_key_upgrades() {
+        let key = PreSharedKey::new(rand::random());
+        assert_eq!(secio_upgrade(key, key).0, (true, true));
+    }
+
+    #[test]
+    fn different_keys_fail_upgrade() {
+        // The upgrade fails on both sides well before the timeout of the transport.
+        let listener_key = PreSharedKey::new(rand::random());
+        let dialer_key = PreSharedKey::new(rand::random());
+        let (outcome, elapsed) = secio_upgrade(listener_key, dialer_key);
+        assert_eq!(outcome, (false, false));
+        assert!(elapsed < Duration::from_secs(1), "upgrade failed after {:?}", elapsed);
+    }
+}
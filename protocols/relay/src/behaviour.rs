@@ -0,0 +1,647 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::handler::{RelayHandler, RelayHandlerEvent, RelayHandlerIn, RequestId};
+use crate::protocol::{
+    IncomingCanHopRequest,
+    IncomingHopRequest,
+    IncomingStopRequest,
+    OutgoingRelayRequest,
+    RelayError,
+    RelayRequest,
+    RelayStatus
+};
+use crate::transport::{
+    Connection,
+    IncomingRelayedConnection,
+    RelayTransportReceiver,
+    TransportToBehaviourMsg
+};
+use futures::{prelude::*, sync::{mpsc, oneshot}};
+use libp2p_core::{
+    ConnectedPoint,
+    Multiaddr,
+    PeerId,
+    multiaddr::Protocol,
+    upgrade::{Negotiated, UpgradeError}
+};
+use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, ProtocolsHandlerUpgrErr};
+use log::debug;
+use smallvec::SmallVec;
+use std::collections::{HashMap, VecDeque};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Configuration for the `Relay` network behaviour.
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    /// Whether we relay circuits for other peers.
+    hop: bool,
+    /// The maximum number of circuits relayed at the same time.
+    max_circuits: usize,
+}
+
+impl RelayConfig {
+    /// Creates a new `RelayConfig` with the following default settings:
+    ///
+    ///   * [`RelayConfig::with_hop`] false
+    ///   * [`RelayConfig::with_max_circuits`] 64
+    pub fn new() -> Self {
+        RelayConfig {
+            hop: false,
+            max_circuits: 64,
+        }
+    }
+
+    /// Sets whether we relay circuits for other peers.
+    ///
+    /// Circuits are only relayed to destinations we are already connected to.
+    pub fn with_hop(mut self, hop: bool) -> Self {
+        self.hop = hop;
+        self
+    }
+
+    /// Sets the maximum number of circuits relayed at the same time. Further requests are
+    /// denied.
+    pub fn with_max_circuits(mut self, max_circuits: usize) -> Self {
+        self.max_circuits = max_circuits;
+        self
+    }
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        RelayConfig::new()
+    }
+}
+
+/// Network behaviour that implements the circuit relay protocol.
+///
+/// Together with the `RelayTransport` it is paired with, it opens circuits through relays when
+/// dialing relayed addresses, and accepts the circuits relayed to us when listening on a relayed
+/// address. If enabled in the `RelayConfig`, it also relays circuits for other peers.
+pub struct Relay<TSubstream> {
+    /// The configuration of the behaviour.
+    config: RelayConfig,
+    /// Our own identity.
+    local_peer_id: PeerId,
+    /// Receives the requests of the `RelayTransport`.
+    from_transport: mpsc::UnboundedReceiver<TransportToBehaviourMsg>,
+    /// Peers we are connected to, with the address of the connection.
+    connected_peers: HashMap<PeerId, Multiaddr>,
+    /// Addresses of the relays found in relayed addresses.
+    relay_addrs: HashMap<PeerId, SmallVec<[Multiaddr; 4]>>,
+    /// Dials waiting for the connection with their relay to be established.
+    pending_dials: HashMap<PeerId, SmallVec<[PendingDial; 4]>>,
+    /// Dials whose request has been sent to the relay.
+    outgoing_dials: HashMap<RequestId, OutgoingDial>,
+    /// Requests to relay a circuit, waiting for the destination to accept it.
+    pending_hops: HashMap<RequestId, PendingHop<Negotiated<TSubstream>>>,
+    /// Listeners of the `RelayTransport`.
+    listeners: Vec<Listener>,
+    /// Circuits that keep connections alive.
+    circuits: Vec<Circuit>,
+    /// Number of circuits we relay for other peers, including the ones being opened.
+    num_relayed: usize,
+    /// Events waiting to be returned by `poll`.
+    events: VecDeque<NetworkBehaviourAction<RelayHandlerIn, RelayEvent>>,
+    /// Identifier of the next outgoing request.
+    next_request_id: u64,
+}
+
+/// Event produced by the `Relay` network behaviour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayEvent {
+    /// We started relaying a circuit between two peers.
+    CircuitRelayed {
+        /// The peer that requested the circuit.
+        src_peer_id: PeerId,
+        /// The peer at the other end of the circuit.
+        dst_peer_id: PeerId,
+    },
+    /// We denied or failed to open a circuit between two peers.
+    CircuitDenied {
+        /// The peer that requested the circuit.
+        src_peer_id: PeerId,
+        /// The peer at the other end of the circuit.
+        dst_peer_id: PeerId,
+    },
+}
+
+/// A dial waiting for the connection with the relay.
+struct PendingDial {
+    dst_peer_id: PeerId,
+    dst_addr: Option<Multiaddr>,
+    send_back: oneshot::Sender<Result<Connection, RelayError>>,
+}
+
+/// A dial whose request has been sent to the relay.
+struct OutgoingDial {
+    relay_peer_id: PeerId,
+    send_back: oneshot::Sender<Result<Connection, RelayError>>,
+}
+
+/// A request to relay a circuit, waiting for the destination to accept it.
+struct PendingHop<TSubstream> {
+    src_peer_id: PeerId,
+    dst_peer_id: PeerId,
+    request: IncomingHopRequest<TSubstream>,
+}
+
+/// A listener of the `RelayTransport`.
+struct Listener {
+    /// The relay we accept circuits from, or `None` for any relay.
+    relay_peer_id: Option<PeerId>,
+    /// The address the transport listens on.
+    listen_addr: Multiaddr,
+    /// Sends the relayed connections to the listener.
+    sender: mpsc::UnboundedSender<IncomingRelayedConnection>,
+}
+
+/// A circuit going through one or more of our connections.
+struct Circuit {
+    /// The peers whose handler keeps the connection alive for the circuit.
+    peers: SmallVec<[PeerId; 2]>,
+    /// Whether the circuit counts toward `RelayConfig::with_max_circuits`.
+    relayed: bool,
+    /// Resolves when the circuit is closed.
+    closed: Box<dyn Future<Item = (), Error = ()> + Send>,
+}
+
+impl<TSubstream> Relay<TSubstream> {
+    /// Creates a `Relay` behaviour paired with the `RelayTransport` that returned `transport`.
+    pub fn new(local_peer_id: PeerId, transport: RelayTransportReceiver, config: RelayConfig) -> Self {
+        Relay {
+            config,
+            local_peer_id,
+            from_transport: transport.inner,
+            connected_peers: HashMap::new(),
+            relay_addrs: HashMap::new(),
+            pending_dials: HashMap::new(),
+            outgoing_dials: HashMap::new(),
+            pending_hops: HashMap::new(),
+            listeners: Vec::new(),
+            circuits: Vec::new(),
+            num_relayed: 0,
+            events: VecDeque::new(),
+            next_request_id: 0,
+        }
+    }
+
+    /// Returns the number of circuits we currently relay for other peers.
+    pub fn num_relayed_circuits(&self) -> usize {
+        self.num_relayed
+    }
+
+    /// Allocates a new request identifier.
+    fn next_request_id(&mut self) -> RequestId {
+        let request_id = RequestId(self.next_request_id);
+        self.next_request_id += 1;
+        request_id
+    }
+
+    /// Sends a request to open a circuit to `dst_peer_id` to a relay we are connected to.
+    fn send_hop_request(&mut self, relay_peer_id: PeerId, dial: PendingDial) {
+        let request_id = self.next_request_id();
+        let request = OutgoingRelayRequest::Hop {
+            src_peer_id: self.local_peer_id.clone(),
+            dst_peer_id: dial.dst_peer_id,
+            dst_addrs: dial.dst_addr.into_iter().collect(),
+        };
+        self.outgoing_dials.insert(request_id, OutgoingDial {
+            relay_peer_id: relay_peer_id.clone(),
+            send_back: dial.send_back,
+        });
+        self.events.push_back(NetworkBehaviourAction::SendEvent {
+            peer_id: relay_peer_id,
+            event: RelayHandlerIn::OutgoingRequest { request_id, request },
+        });
+    }
+
+    /// Processes a message from the `RelayTransport`.
+    fn on_transport_msg(&mut self, msg: TransportToBehaviourMsg) {
+        match msg {
+            TransportToBehaviourMsg::Dial { relay_peer_id, relay_addr, dst_peer_id, dst_addr, send_back } => {
+                if let Some(addr) = relay_addr {
+                    let addrs = self.relay_addrs.entry(relay_peer_id.clone()).or_default();
+                    if !addrs.contains(&addr) {
+                        addrs.push(addr);
+                    }
+                }
+
+                let dial = PendingDial { dst_peer_id, dst_addr, send_back };
+                if self.connected_peers.contains_key(&relay_peer_id) {
+                    self.send_hop_request(relay_peer_id, dial);
+                } else {
+                    self.pending_dials.entry(relay_peer_id.clone()).or_default().push(dial);
+                    self.events.push_back(NetworkBehaviourAction::DialPeer { peer_id: relay_peer_id });
+                }
+            }
+            TransportToBehaviourMsg::Listen { relay, listen_addr, sender } => {
+                let relay_peer_id = relay.map(|(relay_peer_id, relay_addr)| {
+                    if let Some(addr) = relay_addr {
+                        let addrs = self.relay_addrs.entry(relay_peer_id.clone()).or_default();
+                        if !addrs.contains(&addr) {
+                            addrs.push(addr);
+                        }
+                    }
+
+                    if self.connected_peers.contains_key(&relay_peer_id) {
+                        self.events.push_back(NetworkBehaviourAction::SendEvent {
+                            peer_id: relay_peer_id.clone(),
+                            event: RelayHandlerIn::UsedForListening(true),
+                        });
+                    } else {
+                        self.events.push_back(NetworkBehaviourAction::DialPeer {
+                            peer_id: relay_peer_id.clone(),
+                        });
+                    }
+                    relay_peer_id
+                });
+
+                self.listeners.push(Listener { relay_peer_id, listen_addr, sender });
+            }
+        }
+    }
+
+    /// Processes a request from a remote to relay a circuit.
+    fn on_hop_request(&mut self, src_peer_id: PeerId, request: IncomingHopRequest<Negotiated<TSubstream>>)
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let dst_peer_id = request.dst_peer_id().clone();
+
+        let status = if !self.config.hop {
+            Some(RelayStatus::HOP_CANT_SPEAK_RELAY)
+        } else if dst_peer_id == self.local_peer_id {
+            Some(RelayStatus::HOP_CANT_RELAY_TO_SELF)
+        } else if !self.connected_peers.contains_key(&dst_peer_id) {
+            Some(RelayStatus::HOP_NO_CONN_TO_DST)
+        } else if self.num_relayed >= self.config.max_circuits {
+            Some(RelayStatus::HOP_CANT_SPEAK_RELAY)
+        } else {
+            None
+        };
+
+        if let Some(status) = status {
+            debug!("Denying circuit from {:?} to {:?}: {:?}", src_peer_id, dst_peer_id, status);
+            self.deny_hop(src_peer_id, dst_peer_id, request, status);
+            return;
+        }
+
+        let request_id = self.next_request_id();
+        let stop_request = OutgoingRelayRequest::Stop {
+            src_peer_id: src_peer_id.clone(),
+            src_addrs: request.src_addrs().to_vec(),
+            dst_peer_id: dst_peer_id.clone(),
+        };
+        self.num_relayed += 1;
+        self.pending_hops.insert(request_id, PendingHop {
+            src_peer_id,
+            dst_peer_id: dst_peer_id.clone(),
+            request,
+        });
+        self.events.push_back(NetworkBehaviourAction::SendEvent {
+            peer_id: dst_peer_id,
+            event: RelayHandlerIn::OutgoingRequest { request_id, request: stop_request },
+        });
+    }
+
+    /// Denies a request from `src_peer_id` to relay a circuit to `dst_peer_id`.
+    fn deny_hop(
+        &mut self,
+        src_peer_id: PeerId,
+        dst_peer_id: PeerId,
+        request: IncomingHopRequest<Negotiated<TSubstream>>,
+        status: RelayStatus,
+    )
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        self.circuits.push(Circuit {
+            peers: SmallVec::from_elem(src_peer_id.clone(), 1),
+            relayed: false,
+            closed: Box::new(request.deny(status).map_err(|_| ())),
+        });
+        self.events.push_back(NetworkBehaviourAction::GenerateEvent(RelayEvent::CircuitDenied {
+            src_peer_id,
+            dst_peer_id,
+        }));
+    }
+
+    /// Processes a request from a remote to know whether we relay circuits.
+    fn on_can_hop_request(&mut self, peer_id: PeerId, request: IncomingCanHopRequest<Negotiated<TSubstream>>)
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        self.circuits.push(Circuit {
+            peers: SmallVec::from_elem(peer_id, 1),
+            relayed: false,
+            closed: Box::new(request.answer(self.config.hop).map_err(|_| ())),
+        });
+    }
+
+    /// Processes a request from a relay to accept a circuit.
+    fn on_stop_request(&mut self, relay_peer_id: PeerId, request: IncomingStopRequest<Negotiated<TSubstream>>)
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        self.listeners.retain(|l| !l.sender.is_closed());
+        let listener = self.listeners.iter().find(|l| {
+            l.relay_peer_id.as_ref().map_or(true, |r| r == &relay_peer_id)
+        });
+
+        let peers = SmallVec::from_elem(relay_peer_id.clone(), 1);
+        let listener = match listener {
+            Some(listener) => listener,
+            None => {
+                debug!("Refusing circuit from {:?} relayed by {:?}: not listening",
+                    request.src_peer_id(), relay_peer_id);
+                self.circuits.push(Circuit {
+                    peers,
+                    relayed: false,
+                    closed: Box::new(request.deny(RelayStatus::STOP_RELAY_REFUSED).map_err(|_| ())),
+                });
+                return;
+            }
+        };
+
+        let mut remote_addr = self.connected_peers.get(&relay_peer_id)
+            .cloned()
+            .unwrap_or_else(Multiaddr::empty);
+        remote_addr.push(Protocol::P2p(relay_peer_id.into()));
+        remote_addr.push(Protocol::P2pCircuit);
+        remote_addr.push(Protocol::P2p(request.src_peer_id().clone().into()));
+
+        let sender = listener.sender.clone();
+        let closed = request.accept()
+            .map_err(|err| debug!("Failed to accept relayed connection: {:?}", err))
+            .and_then(move |substream| {
+                let (connection, closed) = Connection::new(substream);
+                sender.unbounded_send(IncomingRelayedConnection { connection, remote_addr })
+                    .map_err(|_| ())
+                    .into_future()
+                    .and_then(|()| closed.map_err(|_| ()))
+            });
+
+        self.circuits.push(Circuit {
+            peers,
+            relayed: false,
+            closed: Box::new(closed),
+        });
+    }
+
+    /// Processes the successful outcome of an outgoing request.
+    fn on_request_success(&mut self, peer_id: PeerId, request_id: RequestId, substream: Negotiated<TSubstream>)
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        // We opened a circuit through the relay `peer_id`.
+        if let Some(dial) = self.outgoing_dials.remove(&request_id) {
+            let (connection, closed) = Connection::new(substream);
+            // If the dialer is gone, the connection is dropped and the circuit closed.
+            let _ = dial.send_back.send(Ok(connection));
+            self.circuits.push(Circuit {
+                peers: SmallVec::from_elem(peer_id, 1),
+                relayed: false,
+                closed: Box::new(closed.map_err(|_| ())),
+            });
+            return;
+        }
+
+        // The destination `peer_id` accepted a circuit that we relay.
+        let hop = match self.pending_hops.remove(&request_id) {
+            Some(hop) => hop,
+            None => {
+                self.events.push_back(NetworkBehaviourAction::SendEvent {
+                    peer_id,
+                    event: RelayHandlerIn::CircuitClosed,
+                });
+                return;
+            }
+        };
+
+        let mut peers = SmallVec::new();
+        peers.push(hop.src_peer_id.clone());
+        peers.push(hop.dst_peer_id.clone());
+        let closed = hop.request.accept()
+            .and_then(move |src_substream| bridge(src_substream, substream).map_err(RelayError::Io))
+            .map_err(|err| debug!("Relayed circuit closed with an error: {:?}", err));
+        self.circuits.push(Circuit {
+            peers,
+            relayed: true,
+            closed: Box::new(closed),
+        });
+        self.events.push_back(NetworkBehaviourAction::GenerateEvent(RelayEvent::CircuitRelayed {
+            src_peer_id: hop.src_peer_id,
+            dst_peer_id: hop.dst_peer_id,
+        }));
+    }
+
+    /// Processes the failure of an outgoing request.
+    fn on_request_failure(&mut self, request_id: RequestId, error: RelayError)
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        if let Some(dial) = self.outgoing_dials.remove(&request_id) {
+            let _ = dial.send_back.send(Err(error));
+            return;
+        }
+
+        if let Some(hop) = self.pending_hops.remove(&request_id) {
+            debug!("Failed to open circuit to {:?}: {:?}", hop.dst_peer_id, error);
+            self.num_relayed -= 1;
+            self.deny_hop(hop.src_peer_id, hop.dst_peer_id, hop.request, RelayStatus::HOP_CANT_OPEN_DST_STREAM);
+        }
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for Relay<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type ProtocolsHandler = RelayHandler<TSubstream>;
+    type OutEvent = RelayEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        RelayHandler::new()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.relay_addrs.get(peer_id)
+            .map(|addrs| addrs.to_vec())
+            .unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        let addr = match endpoint {
+            ConnectedPoint::Dialer { address } => address,
+            ConnectedPoint::Listener { send_back_addr, .. } => send_back_addr,
+        };
+        self.connected_peers.insert(peer_id.clone(), addr);
+
+        if self.listeners.iter().any(|l| l.relay_peer_id.as_ref() == Some(&peer_id)) {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: RelayHandlerIn::UsedForListening(true),
+            });
+        }
+
+        if let Some(dials) = self.pending_dials.remove(&peer_id) {
+            for dial in dials {
+                self.send_hop_request(peer_id.clone(), dial);
+            }
+        }
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.connected_peers.remove(peer_id);
+
+        // The handler is gone along with the outcome of the requests sent to it.
+        let request_ids = self.outgoing_dials.iter()
+            .filter(|(_, dial)| &dial.relay_peer_id == peer_id)
+            .map(|(request_id, _)| *request_id)
+            .collect::<Vec<_>>();
+        for request_id in request_ids {
+            if let Some(dial) = self.outgoing_dials.remove(&request_id) {
+                let _ = dial.send_back.send(Err(RelayError::ConnectionClosed));
+            }
+        }
+
+        let request_ids = self.pending_hops.iter()
+            .filter(|(_, hop)| &hop.dst_peer_id == peer_id)
+            .map(|(request_id, _)| *request_id)
+            .collect::<Vec<_>>();
+        for request_id in request_ids {
+            self.on_request_failure(request_id, RelayError::ConnectionClosed);
+        }
+
+        // The source of a circuit being opened is gone, along with its substream. The destination
+        // is told that the circuit is closed once it answers.
+        let num_relayed = &mut self.num_relayed;
+        self.pending_hops.retain(|_, hop| {
+            if &hop.src_peer_id == peer_id {
+                *num_relayed -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        if let Some(dials) = self.pending_dials.remove(peer_id) {
+            for dial in dials {
+                let _ = dial.send_back.send(Err(RelayError::RelayUnreachable));
+            }
+        }
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: RelayHandlerEvent<TSubstream>) {
+        match event {
+            RelayHandlerEvent::IncomingRequest(RelayRequest::Hop(request)) => {
+                self.on_hop_request(peer_id, request)
+            }
+            RelayHandlerEvent::IncomingRequest(RelayRequest::Stop(request)) => {
+                self.on_stop_request(peer_id, request)
+            }
+            RelayHandlerEvent::IncomingRequest(RelayRequest::CanHop(request)) => {
+                self.on_can_hop_request(peer_id, request)
+            }
+            RelayHandlerEvent::OutgoingRequestSuccess { request_id, substream } => {
+                self.on_request_success(peer_id, request_id, substream)
+            }
+            RelayHandlerEvent::OutgoingRequestFailure { request_id, error } => {
+                debug!("Relay request to {:?} failed: {:?}", peer_id, error);
+                let error = match error {
+                    ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(err)) => err,
+                    _ => RelayError::ConnectionClosed,
+                };
+                self.on_request_failure(request_id, error)
+            }
+        }
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut impl PollParameters,
+    ) -> Async<NetworkBehaviourAction<RelayHandlerIn, Self::OutEvent>> {
+        while let Ok(Async::Ready(Some(msg))) = self.from_transport.poll() {
+            self.on_transport_msg(msg);
+        }
+
+        // Stop keeping the connections with relays alive for the listeners that are gone.
+        let events = &mut self.events;
+        let connected_peers = &self.connected_peers;
+        self.listeners.retain(|listener| {
+            if !listener.sender.is_closed() {
+                return true;
+            }
+            if let Some(relay_peer_id) = &listener.relay_peer_id {
+                if connected_peers.contains_key(relay_peer_id) {
+                    events.push_back(NetworkBehaviourAction::SendEvent {
+                        peer_id: relay_peer_id.clone(),
+                        event: RelayHandlerIn::UsedForListening(false),
+                    });
+                }
+            }
+            false
+        });
+
+        let mut n = 0;
+        while n < self.circuits.len() {
+            match self.circuits[n].closed.poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) | Err(()) => {
+                    let circuit = self.circuits.swap_remove(n);
+                    if circuit.relayed {
+                        self.num_relayed -= 1;
+                    }
+                    for peer_id in circuit.peers {
+                        self.events.push_back(NetworkBehaviourAction::SendEvent {
+                            peer_id,
+                            event: RelayHandlerIn::CircuitClosed,
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(event);
+        }
+
+        Async::NotReady
+    }
+}
+
+/// Forwards the data between two substreams until both directions are closed.
+fn bridge<A, B>(a: A, b: B) -> impl Future<Item = (), Error = std::io::Error>
+where
+    A: AsyncRead + AsyncWrite,
+    B: AsyncRead + AsyncWrite,
+{
+    let (a_read, a_write) = a.split();
+    let (b_read, b_write) = b.split();
+    let a_to_b = tokio_io::io::copy(a_read, b_write)
+        .and_then(|(_, _, b_write)| tokio_io::io::shutdown(b_write));
+    let b_to_a = tokio_io::io::copy(b_read, a_write)
+        .and_then(|(_, _, a_write)| tokio_io::io::shutdown(a_write));
+    a_to_b.join(b_to_a).map(|_| ())
+}
@@ -0,0 +1,234 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::protocol::{OutgoingRelayRequest, RelayError, RelayListen, RelayRequest};
+use futures::prelude::*;
+use libp2p_core::upgrade::{InboundUpgrade, Negotiated, OutboundUpgrade};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use smallvec::SmallVec;
+use std::{marker::PhantomData, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::Instant;
+
+/// Duration after which an idle connection is closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Protocol handler for the circuit relay protocol.
+///
+/// The handler opens and accepts the circuit relay substreams of a connection and hands them
+/// over to the `Relay` network behaviour. It keeps the connection alive as long as circuits go
+/// through it.
+pub struct RelayHandler<TSubstream> {
+    /// Requests waiting for a substream to be opened.
+    queued_requests: SmallVec<[(OutgoingRelayRequest, RequestId); 4]>,
+    /// Number of outbound substreams being opened.
+    pending_requests: usize,
+    /// Events waiting to be reported to the behaviour.
+    pending_events: SmallVec<[RelayHandlerEvent<TSubstream>; 4]>,
+    /// Number of circuits going through the connection that are still open.
+    circuits: usize,
+    /// Whether the connection is used to listen for relayed connections.
+    used_for_listening: bool,
+    /// Until when the connection is kept alive if there is no activity.
+    idle_keep_alive: KeepAlive,
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+/// Identifier of an outgoing request sent to a `RelayHandler`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(pub(crate) u64);
+
+/// Event sent from the `Relay` behaviour to a `RelayHandler`.
+#[derive(Debug)]
+pub enum RelayHandlerIn {
+    /// Opens a substream with the remote and sends the request on it.
+    OutgoingRequest {
+        /// Identifier reported back with the outcome of the request.
+        request_id: RequestId,
+        /// The request to send.
+        request: OutgoingRelayRequest,
+    },
+    /// One of the circuits going through the connection has been closed.
+    CircuitClosed,
+    /// Whether the connection is used to listen for relayed connections, in which case it is
+    /// kept alive.
+    UsedForListening(bool),
+}
+
+/// Event produced by a `RelayHandler`.
+pub enum RelayHandlerEvent<TSubstream> {
+    /// The remote sent us a request.
+    IncomingRequest(RelayRequest<Negotiated<TSubstream>>),
+    /// The remote accepted an outgoing request. The substream now carries the relayed data.
+    OutgoingRequestSuccess {
+        /// Identifier of the request.
+        request_id: RequestId,
+        /// The substream of the circuit.
+        substream: Negotiated<TSubstream>,
+    },
+    /// An outgoing request failed.
+    OutgoingRequestFailure {
+        /// Identifier of the request.
+        request_id: RequestId,
+        /// The error that happened.
+        error: ProtocolsHandlerUpgrErr<RelayError>,
+    },
+}
+
+impl<TSubstream> RelayHandler<TSubstream> {
+    /// Builds a new `RelayHandler`.
+    pub fn new() -> Self {
+        RelayHandler {
+            queued_requests: SmallVec::new(),
+            pending_requests: 0,
+            pending_events: SmallVec::new(),
+            circuits: 0,
+            used_for_listening: false,
+            idle_keep_alive: KeepAlive::Until(Instant::now() + IDLE_TIMEOUT),
+            marker: PhantomData,
+        }
+    }
+
+    /// Restarts the idle timeout of the connection.
+    fn reset_idle_timeout(&mut self) {
+        self.idle_keep_alive = KeepAlive::Until(Instant::now() + IDLE_TIMEOUT);
+    }
+}
+
+impl<TSubstream> Default for RelayHandler<TSubstream> {
+    fn default() -> Self {
+        RelayHandler::new()
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for RelayHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type InEvent = RelayHandlerIn;
+    type OutEvent = RelayHandlerEvent<TSubstream>;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = RelayListen;
+    type OutboundProtocol = OutgoingRelayRequest;
+    type OutboundOpenInfo = RequestId;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        SubstreamProtocol::new(RelayListen)
+    }
+
+    fn inject_fully_negotiated_inbound(
+        &mut self,
+        request: <Self::InboundProtocol as InboundUpgrade<TSubstream>>::Output
+    ) {
+        self.circuits += 1;
+        self.pending_events.push(RelayHandlerEvent::IncomingRequest(request));
+    }
+
+    fn inject_fully_negotiated_outbound(
+        &mut self,
+        substream: <Self::OutboundProtocol as OutboundUpgrade<TSubstream>>::Output,
+        request_id: Self::OutboundOpenInfo
+    ) {
+        self.pending_requests -= 1;
+        self.circuits += 1;
+        self.pending_events.push(RelayHandlerEvent::OutgoingRequestSuccess {
+            request_id,
+            substream,
+        });
+    }
+
+    fn inject_event(&mut self, event: Self::InEvent) {
+        match event {
+            RelayHandlerIn::OutgoingRequest { request_id, request } => {
+                self.queued_requests.push((request, request_id));
+            }
+            RelayHandlerIn::CircuitClosed => {
+                self.circuits = self.circuits.saturating_sub(1);
+                if self.circuits == 0 {
+                    self.reset_idle_timeout();
+                }
+            }
+            RelayHandlerIn::UsedForListening(used) => {
+                self.used_for_listening = used;
+                self.reset_idle_timeout();
+            }
+        }
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        request_id: Self::OutboundOpenInfo,
+        error: ProtocolsHandlerUpgrErr<
+            <Self::OutboundProtocol as OutboundUpgrade<Self::Substream>>::Error
+        >
+    ) {
+        self.pending_requests -= 1;
+        self.reset_idle_timeout();
+        self.pending_events.push(RelayHandlerEvent::OutgoingRequestFailure {
+            request_id,
+            error,
+        });
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        if self.used_for_listening
+            || self.circuits > 0
+            || self.pending_requests > 0
+            || !self.queued_requests.is_empty()
+        {
+            KeepAlive::Yes
+        } else {
+            self.idle_keep_alive
+        }
+    }
+
+    fn poll(
+        &mut self,
+    ) -> Poll<
+        ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent>,
+        Self::Error,
+    > {
+        if !self.pending_events.is_empty() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(
+                self.pending_events.remove(0),
+            )));
+        }
+
+        if !self.queued_requests.is_empty() {
+            let (request, request_id) = self.queued_requests.remove(0);
+            self.pending_requests += 1;
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+                protocol: SubstreamProtocol::new(request),
+                info: request_id,
+            }));
+        }
+
+        Ok(Async::NotReady)
+    }
+}
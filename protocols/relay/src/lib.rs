@@ -0,0 +1,55 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the circuit relay protocol (`/libp2p/circuit/relay/0.1.0`), see also the
+//! [spec](https://github.com/libp2p/specs/tree/master/relay).
+//!
+//! The protocol allows two nodes that cannot reach each other directly to communicate through a
+//! third node, the relay. The source opens a substream with the relay and asks it to relay a
+//! circuit to the destination (`HOP`). The relay then opens a substream with the destination,
+//! which accepts the circuit (`STOP`), and forwards the data between the two substreams.
+//!
+//! # Usage
+//!
+//! The `RelayTransport` wraps around another transport and handles the relayed addresses, of the
+//! form `<relay-addr>/p2p/<relay-id>/p2p-circuit/p2p/<dst-id>` when dialing and
+//! `[<relay-addr>/p2p/<relay-id>]/p2p-circuit` when listening. The substreams are opened and
+//! accepted by the `Relay` network behaviour, which must be part of the same `Swarm` as the
+//! transport: `RelayTransport::new` returns a `RelayTransportReceiver` that must be passed to
+//! `Relay::new`.
+//!
+//! The relayed connections are raw streams on which encryption and multiplexing are negotiated
+//! like for any other connection.
+//!
+//! > **Note**: The relay only relays circuits to the destinations it is already connected to.
+//! >           The connection of a listening node with its relay is kept alive for as long as it
+//! >           listens through that relay.
+
+pub mod protocol;
+
+mod behaviour;
+mod handler;
+mod message_proto;
+mod transport;
+
+pub use self::behaviour::{Relay, RelayConfig, RelayEvent};
+pub use self::handler::{RelayHandler, RelayHandlerEvent, RelayHandlerIn, RequestId};
+pub use self::protocol::{RelayError, RelayStatus};
+pub use self::transport::{Connection, RelayListener, RelayTransport, RelayTransportReceiver, RelayedDial};
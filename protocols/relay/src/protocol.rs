@@ -0,0 +1,559 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::message_proto::{CircuitRelay, CircuitRelay_Peer, CircuitRelay_Type};
+use futures::{future::{self, Either, Loop}, prelude::*};
+use libp2p_core::{
+    Multiaddr,
+    PeerId,
+    upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, Negotiated}
+};
+use protobuf::{Message as ProtobufMessage, ProtobufError, RepeatedField};
+use std::{convert::TryFrom, error, fmt, io, iter};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+pub use crate::message_proto::CircuitRelay_Status as RelayStatus;
+
+/// Maximum size of a circuit relay message, in bytes.
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Upgrade that accepts incoming circuit relay requests, whether we are asked to act as a relay
+/// (`HOP`) or to be the destination of a relayed circuit (`STOP`).
+#[derive(Debug, Clone, Default)]
+pub struct RelayListen;
+
+impl UpgradeInfo for RelayListen {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/libp2p/circuit/relay/0.1.0")
+    }
+}
+
+impl<C> InboundUpgrade<C> for RelayListen
+where
+    C: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = RelayRequest<Negotiated<C>>;
+    type Error = RelayError;
+    type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+    fn upgrade_inbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        let future = read_message(socket).and_then(|(socket, message)| {
+            match RelayRequest::from_message(socket, message) {
+                Ok(request) => Either::A(future::ok(request)),
+                Err((socket, status)) => Either::B(
+                    send_status(socket, status).and_then(|_| Err(RelayError::InvalidMessage))
+                ),
+            }
+        });
+
+        Box::new(future)
+    }
+}
+
+/// Request received from a remote on a circuit relay substream.
+pub enum RelayRequest<TSubstream> {
+    /// The remote asks us to relay a circuit to a destination.
+    Hop(IncomingHopRequest<TSubstream>),
+    /// A relay asks us to accept a circuit that it relays from a source.
+    Stop(IncomingStopRequest<TSubstream>),
+    /// The remote asks whether we relay circuits.
+    CanHop(IncomingCanHopRequest<TSubstream>),
+}
+
+impl<TSubstream> RelayRequest<TSubstream> {
+    /// Builds a request from a received message. On error, returns the status to send back to
+    /// the remote.
+    fn from_message(socket: TSubstream, message: CircuitRelay)
+        -> Result<Self, (TSubstream, RelayStatus)>
+    {
+        if !message.has_field_type() {
+            return Err((socket, RelayStatus::MALFORMED_MESSAGE));
+        }
+
+        match message.get_field_type() {
+            CircuitRelay_Type::HOP => {
+                let (dst_peer_id, dst_addrs) = match parse_peer(message.get_dstPeer()) {
+                    Some(peer) => peer,
+                    None => return Err((socket, RelayStatus::HOP_DST_MULTIADDR_INVALID)),
+                };
+                let src_addrs = parse_peer(message.get_srcPeer())
+                    .map(|(_, addrs)| addrs)
+                    .unwrap_or_default();
+                Ok(RelayRequest::Hop(IncomingHopRequest {
+                    socket,
+                    dst_peer_id,
+                    dst_addrs,
+                    src_addrs,
+                }))
+            }
+            CircuitRelay_Type::STOP => {
+                let (src_peer_id, src_addrs) = match parse_peer(message.get_srcPeer()) {
+                    Some(peer) => peer,
+                    None => return Err((socket, RelayStatus::STOP_SRC_MULTIADDR_INVALID)),
+                };
+                Ok(RelayRequest::Stop(IncomingStopRequest {
+                    socket,
+                    src_peer_id,
+                    src_addrs,
+                }))
+            }
+            CircuitRelay_Type::CAN_HOP => Ok(RelayRequest::CanHop(IncomingCanHopRequest { socket })),
+            CircuitRelay_Type::STATUS => Err((socket, RelayStatus::MALFORMED_MESSAGE)),
+        }
+    }
+}
+
+impl<TSubstream> fmt::Debug for RelayRequest<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayRequest::Hop(request) => f.debug_tuple("Hop").field(request).finish(),
+            RelayRequest::Stop(request) => f.debug_tuple("Stop").field(request).finish(),
+            RelayRequest::CanHop(request) => f.debug_tuple("CanHop").field(request).finish(),
+        }
+    }
+}
+
+/// Request from a remote for us to relay a circuit to a destination.
+///
+/// The request must be either accepted or denied, after which the substream is closed.
+pub struct IncomingHopRequest<TSubstream> {
+    socket: TSubstream,
+    dst_peer_id: PeerId,
+    dst_addrs: Vec<Multiaddr>,
+    src_addrs: Vec<Multiaddr>,
+}
+
+impl<TSubstream> IncomingHopRequest<TSubstream> {
+    /// Returns the peer the remote wants to reach.
+    pub fn dst_peer_id(&self) -> &PeerId {
+        &self.dst_peer_id
+    }
+
+    /// Returns the addresses of the destination, as provided by the remote.
+    pub fn dst_addrs(&self) -> &[Multiaddr] {
+        &self.dst_addrs
+    }
+
+    /// Returns the addresses of the remote, as provided by the remote itself.
+    pub fn src_addrs(&self) -> &[Multiaddr] {
+        &self.src_addrs
+    }
+}
+
+impl<TSubstream> IncomingHopRequest<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    /// Accepts the request.
+    ///
+    /// The returned future resolves to the substream with the source of the circuit once the
+    /// remote has been notified. It is then up to the caller to forward the data between this
+    /// substream and the one opened with the destination.
+    pub fn accept(self) -> impl Future<Item = TSubstream, Error = RelayError> {
+        send_status(self.socket, RelayStatus::SUCCESS)
+    }
+
+    /// Denies the request with the given status.
+    pub fn deny(self, status: RelayStatus) -> impl Future<Item = (), Error = RelayError> {
+        send_status(self.socket, status).and_then(close)
+    }
+}
+
+impl<TSubstream> fmt::Debug for IncomingHopRequest<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncomingHopRequest")
+            .field("dst_peer_id", &self.dst_peer_id)
+            .field("dst_addrs", &self.dst_addrs)
+            .field("src_addrs", &self.src_addrs)
+            .finish()
+    }
+}
+
+/// Request from a relay for us to accept a circuit from a source.
+///
+/// The request must be either accepted or denied.
+pub struct IncomingStopRequest<TSubstream> {
+    socket: TSubstream,
+    src_peer_id: PeerId,
+    src_addrs: Vec<Multiaddr>,
+}
+
+impl<TSubstream> IncomingStopRequest<TSubstream> {
+    /// Returns the peer at the other end of the circuit.
+    pub fn src_peer_id(&self) -> &PeerId {
+        &self.src_peer_id
+    }
+
+    /// Returns the addresses of the source, as provided by the relay.
+    pub fn src_addrs(&self) -> &[Multiaddr] {
+        &self.src_addrs
+    }
+}
+
+impl<TSubstream> IncomingStopRequest<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    /// Accepts the request.
+    ///
+    /// The returned future resolves to the substream, which from then on carries the data of
+    /// the relayed connection with the source.
+    pub fn accept(self) -> impl Future<Item = TSubstream, Error = RelayError> {
+        send_status(self.socket, RelayStatus::SUCCESS)
+    }
+
+    /// Denies the request with the given status.
+    pub fn deny(self, status: RelayStatus) -> impl Future<Item = (), Error = RelayError> {
+        send_status(self.socket, status).and_then(close)
+    }
+}
+
+impl<TSubstream> fmt::Debug for IncomingStopRequest<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncomingStopRequest")
+            .field("src_peer_id", &self.src_peer_id)
+            .field("src_addrs", &self.src_addrs)
+            .finish()
+    }
+}
+
+/// Request from a remote to know whether we relay circuits.
+///
+/// The request must be answered, after which the substream is closed.
+pub struct IncomingCanHopRequest<TSubstream> {
+    socket: TSubstream,
+}
+
+impl<TSubstream> IncomingCanHopRequest<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    /// Answers the request, with `SUCCESS` if we relay circuits and `HOP_CANT_SPEAK_RELAY`
+    /// otherwise.
+    pub fn answer(self, can_hop: bool) -> impl Future<Item = (), Error = RelayError> {
+        let status = if can_hop {
+            RelayStatus::SUCCESS
+        } else {
+            RelayStatus::HOP_CANT_SPEAK_RELAY
+        };
+        send_status(self.socket, status).and_then(close)
+    }
+}
+
+impl<TSubstream> fmt::Debug for IncomingCanHopRequest<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncomingCanHopRequest").finish()
+    }
+}
+
+/// Upgrade that sends a circuit relay request to the remote and waits for it to be accepted.
+///
+/// On success, the substream carries the data of the relayed connection.
+#[derive(Debug, Clone)]
+pub enum OutgoingRelayRequest {
+    /// Asks the remote to relay a circuit from us to a destination.
+    Hop {
+        /// Our own identity.
+        src_peer_id: PeerId,
+        /// The peer we want to reach through the remote.
+        dst_peer_id: PeerId,
+        /// Addresses of the destination known to us.
+        dst_addrs: Vec<Multiaddr>,
+    },
+    /// Asks the remote to accept a circuit that we relay from a source.
+    Stop {
+        /// The peer at the other end of the circuit.
+        src_peer_id: PeerId,
+        /// Addresses of the source, as provided by the source itself.
+        src_addrs: Vec<Multiaddr>,
+        /// The identity of the remote.
+        dst_peer_id: PeerId,
+    },
+}
+
+impl OutgoingRelayRequest {
+    /// Turns the request into the message to send to the remote.
+    fn into_message(self) -> CircuitRelay {
+        let mut message = CircuitRelay::new();
+        match self {
+            OutgoingRelayRequest::Hop { src_peer_id, dst_peer_id, dst_addrs } => {
+                message.set_field_type(CircuitRelay_Type::HOP);
+                message.set_srcPeer(peer_message(src_peer_id, Vec::new()));
+                message.set_dstPeer(peer_message(dst_peer_id, dst_addrs));
+            }
+            OutgoingRelayRequest::Stop { src_peer_id, src_addrs, dst_peer_id } => {
+                message.set_field_type(CircuitRelay_Type::STOP);
+                message.set_srcPeer(peer_message(src_peer_id, src_addrs));
+                message.set_dstPeer(peer_message(dst_peer_id, Vec::new()));
+            }
+        }
+        message
+    }
+}
+
+impl UpgradeInfo for OutgoingRelayRequest {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/libp2p/circuit/relay/0.1.0")
+    }
+}
+
+impl<C> OutboundUpgrade<C> for OutgoingRelayRequest
+where
+    C: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = Negotiated<C>;
+    type Error = RelayError;
+    type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+    fn upgrade_outbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        let future = write_message(socket, &self.into_message())
+            .and_then(read_message)
+            .and_then(|(socket, message)| {
+                if !message.has_field_type() || message.get_field_type() != CircuitRelay_Type::STATUS {
+                    return Err(RelayError::InvalidMessage);
+                }
+                match message.get_code() {
+                    RelayStatus::SUCCESS => Ok(socket),
+                    status => Err(RelayError::Status(status)),
+                }
+            });
+
+        Box::new(future)
+    }
+}
+
+/// Error that can happen while opening or accepting a relayed circuit.
+#[derive(Debug)]
+pub enum RelayError {
+    /// Error on the underlying substream.
+    Io(io::Error),
+    /// Failed to decode a message.
+    Protobuf(ProtobufError),
+    /// The remote sent a message over the maximum allowed size.
+    MessageTooLarge,
+    /// The remote sent a message that doesn't make sense at this point.
+    InvalidMessage,
+    /// The remote denied the request with the given status.
+    Status(RelayStatus),
+    /// The multiaddress is not a valid relayed address.
+    InvalidAddress,
+    /// The relay could not be reached.
+    RelayUnreachable,
+    /// The connection with the relay was closed before the request completed.
+    ConnectionClosed,
+    /// The `Relay` network behaviour associated with the transport is gone.
+    BehaviourGone,
+}
+
+impl From<io::Error> for RelayError {
+    fn from(err: io::Error) -> Self {
+        RelayError::Io(err)
+    }
+}
+
+impl From<ProtobufError> for RelayError {
+    fn from(err: ProtobufError) -> Self {
+        RelayError::Protobuf(err)
+    }
+}
+
+impl fmt::Display for RelayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayError::Io(err) => write!(f, "I/O error: {}", err),
+            RelayError::Protobuf(err) => write!(f, "Failed to decode message: {}", err),
+            RelayError::MessageTooLarge => write!(f, "Message over the maximum allowed size"),
+            RelayError::InvalidMessage => write!(f, "Unexpected or malformed message"),
+            RelayError::Status(status) => write!(f, "Request denied by the remote: {:?}", status),
+            RelayError::InvalidAddress => write!(f, "Invalid relayed address"),
+            RelayError::RelayUnreachable => write!(f, "Failed to reach the relay"),
+            RelayError::ConnectionClosed => write!(f, "Connection with the relay closed"),
+            RelayError::BehaviourGone => write!(f, "The relay behaviour is gone"),
+        }
+    }
+}
+
+impl error::Error for RelayError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RelayError::Io(err) => Some(err),
+            RelayError::Protobuf(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Builds the protobuf representation of a peer.
+fn peer_message(peer_id: PeerId, addrs: Vec<Multiaddr>) -> CircuitRelay_Peer {
+    let mut peer = CircuitRelay_Peer::new();
+    peer.set_id(peer_id.into_bytes());
+    peer.set_addrs(RepeatedField::from_vec(addrs.into_iter().map(|a| a.to_vec()).collect()));
+    peer
+}
+
+/// Parses the protobuf representation of a peer. Invalid addresses are ignored.
+fn parse_peer(peer: &CircuitRelay_Peer) -> Option<(PeerId, Vec<Multiaddr>)> {
+    let peer_id = PeerId::from_bytes(peer.get_id().to_vec()).ok()?;
+    let addrs = peer.get_addrs()
+        .iter()
+        .filter_map(|a| Multiaddr::try_from(a.clone()).ok())
+        .collect();
+    Some((peer_id, addrs))
+}
+
+/// Sends a `STATUS` message with the given code. Resolves to the substream.
+fn send_status<S>(socket: S, status: RelayStatus) -> impl Future<Item = S, Error = RelayError>
+where
+    S: AsyncWrite,
+{
+    let mut message = CircuitRelay::new();
+    message.set_field_type(CircuitRelay_Type::STATUS);
+    message.set_code(status);
+    write_message(socket, &message)
+}
+
+/// Closes the writing side of the substream.
+fn close<S>(socket: S) -> impl Future<Item = (), Error = RelayError>
+where
+    S: AsyncWrite,
+{
+    tokio_io::io::shutdown(socket).map(|_| ()).map_err(RelayError::Io)
+}
+
+/// Writes a length-prefixed message to the substream and flushes it.
+fn write_message<S>(socket: S, message: &CircuitRelay) -> impl Future<Item = S, Error = RelayError>
+where
+    S: AsyncWrite,
+{
+    let body = message
+        .write_to_bytes()
+        .expect("writing protobuf failed; should never happen");
+    let mut len_buf = unsigned_varint::encode::usize_buffer();
+    let mut bytes = unsigned_varint::encode::usize(body.len(), &mut len_buf).to_vec();
+    bytes.extend_from_slice(&body);
+
+    tokio_io::io::write_all(socket, bytes)
+        .and_then(|(socket, _)| tokio_io::io::flush(socket))
+        .map_err(RelayError::Io)
+}
+
+/// Reads a length-prefixed message from the substream.
+///
+/// Contrary to `upgrade::read_one`, this never reads past the end of the message, as the
+/// substream goes on carrying the data of the relayed connection afterwards.
+fn read_message<S>(socket: S) -> impl Future<Item = (S, CircuitRelay), Error = RelayError>
+where
+    S: AsyncRead,
+{
+    future::loop_fn((socket, Vec::with_capacity(10)), |(socket, mut len_buf)| {
+        tokio_io::io::read_exact(socket, [0; 1])
+            .map_err(RelayError::Io)
+            .and_then(move |(socket, byte)| {
+                len_buf.push(byte[0]);
+                if byte[0] & 0x80 == 0 {
+                    Ok(Loop::Break((socket, len_buf)))
+                } else if len_buf.len() >= 10 {
+                    Err(RelayError::InvalidMessage)
+                } else {
+                    Ok(Loop::Continue((socket, len_buf)))
+                }
+            })
+    })
+    .and_then(|(socket, len_buf)| {
+        let len = match unsigned_varint::decode::usize(&len_buf) {
+            Ok((len, _)) => len,
+            Err(_) => return Either::A(future::err(RelayError::InvalidMessage)),
+        };
+        if len > MAX_MESSAGE_SIZE {
+            return Either::A(future::err(RelayError::MessageTooLarge));
+        }
+        Either::B(tokio_io::io::read_exact(socket, vec![0; len]).map_err(RelayError::Io))
+    })
+    .and_then(|(socket, bytes)| {
+        let message = protobuf::parse_from_bytes::<CircuitRelay>(&bytes)?;
+        Ok((socket, message))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn message_round_trip() {
+        let src_peer_id = PeerId::random();
+        let dst_peer_id = PeerId::random();
+        let dst_addr: Multiaddr = "/ip4/1.2.3.4/tcp/5".parse().unwrap();
+        let request = OutgoingRelayRequest::Hop {
+            src_peer_id: src_peer_id.clone(),
+            dst_peer_id: dst_peer_id.clone(),
+            dst_addrs: vec![dst_addr.clone()],
+        };
+
+        let buf = write_message(Cursor::new(Vec::new()), &request.into_message())
+            .wait()
+            .unwrap()
+            .into_inner();
+
+        let (_, message) = read_message(Cursor::new(buf)).wait().unwrap();
+        match RelayRequest::from_message((), message) {
+            Ok(RelayRequest::Hop(request)) => {
+                assert_eq!(request.dst_peer_id(), &dst_peer_id);
+                assert_eq!(request.dst_addrs(), &[dst_addr][..]);
+            }
+            _ => panic!("Unexpected request"),
+        }
+    }
+
+    #[test]
+    fn can_hop_is_a_request() {
+        let mut message = CircuitRelay::new();
+        message.set_field_type(CircuitRelay_Type::CAN_HOP);
+        match RelayRequest::from_message((), message) {
+            Ok(RelayRequest::CanHop(_)) => {}
+            _ => panic!("Unexpected request"),
+        }
+    }
+
+    #[test]
+    fn read_message_stops_at_message_end() {
+        let mut message = CircuitRelay::new();
+        message.set_field_type(CircuitRelay_Type::STATUS);
+        message.set_code(RelayStatus::SUCCESS);
+
+        let mut buf = write_message(Cursor::new(Vec::new()), &message)
+            .wait()
+            .unwrap()
+            .into_inner();
+        let message_len = buf.len();
+        buf.extend_from_slice(b"relayed data");
+
+        let (socket, received) = read_message(Cursor::new(buf)).wait().unwrap();
+        assert_eq!(received.get_code(), RelayStatus::SUCCESS);
+        assert_eq!(socket.position() as usize, message_len);
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::protocol::RelayError;
+use futures::{future::{self, FutureResult}, prelude::*, sync::{mpsc, oneshot}};
+use libp2p_core::{
+    either::{EitherError, EitherFuture, EitherListenStream, EitherOutput},
+    multiaddr::{Multiaddr, Protocol},
+    transport::{ListenerEvent, Transport, TransportError},
+    PeerId
+};
+use std::{fmt, io};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Transport that dials and listens on relayed addresses, and forwards all other addresses to
+/// an inner transport.
+///
+/// Dialing `<relay-addr>/p2p/<relay-id>/p2p-circuit/p2p/<dst-id>` opens a substream with the
+/// relay and asks it to relay a circuit to the destination. Listening on
+/// `[<relay-addr>/p2p/<relay-id>]/p2p-circuit` accepts the circuits relayed to us, either by the
+/// given relay or by any relay.
+///
+/// The substreams are opened and accepted by the `Relay` network behaviour, which must be given
+/// the `RelayTransportReceiver` returned by `RelayTransport::new` and be part of the same `Swarm`.
+/// The relayed connections are raw streams: encryption and multiplexing are negotiated on top of
+/// them by the upgrades applied to the `RelayTransport`, like for any other connection.
+#[derive(Clone)]
+pub struct RelayTransport<TTransport> {
+    inner: TTransport,
+    to_behaviour: mpsc::UnboundedSender<TransportToBehaviourMsg>,
+}
+
+/// Receiving end of the channel between a `RelayTransport` and the `Relay` network behaviour.
+pub struct RelayTransportReceiver {
+    pub(crate) inner: mpsc::UnboundedReceiver<TransportToBehaviourMsg>,
+}
+
+impl fmt::Debug for RelayTransportReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RelayTransportReceiver").finish()
+    }
+}
+
+/// Message sent from a `RelayTransport` to the `Relay` network behaviour.
+pub(crate) enum TransportToBehaviourMsg {
+    /// Open a circuit to `dst_peer_id` through `relay_peer_id`.
+    Dial {
+        relay_peer_id: PeerId,
+        relay_addr: Option<Multiaddr>,
+        dst_peer_id: PeerId,
+        dst_addr: Option<Multiaddr>,
+        send_back: oneshot::Sender<Result<Connection, RelayError>>,
+    },
+    /// Accept the circuits relayed to us, by the given relay or by any relay.
+    Listen {
+        relay: Option<(PeerId, Option<Multiaddr>)>,
+        listen_addr: Multiaddr,
+        sender: mpsc::UnboundedSender<IncomingRelayedConnection>,
+    },
+}
+
+/// A relayed connection reported to a `RelayListener`.
+pub(crate) struct IncomingRelayedConnection {
+    pub(crate) connection: Connection,
+    /// The relayed address of the source.
+    pub(crate) remote_addr: Multiaddr,
+}
+
+impl<TTransport> RelayTransport<TTransport> {
+    /// Wraps around `inner`. The returned `RelayTransportReceiver` must be passed to `Relay::new`.
+    pub fn new(inner: TTransport) -> (Self, RelayTransportReceiver) {
+        let (to_behaviour, from_transport) = mpsc::unbounded();
+        let transport = RelayTransport { inner, to_behaviour };
+        (transport, RelayTransportReceiver { inner: from_transport })
+    }
+}
+
+impl<TTransport> Transport for RelayTransport<TTransport>
+where
+    TTransport: Transport,
+{
+    type Output = EitherOutput<TTransport::Output, Connection>;
+    type Error = EitherError<TTransport::Error, RelayError>;
+    type Listener = EitherListenStream<TTransport::Listener, RelayListener>;
+    type ListenerUpgrade = EitherFuture<TTransport::ListenerUpgrade, FutureResult<Connection, RelayError>>;
+    type Dial = EitherFuture<TTransport::Dial, RelayedDial>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let relay = match split_relayed_addr(&addr) {
+            None => {
+                return self.inner.listen_on(addr)
+                    .map(EitherListenStream::First)
+                    .map_err(|err| err.map(EitherError::A));
+            }
+            Some((relay_addr, dst_addr)) => {
+                if !dst_addr.iter().next().is_none() {
+                    return Err(TransportError::MultiaddrNotSupported(addr));
+                }
+                if relay_addr.iter().next().is_none() {
+                    None
+                } else {
+                    match split_peer_id(relay_addr) {
+                        Some((relay_peer_id, relay_addr)) => Some((relay_peer_id, relay_addr)),
+                        None => return Err(TransportError::Other(EitherError::B(RelayError::InvalidAddress))),
+                    }
+                }
+            }
+        };
+
+        let (sender, receiver) = mpsc::unbounded();
+        let msg = TransportToBehaviourMsg::Listen { relay, listen_addr: addr.clone(), sender };
+        if self.to_behaviour.unbounded_send(msg).is_err() {
+            return Err(TransportError::Other(EitherError::B(RelayError::BehaviourGone)));
+        }
+
+        Ok(EitherListenStream::Second(RelayListener {
+            listen_addr: addr,
+            reported_addr: false,
+            receiver,
+        }))
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let (relay_addr, dst_addr) = match split_relayed_addr(&addr) {
+            None => {
+                return self.inner.dial(addr)
+                    .map(EitherFuture::First)
+                    .map_err(|err| err.map(EitherError::A));
+            }
+            Some(split) => split,
+        };
+
+        let invalid_addr = || TransportError::Other(EitherError::B(RelayError::InvalidAddress));
+        let (relay_peer_id, relay_addr) = split_peer_id(relay_addr).ok_or_else(invalid_addr)?;
+        let (dst_peer_id, dst_addr) = split_peer_id(dst_addr).ok_or_else(invalid_addr)?;
+
+        let (send_back, receiver) = oneshot::channel();
+        let msg = TransportToBehaviourMsg::Dial {
+            relay_peer_id,
+            relay_addr,
+            dst_peer_id,
+            dst_addr,
+            send_back,
+        };
+        if self.to_behaviour.unbounded_send(msg).is_err() {
+            return Err(TransportError::Other(EitherError::B(RelayError::BehaviourGone)));
+        }
+
+        Ok(EitherFuture::Second(RelayedDial { receiver }))
+    }
+}
+
+/// Splits a relayed address around its `/p2p-circuit` component, into the address of the relay
+/// and the address of the destination. Returns `None` if the address is not relayed.
+fn split_relayed_addr(addr: &Multiaddr) -> Option<(Multiaddr, Multiaddr)> {
+    let pos = addr.iter().position(|p| p == Protocol::P2pCircuit)?;
+    let relay_addr = addr.iter().take(pos).collect();
+    let dst_addr = addr.iter().skip(pos + 1).collect();
+    Some((relay_addr, dst_addr))
+}
+
+/// Splits an address ending with `/p2p/<peer-id>` into the peer id and the rest of the address,
+/// if any.
+fn split_peer_id(mut addr: Multiaddr) -> Option<(PeerId, Option<Multiaddr>)> {
+    let peer_id = match addr.pop()? {
+        Protocol::P2p(hash) => PeerId::from_multihash(hash).ok()?,
+        _ => return None,
+    };
+    if addr.iter().next().is_none() {
+        Some((peer_id, None))
+    } else {
+        Some((peer_id, Some(addr)))
+    }
+}
+
+/// Future returned by `RelayTransport::dial` for relayed addresses.
+#[must_use = "futures do nothing unless polled"]
+pub struct RelayedDial {
+    receiver: oneshot::Receiver<Result<Connection, RelayError>>,
+}
+
+impl Future for RelayedDial {
+    type Item = Connection;
+    type Error = RelayError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.receiver.poll() {
+            Ok(Async::Ready(Ok(connection))) => Ok(Async::Ready(connection)),
+            Ok(Async::Ready(Err(err))) => Err(err),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(oneshot::Canceled) => Err(RelayError::BehaviourGone),
+        }
+    }
+}
+
+impl fmt::Debug for RelayedDial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RelayedDial").finish()
+    }
+}
+
+/// Listener returned by `RelayTransport::listen_on` for relayed addresses.
+pub struct RelayListener {
+    /// The address we are listening on.
+    listen_addr: Multiaddr,
+    /// Whether `listen_addr` has been reported as a new address yet.
+    reported_addr: bool,
+    /// Receives the relayed connections from the `Relay` behaviour.
+    receiver: mpsc::UnboundedReceiver<IncomingRelayedConnection>,
+}
+
+impl Stream for RelayListener {
+    type Item = ListenerEvent<FutureResult<Connection, RelayError>>;
+    type Error = RelayError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if !self.reported_addr {
+            self.reported_addr = true;
+            return Ok(Async::Ready(Some(ListenerEvent::NewAddress(self.listen_addr.clone()))));
+        }
+
+        match self.receiver.poll() {
+            Ok(Async::Ready(Some(incoming))) => Ok(Async::Ready(Some(ListenerEvent::Upgrade {
+                upgrade: future::ok(incoming.connection),
+                local_addr: self.listen_addr.clone(),
+                remote_addr: incoming.remote_addr,
+            }))),
+            Ok(Async::Ready(None)) | Err(()) => Ok(Async::Ready(None)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl fmt::Debug for RelayListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RelayListener")
+            .field("listen_addr", &self.listen_addr)
+            .finish()
+    }
+}
+
+/// A connection relayed through a third node.
+///
+/// The behaviour is notified when the connection is dropped, so that the underlying connection
+/// with the relay is no longer kept alive on its behalf.
+pub struct Connection {
+    stream: Box<dyn AsyncReadWrite + Send>,
+    /// Dropped together with the connection.
+    _closed: oneshot::Sender<()>,
+}
+
+/// Combination of `AsyncRead` and `AsyncWrite` usable as a trait object.
+trait AsyncReadWrite: AsyncRead + AsyncWrite {}
+
+impl<T: AsyncRead + AsyncWrite> AsyncReadWrite for T {}
+
+impl Connection {
+    /// Wraps a substream. The returned receiver is notified when the connection is dropped.
+    pub(crate) fn new<TSubstream>(stream: TSubstream) -> (Self, oneshot::Receiver<()>)
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (closed, closed_rx) = oneshot::channel();
+        let connection = Connection { stream: Box::new(stream), _closed: closed };
+        (connection, closed_rx)
+    }
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection").finish()
+    }
+}
+
+impl io::Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl AsyncRead for Connection {}
+
+impl io::Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.stream.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_relayed_addresses() {
+        let relay_peer_id = PeerId::random();
+        let dst_peer_id = PeerId::random();
+        let relay_addr: Multiaddr = "/ip4/1.2.3.4/tcp/5".parse().unwrap();
+
+        let addr = relay_addr.clone()
+            .with(Protocol::P2p(relay_peer_id.clone().into()))
+            .with(Protocol::P2pCircuit)
+            .with(Protocol::P2p(dst_peer_id.clone().into()));
+        let (relay, dst) = split_relayed_addr(&addr).unwrap();
+        assert_eq!(split_peer_id(relay), Some((relay_peer_id, Some(relay_addr))));
+        assert_eq!(split_peer_id(dst), Some((dst_peer_id, None)));
+
+        let addr: Multiaddr = "/p2p-circuit".parse().unwrap();
+        let (relay, dst) = split_relayed_addr(&addr).unwrap();
+        assert!(relay.iter().next().is_none());
+        assert!(dst.iter().next().is_none());
+
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/5".parse().unwrap();
+        assert!(split_relayed_addr(&addr).is_none());
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Integration tests for the circuit relay.
+
+use futures::{future, prelude::*};
+use libp2p_core::{
+    Multiaddr,
+    PeerId,
+    Transport,
+    identity,
+    multiaddr::Protocol,
+    muxing::StreamMuxerBox,
+    nodes::Substream,
+    transport::{MemoryTransport, boxed::Boxed},
+    upgrade,
+};
+use libp2p_relay::{Relay, RelayConfig, RelayEvent, RelayTransport};
+use libp2p_secio::SecioConfig;
+use libp2p_swarm::Swarm;
+use libp2p_yamux as yamux;
+use rand::random;
+use std::io;
+use tokio::runtime::current_thread;
+
+type TestSwarm = Swarm<
+    Boxed<(PeerId, StreamMuxerBox), io::Error>,
+    Relay<Substream<StreamMuxerBox>>
+>;
+
+fn build_node(config: RelayConfig) -> TestSwarm {
+    let local_key = identity::Keypair::generate_ed25519();
+    let local_id = local_key.public().into_peer_id();
+    let (transport, receiver) = RelayTransport::new(MemoryTransport::default());
+    let transport = transport
+        .with_upgrade(SecioConfig::new(local_key))
+        .and_then(move |out, endpoint| {
+            let peer_id = out.remote_key.into_peer_id();
+            let yamux = yamux::Config::default();
+            upgrade::apply(out.stream, yamux, endpoint)
+                .map(|muxer| (peer_id, StreamMuxerBox::new(muxer)))
+        })
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))
+        .boxed();
+
+    let behaviour = Relay::new(local_id.clone(), receiver, config);
+    Swarm::new(transport, behaviour, local_id)
+}
+
+#[test]
+fn relayed_circuit() {
+    let mut src = build_node(RelayConfig::new());
+    let mut relay = build_node(RelayConfig::new().with_hop(true));
+    let mut dst = build_node(RelayConfig::new());
+
+    let src_id = Swarm::local_peer_id(&src).clone();
+    let relay_id = Swarm::local_peer_id(&relay).clone();
+    let dst_id = Swarm::local_peer_id(&dst).clone();
+
+    let relay_addr: Multiaddr = Protocol::Memory(1 + random::<u64>()).into();
+    Swarm::listen_on(&mut relay, relay_addr.clone()).unwrap();
+
+    // The destination listens through the relay, which makes it connect to the relay.
+    let dst_listen_addr = relay_addr
+        .with(Protocol::P2p(relay_id.clone().into()))
+        .with(Protocol::P2pCircuit);
+    Swarm::listen_on(&mut dst, dst_listen_addr.clone()).unwrap();
+
+    let mut dialed = false;
+    let mut relayed = false;
+    let future = future::poll_fn(move || -> Result<_, io::Error> {
+        loop {
+            let mut progress = false;
+
+            match relay.poll()? {
+                Async::Ready(Some(RelayEvent::CircuitRelayed { src_peer_id, dst_peer_id })) => {
+                    assert_eq!(src_peer_id, src_id);
+                    assert_eq!(dst_peer_id, dst_id);
+                    relayed = true;
+                    progress = true;
+                }
+                Async::Ready(Some(RelayEvent::CircuitDenied { .. })) => panic!("Circuit denied"),
+                Async::Ready(None) => panic!("Relay swarm ended"),
+                Async::NotReady => {}
+            }
+
+            for swarm in &mut [&mut src, &mut dst] {
+                match swarm.poll()? {
+                    Async::Ready(Some(_)) => progress = true,
+                    Async::Ready(None) => panic!("Swarm ended"),
+                    Async::NotReady => {}
+                }
+            }
+
+            // Only dial once the destination is connected to the relay, as the relay doesn't
+            // relay circuits to peers it is not connected to.
+            if !dialed && Swarm::connection_info(&mut relay, &dst_id).is_some() {
+                let dial_addr = dst_listen_addr.clone().with(Protocol::P2p(dst_id.clone().into()));
+                Swarm::dial_addr(&mut src, dial_addr).unwrap();
+                dialed = true;
+                progress = true;
+            }
+
+            if relayed
+                && Swarm::connection_info(&mut src, &dst_id).is_some()
+                && Swarm::connection_info(&mut dst, &src_id).is_some()
+            {
+                return Ok(Async::Ready(()));
+            }
+
+            if !progress {
+                return Ok(Async::NotReady);
+            }
+        }
+    });
+
+    current_thread::run(future.map_err(|e| panic!("{:?}", e)));
+}
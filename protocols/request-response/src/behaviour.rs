@@ -0,0 +1,557 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::codec::RequestResponseCodec;
+use crate::handler::{RequestId, RequestResponseHandler, RequestResponseHandlerEvent};
+use crate::protocol::RequestProtocol;
+use futures::{prelude::*, sync::oneshot};
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use log::warn;
+use smallvec::SmallVec;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    error,
+    fmt,
+    io,
+    marker::PhantomData,
+    sync::{Arc, atomic::AtomicU64},
+    time::Duration
+};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Configuration for the `RequestResponse` network behaviour.
+#[derive(Debug, Clone)]
+pub struct RequestResponseConfig {
+    /// The timeout of a request, on both sides.
+    request_timeout: Duration,
+    /// How long to keep a connection alive once no request is in progress.
+    connection_keep_alive: Duration,
+}
+
+impl RequestResponseConfig {
+    /// Creates a new `RequestResponseConfig` with the following default settings:
+    ///
+    ///   * [`RequestResponseConfig::with_request_timeout`] 10s
+    ///   * [`RequestResponseConfig::with_connection_keep_alive`] 10s
+    pub fn new() -> Self {
+        RequestResponseConfig {
+            request_timeout: Duration::from_secs(10),
+            connection_keep_alive: Duration::from_secs(10),
+        }
+    }
+
+    /// Sets the timeout of a request.
+    ///
+    /// For an outbound request, the timeout starts when the request is sent with
+    /// `RequestResponse::send_request` and covers dialing the peer if necessary, the opening of
+    /// the substream, the sending of the request and the reception of the response. For an inbound request, it covers the
+    /// time it takes to provide and send the response.
+    pub fn with_request_timeout(mut self, d: Duration) -> Self {
+        self.request_timeout = d;
+        self
+    }
+
+    /// Sets how long a connection is kept alive once no request is in progress.
+    pub fn with_connection_keep_alive(mut self, d: Duration) -> Self {
+        self.connection_keep_alive = d;
+        self
+    }
+}
+
+impl Default for RequestResponseConfig {
+    fn default() -> Self {
+        RequestResponseConfig::new()
+    }
+}
+
+/// The directions in which a protocol is supported.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProtocolSupport {
+    /// We only answer requests on the protocol.
+    Inbound,
+    /// We only send requests on the protocol.
+    Outbound,
+    /// We send and answer requests on the protocol.
+    Full,
+}
+
+impl ProtocolSupport {
+    /// Returns whether we answer requests on the protocol.
+    pub fn inbound(&self) -> bool {
+        match self {
+            ProtocolSupport::Inbound | ProtocolSupport::Full => true,
+            ProtocolSupport::Outbound => false,
+        }
+    }
+
+    /// Returns whether we send requests on the protocol.
+    pub fn outbound(&self) -> bool {
+        match self {
+            ProtocolSupport::Outbound | ProtocolSupport::Full => true,
+            ProtocolSupport::Inbound => false,
+        }
+    }
+}
+
+/// Event produced by the `RequestResponse` network behaviour.
+#[derive(Debug)]
+pub enum RequestResponseEvent<TRequest, TResponse> {
+    /// We received a request or a response.
+    Message {
+        /// The peer the message comes from.
+        peer: PeerId,
+        /// The message.
+        message: RequestResponseMessage<TRequest, TResponse>,
+    },
+    /// One of our requests failed.
+    OutboundFailure {
+        /// The peer the request was sent to.
+        peer: PeerId,
+        /// Identifier of the request, as returned by `RequestResponse::send_request`.
+        request_id: RequestId,
+        /// The error that happened.
+        error: OutboundFailure,
+    },
+    /// We failed to answer a request.
+    InboundFailure {
+        /// The peer the request came from.
+        peer: PeerId,
+        /// Identifier of the request, as in the corresponding `ResponseChannel`.
+        request_id: RequestId,
+        /// The error that happened.
+        error: InboundFailure,
+    },
+}
+
+/// A request or a response received from a peer.
+#[derive(Debug)]
+pub enum RequestResponseMessage<TRequest, TResponse> {
+    /// A request, to be answered through `RequestResponse::send_response`.
+    Request {
+        /// The request.
+        request: TRequest,
+        /// The channel on which to send the response.
+        channel: ResponseChannel<TResponse>,
+    },
+    /// The response to one of our requests.
+    Response {
+        /// Identifier of the request, as returned by `RequestResponse::send_request`.
+        request_id: RequestId,
+        /// The response.
+        response: TResponse,
+    },
+}
+
+/// Reason why one of our requests failed.
+#[derive(Debug)]
+pub enum OutboundFailure {
+    /// We failed to connect to the peer.
+    DialFailure,
+    /// The request timed out before the response was received.
+    Timeout,
+    /// The connection closed before the response was received.
+    ConnectionClosed,
+    /// The peer doesn't support any of the protocols of the request.
+    UnsupportedProtocols,
+    /// An I/O error happened while sending the request or reading the response.
+    Io(io::Error),
+}
+
+impl fmt::Display for OutboundFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutboundFailure::DialFailure => f.write_str("Failed to dial the peer"),
+            OutboundFailure::Timeout => f.write_str("Timeout while waiting for a response"),
+            OutboundFailure::ConnectionClosed => f.write_str("Connection closed before a response was received"),
+            OutboundFailure::UnsupportedProtocols => f.write_str("The peer doesn't support the request protocols"),
+            OutboundFailure::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for OutboundFailure {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            OutboundFailure::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Reason why we failed to answer a request.
+#[derive(Debug)]
+pub enum InboundFailure {
+    /// The response wasn't provided and sent before the timeout.
+    Timeout,
+    /// The `ResponseChannel` was dropped without a response being sent.
+    ResponseOmission,
+    /// An I/O error happened while sending the response.
+    Io(io::Error),
+}
+
+impl fmt::Display for InboundFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InboundFailure::Timeout => f.write_str("Timeout while sending the response"),
+            InboundFailure::ResponseOmission => f.write_str("No response was provided"),
+            InboundFailure::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for InboundFailure {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            InboundFailure::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Channel on which to send the response to an inbound request.
+///
+/// Dropping the channel without sending a response produces an
+/// `InboundFailure::ResponseOmission`.
+#[derive(Debug)]
+pub struct ResponseChannel<TResponse> {
+    /// The peer that sent the request.
+    peer: PeerId,
+    /// Identifier of the request.
+    request_id: RequestId,
+    /// Sender to the handler of the connection the request came from.
+    sender: oneshot::Sender<TResponse>,
+}
+
+impl<TResponse> ResponseChannel<TResponse> {
+    /// Returns the peer that sent the request.
+    pub fn peer(&self) -> &PeerId {
+        &self.peer
+    }
+
+    /// Returns the identifier of the request.
+    pub fn request_id(&self) -> RequestId {
+        self.request_id
+    }
+
+    /// Returns `true` if a response can still be sent, that is if the request hasn't timed out
+    /// and the connection is still open.
+    pub fn is_open(&self) -> bool {
+        !self.sender.is_canceled()
+    }
+}
+
+/// Network behaviour that sends requests to peers and answers their requests, using a
+/// `RequestResponseCodec` to encode and decode the messages.
+///
+/// Requests sent to peers we are not connected to are queued while the peer is dialed, using
+/// the addresses registered with `RequestResponse::add_address`.
+pub struct RequestResponse<TCodec, TSubstream>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// The protocols we accept requests on.
+    inbound_protocols: SmallVec<[TCodec::Protocol; 2]>,
+    /// The protocols we send requests on, by order of preference.
+    outbound_protocols: SmallVec<[TCodec::Protocol; 2]>,
+    /// The codec, cloned for every request and connection.
+    codec: TCodec,
+    /// The configuration of the behaviour.
+    config: RequestResponseConfig,
+    /// The identifier of the next outbound request.
+    next_request_id: RequestId,
+    /// The identifier of the next inbound request, shared with the handlers.
+    next_inbound_id: Arc<AtomicU64>,
+    /// The peers we are connected to.
+    connected: HashSet<PeerId>,
+    /// The addresses of peers, used to dial them.
+    addresses: HashMap<PeerId, SmallVec<[Multiaddr; 6]>>,
+    /// Requests waiting for a connection to the peer.
+    pending_requests: HashMap<PeerId, SmallVec<[RequestProtocol<TCodec>; 10]>>,
+    /// Fires when the first request of `pending_requests` times out, if any.
+    pending_requests_timer: Option<Delay>,
+    /// Requests sent to a connected peer and waiting for a response.
+    pending_responses: HashMap<PeerId, HashSet<RequestId>>,
+    /// Events waiting to be returned by `poll`.
+    pending_events: VecDeque<NetworkBehaviourAction<
+        RequestProtocol<TCodec>,
+        RequestResponseEvent<TCodec::Request, TCodec::Response>
+    >>,
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+impl<TCodec, TSubstream> RequestResponse<TCodec, TSubstream>
+where
+    TCodec: RequestResponseCodec + Clone,
+{
+    /// Creates a new `RequestResponse` behaviour for the given protocols, by order of
+    /// preference.
+    pub fn new<I>(codec: TCodec, protocols: I, config: RequestResponseConfig) -> Self
+    where
+        I: IntoIterator<Item = (TCodec::Protocol, ProtocolSupport)>
+    {
+        let mut inbound_protocols = SmallVec::new();
+        let mut outbound_protocols = SmallVec::new();
+        for (protocol, support) in protocols {
+            if support.inbound() {
+                inbound_protocols.push(protocol.clone());
+            }
+            if support.outbound() {
+                outbound_protocols.push(protocol);
+            }
+        }
+
+        RequestResponse {
+            inbound_protocols,
+            outbound_protocols,
+            codec,
+            config,
+            next_request_id: RequestId(1),
+            next_inbound_id: Arc::new(AtomicU64::new(1)),
+            connected: HashSet::new(),
+            addresses: HashMap::new(),
+            pending_requests: HashMap::new(),
+            pending_requests_timer: None,
+            pending_responses: HashMap::new(),
+            pending_events: VecDeque::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Sends a request to a peer.
+    ///
+    /// If we are not connected to the peer, it is dialed and the request is sent once the
+    /// connection is established, unless the request times out first. The outcome of the
+    /// request is reported by a `RequestResponseEvent` carrying the returned identifier.
+    pub fn send_request(&mut self, peer: &PeerId, request: TCodec::Request) -> RequestId {
+        let request_id = self.next_request_id;
+        self.next_request_id = RequestId(request_id.0 + 1);
+
+        let request = RequestProtocol {
+            codec: self.codec.clone(),
+            protocols: self.outbound_protocols.clone(),
+            request_id,
+            request,
+            deadline: Instant::now() + self.config.request_timeout,
+        };
+
+        if self.connected.contains(peer) {
+            self.send_to_handler(peer.clone(), request);
+        } else {
+            if !self.pending_requests.contains_key(peer) {
+                self.pending_events.push_back(NetworkBehaviourAction::DialPeer {
+                    peer_id: peer.clone(),
+                });
+            }
+            if self.pending_requests_timer.is_none() {
+                self.pending_requests_timer = Some(Delay::new(request.deadline));
+            }
+            self.pending_requests.entry(peer.clone()).or_default().push(request);
+        }
+
+        request_id
+    }
+
+    /// Sends the response to an inbound request.
+    ///
+    /// Fails and hands the response back if the request timed out or the connection it came
+    /// from was closed. Otherwise, a failure to send the response is reported by an
+    /// `InboundFailure` event.
+    pub fn send_response(&mut self, channel: ResponseChannel<TCodec::Response>, response: TCodec::Response)
+        -> Result<(), TCodec::Response>
+    {
+        channel.sender.send(response)
+    }
+
+    /// Adds a known address of a peer, used to dial it when sending it a request.
+    pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        let addrs = self.addresses.entry(peer.clone()).or_default();
+        if !addrs.contains(&address) {
+            addrs.push(address);
+        }
+    }
+
+    /// Removes an address of a peer.
+    pub fn remove_address(&mut self, peer: &PeerId, address: &Multiaddr) {
+        if let Some(addrs) = self.addresses.get_mut(peer) {
+            addrs.retain(|a| a != address);
+            if addrs.is_empty() {
+                self.addresses.remove(peer);
+            }
+        }
+    }
+
+    /// Returns `true` if we are connected to the peer.
+    pub fn is_connected(&self, peer: &PeerId) -> bool {
+        self.connected.contains(peer)
+    }
+
+    /// Returns `true` if a request to the peer is in progress.
+    pub fn is_pending(&self, peer: &PeerId) -> bool {
+        self.pending_requests.contains_key(peer)
+            || self.pending_responses.get(peer).map_or(false, |ids| !ids.is_empty())
+    }
+
+    /// Hands a request over to the handler of the connection to the peer.
+    fn send_to_handler(&mut self, peer: PeerId, request: RequestProtocol<TCodec>) {
+        self.pending_responses.entry(peer.clone()).or_default().insert(request.request_id);
+        self.pending_events.push_back(NetworkBehaviourAction::SendEvent {
+            peer_id: peer,
+            event: request,
+        });
+    }
+
+    /// Fails the requests that timed out while waiting for a connection to the peer, and
+    /// schedules `pending_requests_timer` for the next one.
+    fn expire_pending_requests(&mut self) {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        for (peer, requests) in self.pending_requests.iter_mut() {
+            requests.retain(|request| {
+                if request.deadline <= now {
+                    expired.push((peer.clone(), request.request_id));
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        self.pending_requests.retain(|_, requests| !requests.is_empty());
+        for (peer, request_id) in expired {
+            self.outbound_failure(peer, request_id, OutboundFailure::Timeout);
+        }
+
+        self.pending_requests_timer = self.pending_requests.values()
+            .flat_map(|requests| requests.iter().map(|request| request.deadline))
+            .min()
+            .map(Delay::new);
+    }
+
+    /// Reports the failure of an outbound request.
+    fn outbound_failure(&mut self, peer: PeerId, request_id: RequestId, error: OutboundFailure) {
+        self.pending_events.push_back(NetworkBehaviourAction::GenerateEvent(
+            RequestResponseEvent::OutboundFailure { peer, request_id, error }
+        ));
+    }
+}
+
+impl<TCodec, TSubstream> NetworkBehaviour for RequestResponse<TCodec, TSubstream>
+where
+    TCodec: RequestResponseCodec + Clone + Send + 'static,
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type ProtocolsHandler = RequestResponseHandler<TCodec, TSubstream>;
+    type OutEvent = RequestResponseEvent<TCodec::Request, TCodec::Response>;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        RequestResponseHandler::new(
+            self.inbound_protocols.clone(),
+            self.codec.clone(),
+            self.config.connection_keep_alive,
+            self.config.request_timeout,
+            self.next_inbound_id.clone(),
+        )
+    }
+
+    fn addresses_of_peer(&mut self, peer: &PeerId) -> Vec<Multiaddr> {
+        self.addresses.get(peer).map_or_else(Vec::new, |addrs| addrs.to_vec())
+    }
+
+    fn inject_connected(&mut self, peer: PeerId, _: ConnectedPoint) {
+        self.connected.insert(peer.clone());
+        if let Some(requests) = self.pending_requests.remove(&peer) {
+            for request in requests {
+                self.send_to_handler(peer.clone(), request);
+            }
+        }
+    }
+
+    fn inject_disconnected(&mut self, peer: &PeerId, _: ConnectedPoint) {
+        self.connected.remove(peer);
+        if let Some(request_ids) = self.pending_responses.remove(peer) {
+            for request_id in request_ids {
+                self.outbound_failure(peer.clone(), request_id, OutboundFailure::ConnectionClosed);
+            }
+        }
+    }
+
+    fn inject_dial_failure(&mut self, peer: &PeerId) {
+        if let Some(requests) = self.pending_requests.remove(peer) {
+            for request in requests {
+                self.outbound_failure(peer.clone(), request.request_id, OutboundFailure::DialFailure);
+            }
+        }
+    }
+
+    fn inject_node_event(&mut self, peer: PeerId, event: RequestResponseHandlerEvent<TCodec>) {
+        match event {
+            RequestResponseHandlerEvent::Request { request_id, request, sender } => {
+                let channel = ResponseChannel { peer: peer.clone(), request_id, sender };
+                let message = RequestResponseMessage::Request { request, channel };
+                self.pending_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RequestResponseEvent::Message { peer, message }
+                ));
+            }
+            RequestResponseHandlerEvent::Response { request_id, response } => {
+                if let Some(ids) = self.pending_responses.get_mut(&peer) {
+                    ids.remove(&request_id);
+                }
+                let message = RequestResponseMessage::Response { request_id, response };
+                self.pending_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RequestResponseEvent::Message { peer, message }
+                ));
+            }
+            RequestResponseHandlerEvent::OutboundFailure { request_id, error } => {
+                if let Some(ids) = self.pending_responses.get_mut(&peer) {
+                    ids.remove(&request_id);
+                }
+                self.outbound_failure(peer, request_id, error);
+            }
+            RequestResponseHandlerEvent::InboundFailure { request_id, error } => {
+                self.pending_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RequestResponseEvent::InboundFailure { peer, request_id, error }
+                ));
+            }
+        }
+    }
+
+    fn poll(&mut self, _: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<RequestProtocol<TCodec>, Self::OutEvent>>
+    {
+        while let Some(timer) = self.pending_requests_timer.as_mut() {
+            match timer.poll() {
+                Ok(Async::Ready(())) => self.expire_pending_requests(),
+                Ok(Async::NotReady) => break,
+                Err(err) => {
+                    warn!("Request timer errored: {:?}", err);
+                    self.pending_requests_timer = None;
+                }
+            }
+        }
+
+        if let Some(event) = self.pending_events.pop_front() {
+            return Async::Ready(event);
+        }
+
+        Async::NotReady
+    }
+}
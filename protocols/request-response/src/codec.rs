@@ -0,0 +1,64 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::prelude::*;
+use libp2p_core::upgrade::ProtocolName;
+use std::io;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A `RequestResponseCodec` defines the request and response types of a `RequestResponse`
+/// protocol and how they are encoded and decoded on a substream.
+///
+/// Each method takes the substream by value and hands it back once done, so that the response
+/// can be exchanged on the same substream as the request. The requesting side shuts down its
+/// writing side after the request has been written, and the responding side does the same after
+/// the response has been written, so a codec may read until the end of the substream.
+pub trait RequestResponseCodec {
+    /// The type of protocol(s) or protocol versions being negotiated.
+    type Protocol: ProtocolName + Clone + Send + 'static;
+    /// The type of inbound and outbound requests.
+    type Request: Send + 'static;
+    /// The type of inbound and outbound responses.
+    type Response: Send + 'static;
+
+    /// Reads a request from a substream negotiated with `protocol`.
+    fn read_request<T>(&mut self, protocol: &Self::Protocol, io: T)
+        -> Box<dyn Future<Item = (Self::Request, T), Error = io::Error> + Send>
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static;
+
+    /// Reads a response from a substream negotiated with `protocol`.
+    fn read_response<T>(&mut self, protocol: &Self::Protocol, io: T)
+        -> Box<dyn Future<Item = (Self::Response, T), Error = io::Error> + Send>
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static;
+
+    /// Writes a request to a substream negotiated with `protocol`.
+    fn write_request<T>(&mut self, protocol: &Self::Protocol, io: T, request: Self::Request)
+        -> Box<dyn Future<Item = T, Error = io::Error> + Send>
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static;
+
+    /// Writes a response to a substream negotiated with `protocol`.
+    fn write_response<T>(&mut self, protocol: &Self::Protocol, io: T, response: Self::Response)
+        -> Box<dyn Future<Item = T, Error = io::Error> + Send>
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static;
+}
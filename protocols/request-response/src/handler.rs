@@ -0,0 +1,297 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{InboundFailure, OutboundFailure};
+use crate::codec::RequestResponseCodec;
+use crate::protocol::{RequestProtocol, ResponseProtocol};
+use futures::{future::Either, prelude::*, sync::oneshot};
+use libp2p_core::upgrade::{InboundUpgrade, NegotiationError, OutboundUpgrade, UpgradeError};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use smallvec::SmallVec;
+use std::{
+    collections::VecDeque,
+    io,
+    marker::PhantomData,
+    sync::{Arc, atomic::{AtomicU64, Ordering}},
+    time::Duration
+};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::{Delay, Instant};
+
+/// Identifier of a request, unique among the outbound requests of a `RequestResponse`
+/// behaviour, and among its inbound requests.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RequestId(pub(crate) u64);
+
+/// Protocol handler of the `RequestResponse` behaviour.
+///
+/// The handler sends the requests it receives from the behaviour on new outbound substreams,
+/// and reports the inbound requests along with a channel for the response. The connection is
+/// kept alive as long as requests are in progress, and for a configurable duration afterwards.
+pub struct RequestResponseHandler<TCodec, TSubstream>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// The protocols we accept requests on.
+    inbound_protocols: SmallVec<[TCodec::Protocol; 2]>,
+    /// The codec used to read requests and write responses.
+    codec: TCodec,
+    /// How long to keep the connection alive once no request is in progress.
+    keep_alive_timeout: Duration,
+    /// The timeout of a request, on both sides.
+    request_timeout: Duration,
+    /// The current keep-alive of the connection.
+    keep_alive: KeepAlive,
+    /// Events waiting to be reported to the behaviour.
+    pending_events: VecDeque<RequestResponseHandlerEvent<TCodec>>,
+    /// Outbound requests waiting for a substream to be opened.
+    outbound: VecDeque<RequestProtocol<TCodec>>,
+    /// Number of outbound requests whose substream is being opened or used.
+    pending_outbound: usize,
+    /// Inbound requests whose response is awaited or being sent.
+    inbound: Vec<(RequestId, Box<dyn Future<Item = (), Error = InboundFailure> + Send>)>,
+    /// Counter shared with the behaviour, used to assign identifiers to inbound requests.
+    inbound_request_id: Arc<AtomicU64>,
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+/// Event produced by a `RequestResponseHandler`.
+pub enum RequestResponseHandlerEvent<TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// The remote sent a request.
+    Request {
+        /// Identifier of the request.
+        request_id: RequestId,
+        /// The request.
+        request: TCodec::Request,
+        /// Channel on which to send the response.
+        sender: oneshot::Sender<TCodec::Response>,
+    },
+    /// The remote answered one of our requests.
+    Response {
+        /// Identifier of the request.
+        request_id: RequestId,
+        /// The response.
+        response: TCodec::Response,
+    },
+    /// One of our requests failed.
+    OutboundFailure {
+        /// Identifier of the request.
+        request_id: RequestId,
+        /// The error that happened.
+        error: OutboundFailure,
+    },
+    /// We failed to answer a request of the remote.
+    InboundFailure {
+        /// Identifier of the request.
+        request_id: RequestId,
+        /// The error that happened.
+        error: InboundFailure,
+    },
+}
+
+impl<TCodec, TSubstream> RequestResponseHandler<TCodec, TSubstream>
+where
+    TCodec: RequestResponseCodec,
+{
+    pub(crate) fn new(
+        inbound_protocols: SmallVec<[TCodec::Protocol; 2]>,
+        codec: TCodec,
+        keep_alive_timeout: Duration,
+        request_timeout: Duration,
+        inbound_request_id: Arc<AtomicU64>
+    ) -> Self {
+        RequestResponseHandler {
+            inbound_protocols,
+            codec,
+            keep_alive_timeout,
+            request_timeout,
+            keep_alive: KeepAlive::Until(Instant::now() + keep_alive_timeout),
+            pending_events: VecDeque::new(),
+            outbound: VecDeque::new(),
+            pending_outbound: 0,
+            inbound: Vec::new(),
+            inbound_request_id,
+            marker: PhantomData,
+        }
+    }
+
+    /// Updates the keep-alive of the connection depending on the requests in progress.
+    fn update_keep_alive(&mut self) {
+        if !self.outbound.is_empty() || self.pending_outbound > 0 || !self.inbound.is_empty() {
+            self.keep_alive = KeepAlive::Yes;
+        } else if self.keep_alive.is_yes() {
+            self.keep_alive = KeepAlive::Until(Instant::now() + self.keep_alive_timeout);
+        }
+    }
+}
+
+impl<TCodec, TSubstream> ProtocolsHandler for RequestResponseHandler<TCodec, TSubstream>
+where
+    TCodec: RequestResponseCodec + Clone + Send + 'static,
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type InEvent = RequestProtocol<TCodec>;
+    type OutEvent = RequestResponseHandlerEvent<TCodec>;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = ResponseProtocol<TCodec>;
+    type OutboundProtocol = RequestProtocol<TCodec>;
+    type OutboundOpenInfo = RequestId;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        let protocol = ResponseProtocol {
+            codec: self.codec.clone(),
+            protocols: self.inbound_protocols.clone(),
+        };
+        SubstreamProtocol::new(protocol).with_timeout(self.request_timeout)
+    }
+
+    fn inject_fully_negotiated_inbound(
+        &mut self,
+        (protocol, request, io): <Self::InboundProtocol as InboundUpgrade<TSubstream>>::Output
+    ) {
+        let request_id = RequestId(self.inbound_request_id.fetch_add(1, Ordering::Relaxed));
+        let (sender, receiver) = oneshot::channel();
+
+        // Wait for the response, then send it, within the request timeout.
+        let mut codec = self.codec.clone();
+        let response = receiver
+            .map_err(|_| InboundFailure::ResponseOmission)
+            .and_then(move |response| {
+                codec.write_response(&protocol, io, response)
+                    .and_then(tokio_io::io::shutdown)
+                    .map(|_| ())
+                    .map_err(InboundFailure::Io)
+            })
+            .select2(Delay::new(Instant::now() + self.request_timeout))
+            .then(|result| match result {
+                Ok(Either::A(((), _))) => Ok(()),
+                Err(Either::A((error, _))) => Err(error),
+                Ok(Either::B(_)) | Err(Either::B(_)) => Err(InboundFailure::Timeout),
+            });
+
+        self.inbound.push((request_id, Box::new(response)));
+        self.pending_events.push_back(RequestResponseHandlerEvent::Request {
+            request_id,
+            request,
+            sender,
+        });
+    }
+
+    fn inject_fully_negotiated_outbound(
+        &mut self,
+        response: <Self::OutboundProtocol as OutboundUpgrade<TSubstream>>::Output,
+        request_id: Self::OutboundOpenInfo
+    ) {
+        self.pending_outbound -= 1;
+        self.pending_events.push_back(RequestResponseHandlerEvent::Response {
+            request_id,
+            response,
+        });
+    }
+
+    fn inject_event(&mut self, request: Self::InEvent) {
+        self.outbound.push_back(request);
+        self.update_keep_alive();
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        request_id: Self::OutboundOpenInfo,
+        error: ProtocolsHandlerUpgrErr<io::Error>
+    ) {
+        self.pending_outbound -= 1;
+        let error = match error {
+            ProtocolsHandlerUpgrErr::Timeout | ProtocolsHandlerUpgrErr::Timer => {
+                OutboundFailure::Timeout
+            }
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(NegotiationError::Failed)) => {
+                OutboundFailure::UnsupportedProtocols
+            }
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(NegotiationError::ProtocolError(e))) => {
+                OutboundFailure::Io(e.into())
+            }
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(e)) => OutboundFailure::Io(e),
+        };
+        self.pending_events.push_back(RequestResponseHandlerEvent::OutboundFailure {
+            request_id,
+            error,
+        });
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    fn poll(
+        &mut self,
+    ) -> Poll<
+        ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent>,
+        Self::Error,
+    > {
+        let mut n = 0;
+        while n < self.inbound.len() {
+            match self.inbound[n].1.poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) => {
+                    self.inbound.swap_remove(n);
+                }
+                Err(error) => {
+                    let (request_id, _) = self.inbound.swap_remove(n);
+                    self.pending_events.push_back(RequestResponseHandlerEvent::InboundFailure {
+                        request_id,
+                        error,
+                    });
+                }
+            }
+        }
+
+        if let Some(event) = self.pending_events.pop_front() {
+            self.update_keep_alive();
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(event)));
+        }
+
+        if let Some(request) = self.outbound.pop_front() {
+            self.pending_outbound += 1;
+            let request_id = request.request_id;
+            // The request may already have waited for the connection to be established.
+            let now = Instant::now();
+            let timeout = if request.deadline > now { request.deadline - now } else { Duration::from_secs(0) };
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+                protocol: SubstreamProtocol::new(request).with_timeout(timeout),
+                info: request_id,
+            }));
+        }
+
+        self.update_keep_alive();
+        Ok(Async::NotReady)
+    }
+}
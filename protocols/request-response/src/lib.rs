@@ -0,0 +1,60 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Generic request/response protocols.
+//!
+//! Many protocols consist of a peer sending a request on a new substream and the remote
+//! answering with a response on that same substream. The `RequestResponse` network behaviour
+//! implements this pattern once, so that such a protocol only needs to provide the types of its
+//! messages and their encoding, through the `RequestResponseCodec` trait.
+//!
+//! # Usage
+//!
+//! Requests are sent with `RequestResponse::send_request`, which returns a `RequestId`. The
+//! response, or the failure of the request, is reported by a `RequestResponseEvent` carrying
+//! that identifier. Requests to peers we are not connected to are sent once they have been
+//! dialed.
+//!
+//! Inbound requests are reported along with a `ResponseChannel`, to be passed to
+//! `RequestResponse::send_response` along with the response. The response must be sent before
+//! the request timeout of the `RequestResponseConfig` elapses.
+//!
+//! A single behaviour can support several protocols, for instance several versions of the same
+//! protocol, each in one or both directions, as described by `ProtocolSupport`.
+
+pub mod codec;
+
+mod behaviour;
+mod handler;
+mod protocol;
+
+pub use self::behaviour::{
+    InboundFailure,
+    OutboundFailure,
+    ProtocolSupport,
+    RequestResponse,
+    RequestResponseConfig,
+    RequestResponseEvent,
+    RequestResponseMessage,
+    ResponseChannel
+};
+pub use self::codec::RequestResponseCodec;
+pub use self::handler::{RequestId, RequestResponseHandler, RequestResponseHandlerEvent};
+pub use self::protocol::{RequestProtocol, ResponseProtocol};
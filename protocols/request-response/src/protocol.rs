@@ -0,0 +1,121 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The upgrades used to exchange a request and its response on a substream.
+//!
+//! The requesting side writes the request and reads the response in a single outbound upgrade.
+//! The responding side only reads the request in its inbound upgrade; the substream is then
+//! kept by the handler until the response is provided.
+
+use crate::codec::RequestResponseCodec;
+use crate::handler::RequestId;
+use futures::prelude::*;
+use libp2p_core::upgrade::{InboundUpgrade, Negotiated, OutboundUpgrade, UpgradeInfo};
+use smallvec::SmallVec;
+use std::io;
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::Instant;
+
+/// Inbound upgrade that reads a request.
+pub struct ResponseProtocol<TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// The codec used to read the request.
+    pub(crate) codec: TCodec,
+    /// The protocols we accept requests on.
+    pub(crate) protocols: SmallVec<[TCodec::Protocol; 2]>,
+}
+
+impl<TCodec> UpgradeInfo for ResponseProtocol<TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    type Info = TCodec::Protocol;
+    type InfoIter = smallvec::IntoIter<[Self::Info; 2]>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.protocols.clone().into_iter()
+    }
+}
+
+impl<TCodec, TSubstream> InboundUpgrade<TSubstream> for ResponseProtocol<TCodec>
+where
+    TCodec: RequestResponseCodec,
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// The request, along with the protocol and the substream to send the response on.
+    type Output = (TCodec::Protocol, TCodec::Request, Negotiated<TSubstream>);
+    type Error = io::Error;
+    type Future = Box<dyn Future<Item = Self::Output, Error = io::Error> + Send>;
+
+    fn upgrade_inbound(mut self, io: Negotiated<TSubstream>, protocol: Self::Info) -> Self::Future {
+        let read = self.codec.read_request(&protocol, io);
+        Box::new(read.map(move |(request, io)| (protocol, request, io)))
+    }
+}
+
+/// Outbound upgrade that sends a request and reads the response.
+pub struct RequestProtocol<TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// The codec used to write the request and read the response.
+    pub(crate) codec: TCodec,
+    /// The protocols to negotiate, by order of preference.
+    pub(crate) protocols: SmallVec<[TCodec::Protocol; 2]>,
+    /// The identifier of the request.
+    pub(crate) request_id: RequestId,
+    /// The request to send.
+    pub(crate) request: TCodec::Request,
+    /// When the request times out, counting from the call to `RequestResponse::send_request`.
+    pub(crate) deadline: Instant,
+}
+
+impl<TCodec> UpgradeInfo for RequestProtocol<TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    type Info = TCodec::Protocol;
+    type InfoIter = smallvec::IntoIter<[Self::Info; 2]>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.protocols.clone().into_iter()
+    }
+}
+
+impl<TCodec, TSubstream> OutboundUpgrade<TSubstream> for RequestProtocol<TCodec>
+where
+    TCodec: RequestResponseCodec + Send + 'static,
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = TCodec::Response;
+    type Error = io::Error;
+    type Future = Box<dyn Future<Item = Self::Output, Error = io::Error> + Send>;
+
+    fn upgrade_outbound(self, io: Negotiated<TSubstream>, protocol: Self::Info) -> Self::Future {
+        let RequestProtocol { mut codec, request, .. } = self;
+        let future = codec.write_request(&protocol, io, request)
+            .and_then(tokio_io::io::shutdown)
+            .and_then(move |io| codec.read_response(&protocol, io))
+            .map(|(response, _)| response);
+        Box::new(future)
+    }
+}
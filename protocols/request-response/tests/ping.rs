@@ -0,0 +1,283 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Integration tests for the `RequestResponse` network behaviour.
+
+use futures::{future, prelude::*};
+use libp2p_core::{
+    Multiaddr,
+    PeerId,
+    identity,
+    muxing::StreamMuxer,
+    upgrade::{self, OutboundUpgradeExt, InboundUpgradeExt, ProtocolName},
+    transport::Transport
+};
+use libp2p_request_response::*;
+use libp2p_secio::SecioConfig;
+use libp2p_swarm::Swarm;
+use libp2p_tcp::TcpConfig;
+use libp2p_yamux as yamux;
+use std::{fmt, io, iter, sync::mpsc::sync_channel, time::{Duration, Instant}};
+use tokio::runtime::Runtime;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+#[derive(Debug, Clone)]
+struct PingProtocol;
+
+impl ProtocolName for PingProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        b"/ping/1"
+    }
+}
+
+/// A codec sending the request and the response as is, up to the end of the substream.
+#[derive(Clone)]
+struct PingCodec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ping(Vec<u8>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pong(Vec<u8>);
+
+impl RequestResponseCodec for PingCodec {
+    type Protocol = PingProtocol;
+    type Request = Ping;
+    type Response = Pong;
+
+    fn read_request<T>(&mut self, _: &PingProtocol, io: T)
+        -> Box<dyn Future<Item = (Ping, T), Error = io::Error> + Send>
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static
+    {
+        Box::new(tokio_io::io::read_to_end(io, Vec::new()).map(|(io, data)| (Ping(data), io)))
+    }
+
+    fn read_response<T>(&mut self, _: &PingProtocol, io: T)
+        -> Box<dyn Future<Item = (Pong, T), Error = io::Error> + Send>
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static
+    {
+        Box::new(tokio_io::io::read_to_end(io, Vec::new()).map(|(io, data)| (Pong(data), io)))
+    }
+
+    fn write_request<T>(&mut self, _: &PingProtocol, io: T, Ping(data): Ping)
+        -> Box<dyn Future<Item = T, Error = io::Error> + Send>
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static
+    {
+        Box::new(tokio_io::io::write_all(io, data).map(|(io, _)| io))
+    }
+
+    fn write_response<T>(&mut self, _: &PingProtocol, io: T, Pong(data): Pong)
+        -> Box<dyn Future<Item = T, Error = io::Error> + Send>
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static
+    {
+        Box::new(tokio_io::io::write_all(io, data).map(|(io, _)| io))
+    }
+}
+
+fn mk_behaviour<TSubstream>() -> RequestResponse<PingCodec, TSubstream> {
+    let protocols = iter::once((PingProtocol, ProtocolSupport::Full));
+    RequestResponse::new(PingCodec, protocols, RequestResponseConfig::new())
+}
+
+#[test]
+fn ping_protocol() {
+    let ping = Ping(b"ping".to_vec());
+    let pong = Pong(b"pong".to_vec());
+
+    let (peer1_id, trans) = mk_transport();
+    let mut swarm1 = Swarm::new(trans, mk_behaviour(), peer1_id.clone());
+
+    let (peer2_id, trans) = mk_transport();
+    let mut swarm2 = Swarm::new(trans, mk_behaviour(), peer2_id.clone());
+
+    let (tx, rx) = sync_channel::<Multiaddr>(1);
+
+    let addr = "/ip4/127.0.0.1/tcp/0".parse().unwrap();
+    Swarm::listen_on(&mut swarm1, addr).unwrap();
+
+    let expected_ping = ping.clone();
+    let expected_pong = pong.clone();
+    let mut listening = false;
+    let peer1 = future::poll_fn(move || -> Result<_, ()> {
+        loop {
+            match swarm1.poll().expect("Error while polling swarm") {
+                Async::Ready(Some(RequestResponseEvent::Message { peer, message })) => {
+                    assert_eq!(peer, peer2_id);
+                    match message {
+                        RequestResponseMessage::Request { request, channel } => {
+                            assert_eq!(request, expected_ping);
+                            assert_eq!(channel.peer(), &peer2_id);
+                            swarm1.send_response(channel, expected_pong.clone()).unwrap();
+                        }
+                        RequestResponseMessage::Response { .. } => panic!("Unexpected response"),
+                    }
+                }
+                Async::Ready(Some(e)) => panic!("Peer1: Unexpected event: {:?}", e),
+                Async::Ready(None) => return Ok(Async::Ready(())),
+                Async::NotReady => {
+                    if !listening {
+                        for l in Swarm::listeners(&swarm1) {
+                            tx.send(l.clone()).unwrap();
+                            listening = true;
+                        }
+                    }
+                    return Ok(Async::NotReady)
+                }
+            }
+        }
+    });
+
+    let num_pings = 5;
+    let mut count = 0;
+    let mut request_id = None;
+    let peer2 = future::poll_fn(move || -> Result<_, ()> {
+        if request_id.is_none() {
+            // The request is queued until the connection is established.
+            swarm2.add_address(&peer1_id, rx.recv().unwrap());
+            request_id = Some(swarm2.send_request(&peer1_id, ping.clone()));
+            assert!(swarm2.is_pending(&peer1_id));
+        }
+        loop {
+            match swarm2.poll().expect("Error while polling swarm") {
+                Async::Ready(Some(RequestResponseEvent::Message { peer, message })) => {
+                    assert_eq!(peer, peer1_id);
+                    match message {
+                        RequestResponseMessage::Response { request_id: id, response } => {
+                            assert_eq!(Some(id), request_id);
+                            assert_eq!(response, pong);
+                            count += 1;
+                            if count >= num_pings {
+                                return Ok(Async::Ready(()))
+                            }
+                            request_id = Some(swarm2.send_request(&peer1_id, ping.clone()));
+                        }
+                        RequestResponseMessage::Request { .. } => panic!("Unexpected request"),
+                    }
+                }
+                Async::Ready(Some(e)) => panic!("Peer2: Unexpected event: {:?}", e),
+                Async::Ready(None) => return Ok(Async::Ready(())),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    });
+
+    let result = peer1.select(peer2).map_err(|_| panic!());
+    Runtime::new().unwrap().block_on(result).unwrap();
+}
+
+#[test]
+fn dial_failure_is_reported() {
+    let (peer_id, trans) = mk_transport();
+    let mut swarm = Swarm::new(trans, mk_behaviour(), peer_id);
+
+    // We know no address of the peer, so dialing fails.
+    let unknown = PeerId::random();
+    let expected_id = swarm.send_request(&unknown, Ping(b"ping".to_vec()));
+
+    let test = future::poll_fn(move || -> Result<_, ()> {
+        loop {
+            match swarm.poll().expect("Error while polling swarm") {
+                Async::Ready(Some(RequestResponseEvent::OutboundFailure { peer, request_id, error })) => {
+                    assert_eq!(peer, unknown);
+                    assert_eq!(request_id, expected_id);
+                    match error {
+                        OutboundFailure::DialFailure => return Ok(Async::Ready(())),
+                        e => panic!("Unexpected failure: {:?}", e),
+                    }
+                }
+                Async::Ready(Some(e)) => panic!("Unexpected event: {:?}", e),
+                Async::Ready(None) => panic!("Swarm ended"),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    });
+
+    Runtime::new().unwrap().block_on(test).unwrap();
+}
+
+#[test]
+fn timeout_covers_dialing() {
+    // The peer accepts TCP connections but never answers the security handshake, so the
+    // connection is never established and the request must time out while dialing.
+    let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", silent.local_addr().unwrap().port())
+        .parse()
+        .unwrap();
+
+    let (peer_id, trans) = mk_transport();
+    let protocols = iter::once((PingProtocol, ProtocolSupport::Full));
+    let config = RequestResponseConfig::new().with_request_timeout(Duration::from_millis(500));
+    let mut swarm = Swarm::new(trans, RequestResponse::new(PingCodec, protocols, config), peer_id);
+
+    let unresponsive = PeerId::random();
+    swarm.add_address(&unresponsive, addr);
+    let expected_id = swarm.send_request(&unresponsive, Ping(b"ping".to_vec()));
+    let start = Instant::now();
+
+    let test = future::poll_fn(move || -> Result<_, ()> {
+        loop {
+            match swarm.poll().expect("Error while polling swarm") {
+                Async::Ready(Some(RequestResponseEvent::OutboundFailure { peer, request_id, error })) => {
+                    assert_eq!(peer, unresponsive);
+                    assert_eq!(request_id, expected_id);
+                    assert!(!swarm.is_pending(&unresponsive));
+                    match error {
+                        OutboundFailure::Timeout => return Ok(Async::Ready(())),
+                        e => panic!("Unexpected failure: {:?}", e),
+                    }
+                }
+                Async::Ready(Some(e)) => panic!("Unexpected event: {:?}", e),
+                Async::Ready(None) => panic!("Swarm ended"),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    });
+
+    Runtime::new().unwrap().block_on(test).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    drop(silent);
+}
+
+fn mk_transport() -> (PeerId, impl Transport<
+    Output = (PeerId, impl StreamMuxer<Substream = impl Send, OutboundSubstream = impl Send, Error = impl Into<io::Error>>),
+    Listener = impl Send,
+    ListenerUpgrade = impl Send,
+    Dial = impl Send,
+    Error = impl fmt::Debug
+> + Clone) {
+    let id_keys = identity::Keypair::generate_ed25519();
+    let peer_id = id_keys.public().into_peer_id();
+    let transport = TcpConfig::new()
+        .nodelay(true)
+        .with_upgrade(SecioConfig::new(id_keys))
+        .and_then(move |out, endpoint| {
+            let peer_id = out.remote_key.into_peer_id();
+            let peer_id2 = peer_id.clone();
+            let upgrade = yamux::Config::default()
+                .map_outbound(move |muxer| (peer_id, muxer))
+                .map_inbound(move |muxer| (peer_id2, muxer));
+            upgrade::apply(out.stream, upgrade, endpoint)
+        });
+    (peer_id, transport)
+}
@@ -0,0 +1,487 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Generation and verification of the certificates used to authenticate TLS connections.
+//!
+//! As described in the [libp2p TLS spec](https://github.com/libp2p/specs/blob/master/tls/tls.md),
+//! each node generates a self-signed certificate for an ephemeral key. The certificate carries an
+//! extension in which the libp2p identity key of the node signs the public key of the
+//! certificate. The TLS handshake proves that the remote owns the certificate key, and the
+//! extension proves that the certificate key belongs to the remote's identity.
+//!
+//! When verifying a certificate, we also check its self-signature and its validity period, as
+//! required by the spec.
+
+use libp2p_core::{PeerId, identity};
+use std::{error, fmt, time::{SystemTime, UNIX_EPOCH}};
+
+/// Object identifier of the libp2p certificate extension (1.3.6.1.4.1.53594.1.1).
+const P2P_EXT_OID: [u64; 9] = [1, 3, 6, 1, 4, 1, 53594, 1, 1];
+
+/// DER encoding of `P2P_EXT_OID`, without the tag and length.
+const P2P_EXT_OID_DER: [u8; 10] = [0x2b, 0x06, 0x01, 0x04, 0x01, 0x83, 0xa2, 0x5a, 0x01, 0x01];
+
+/// Prefix of the message signed by the identity key.
+const P2P_SIGNING_PREFIX: &[u8] = b"libp2p-tls-handshake:";
+
+/// DER tags used when encoding and parsing certificates.
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_VERSION: u8 = 0xa0;
+const TAG_EXTENSIONS: u8 = 0xa3;
+
+/// Generates a self-signed certificate authenticated by `keypair`.
+///
+/// Returns the DER encodings of the certificate and of its private key.
+pub fn make_certificate(keypair: &identity::Keypair)
+    -> Result<(rustls::Certificate, rustls::PrivateKey), CertificateError>
+{
+    make_certificate_with_params(keypair, rcgen::CertificateParams::new(Vec::new()))
+}
+
+/// Same as `make_certificate`, but starts from the given parameters, e.g. to choose the validity
+/// period of the certificate.
+fn make_certificate_with_params(keypair: &identity::Keypair, mut params: rcgen::CertificateParams)
+    -> Result<(rustls::Certificate, rustls::PrivateKey), CertificateError>
+{
+    let cert_key = rcgen::KeyPair::generate(&rcgen::PKCS_ECDSA_P256_SHA256)
+        .map_err(CertificateError::Generation)?;
+
+    let mut msg = P2P_SIGNING_PREFIX.to_vec();
+    msg.extend_from_slice(&cert_key.public_key_der());
+    let signature = keypair.sign(&msg).map_err(CertificateError::Signing)?;
+    let public_key = keypair.public().into_protobuf_encoding();
+
+    let mut content = der_encode(TAG_OCTET_STRING, &public_key);
+    content.extend(der_encode(TAG_OCTET_STRING, &signature));
+    let extension = rcgen::CustomExtension::from_oid_content(
+        &P2P_EXT_OID,
+        der_encode(TAG_SEQUENCE, &content)
+    );
+
+    params.alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+    params.key_pair = Some(cert_key);
+    params.custom_extensions.push(extension);
+
+    let cert = rcgen::Certificate::from_params(params).map_err(CertificateError::Generation)?;
+    let cert_der = cert.serialize_der().map_err(CertificateError::Generation)?;
+    let key_der = cert.serialize_private_key_der();
+    Ok((rustls::Certificate(cert_der), rustls::PrivateKey(key_der)))
+}
+
+/// Checks a DER-encoded certificate and returns the identity of its owner.
+///
+/// The certificate must be currently valid, be signed by its own key and carry a libp2p
+/// extension in which the identity key signs the certificate key.
+///
+/// > **Note**: This doesn't check that the remote owns the certificate key, which is the job of
+/// >           the TLS handshake.
+pub fn verify_certificate(cert: &[u8]) -> Result<PeerId, CertificateError> {
+    verify_certificate_at(cert, SystemTime::now())
+}
+
+/// Same as `verify_certificate`, but checks the validity period against `now`.
+fn verify_certificate_at(cert_der: &[u8], now: SystemTime) -> Result<PeerId, CertificateError> {
+    // Let webpki parse the certificate first, which rejects anything that isn't a well-formed
+    // X.509 v3 certificate, and then check that the certificate is signed by its own key.
+    let end_entity = webpki::EndEntityCert::from(cert_der)
+        .map_err(|_| CertificateError::Malformed)?;
+
+    let (cert, _) = der_element(cert_der, TAG_SEQUENCE)?;
+    let (tbs, rest) = der_element(cert.content, TAG_SEQUENCE)?;
+    let (signature_alg, rest) = der_element(rest, TAG_SEQUENCE)?;
+    let (signature, _) = der_element(rest, TAG_BIT_STRING)?;
+    // The first byte of a bit string is the number of unused bits, which must be zero for a
+    // signature.
+    let signature = match signature.content.split_first() {
+        Some((0, signature)) => signature,
+        _ => return Err(CertificateError::Malformed),
+    };
+    let (alg_oid, _) = der_element(signature_alg.content, TAG_OID)?;
+    let self_signed = signature_algorithms(alg_oid.content)
+        .iter()
+        .any(|alg| end_entity.verify_signature(alg, tbs.raw, signature).is_ok());
+    if !self_signed {
+        return Err(CertificateError::InvalidSelfSignature);
+    }
+
+    // The fields of the `TBSCertificate` are: the optional version, the serial number, the
+    // signature algorithm, the issuer, the validity, the subject, the public key and then
+    // optional fields, among which the extensions.
+    let mut fields = Vec::new();
+    let mut rest = tbs.content;
+    while !rest.is_empty() {
+        let (field, r) = der_any(rest)?;
+        fields.push(field);
+        rest = r;
+    }
+    let first = if fields.first().map(|f| f.tag) == Some(TAG_VERSION) { 1 } else { 0 };
+    // The algorithm in the `TBSCertificate` must be the one used for the signature.
+    let tbs_signature_alg = fields.get(first + 1).ok_or(CertificateError::Malformed)?;
+    if tbs_signature_alg.raw != signature_alg.raw {
+        return Err(CertificateError::InvalidSelfSignature);
+    }
+
+    let validity = fields.get(first + 3).ok_or(CertificateError::Malformed)?;
+    let (not_before, rest) = der_any(validity.content)?;
+    let (not_after, _) = der_any(rest)?;
+    let now = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    if now < der_time(&not_before)? {
+        return Err(CertificateError::NotYetValid);
+    }
+    if now > der_time(&not_after)? {
+        return Err(CertificateError::Expired);
+    }
+
+    let spki = fields.get(first + 5).ok_or(CertificateError::Malformed)?;
+    let extensions = fields.iter()
+        .find(|f| f.tag == TAG_EXTENSIONS)
+        .ok_or(CertificateError::MissingExtension)?;
+
+    let (extensions, _) = der_element(extensions.content, TAG_SEQUENCE)?;
+    let mut value = None;
+    let mut rest = extensions.content;
+    while !rest.is_empty() {
+        let (extension, r) = der_element(rest, TAG_SEQUENCE)?;
+        rest = r;
+        // An extension is made of an identifier, an optional criticality flag and a value.
+        let (oid, ext_rest) = der_any(extension.content)?;
+        if oid.content != &P2P_EXT_OID_DER[..] {
+            continue;
+        }
+        let (mut v, ext_rest) = der_any(ext_rest)?;
+        if v.tag != TAG_OCTET_STRING {
+            v = der_element(ext_rest, TAG_OCTET_STRING)?.0;
+        }
+        value = Some(v.content);
+        break;
+    }
+    let value = value.ok_or(CertificateError::MissingExtension)?;
+
+    let (signed_key, _) = der_element(value, TAG_SEQUENCE)?;
+    let (public_key, rest) = der_element(signed_key.content, TAG_OCTET_STRING)?;
+    let (signature, _) = der_element(rest, TAG_OCTET_STRING)?;
+
+    let public_key = identity::PublicKey::from_protobuf_encoding(public_key.content)
+        .map_err(|_| CertificateError::InvalidPublicKey)?;
+    let mut msg = P2P_SIGNING_PREFIX.to_vec();
+    msg.extend_from_slice(spki.raw);
+    if !public_key.verify(&msg, signature.content) {
+        return Err(CertificateError::InvalidSignature);
+    }
+
+    Ok(public_key.into_peer_id())
+}
+
+/// Error while generating or verifying a certificate.
+#[derive(Debug)]
+pub enum CertificateError {
+    /// Failed to generate the certificate.
+    Generation(rcgen::RcgenError),
+    /// Failed to sign the certificate key with the identity key.
+    Signing(identity::error::SigningError),
+    /// The certificate is not valid DER.
+    Malformed,
+    /// The certificate doesn't have the libp2p extension.
+    MissingExtension,
+    /// The public key in the extension couldn't be decoded.
+    InvalidPublicKey,
+    /// The signature in the extension doesn't match the certificate key.
+    InvalidSignature,
+    /// The certificate isn't signed by its own key, or with an unsupported algorithm.
+    InvalidSelfSignature,
+    /// The validity period of the certificate hasn't started yet.
+    NotYetValid,
+    /// The validity period of the certificate is over.
+    Expired,
+}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificateError::Generation(e) => write!(f, "Failed to generate the certificate: {:?}", e),
+            CertificateError::Signing(e) => write!(f, "Failed to sign the certificate key: {}", e),
+            CertificateError::Malformed => f.write_str("Malformed certificate"),
+            CertificateError::MissingExtension => f.write_str("Missing libp2p certificate extension"),
+            CertificateError::InvalidPublicKey => f.write_str("Invalid public key in certificate extension"),
+            CertificateError::InvalidSignature => f.write_str("Invalid signature in certificate extension"),
+            CertificateError::InvalidSelfSignature => f.write_str("Invalid certificate self-signature"),
+            CertificateError::NotYetValid => f.write_str("Certificate not valid yet"),
+            CertificateError::Expired => f.write_str("Certificate expired"),
+        }
+    }
+}
+
+impl error::Error for CertificateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CertificateError::Signing(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the algorithms that may have produced a signature whose algorithm identifier has the
+/// given DER-encoded object identifier. The curve of ECDSA signatures depends on the key, hence
+/// several candidates.
+fn signature_algorithms(oid: &[u8]) -> &'static [&'static webpki::SignatureAlgorithm] {
+    static ECDSA_SHA256: [&webpki::SignatureAlgorithm; 2] =
+        [&webpki::ECDSA_P256_SHA256, &webpki::ECDSA_P384_SHA256];
+    static ECDSA_SHA384: [&webpki::SignatureAlgorithm; 2] =
+        [&webpki::ECDSA_P256_SHA384, &webpki::ECDSA_P384_SHA384];
+    static ED25519: [&webpki::SignatureAlgorithm; 1] = [&webpki::ED25519];
+    static RSA_SHA256: [&webpki::SignatureAlgorithm; 1] = [&webpki::RSA_PKCS1_2048_8192_SHA256];
+    static RSA_SHA384: [&webpki::SignatureAlgorithm; 1] = [&webpki::RSA_PKCS1_2048_8192_SHA384];
+    static RSA_SHA512: [&webpki::SignatureAlgorithm; 1] = [&webpki::RSA_PKCS1_2048_8192_SHA512];
+
+    match oid {
+        // ecdsa-with-SHA256 (1.2.840.10045.4.3.2)
+        [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02] => &ECDSA_SHA256,
+        // ecdsa-with-SHA384 (1.2.840.10045.4.3.3)
+        [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03] => &ECDSA_SHA384,
+        // Ed25519 (1.3.101.112)
+        [0x2b, 0x65, 0x70] => &ED25519,
+        // sha256WithRSAEncryption (1.2.840.113549.1.1.11)
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b] => &RSA_SHA256,
+        // sha384WithRSAEncryption (1.2.840.113549.1.1.12)
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c] => &RSA_SHA384,
+        // sha512WithRSAEncryption (1.2.840.113549.1.1.13)
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d] => &RSA_SHA512,
+        _ => &[],
+    }
+}
+
+/// Decodes a `UTCTime` or `GeneralizedTime` of the form used in certificates (e.g.
+/// `YYMMDDHHMMSSZ`) into a number of seconds since the Unix epoch. Times before the epoch are
+/// returned as 0.
+fn der_time(element: &Element<'_>) -> Result<u64, CertificateError> {
+    let digits = match (element.tag, element.content.split_last()) {
+        (TAG_UTC_TIME, Some((b'Z', digits))) if digits.len() == 12 => digits,
+        (TAG_GENERALIZED_TIME, Some((b'Z', digits))) if digits.len() == 14 => digits,
+        _ => return Err(CertificateError::Malformed),
+    };
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return Err(CertificateError::Malformed);
+    }
+    let number = |s: &[u8]| s.iter().fold(0i64, |n, d| n * 10 + i64::from(d - b'0'));
+
+    let (year, rest) = if element.tag == TAG_UTC_TIME {
+        // Two-digit years are in the range 1950 to 2049.
+        let year = number(&digits[.. 2]);
+        (if year < 50 { 2000 + year } else { 1900 + year }, &digits[2 ..])
+    } else {
+        (number(&digits[.. 4]), &digits[4 ..])
+    };
+    let (month, day) = (number(&rest[0 .. 2]), number(&rest[2 .. 4]));
+    let (hour, minute, second) = (number(&rest[4 .. 6]), number(&rest[6 .. 8]), number(&rest[8 .. 10]));
+    if month < 1 || month > 12 || day < 1 || day > 31 || hour > 23 || minute > 59 || second > 59 {
+        return Err(CertificateError::Malformed);
+    }
+
+    // Number of days since the epoch of the given date of the proleptic Gregorian calendar.
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second;
+    Ok(if secs < 0 { 0 } else { secs as u64 })
+}
+
+/// A DER element.
+struct Element<'a> {
+    /// The tag of the element.
+    tag: u8,
+    /// The whole encoding of the element.
+    raw: &'a [u8],
+    /// The content of the element.
+    content: &'a [u8],
+}
+
+/// Splits the first DER element off `input`. Returns the element and the remaining input.
+fn der_any(input: &[u8]) -> Result<(Element<'_>, &[u8]), CertificateError> {
+    let tag = *input.get(0).ok_or(CertificateError::Malformed)?;
+    let first = *input.get(1).ok_or(CertificateError::Malformed)?;
+    let (len, header) = if first < 0x80 {
+        (first as usize, 2)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 {
+            return Err(CertificateError::Malformed);
+        }
+        let bytes = input.get(2 .. 2 + n).ok_or(CertificateError::Malformed)?;
+        (bytes.iter().fold(0, |len, b| (len << 8) | *b as usize), 2 + n)
+    };
+    let end = header.checked_add(len).ok_or(CertificateError::Malformed)?;
+    if input.len() < end {
+        return Err(CertificateError::Malformed);
+    }
+    let element = Element { tag, raw: &input[.. end], content: &input[header .. end] };
+    Ok((element, &input[end ..]))
+}
+
+/// Same as `der_any`, but fails if the element doesn't have the given tag.
+fn der_element(input: &[u8], tag: u8) -> Result<(Element<'_>, &[u8]), CertificateError> {
+    let (element, rest) = der_any(input)?;
+    if element.tag != tag {
+        return Err(CertificateError::Malformed);
+    }
+    Ok((element, rest))
+}
+
+/// Encodes a DER element.
+fn der_encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&bytes[skip ..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn certificate_round_trip() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let (cert, _) = make_certificate(&keypair).unwrap();
+        let peer_id = verify_certificate(&cert.0).unwrap();
+        assert_eq!(peer_id, keypair.public().into_peer_id());
+    }
+
+    #[test]
+    fn tampered_certificate_is_rejected() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let (cert, _) = make_certificate(&keypair).unwrap();
+        // Flip a byte of the signature, which follows the encoded public key in the extension.
+        let public_key = keypair.public().into_protobuf_encoding();
+        let mut tampered = cert.0.clone();
+        let pos = tampered.windows(public_key.len())
+            .position(|w| w == &public_key[..])
+            .unwrap();
+        tampered[pos + public_key.len() + 10] ^= 0xff;
+        assert!(verify_certificate(&tampered).is_err());
+    }
+
+    #[test]
+    fn tampered_tbs_certificate_is_rejected() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let (cert, _) = make_certificate(&keypair).unwrap();
+        // Change the subject of the certificate, which leaves the libp2p extension intact but
+        // invalidates the self-signature.
+        let subject = b"rcgen self signed cert";
+        let mut tampered = cert.0.clone();
+        let pos = tampered.windows(subject.len())
+            .position(|w| w == &subject[..])
+            .unwrap();
+        tampered[pos] = b'R';
+        match verify_certificate(&tampered) {
+            Err(CertificateError::InvalidSelfSignature) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn expired_certificate_is_rejected() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let mut params = rcgen::CertificateParams::new(Vec::new());
+        params.not_before = rcgen::date_time_ymd(1990, 1, 1);
+        params.not_after = rcgen::date_time_ymd(2000, 1, 1);
+        let (cert, _) = make_certificate_with_params(&keypair, params).unwrap();
+        match verify_certificate(&cert.0) {
+            Err(CertificateError::Expired) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn not_yet_valid_certificate_is_rejected() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let mut params = rcgen::CertificateParams::new(Vec::new());
+        params.not_before = rcgen::date_time_ymd(3000, 1, 1);
+        params.not_after = rcgen::date_time_ymd(3001, 1, 1);
+        let (cert, _) = make_certificate_with_params(&keypair, params).unwrap();
+        match verify_certificate(&cert.0) {
+            Err(CertificateError::NotYetValid) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validity_period_is_checked_against_time() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let mut params = rcgen::CertificateParams::new(Vec::new());
+        params.not_before = rcgen::date_time_ymd(2020, 1, 1);
+        params.not_after = rcgen::date_time_ymd(2060, 1, 1);
+        let (cert, _) = make_certificate_with_params(&keypair, params).unwrap();
+        // 2019-12-31, 2030-01-01 and 2060-01-02 at midnight.
+        let at = |secs| UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        assert!(verify_certificate_at(&cert.0, at(1_577_750_400)).is_err());
+        assert!(verify_certificate_at(&cert.0, at(1_893_456_000)).is_ok());
+        assert!(verify_certificate_at(&cert.0, at(2_840_227_200)).is_err());
+    }
+
+    #[test]
+    fn der_time_decoding() {
+        let utc = der_encode(TAG_UTC_TIME, b"700101000000Z");
+        assert_eq!(der_time(&der_any(&utc).unwrap().0).unwrap(), 0);
+        let utc = der_encode(TAG_UTC_TIME, b"200101000000Z");
+        assert_eq!(der_time(&der_any(&utc).unwrap().0).unwrap(), 1_577_836_800);
+        let generalized = der_encode(TAG_GENERALIZED_TIME, b"20600101000000Z");
+        assert_eq!(der_time(&der_any(&generalized).unwrap().0).unwrap(), 2_840_140_800);
+        let before_epoch = der_encode(TAG_UTC_TIME, b"500101000000Z");
+        assert_eq!(der_time(&der_any(&before_epoch).unwrap().0).unwrap(), 0);
+        let invalid = der_encode(TAG_UTC_TIME, b"201301000000Z");
+        assert!(der_time(&der_any(&invalid).unwrap().0).is_err());
+    }
+
+    #[test]
+    fn der_encoding_round_trip() {
+        for len in &[0, 1, 127, 128, 255, 256, 70_000] {
+            let content = vec![0x42; *len];
+            let encoded = der_encode(TAG_OCTET_STRING, &content);
+            let (element, rest) = der_element(&encoded, TAG_OCTET_STRING).unwrap();
+            assert_eq!(element.content, &content[..]);
+            assert_eq!(element.raw, &encoded[..]);
+            assert!(rest.is_empty());
+        }
+    }
+
+    #[test]
+    fn truncated_der_is_rejected() {
+        let encoded = der_encode(TAG_SEQUENCE, &[1, 2, 3]);
+        assert!(der_any(&encoded[.. 3]).is_err());
+        assert!(der_any(&[]).is_err());
+    }
+}
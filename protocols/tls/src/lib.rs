@@ -0,0 +1,222 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Implementation of the [libp2p TLS handshake](https://github.com/libp2p/specs/blob/master/tls/tls.md)
+//! (`/tls/1.0.0`), which secures a connection with TLS 1.3 and authenticates the remote through
+//! its libp2p identity key.
+//!
+//! # Connection upgrade
+//!
+//! The `TlsConfig` struct implements the `InboundUpgrade` and `OutboundUpgrade` traits. You can
+//! apply it over a `Transport` by using the `with_upgrade` method. The output of the upgrade is
+//! the `PeerId` of the remote, taken from its certificate, and the encrypted stream.
+//!
+//! ```
+//! use libp2p_core::{identity, Transport};
+//! use libp2p_tcp::TcpConfig;
+//! use libp2p_tls::TlsConfig;
+//!
+//! let keypair = identity::Keypair::generate_ed25519();
+//! let transport = TcpConfig::new()
+//!     .with_upgrade(TlsConfig::new(&keypair).unwrap())
+//!     .map(|(_peer_id, stream), _| stream);
+//! ```
+//!
+//! # Certificates
+//!
+//! Each node generates a self-signed certificate carrying an extension in which its identity key
+//! signs the public key of the certificate, see the `certificate` module. The same certificates
+//! are used to authenticate QUIC connections.
+
+pub mod certificate;
+pub mod verifier;
+
+mod error;
+
+pub use self::certificate::CertificateError;
+pub use self::error::TlsError;
+
+use futures::{Future, Poll};
+use libp2p_core::{PeerId, identity, upgrade::{UpgradeInfo, InboundUpgrade, OutboundUpgrade, Negotiated}};
+use log::debug;
+use rustls::Session;
+use std::{io, iter, sync::Arc};
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{TlsAcceptor, TlsConnector, client, server};
+
+/// Protocol negotiated through ALPN during the TLS handshake.
+const ALPN: &[u8] = b"libp2p";
+
+/// Server name sent during the TLS handshake. It is ignored, since the identity of the remote is
+/// taken from its certificate.
+const SERVER_NAME: &str = "l";
+
+/// Generates a certificate authenticated by `keypair` and builds the TLS 1.3 configurations used
+/// when dialing and when listening, which only accept remotes presenting a valid libp2p
+/// certificate.
+pub fn make_configs(keypair: &identity::Keypair)
+    -> Result<(rustls::ClientConfig, rustls::ServerConfig), CertificateError>
+{
+    let (cert, key) = certificate::make_certificate(keypair)?;
+
+    let mut client = rustls::ClientConfig::new();
+    client.versions = vec![rustls::ProtocolVersion::TLSv1_3];
+    client.alpn_protocols = vec![ALPN.to_vec()];
+    client.dangerous().set_certificate_verifier(Arc::new(verifier::Libp2pServerCertVerifier));
+    client.set_single_client_cert(vec![cert.clone()], key.clone());
+
+    let mut server = rustls::ServerConfig::new(Arc::new(verifier::Libp2pClientCertVerifier));
+    server.versions = vec![rustls::ProtocolVersion::TLSv1_3];
+    server.alpn_protocols = vec![ALPN.to_vec()];
+    server.set_single_cert(vec![cert], key)
+        .expect("the key was generated along with the certificate; qed");
+
+    Ok((client, server))
+}
+
+/// Implementation of the `InboundUpgrade` and `OutboundUpgrade` traits of `libp2p_core`.
+/// Automatically applies TLS on any connection.
+#[derive(Clone)]
+pub struct TlsConfig {
+    /// TLS configuration used when dialing.
+    client: Arc<rustls::ClientConfig>,
+    /// TLS configuration used when listening.
+    server: Arc<rustls::ServerConfig>,
+}
+
+impl TlsConfig {
+    /// Creates a new `TlsConfig`, authenticating the connections with `keypair`.
+    pub fn new(keypair: &identity::Keypair) -> Result<Self, CertificateError> {
+        let (client, server) = make_configs(keypair)?;
+        Ok(TlsConfig {
+            client: Arc::new(client),
+            server: Arc::new(server),
+        })
+    }
+}
+
+impl UpgradeInfo for TlsConfig {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/tls/1.0.0")
+    }
+}
+
+impl<T> InboundUpgrade<T> for TlsConfig
+where
+    T: AsyncRead + AsyncWrite + Send + 'static
+{
+    type Output = (PeerId, TlsOutput<Negotiated<T>>);
+    type Error = TlsError;
+    type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+    fn upgrade_inbound(self, socket: Negotiated<T>, _: Self::Info) -> Self::Future {
+        debug!("Starting TLS upgrade as listener");
+        let future = TlsAcceptor::from(self.server)
+            .accept(socket)
+            .map_err(TlsError::Io)
+            .and_then(|stream| {
+                let peer_id = remote_peer_id(stream.get_ref().1)?;
+                Ok((peer_id, TlsOutput { stream: TlsStream::Server(stream) }))
+            });
+        Box::new(future)
+    }
+}
+
+impl<T> OutboundUpgrade<T> for TlsConfig
+where
+    T: AsyncRead + AsyncWrite + Send + 'static
+{
+    type Output = (PeerId, TlsOutput<Negotiated<T>>);
+    type Error = TlsError;
+    type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+    fn upgrade_outbound(self, socket: Negotiated<T>, _: Self::Info) -> Self::Future {
+        debug!("Starting TLS upgrade as dialer");
+        let name = webpki::DNSNameRef::try_from_ascii_str(SERVER_NAME)
+            .expect("SERVER_NAME is a valid DNS name; qed");
+        let future = TlsConnector::from(self.client)
+            .connect(name, socket)
+            .map_err(TlsError::Io)
+            .and_then(|stream| {
+                let peer_id = remote_peer_id(stream.get_ref().1)?;
+                Ok((peer_id, TlsOutput { stream: TlsStream::Client(stream) }))
+            });
+        Box::new(future)
+    }
+}
+
+/// Returns the identity of the remote of a TLS session whose handshake is over.
+fn remote_peer_id(session: &impl Session) -> Result<PeerId, TlsError> {
+    let certs = session.get_peer_certificates()
+        .ok_or(TlsError::InvalidRemoteCertificate)?;
+    verifier::verify_presented_certs(&certs)
+        .map_err(|_| TlsError::InvalidRemoteCertificate)
+}
+
+/// Output of the TLS protocol, i.e. the encrypted stream.
+pub struct TlsOutput<S> {
+    stream: TlsStream<S>,
+}
+
+/// The TLS stream, depending on the side of the handshake.
+enum TlsStream<S> {
+    Client(client::TlsStream<S>),
+    Server(server::TlsStream<S>),
+}
+
+impl<S: AsyncRead + AsyncWrite> io::Read for TlsOutput<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.stream {
+            TlsStream::Client(s) => s.read(buf),
+            TlsStream::Server(s) => s.read(buf),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncRead for TlsOutput<S> {}
+
+impl<S: AsyncRead + AsyncWrite> io::Write for TlsOutput<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.stream {
+            TlsStream::Client(s) => s.write(buf),
+            TlsStream::Server(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stream {
+            TlsStream::Client(s) => s.flush(),
+            TlsStream::Server(s) => s.flush(),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncWrite for TlsOutput<S> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match &mut self.stream {
+            TlsStream::Client(s) => s.shutdown(),
+            TlsStream::Server(s) => s.shutdown(),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! TLS certificate verifiers that accept the self-signed certificates of libp2p nodes.
+//!
+//! Instead of checking a chain of trust, the verifiers check that the certificate is valid and
+//! self-signed, and check its libp2p extension. The identity of the remote is then extracted from the certificate once the
+//! handshake is over.
+
+use crate::certificate;
+use libp2p_core::PeerId;
+use rustls::{
+    Certificate,
+    ClientCertVerified,
+    ClientCertVerifier,
+    DistinguishedNames,
+    RootCertStore,
+    ServerCertVerified,
+    ServerCertVerifier,
+    TLSError
+};
+
+/// Verifies the certificate of the server we connect to.
+pub struct Libp2pServerCertVerifier;
+
+impl ServerCertVerifier for Libp2pServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        _dns_name: webpki::DNSNameRef<'_>,
+        _ocsp_response: &[u8]
+    ) -> Result<ServerCertVerified, TLSError> {
+        verify_presented_certs(presented_certs).map(|_| ServerCertVerified::assertion())
+    }
+}
+
+/// Verifies the certificate of the clients connecting to us. Clients must present a
+/// certificate.
+pub struct Libp2pClientCertVerifier;
+
+impl ClientCertVerifier for Libp2pClientCertVerifier {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        true
+    }
+
+    fn client_auth_root_subjects(&self) -> DistinguishedNames {
+        DistinguishedNames::new()
+    }
+
+    fn verify_client_cert(&self, presented_certs: &[Certificate])
+        -> Result<ClientCertVerified, TLSError>
+    {
+        verify_presented_certs(presented_certs).map(|_| ClientCertVerified::assertion())
+    }
+}
+
+/// Checks the certificates presented by a remote and returns its identity.
+///
+/// The remote must present exactly one certificate.
+pub fn verify_presented_certs(presented_certs: &[Certificate]) -> Result<PeerId, TLSError> {
+    if presented_certs.len() != 1 {
+        return Err(TLSError::General("Expected exactly one certificate".to_owned()));
+    }
+    certificate::verify_certificate(&presented_certs[0].0)
+        .map_err(|e| TLSError::General(e.to_string()))
+}
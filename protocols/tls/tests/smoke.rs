@@ -0,0 +1,75 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use futures::prelude::*;
+use libp2p_core::identity;
+use libp2p_core::transport::{Transport, ListenerEvent};
+use libp2p_tcp::TcpConfig;
+use libp2p_tls::TlsConfig;
+use tokio::{io, runtime::current_thread::Runtime};
+
+#[test]
+fn handshake_authenticates_both_sides() {
+    let listener_keys = identity::Keypair::generate_ed25519();
+    let listener_id = listener_keys.public().into_peer_id();
+    let dialer_keys = identity::Keypair::generate_ed25519();
+    let dialer_id = dialer_keys.public().into_peer_id();
+
+    let mut listener = TcpConfig::new()
+        .with_upgrade(TlsConfig::new(&listener_keys).unwrap())
+        .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+        .unwrap();
+    let addr = listener.by_ref()
+        .wait()
+        .next()
+        .expect("some event")
+        .expect("no error")
+        .into_new_address()
+        .expect("listen address");
+
+    let server = listener
+        .filter_map(ListenerEvent::into_upgrade)
+        .into_future()
+        .map_err(|(e, _)| panic!("listener error: {:?}", e))
+        .and_then(|(upgrade, _)| {
+            let (upgrade, _) = upgrade.expect("incoming connection");
+            upgrade.map_err(|e| panic!("upgrade error: {:?}", e))
+        })
+        .and_then(move |(peer_id, stream)| {
+            assert_eq!(peer_id, dialer_id);
+            io::read_exact(stream, [0; 5]).map_err(|e| panic!("read error: {:?}", e))
+        })
+        .map(|(_, buf)| assert_eq!(&buf, b"hello"));
+
+    let client = TcpConfig::new()
+        .with_upgrade(TlsConfig::new(&dialer_keys).unwrap())
+        .dial(addr)
+        .unwrap()
+        .map_err(|e| panic!("dial error: {:?}", e))
+        .and_then(move |(peer_id, stream)| {
+            assert_eq!(peer_id, listener_id);
+            io::write_all(stream, b"hello").map_err(|e| panic!("write error: {:?}", e))
+        })
+        .and_then(|(stream, _)| io::flush(stream).map_err(|e| panic!("flush error: {:?}", e)));
+
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(server.join(client)).unwrap();
+}
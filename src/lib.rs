@@ -161,6 +161,8 @@ pub use multihash;
 pub use tokio_io;
 pub use tokio_codec;
 
+#[doc(inline)]
+pub use libp2p_autonat as autonat;
 #[doc(inline)]
 pub use libp2p_core as core;
 #[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
@@ -176,6 +178,10 @@ pub use libp2p_kad as kad;
 #[doc(inline)]
 pub use libp2p_floodsub as floodsub;
 #[doc(inline)]
+pub use libp2p_gossipsub as gossipsub;
+#[doc(inline)]
+pub use libp2p_metrics as metrics;
+#[doc(inline)]
 pub use libp2p_mplex as mplex;
 #[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
 #[doc(inline)]
@@ -188,14 +194,26 @@ pub use libp2p_ping as ping;
 #[doc(inline)]
 pub use libp2p_plaintext as plaintext;
 #[doc(inline)]
+pub use libp2p_pnet as pnet;
+#[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
+#[doc(inline)]
+pub use libp2p_quic as quic;
+#[doc(inline)]
 pub use libp2p_ratelimit as ratelimit;
 #[doc(inline)]
+pub use libp2p_relay as relay;
+#[doc(inline)]
+pub use libp2p_request_response as request_response;
+#[doc(inline)]
 pub use libp2p_secio as secio;
 #[doc(inline)]
 pub use libp2p_swarm as swarm;
 #[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
 #[doc(inline)]
 pub use libp2p_tcp as tcp;
+#[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
+#[doc(inline)]
+pub use libp2p_tls as tls;
 #[doc(inline)]
 pub use libp2p_uds as uds;
 #[doc(inline)]
@@ -261,6 +279,43 @@ pub fn build_tcp_ws_secio_mplex_yamux(keypair: identity::Keypair)
         .with_timeout(Duration::from_secs(20))
 }
 
+/// Same as [`build_development_transport`], but uses noise instead of secio as the
+/// encryption layer.
+///
+/// > **Note**: This `Transport` is not suitable for production usage, as its implementation
+/// >           reserves the right to support additional protocols or remove deprecated protocols.
+#[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
+pub fn build_development_transport_noise(keypair: identity::Keypair)
+    -> Result<impl Transport<Output = (PeerId, impl core::muxing::StreamMuxer<OutboundSubstream = impl Send, Substream = impl Send, Error = impl Into<io::Error>> + Send + Sync), Error = impl error::Error + Send, Listener = impl Send, Dial = impl Send, ListenerUpgrade = impl Send> + Clone, noise::NoiseError>
+{
+    build_tcp_ws_noise_mplex_yamux(keypair)
+}
+
+/// Builds an implementation of `Transport` that is suitable for usage with the `Swarm`.
+///
+/// The implementation supports TCP/IP, WebSockets over TCP/IP, noise (with the `XX` handshake
+/// pattern, authenticating the remote with its identity key) as the encryption layer,
+/// and mplex or yamux as the multiplexing layer.
+///
+/// Fails if the static DH key of noise can't be signed with `keypair`.
+#[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
+pub fn build_tcp_ws_noise_mplex_yamux(keypair: identity::Keypair)
+    -> Result<impl Transport<Output = (PeerId, impl core::muxing::StreamMuxer<OutboundSubstream = impl Send, Substream = impl Send, Error = impl Into<io::Error>> + Send + Sync), Error = impl error::Error + Send, Listener = impl Send, Dial = impl Send, ListenerUpgrade = impl Send> + Clone, noise::NoiseError>
+{
+    let transport = CommonTransport::new()
+        .with_upgrade(noise::NoiseAuthenticated::xx(&keypair)?)
+        .and_then(move |(peer_id, stream), endpoint| {
+            let peer_id2 = peer_id.clone();
+            let upgrade = core::upgrade::SelectUpgrade::new(yamux::Config::default(), mplex::MplexConfig::new())
+                .map_inbound(move |muxer| (peer_id, muxer))
+                .map_outbound(move |muxer| (peer_id2, muxer));
+            core::upgrade::apply(stream, upgrade, endpoint)
+                .map(|(id, muxer)| (id, core::muxing::StreamMuxerBox::new(muxer)))
+        })
+        .with_timeout(Duration::from_secs(20));
+    Ok(transport)
+}
+
 /// Implementation of `Transport` that supports the most common protocols.
 ///
 /// The list currently is TCP/IP, DNS, and WebSockets. However this list could change in the
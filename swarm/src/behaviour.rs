@@ -108,6 +108,13 @@ pub trait NetworkBehaviour {
     fn inject_dial_failure(&mut self, _peer_id: &PeerId) {
     }
 
+    /// Indicates to the behaviour that an incoming connection failed to be established.
+    ///
+    /// This includes incoming connections that were denied because a connection limit
+    /// has been reached, in which case the error is an `IncomingError::ConnectionLimit`.
+    fn inject_incoming_connection_error(&mut self, _local_addr: &Multiaddr, _send_back_addr: &Multiaddr, _error: &dyn error::Error) {
+    }
+
     /// Indicates to the behaviour that we have started listening on a new multiaddr.
     fn inject_new_listen_addr(&mut self, _addr: &Multiaddr) {
     }
@@ -121,6 +128,10 @@ pub trait NetworkBehaviour {
     fn inject_new_external_addr(&mut self, _addr: &Multiaddr) {
     }
 
+    /// Indicates to the behaviour that an external address of ours has been removed.
+    fn inject_expired_external_addr(&mut self, _addr: &Multiaddr) {
+    }
+
     /// A listener experienced an error.
     fn inject_listener_error(&mut self, _id: ListenerId, _err: &(dyn std::error::Error + 'static)) {
     }
@@ -226,4 +237,85 @@ pub enum NetworkBehaviourAction<TInEvent, TOutEvent> {
         /// The observed address of the local node.
         address: Multiaddr,
     },
+
+    /// Instructs the `Swarm` to add an address that has been confirmed to be reachable by
+    /// other nodes to the list of external addresses.
+    ///
+    /// Contrary to `ReportObservedAddr`, the address is added as is.
+    AddExternalAddr {
+        /// The confirmed address of the local node.
+        address: Multiaddr,
+    },
+
+    /// Instructs the `Swarm` to remove an address from the list of external addresses, for
+    /// example because other nodes failed to reach us on it.
+    RemoveExternalAddr {
+        /// The address to remove.
+        address: Multiaddr,
+    },
+
+    /// Instructs the `Swarm` to set a tag on a peer, which contributes to the score
+    /// of the peer in the connection manager.
+    ///
+    /// When the connection manager needs to close connections, the connections to the
+    /// peers with the lowest score are closed first. The tag replaces any previous
+    /// value of the same tag on that peer.
+    ///
+    /// Has no effect if the `Swarm` has no connection manager.
+    TagPeer {
+        /// The peer to tag.
+        peer_id: PeerId,
+        /// The name of the tag.
+        tag: String,
+        /// The value of the tag.
+        value: i32,
+    },
+
+    /// Instructs the `Swarm` to remove a tag previously set with `TagPeer`.
+    UntagPeer {
+        /// The peer to untag.
+        peer_id: PeerId,
+        /// The name of the tag.
+        tag: String,
+    },
+
+    /// Instructs the `Swarm` to protect the connection to a peer from being closed
+    /// by the connection manager, under the given tag.
+    ///
+    /// Has no effect if the `Swarm` has no connection manager.
+    ProtectPeer {
+        /// The peer to protect.
+        peer_id: PeerId,
+        /// The tag under which the peer is protected.
+        tag: String,
+    },
+
+    /// Instructs the `Swarm` to remove a protection previously set with `ProtectPeer`.
+    ///
+    /// The peer remains protected if it is protected under other tags.
+    UnprotectPeer {
+        /// The peer to unprotect.
+        peer_id: PeerId,
+        /// The tag under which the peer was protected.
+        tag: String,
+    },
+
+    /// Instructs the `Swarm` to close the connection to a peer, for example because it
+    /// misbehaved.
+    ///
+    /// [`NetworkBehaviour::inject_disconnected`] is invoked if we were connected to the peer.
+    /// Nothing prevents the peer from connecting again afterwards.
+    DisconnectPeer {
+        /// The peer to disconnect from.
+        peer_id: PeerId,
+    },
+
+    /// Instructs the `Swarm` to ban a peer, as with `Swarm::ban_peer_id`, and to close the
+    /// connection to it if any.
+    ///
+    /// [`NetworkBehaviour::inject_disconnected`] is invoked if we were connected to the peer.
+    BanPeer {
+        /// The peer to ban.
+        peer_id: PeerId,
+    },
 }
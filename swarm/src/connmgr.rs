@@ -0,0 +1,349 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Management of the number of connections of a `Swarm`.
+//!
+//! The [`ConnectionManager`] keeps the number of connections between a low and
+//! a high watermark. When the number of connections exceeds the high watermark,
+//! connections are closed until the low watermark is reached, starting with the
+//! peers with the lowest score. The score of a peer is the sum of the values of
+//! the tags set on that peer, usually by the `NetworkBehaviour`s through
+//! `NetworkBehaviourAction::TagPeer`. Peers that are protected, as well as peers
+//! connected for less than the grace period, are never disconnected.
+
+use futures::prelude::*;
+use libp2p_core::PeerId;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use wasm_timer::{Delay, Instant};
+
+/// Configuration of a [`ConnectionManager`].
+#[derive(Debug, Clone)]
+pub struct ConnectionManagerConfig {
+    low_watermark: usize,
+    high_watermark: usize,
+    grace_period: Duration,
+}
+
+impl ConnectionManagerConfig {
+    /// Creates a new configuration with the given watermarks and a grace period
+    /// of one minute.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low_watermark` is greater than `high_watermark`.
+    pub fn new(low_watermark: usize, high_watermark: usize) -> Self {
+        assert!(low_watermark <= high_watermark, "low watermark exceeds high watermark");
+        ConnectionManagerConfig {
+            low_watermark,
+            high_watermark,
+            grace_period: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets the duration during which a new connection is not considered for closing.
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// Returns the number of connections kept when trimming connections.
+    pub fn low_watermark(&self) -> usize {
+        self.low_watermark
+    }
+
+    /// Returns the number of connections above which connections are trimmed.
+    pub fn high_watermark(&self) -> usize {
+        self.high_watermark
+    }
+
+    /// Returns the duration during which a new connection is not considered for closing.
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+}
+
+/// Keeps track of the connected peers, their tags and protections, and decides
+/// which connections to close.
+#[derive(Debug)]
+pub struct ConnectionManager {
+    config: ConnectionManagerConfig,
+    /// Known peers, i.e. connected peers and peers with tags or protections.
+    peers: HashMap<PeerId, PeerInfo>,
+    /// Number of peers in `peers` that are connected.
+    num_connected: usize,
+    /// Timer for the next attempt at trimming connections, if connections
+    /// could not be trimmed because they were still in their grace period.
+    next_trim: Option<Delay>,
+}
+
+#[derive(Debug, Default)]
+struct PeerInfo {
+    /// The tags of the peer and their values.
+    tags: HashMap<String, i32>,
+    /// The tags under which the peer is protected.
+    protections: HashSet<String>,
+    /// When the peer connected, if it is connected.
+    connected_since: Option<Instant>,
+}
+
+impl PeerInfo {
+    fn score(&self) -> i32 {
+        self.tags.values().fold(0, |acc, v| acc.saturating_add(*v))
+    }
+
+    fn is_unused(&self) -> bool {
+        self.tags.is_empty() && self.protections.is_empty() && self.connected_since.is_none()
+    }
+}
+
+impl ConnectionManager {
+    /// Creates a new `ConnectionManager` with the given configuration.
+    pub fn new(config: ConnectionManagerConfig) -> Self {
+        ConnectionManager {
+            config,
+            peers: HashMap::new(),
+            num_connected: 0,
+            next_trim: None,
+        }
+    }
+
+    /// Returns the configuration of the connection manager.
+    pub fn config(&self) -> &ConnectionManagerConfig {
+        &self.config
+    }
+
+    /// Returns the number of connected peers.
+    pub fn num_connected(&self) -> usize {
+        self.num_connected
+    }
+
+    /// Sets the tag of a peer to the given value, replacing the previous value of that tag.
+    pub fn tag_peer(&mut self, peer_id: &PeerId, tag: impl Into<String>, value: i32) {
+        self.peers.entry(peer_id.clone()).or_default().tags.insert(tag.into(), value);
+    }
+
+    /// Removes a tag from a peer.
+    pub fn untag_peer(&mut self, peer_id: &PeerId, tag: &str) {
+        if let Some(info) = self.peers.get_mut(peer_id) {
+            info.tags.remove(tag);
+        }
+        self.remove_if_unused(peer_id);
+    }
+
+    /// Protects a peer from being disconnected, under the given tag.
+    ///
+    /// A peer remains protected as long as it is protected under at least one tag.
+    pub fn protect_peer(&mut self, peer_id: &PeerId, tag: impl Into<String>) {
+        self.peers.entry(peer_id.clone()).or_default().protections.insert(tag.into());
+    }
+
+    /// Removes the protection of a peer under the given tag.
+    ///
+    /// Returns `true` if the peer is still protected under another tag.
+    pub fn unprotect_peer(&mut self, peer_id: &PeerId, tag: &str) -> bool {
+        let protected = if let Some(info) = self.peers.get_mut(peer_id) {
+            info.protections.remove(tag);
+            !info.protections.is_empty()
+        } else {
+            false
+        };
+        self.remove_if_unused(peer_id);
+        protected
+    }
+
+    /// Returns `true` if the peer is protected from being disconnected.
+    pub fn is_protected(&self, peer_id: &PeerId) -> bool {
+        self.peers.get(peer_id).map_or(false, |info| !info.protections.is_empty())
+    }
+
+    /// Returns the value of a tag of a peer.
+    pub fn tag_value(&self, peer_id: &PeerId, tag: &str) -> Option<i32> {
+        self.peers.get(peer_id).and_then(|info| info.tags.get(tag).cloned())
+    }
+
+    /// Returns the score of a peer, i.e. the sum of the values of its tags.
+    pub fn score(&self, peer_id: &PeerId) -> i32 {
+        self.peers.get(peer_id).map_or(0, PeerInfo::score)
+    }
+
+    /// Records that a connection to the given peer has been established.
+    pub(crate) fn on_connected(&mut self, peer_id: &PeerId) {
+        let info = self.peers.entry(peer_id.clone()).or_default();
+        if info.connected_since.is_none() {
+            self.num_connected += 1;
+        }
+        info.connected_since = Some(Instant::now());
+    }
+
+    /// Records that the connection to the given peer has been closed.
+    pub(crate) fn on_disconnected(&mut self, peer_id: &PeerId) {
+        if let Some(info) = self.peers.get_mut(peer_id) {
+            if info.connected_since.take().is_some() {
+                self.num_connected -= 1;
+            }
+        }
+        self.remove_if_unused(peer_id);
+    }
+
+    /// Returns the peers whose connection should be closed in order to bring
+    /// the number of connections back to the low watermark, if the high
+    /// watermark is exceeded.
+    ///
+    /// The returned peers are considered disconnected by the connection manager.
+    pub(crate) fn poll_trim(&mut self) -> Vec<PeerId> {
+        if self.num_connected <= self.config.high_watermark {
+            self.next_trim = None;
+            return Vec::new()
+        }
+
+        if let Some(next_trim) = self.next_trim.as_mut() {
+            match next_trim.poll() {
+                Ok(Async::NotReady) => return Vec::new(),
+                Ok(Async::Ready(())) | Err(_) => self.next_trim = None,
+            }
+        }
+
+        let now = Instant::now();
+        let grace_period = self.config.grace_period;
+        let mut candidates = Vec::new();
+        let mut earliest_in_grace: Option<Instant> = None;
+
+        for (peer_id, info) in &self.peers {
+            let since = match info.connected_since {
+                Some(since) if info.protections.is_empty() => since,
+                _ => continue,
+            };
+            if since + grace_period <= now {
+                candidates.push((info.score(), since, peer_id.clone()));
+            } else {
+                let end = since + grace_period;
+                earliest_in_grace = Some(earliest_in_grace.map_or(end, |e| e.min(end)));
+            }
+        }
+
+        // Lowest score first and, among equal scores, the most recent connections first.
+        candidates.sort_by(|(s1, t1, _), (s2, t2, _)| s1.cmp(s2).then(t2.cmp(t1)));
+
+        let num_to_close = self.num_connected - self.config.low_watermark;
+        let to_close = candidates.into_iter()
+            .take(num_to_close)
+            .map(|(_, _, peer_id)| peer_id)
+            .collect::<Vec<_>>();
+
+        for peer_id in &to_close {
+            self.on_disconnected(peer_id);
+        }
+
+        // If the high watermark is still exceeded because of connections in their
+        // grace period, try again once the first of them has expired.
+        if self.num_connected > self.config.high_watermark {
+            if let Some(at) = earliest_in_grace {
+                let mut delay = Delay::new(at);
+                // Register the current task for wake-up.
+                let _ = delay.poll();
+                self.next_trim = Some(delay);
+            }
+        }
+
+        to_close
+    }
+
+    fn remove_if_unused(&mut self, peer_id: &PeerId) {
+        if self.peers.get(peer_id).map_or(false, PeerInfo::is_unused) {
+            self.peers.remove(peer_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(low: usize, high: usize) -> ConnectionManager {
+        let config = ConnectionManagerConfig::new(low, high).with_grace_period(Duration::from_secs(0));
+        ConnectionManager::new(config)
+    }
+
+    #[test]
+    fn no_trim_below_high_watermark() {
+        let mut mgr = manager(2, 4);
+        for _ in 0 .. 4 {
+            mgr.on_connected(&PeerId::random());
+        }
+        assert!(mgr.poll_trim().is_empty());
+        assert_eq!(mgr.num_connected(), 4);
+    }
+
+    #[test]
+    fn trim_lowest_scores_to_low_watermark() {
+        let mut mgr = manager(2, 4);
+        let peers = (0 .. 5).map(|_| PeerId::random()).collect::<Vec<_>>();
+        for (i, peer) in peers.iter().enumerate() {
+            mgr.on_connected(peer);
+            mgr.tag_peer(peer, "test", i as i32);
+        }
+        // Protected peers are never closed, regardless of their score.
+        mgr.protect_peer(&peers[0], "test");
+
+        let mut closed = mgr.poll_trim();
+        closed.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        let mut expected = vec![peers[1].clone(), peers[2].clone(), peers[3].clone()];
+        expected.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        assert_eq!(closed, expected);
+        assert_eq!(mgr.num_connected(), 2);
+        assert!(mgr.poll_trim().is_empty());
+    }
+
+    #[test]
+    fn grace_period_prevents_trim() {
+        let config = ConnectionManagerConfig::new(0, 1).with_grace_period(Duration::from_secs(60));
+        let mut mgr = ConnectionManager::new(config);
+        mgr.on_connected(&PeerId::random());
+        mgr.on_connected(&PeerId::random());
+        // Polling the trim timer requires a task context.
+        futures::future::lazy(move || {
+            assert!(mgr.poll_trim().is_empty());
+            assert_eq!(mgr.num_connected(), 2);
+            Ok::<_, ()>(())
+        }).wait().unwrap();
+    }
+
+    #[test]
+    fn tags_and_protections() {
+        let mut mgr = manager(0, 1);
+        let peer = PeerId::random();
+        mgr.tag_peer(&peer, "a", 3);
+        mgr.tag_peer(&peer, "b", -1);
+        assert_eq!(mgr.score(&peer), 2);
+        mgr.untag_peer(&peer, "a");
+        assert_eq!(mgr.score(&peer), -1);
+        assert_eq!(mgr.tag_value(&peer, "b"), Some(-1));
+
+        mgr.protect_peer(&peer, "x");
+        mgr.protect_peer(&peer, "y");
+        assert!(mgr.unprotect_peer(&peer, "x"));
+        assert!(!mgr.unprotect_peer(&peer, "y"));
+        assert!(!mgr.is_protected(&peer));
+
+        mgr.untag_peer(&peer, "b");
+        assert!(mgr.peers.is_empty());
+    }
+}
@@ -54,11 +54,14 @@
 //!
 
 mod behaviour;
+mod metrics;
 mod registry;
 
+pub mod connmgr;
 pub mod protocols_handler;
 pub mod toggle;
 
+pub use connmgr::{ConnectionManager, ConnectionManagerConfig};
 pub use behaviour::{
     NetworkBehaviour,
     NetworkBehaviourAction,
@@ -87,10 +90,12 @@ use libp2p_core::{
         collection::ConnectionInfo,
         handled_node::NodeHandler,
         node::Substream,
-        network::{self, Network, NetworkEvent}
+        network::{self, ConnectionLimits, Network, NetworkEvent}
     },
     transport::TransportError
 };
+use libp2p_metrics::Registry;
+use metrics::SwarmMetrics;
 use registry::{Addresses, AddressIntoIter};
 use smallvec::SmallVec;
 use std::{error, fmt, io, ops::{Deref, DerefMut}};
@@ -139,6 +144,12 @@ where
     /// List of nodes for which we deny any incoming connection.
     banned_peers: HashSet<PeerId>,
 
+    /// The connection manager, if enabled.
+    connection_manager: Option<ConnectionManager>,
+
+    /// The metrics of the swarm, if enabled.
+    metrics: Option<SwarmMetrics>,
+
     /// Pending event message to be delivered.
     ///
     /// If the pair's second element is `AsyncSink::NotReady`, the event
@@ -232,8 +243,11 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
     ///
     /// Returns an error if the address is not supported.
     pub fn dial_addr(me: &mut Self, addr: Multiaddr) -> Result<(), TransportError<TTransport::Error>> {
-        let handler = me.behaviour.new_handler();
-        me.network.dial(addr, handler.into_node_handler_builder())
+        let negotiations = me.metrics.as_ref().map(SwarmMetrics::substream_negotiations);
+        let handler = me.behaviour.new_handler()
+            .into_node_handler_builder()
+            .with_substream_negotiations(negotiations);
+        me.network.dial(addr, handler)
     }
 
     /// Tries to reach the given peer using the elements in the topology.
@@ -244,7 +258,10 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
         let addrs = me.behaviour.addresses_of_peer(&peer_id);
         match me.network.peer(peer_id.clone()) {
             network::Peer::NotConnected(peer) => {
-                let handler = me.behaviour.new_handler().into_node_handler_builder();
+                let negotiations = me.metrics.as_ref().map(SwarmMetrics::substream_negotiations);
+                let handler = me.behaviour.new_handler()
+                    .into_node_handler_builder()
+                    .with_substream_negotiations(negotiations);
                 if peer.connect_iter(addrs, handler).is_err() {
                     me.behaviour.inject_dial_failure(&peer_id);
                 }
@@ -280,6 +297,13 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
         me.external_addrs.add(addr)
     }
 
+    /// Removes an external address.
+    ///
+    /// Returns `true` if the address was one of our external addresses.
+    pub fn remove_external_address(me: &mut Self, addr: &Multiaddr) -> bool {
+        me.external_addrs.remove(addr)
+    }
+
     /// Returns the connection info of a node, or `None` if we're not connected to it.
     // TODO: should take &self instead of &mut self, but the API in network requires &mut
     pub fn connection_info(me: &mut Self, peer_id: &PeerId) -> Option<TConnInfo> {
@@ -296,7 +320,13 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
     /// This function has no effect is the peer is already banned.
     pub fn ban_peer_id(me: &mut Self, peer_id: PeerId) {
         me.banned_peers.insert(peer_id.clone());
+        if let Some(manager) = me.connection_manager.as_mut() {
+            manager.on_disconnected(&peer_id);
+        }
         if let Some(c) = me.network.peer(peer_id).into_connected() {
+            if let Some(metrics) = me.metrics.as_ref() {
+                metrics.connection_closed(c.endpoint());
+            }
             c.close();
         }
     }
@@ -305,6 +335,33 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
     pub fn unban_peer_id(me: &mut Self, peer_id: PeerId) {
         me.banned_peers.remove(&peer_id);
     }
+
+    /// Closes the connection to a peer, if any, and notifies the behaviour.
+    fn disconnect(me: &mut Self, peer_id: PeerId) {
+        if let Some(peer) = me.network.peer(peer_id.clone()).into_connected() {
+            let endpoint = peer.endpoint().clone();
+            peer.close();
+            if let Some(manager) = me.connection_manager.as_mut() {
+                manager.on_disconnected(&peer_id);
+            }
+            if let Some(metrics) = me.metrics.as_ref() {
+                metrics.connection_closed(&endpoint);
+            }
+            me.behaviour.inject_disconnected(&peer_id, endpoint);
+        }
+    }
+
+    /// Returns the connection manager, if enabled through
+    /// `SwarmBuilder::connection_manager`.
+    pub fn connection_manager(me: &Self) -> Option<&ConnectionManager> {
+        me.connection_manager.as_ref()
+    }
+
+    /// Returns the connection manager mutably, e.g. in order to tag or
+    /// protect peers, if enabled through `SwarmBuilder::connection_manager`.
+    pub fn connection_manager_mut(me: &mut Self) -> Option<&mut ConnectionManager> {
+        me.connection_manager.as_mut()
+    }
 }
 
 impl<TTransport, TBehaviour, TMuxer, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo> Stream for
@@ -353,24 +410,49 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                     self.behaviour.inject_node_event(conn_info.peer_id().clone(), event);
                 },
                 Async::Ready(NetworkEvent::Connected { conn_info, endpoint }) => {
+                    if let Some(metrics) = self.metrics.as_ref() {
+                        metrics.connection_opened(&endpoint);
+                    }
                     if self.banned_peers.contains(conn_info.peer_id()) {
+                        if let Some(metrics) = self.metrics.as_ref() {
+                            metrics.connection_closed(&endpoint);
+                        }
                         self.network.peer(conn_info.peer_id().clone())
                             .into_connected()
                             .expect("the Network just notified us that we were connected; QED")
                             .close();
                     } else {
+                        if let Some(manager) = self.connection_manager.as_mut() {
+                            manager.on_connected(conn_info.peer_id());
+                        }
                         self.behaviour.inject_connected(conn_info.peer_id().clone(), endpoint);
                     }
                 },
                 Async::Ready(NetworkEvent::NodeClosed { conn_info, endpoint, .. }) => {
+                    if let Some(manager) = self.connection_manager.as_mut() {
+                        manager.on_disconnected(conn_info.peer_id());
+                    }
+                    if let Some(metrics) = self.metrics.as_ref() {
+                        metrics.connection_closed(&endpoint);
+                    }
                     self.behaviour.inject_disconnected(conn_info.peer_id(), endpoint);
                 },
                 Async::Ready(NetworkEvent::Replaced { new_info, closed_endpoint, endpoint, .. }) => {
+                    if let Some(manager) = self.connection_manager.as_mut() {
+                        manager.on_connected(new_info.peer_id());
+                    }
+                    if let Some(metrics) = self.metrics.as_ref() {
+                        metrics.connection_closed(&closed_endpoint);
+                        metrics.connection_opened(&endpoint);
+                    }
                     self.behaviour.inject_replaced(new_info.peer_id().clone(), closed_endpoint, endpoint);
                 },
                 Async::Ready(NetworkEvent::IncomingConnection(incoming)) => {
-                    let handler = self.behaviour.new_handler();
-                    incoming.accept(handler.into_node_handler_builder());
+                    let negotiations = self.metrics.as_ref().map(SwarmMetrics::substream_negotiations);
+                    let handler = self.behaviour.new_handler()
+                        .into_node_handler_builder()
+                        .with_substream_negotiations(negotiations);
+                    incoming.accept(handler);
                 },
                 Async::Ready(NetworkEvent::NewListenerAddress { listen_addr, .. }) => {
                     if !self.listened_addrs.contains(&listen_addr) {
@@ -386,14 +468,25 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                     self.behaviour.inject_listener_closed(listener_id),
                 Async::Ready(NetworkEvent::ListenerError { listener_id, error }) =>
                     self.behaviour.inject_listener_error(listener_id, &error),
-                Async::Ready(NetworkEvent::IncomingConnectionError { .. }) => {},
+                Async::Ready(NetworkEvent::IncomingConnectionError { local_addr, send_back_addr, error }) => {
+                    if let Some(metrics) = self.metrics.as_ref() {
+                        metrics.connection_error("IncomingError");
+                    }
+                    self.behaviour.inject_incoming_connection_error(&local_addr, &send_back_addr, &error);
+                },
                 Async::Ready(NetworkEvent::DialError { peer_id, multiaddr, error, new_state }) => {
+                    if let Some(metrics) = self.metrics.as_ref() {
+                        metrics.connection_error("NetworkReachError");
+                    }
                     self.behaviour.inject_addr_reach_failure(Some(&peer_id), &multiaddr, &error);
                     if let network::PeerState::NotConnected = new_state {
                         self.behaviour.inject_dial_failure(&peer_id);
                     }
                 },
                 Async::Ready(NetworkEvent::UnknownPeerDialError { multiaddr, error, .. }) => {
+                    if let Some(metrics) = self.metrics.as_ref() {
+                        metrics.connection_error("UnknownPeerDialErr");
+                    }
                     self.behaviour.inject_addr_reach_failure(None, &multiaddr, &error);
                 },
             }
@@ -416,6 +509,20 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                 return Ok(Async::NotReady)
             }
 
+            // Close the connections selected by the connection manager, if any.
+            if let Some(manager) = self.connection_manager.as_mut() {
+                for peer_id in manager.poll_trim() {
+                    if let Some(peer) = self.network.peer(peer_id.clone()).into_connected() {
+                        let endpoint = peer.endpoint().clone();
+                        peer.close();
+                        if let Some(metrics) = self.metrics.as_ref() {
+                            metrics.connection_closed(&endpoint);
+                        }
+                        self.behaviour.inject_disconnected(&peer_id, endpoint);
+                    }
+                }
+            }
+
             let behaviour_poll = {
                 let mut parameters = SwarmPollParameters {
                     local_peer_id: &mut self.network.local_peer_id(),
@@ -459,6 +566,44 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
                         self.external_addrs.add(addr)
                     }
                 },
+                Async::Ready(NetworkBehaviourAction::AddExternalAddr { address }) => {
+                    if self.external_addrs.iter().all(|a| *a != address) {
+                        self.behaviour.inject_new_external_addr(&address);
+                    }
+                    self.external_addrs.add(address)
+                },
+                Async::Ready(NetworkBehaviourAction::RemoveExternalAddr { address }) => {
+                    if self.external_addrs.remove(&address) {
+                        self.behaviour.inject_expired_external_addr(&address);
+                    }
+                },
+                Async::Ready(NetworkBehaviourAction::TagPeer { peer_id, tag, value }) => {
+                    if let Some(manager) = self.connection_manager.as_mut() {
+                        manager.tag_peer(&peer_id, tag, value);
+                    }
+                },
+                Async::Ready(NetworkBehaviourAction::UntagPeer { peer_id, tag }) => {
+                    if let Some(manager) = self.connection_manager.as_mut() {
+                        manager.untag_peer(&peer_id, &tag);
+                    }
+                },
+                Async::Ready(NetworkBehaviourAction::ProtectPeer { peer_id, tag }) => {
+                    if let Some(manager) = self.connection_manager.as_mut() {
+                        manager.protect_peer(&peer_id, tag);
+                    }
+                },
+                Async::Ready(NetworkBehaviourAction::UnprotectPeer { peer_id, tag }) => {
+                    if let Some(manager) = self.connection_manager.as_mut() {
+                        manager.unprotect_peer(&peer_id, &tag);
+                    }
+                },
+                Async::Ready(NetworkBehaviourAction::DisconnectPeer { peer_id }) => {
+                    ExpandedSwarm::disconnect(self, peer_id);
+                },
+                Async::Ready(NetworkBehaviourAction::BanPeer { peer_id }) => {
+                    self.banned_peers.insert(peer_id.clone());
+                    ExpandedSwarm::disconnect(self, peer_id);
+                },
             }
         }
     }
@@ -496,7 +641,9 @@ impl<'a> PollParameters for SwarmPollParameters<'a> {
 }
 
 pub struct SwarmBuilder<TTransport, TBehaviour> {
-    incoming_limit: Option<u32>,
+    limits: ConnectionLimits,
+    connection_manager: Option<ConnectionManagerConfig>,
+    metrics: Option<SwarmMetrics>,
     local_peer_id: PeerId,
     transport: TTransport,
     behaviour: TBehaviour,
@@ -535,15 +682,49 @@ where TBehaviour: NetworkBehaviour,
 {
     pub fn new(transport: TTransport, behaviour: TBehaviour, local_peer_id: PeerId) -> Self {
         SwarmBuilder {
-            incoming_limit: None,
+            limits: ConnectionLimits::default(),
+            connection_manager: None,
+            metrics: None,
             local_peer_id,
             transport,
             behaviour,
         }
     }
 
+    /// Configures the maximum number of incoming connections that are being negotiated.
+    ///
+    /// Equivalent to `ConnectionLimits::with_max_pending_incoming`.
     pub fn incoming_limit(mut self, incoming_limit: Option<u32>) -> Self {
-        self.incoming_limit = incoming_limit;
+        self.limits = self.limits.with_max_pending_incoming(incoming_limit);
+        self
+    }
+
+    /// Configures the connection limits of the swarm, replacing any limit
+    /// previously configured, including the `incoming_limit`.
+    ///
+    /// Dials refused and connections closed because a limit is reached are
+    /// reported to the `NetworkBehaviour` with a `ConnectionLimit` error.
+    pub fn connection_limits(mut self, limits: ConnectionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Enables the connection manager with the given configuration.
+    ///
+    /// The connection manager closes the connections with the lowest score
+    /// when the number of connections exceeds the high watermark.
+    pub fn connection_manager(mut self, config: ConnectionManagerConfig) -> Self {
+        self.connection_manager = Some(config);
+        self
+    }
+
+    /// Enables the metrics of the swarm and registers them in the given registry.
+    ///
+    /// The swarm counts the connections opened and closed, by transport and
+    /// role, the errors while dialing or accepting connections, by kind, and
+    /// the substreams negotiated, by protocol name and direction.
+    pub fn metrics(mut self, registry: &Registry) -> Self {
+        self.metrics = Some(SwarmMetrics::new(registry));
         self
     }
 
@@ -556,7 +737,7 @@ where TBehaviour: NetworkBehaviour,
             .map(|info| info.protocol_name().to_vec())
             .collect();
 
-        let network = Network::new_with_incoming_limit(self.transport, self.local_peer_id, self.incoming_limit);
+        let network = Network::new_with_limits(self.transport, self.local_peer_id, self.limits);
 
         ExpandedSwarm {
             network,
@@ -565,6 +746,8 @@ where TBehaviour: NetworkBehaviour,
             listened_addrs: SmallVec::new(),
             external_addrs: Addresses::default(),
             banned_peers: HashSet::new(),
+            connection_manager: self.connection_manager.map(ConnectionManager::new),
+            metrics: self.metrics,
             send_event_to_complete: None
         }
     }
@@ -573,18 +756,32 @@ where TBehaviour: NetworkBehaviour,
 #[cfg(test)]
 mod tests {
     use crate::protocols_handler::{DummyProtocolsHandler, ProtocolsHandler};
-    use crate::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, SwarmBuilder};
+    use crate::{
+        ConnectionManagerConfig,
+        NetworkBehaviour,
+        NetworkBehaviourAction,
+        OneShotHandler,
+        PollParameters,
+        SubstreamProtocol,
+        Swarm,
+        SwarmBuilder
+    };
     use libp2p_core::{
         ConnectedPoint,
         identity,
         Multiaddr,
         PeerId,
         PublicKey,
-        transport::dummy::{DummyStream, DummyTransport}
+        Transport,
+        multiaddr::Protocol,
+        muxing::StreamMuxerBox,
+        nodes::network::ConnectionLimits,
+        transport::{MemoryTransport, boxed::Boxed, dummy::{DummyStream, DummyTransport}},
+        upgrade::{self, DeniedUpgrade}
     };
-    use libp2p_mplex::Multiplex;
-    use futures::prelude::*;
-    use std::marker::PhantomData;
+    use libp2p_mplex::{Multiplex, MplexConfig};
+    use futures::{future, prelude::*};
+    use std::{collections::HashMap, io, marker::PhantomData, time::Duration};
     use tokio_io::{AsyncRead, AsyncWrite};
     use void::Void;
 
@@ -626,6 +823,70 @@ mod tests {
 
     }
 
+    /// Behaviour that keeps the connections alive and records the peers that we connect to
+    /// and disconnect from.
+    struct RecordingBehaviour<TSubstream> {
+        connected: Vec<PeerId>,
+        disconnected: Vec<PeerId>,
+        marker: PhantomData<TSubstream>,
+    }
+
+    impl<TSubstream> RecordingBehaviour<TSubstream> {
+        fn new() -> Self {
+            RecordingBehaviour { connected: Vec::new(), disconnected: Vec::new(), marker: PhantomData }
+        }
+    }
+
+    impl<TSubstream> NetworkBehaviour
+        for RecordingBehaviour<TSubstream>
+        where TSubstream: AsyncRead + AsyncWrite
+    {
+        type ProtocolsHandler = OneShotHandler<TSubstream, DeniedUpgrade, DeniedUpgrade, Void>;
+        type OutEvent = Void;
+
+        fn new_handler(&mut self) -> Self::ProtocolsHandler {
+            OneShotHandler::new(SubstreamProtocol::new(DeniedUpgrade), Duration::from_secs(10))
+        }
+
+        fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+            Vec::new()
+        }
+
+        fn inject_connected(&mut self, peer_id: PeerId, _: ConnectedPoint) {
+            self.connected.push(peer_id);
+        }
+
+        fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+            self.disconnected.push(peer_id.clone());
+        }
+
+        fn inject_node_event(&mut self, _: PeerId,
+            _: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent) {}
+
+        fn poll(&mut self, _: &mut impl PollParameters) ->
+            Async<NetworkBehaviourAction<<Self::ProtocolsHandler as
+            ProtocolsHandler>::InEvent, Self::OutEvent>>
+        {
+            Async::NotReady
+        }
+    }
+
+    /// Builds an in-memory transport multiplexed with mplex. The peer ID of a remote is looked
+    /// up in `peers` by address when dialing, and is random when listening.
+    fn memory_transport(peers: HashMap<Multiaddr, PeerId>) -> Boxed<(PeerId, StreamMuxerBox), io::Error> {
+        MemoryTransport::default()
+            .and_then(move |socket, endpoint| {
+                let peer_id = match &endpoint {
+                    ConnectedPoint::Dialer { address } => peers[address].clone(),
+                    ConnectedPoint::Listener { .. } => PeerId::random(),
+                };
+                upgrade::apply(socket, MplexConfig::new(), endpoint)
+                    .map(move |muxer| (peer_id, StreamMuxerBox::new(muxer)))
+            })
+            .map_err(|e| panic!("Failed to create transport: {:?}", e))
+            .boxed()
+    }
+
     fn get_random_id() -> PublicKey {
         identity::Keypair::generate_ed25519().public()
     }
@@ -648,4 +909,67 @@ mod tests {
         let swarm = SwarmBuilder::new(transport, behaviour, id.into()).build();
         assert!(swarm.network.incoming_limit().is_none())
     }
+
+    #[test]
+    fn test_build_swarm_with_connection_limits() {
+        let id = get_random_id();
+        let transport = DummyTransport::<(PeerId, Multiplex<DummyStream>)>::new();
+        let behaviour = DummyBehaviour{marker: PhantomData};
+        let limits = ConnectionLimits::default()
+            .with_max_established_incoming(Some(8))
+            .with_max_established_per_peer(Some(1));
+        let swarm = SwarmBuilder::new(transport, behaviour, id.into())
+            .connection_limits(limits)
+            .incoming_limit(Some(4))
+            .build();
+        assert_eq!(swarm.network.incoming_limit(), Some(4));
+        assert_eq!(swarm.network.connection_limits().max_established_incoming(), Some(8));
+        assert_eq!(swarm.network.connection_limits().max_established_per_peer(), Some(1));
+        assert!(swarm.network.connection_limits().max_pending_outgoing().is_none());
+    }
+
+    #[test]
+    fn connection_manager_closes_unprotected_connections() {
+        let port_base = 1 + rand::random::<u64>() % (u64::max_value() - 2);
+        let addrs = (0 .. 2)
+            .map(|i| Multiaddr::from(Protocol::Memory(port_base + i)))
+            .collect::<Vec<_>>();
+        let mut remotes = addrs.iter()
+            .map(|addr| {
+                let local_id = PeerId::random();
+                let transport = memory_transport(HashMap::new());
+                let mut swarm = Swarm::new(transport, RecordingBehaviour::new(), local_id.clone());
+                Swarm::listen_on(&mut swarm, addr.clone()).unwrap();
+                (local_id, swarm)
+            })
+            .collect::<Vec<_>>();
+        let protected = remotes[0].0.clone();
+        let unprotected = remotes[1].0.clone();
+
+        let peers = addrs.iter().cloned().zip(remotes.iter().map(|(id, _)| id.clone())).collect();
+        let config = ConnectionManagerConfig::new(1, 1).with_grace_period(Duration::from_secs(0));
+        let mut swarm = SwarmBuilder::new(memory_transport(peers), RecordingBehaviour::new(), PeerId::random())
+            .connection_manager(config)
+            .build();
+        Swarm::connection_manager_mut(&mut swarm).unwrap().protect_peer(&protected, "test");
+        for addr in addrs {
+            Swarm::dial_addr(&mut swarm, addr).unwrap();
+        }
+
+        future::poll_fn(move || -> Poll<_, io::Error> {
+            for (_, remote) in &mut remotes {
+                while let Async::Ready(_) = remote.poll()? {}
+            }
+            while let Async::Ready(_) = swarm.poll()? {}
+
+            if swarm.connected.len() < 2 || swarm.disconnected.is_empty() {
+                return Ok(Async::NotReady)
+            }
+            assert_eq!(swarm.disconnected, vec![unprotected.clone()]);
+            assert!(Swarm::connection_info(&mut swarm, &protected).is_some());
+            assert!(Swarm::connection_info(&mut swarm, &unprotected).is_none());
+            assert_eq!(Swarm::connection_manager(&swarm).unwrap().num_connected(), 1);
+            Ok(Async::Ready(()))
+        }).wait().unwrap();
+    }
 }
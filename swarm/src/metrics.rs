@@ -0,0 +1,201 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Metrics of the `Swarm`, enabled with `SwarmBuilder::metrics`.
+
+use libp2p_core::{
+    ConnectedPoint,
+    Multiaddr,
+    ProtocolName,
+    upgrade::{InboundUpgrade, Negotiated, OutboundUpgrade, UpgradeInfo}
+};
+use libp2p_core::multiaddr::Protocol;
+use libp2p_metrics::{Counter, Family, Registry};
+
+/// The metrics of a `Swarm`.
+pub(crate) struct SwarmMetrics {
+    /// Connections opened, by transport and role.
+    connections_opened: Family<Counter>,
+    /// Connections closed, by transport and role.
+    connections_closed: Family<Counter>,
+    /// Dial and incoming connection errors, by kind.
+    connection_errors: Family<Counter>,
+    /// Substreams negotiated, by protocol name and direction.
+    substream_negotiations: Family<Counter>,
+}
+
+impl SwarmMetrics {
+    /// Creates the metrics of a `Swarm` and registers them in the registry.
+    pub(crate) fn new(registry: &Registry) -> Self {
+        let metrics = SwarmMetrics {
+            connections_opened: Family::new(&["transport", "role"]),
+            connections_closed: Family::new(&["transport", "role"]),
+            connection_errors: Family::new(&["kind"]),
+            substream_negotiations: Family::new(&["protocol", "direction"]),
+        };
+
+        registry.register(
+            "libp2p_swarm_connections_opened_total",
+            "Number of connections opened",
+            metrics.connections_opened.clone());
+        registry.register(
+            "libp2p_swarm_connections_closed_total",
+            "Number of connections closed",
+            metrics.connections_closed.clone());
+        registry.register(
+            "libp2p_swarm_connection_errors_total",
+            "Number of errors while dialing or accepting connections",
+            metrics.connection_errors.clone());
+        registry.register(
+            "libp2p_swarm_substream_negotiations_total",
+            "Number of substreams successfully negotiated",
+            metrics.substream_negotiations.clone());
+
+        metrics
+    }
+
+    /// Records a connection being opened.
+    pub(crate) fn connection_opened(&self, endpoint: &ConnectedPoint) {
+        let (transport, role) = labels_of(endpoint);
+        self.connections_opened.get_or_create(&[&transport, role]).inc();
+    }
+
+    /// Records a connection being closed.
+    pub(crate) fn connection_closed(&self, endpoint: &ConnectedPoint) {
+        let (transport, role) = labels_of(endpoint);
+        self.connections_closed.get_or_create(&[&transport, role]).inc();
+    }
+
+    /// Records an error of the given kind while dialing or accepting a connection.
+    pub(crate) fn connection_error(&self, kind: &str) {
+        self.connection_errors.get_or_create(&[kind]).inc();
+    }
+
+    /// Returns the counters of negotiated substreams, to pass to the connection handlers.
+    pub(crate) fn substream_negotiations(&self) -> Family<Counter> {
+        self.substream_negotiations.clone()
+    }
+}
+
+/// Returns the transport and role labels of a connection.
+fn labels_of(endpoint: &ConnectedPoint) -> (String, &'static str) {
+    match endpoint {
+        ConnectedPoint::Dialer { address } => (transport_of(address), "dialer"),
+        ConnectedPoint::Listener { local_addr, .. } => (transport_of(local_addr), "listener"),
+    }
+}
+
+/// Returns the names of the protocols of an address, without their values, e.g. `/ip4/tcp` for
+/// `/ip4/127.0.0.1/tcp/30333`.
+fn transport_of(address: &Multiaddr) -> String {
+    address.iter()
+        .filter(|p| match p {
+            Protocol::P2p(_) => false,
+            _ => true,
+        })
+        .map(|p| {
+            let p = p.to_string();
+            match p[1..].find('/') {
+                Some(end) => p[.. end + 1].to_string(),
+                None => p,
+            }
+        })
+        .collect()
+}
+
+/// Wraps around an upgrade and counts the substreams for which it is negotiated.
+pub(crate) struct MeteredUpgrade<TUpgrade> {
+    inner: TUpgrade,
+    negotiations: Option<Family<Counter>>,
+    direction: &'static str,
+}
+
+impl<TUpgrade> MeteredUpgrade<TUpgrade> {
+    /// Wraps around an upgrade of inbound substreams.
+    pub(crate) fn inbound(inner: TUpgrade, negotiations: Option<Family<Counter>>) -> Self {
+        MeteredUpgrade { inner, negotiations, direction: "inbound" }
+    }
+
+    /// Wraps around an upgrade of outbound substreams.
+    pub(crate) fn outbound(inner: TUpgrade, negotiations: Option<Family<Counter>>) -> Self {
+        MeteredUpgrade { inner, negotiations, direction: "outbound" }
+    }
+
+    fn record(&self, info: &impl ProtocolName) {
+        if let Some(negotiations) = &self.negotiations {
+            let protocol = String::from_utf8_lossy(info.protocol_name());
+            negotiations.get_or_create(&[&protocol, self.direction]).inc();
+        }
+    }
+}
+
+impl<TUpgrade> UpgradeInfo for MeteredUpgrade<TUpgrade>
+where
+    TUpgrade: UpgradeInfo
+{
+    type Info = TUpgrade::Info;
+    type InfoIter = TUpgrade::InfoIter;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.inner.protocol_info()
+    }
+}
+
+impl<C, TUpgrade> InboundUpgrade<C> for MeteredUpgrade<TUpgrade>
+where
+    TUpgrade: InboundUpgrade<C>
+{
+    type Output = TUpgrade::Output;
+    type Error = TUpgrade::Error;
+    type Future = TUpgrade::Future;
+
+    fn upgrade_inbound(self, socket: Negotiated<C>, info: Self::Info) -> Self::Future {
+        self.record(&info);
+        self.inner.upgrade_inbound(socket, info)
+    }
+}
+
+impl<C, TUpgrade> OutboundUpgrade<C> for MeteredUpgrade<TUpgrade>
+where
+    TUpgrade: OutboundUpgrade<C>
+{
+    type Output = TUpgrade::Output;
+    type Error = TUpgrade::Error;
+    type Future = TUpgrade::Future;
+
+    fn upgrade_outbound(self, socket: Negotiated<C>, info: Self::Info) -> Self::Future {
+        self.record(&info);
+        self.inner.upgrade_outbound(socket, info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::transport_of;
+
+    #[test]
+    fn transport_label() {
+        let addr = "/ip4/127.0.0.1/tcp/30333/ws/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC"
+            .parse().unwrap();
+        assert_eq!(transport_of(&addr), "/ip4/tcp/ws");
+        assert_eq!(transport_of(&"/memory/1234".parse().unwrap()), "/memory");
+    }
+}
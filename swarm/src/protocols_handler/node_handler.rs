@@ -18,6 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use crate::metrics::MeteredUpgrade;
 use crate::protocols_handler::{
     KeepAlive,
     ProtocolsHandler,
@@ -33,6 +34,7 @@ use libp2p_core::{
     nodes::handled_node::{IntoNodeHandler, NodeHandler, NodeHandlerEndpoint, NodeHandlerEvent},
     upgrade::{self, InboundUpgradeApply, OutboundUpgradeApply}
 };
+use libp2p_metrics::{Counter, Family};
 use std::{error, fmt, time::Duration};
 use wasm_timer::{Delay, Timeout};
 
@@ -40,6 +42,8 @@ use wasm_timer::{Delay, Timeout};
 pub struct NodeHandlerWrapperBuilder<TIntoProtoHandler> {
     /// The underlying handler.
     handler: TIntoProtoHandler,
+    /// Counters of the negotiated substreams, if metrics are enabled.
+    substream_negotiations: Option<Family<Counter>>,
 }
 
 impl<TIntoProtoHandler> NodeHandlerWrapperBuilder<TIntoProtoHandler>
@@ -51,9 +55,17 @@ where
     pub(crate) fn new(handler: TIntoProtoHandler) -> Self {
         NodeHandlerWrapperBuilder {
             handler,
+            substream_negotiations: None,
         }
     }
 
+    /// Counts the substreams negotiated by the handler with the given counters.
+    #[inline]
+    pub(crate) fn with_substream_negotiations(mut self, counters: Option<Family<Counter>>) -> Self {
+        self.substream_negotiations = counters;
+        self
+    }
+
     /// Builds the `NodeHandlerWrapper`.
     #[deprecated(note = "Pass the NodeHandlerWrapperBuilder directly")]
     #[inline]
@@ -67,6 +79,7 @@ where
             queued_dial_upgrades: Vec::new(),
             unique_dial_upgrade_id: 0,
             shutdown: Shutdown::None,
+            substream_negotiations: self.substream_negotiations,
         }
     }
 }
@@ -88,6 +101,7 @@ where
             queued_dial_upgrades: Vec::new(),
             unique_dial_upgrade_id: 0,
             shutdown: Shutdown::None,
+            substream_negotiations: self.substream_negotiations,
         }
     }
 }
@@ -101,13 +115,18 @@ where
     /// The underlying handler.
     handler: TProtoHandler,
     /// Futures that upgrade incoming substreams.
-    negotiating_in:
-        Vec<Timeout<InboundUpgradeApply<TProtoHandler::Substream, TProtoHandler::InboundProtocol>>>,
+    negotiating_in: Vec<Timeout<InboundUpgradeApply<
+        TProtoHandler::Substream,
+        MeteredUpgrade<TProtoHandler::InboundProtocol>
+    >>>,
     /// Futures that upgrade outgoing substreams. The first element of the tuple is the userdata
     /// to pass back once successfully opened.
     negotiating_out: Vec<(
         TProtoHandler::OutboundOpenInfo,
-        Timeout<OutboundUpgradeApply<TProtoHandler::Substream, TProtoHandler::OutboundProtocol>>,
+        Timeout<OutboundUpgradeApply<
+            TProtoHandler::Substream,
+            MeteredUpgrade<TProtoHandler::OutboundProtocol>
+        >>,
     )>,
     /// For each outbound substream request, how to upgrade it. The first element of the tuple
     /// is the unique identifier (see `unique_dial_upgrade_id`).
@@ -116,6 +135,8 @@ where
     unique_dial_upgrade_id: u64,
     /// The currently planned connection & handler shutdown.
     shutdown: Shutdown,
+    /// Counters of the negotiated substreams, if metrics are enabled.
+    substream_negotiations: Option<Family<Counter>>,
 }
 
 /// The options for a planned connection & handler shutdown.
@@ -197,7 +218,11 @@ where
             NodeHandlerEndpoint::Listener => {
                 let protocol = self.handler.listen_protocol();
                 let timeout = protocol.timeout().clone();
-                let upgrade = upgrade::apply_inbound(substream, protocol.into_upgrade());
+                let protocol = MeteredUpgrade::inbound(
+                    protocol.into_upgrade(),
+                    self.substream_negotiations.clone()
+                );
+                let upgrade = upgrade::apply_inbound(substream, protocol);
                 let with_timeout = Timeout::new(upgrade, timeout);
                 self.negotiating_in.push(with_timeout);
             }
@@ -215,6 +240,10 @@ where
                 };
 
                 let (_, proto_upgrade) = self.queued_dial_upgrades.remove(pos);
+                let proto_upgrade = MeteredUpgrade::outbound(
+                    proto_upgrade,
+                    self.substream_negotiations.clone()
+                );
                 let upgrade = upgrade::apply_outbound(substream, proto_upgrade);
                 let with_timeout = Timeout::new(upgrade, timeout);
                 self.negotiating_out.push((user_data, with_timeout));
@@ -99,6 +99,19 @@ impl Addresses {
         self.registry.push(r)
     }
 
+    /// Remove a [`Multiaddr`] from the collection, along with all its reports.
+    ///
+    /// Returns `true` if the address was part of the collection.
+    pub fn remove(&mut self, a: &Multiaddr) -> bool {
+        self.reports.retain(|r| r != a);
+        if let Some(pos) = self.registry.iter().position(|r| &r.addr == a) {
+            self.registry.remove(pos);
+            true
+        } else {
+            false
+        }
+    }
+
     /// Return an iterator over all [`Multiaddr`] values.
     ///
     /// The iteration is ordered by descending score.
@@ -223,6 +236,24 @@ mod tests {
         assert!(addresses.iter().find(|a| **a == single).is_none());
     }
 
+    #[test]
+    fn removed_address_disappears() {
+        let mut addresses = Addresses::default();
+        let addr: Multiaddr = "/tcp/2108".parse().unwrap();
+        let other: Multiaddr = "/tcp/120".parse().unwrap();
+        addresses.add(addr.clone());
+        addresses.add(addr.clone());
+        addresses.add(other.clone());
+
+        assert!(addresses.remove(&addr));
+        assert!(!addresses.remove(&addr));
+        assert_eq!(addresses.iter().collect::<Vec<_>>(), vec![&other]);
+
+        // The reports of the removed address are gone as well.
+        addresses.add(addr.clone());
+        assert_eq!(addresses.registry.iter().find(|r| r.addr == addr).unwrap().score, 1);
+    }
+
     #[test]
     fn record_score_equals_last_n_reports() {
         #[derive(PartialEq, Eq, Clone, Hash, Debug)]
@@ -114,6 +114,12 @@ where
         }
     }
 
+    fn inject_incoming_connection_error(&mut self, local_addr: &Multiaddr, send_back_addr: &Multiaddr, error: &dyn error::Error) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_incoming_connection_error(local_addr, send_back_addr, error)
+        }
+    }
+
     fn inject_new_listen_addr(&mut self, addr: &Multiaddr) {
         if let Some(inner) = self.inner.as_mut() {
             inner.inject_new_listen_addr(addr)
@@ -132,6 +138,12 @@ where
         }
     }
 
+    fn inject_expired_external_addr(&mut self, addr: &Multiaddr) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_expired_external_addr(addr)
+        }
+    }
+
     fn poll(&mut self, params: &mut impl PollParameters)
         -> Async<NetworkBehaviourAction<<<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent, Self::OutEvent>>
     {
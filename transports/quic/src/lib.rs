@@ -0,0 +1,489 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the libp2p `Transport` trait for QUIC.
+//!
+//! Uses [quinn](https://github.com/djc/quinn) and [the *tokio* library](https://tokio.rs).
+//!
+//! # Usage
+//!
+//! Example:
+//!
+//! ```
+//! use libp2p_core::identity;
+//! use libp2p_quic::QuicConfig;
+//!
+//! let keypair = identity::Keypair::generate_ed25519();
+//! let quic = QuicConfig::new(&keypair).unwrap();
+//! ```
+//!
+//! The addresses handled by the transport are of the form `/ip4/<ip>/udp/<port>/quic`.
+//!
+//! Contrary to TCP, the connections produced by `QuicConfig` are already authenticated and
+//! multiplexed: the TLS 1.3 handshake of QUIC authenticates the remote using its libp2p identity
+//! key, and the streams of QUIC are used as substreams. The output of the transport is a
+//! `(PeerId, QuicMuxer)`, which can be used as is by a `Swarm`, and the transport must not be
+//! upgraded with a security protocol or a stream multiplexer.
+//!
+//! The certificates are generated and verified by `libp2p-tls`, the same way as for the TLS
+//! upgrade over TCP.
+//!
+//! Each dialed connection uses its own UDP socket.
+
+mod muxer;
+
+pub use libp2p_tls::CertificateError;
+pub use self::muxer::{QuicMuxer, QuicOutboundSubstream, QuicSubstream};
+
+use futures::{prelude::*, try_ready};
+use get_if_addrs::get_if_addrs;
+use libp2p_core::{
+    PeerId,
+    Transport,
+    identity,
+    multiaddr::{Protocol, Multiaddr},
+    transport::{ListenerEvent, TransportError}
+};
+use libp2p_tls::verifier;
+use log::debug;
+use self::muxer::into_io_error;
+use std::{
+    collections::VecDeque,
+    fmt,
+    io,
+    iter::{self, FromIterator},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration
+};
+use tokio_executor::Executor;
+
+/// Server name sent during the TLS handshake. It is ignored, since the identity of the remote is
+/// taken from its certificate.
+const SERVER_NAME: &str = "l";
+
+/// Represents the configuration for a QUIC transport capability for libp2p.
+///
+/// The UDP sockets created by libp2p will need to be progressed by running the futures and
+/// streams obtained by libp2p through the tokio reactor.
+#[derive(Clone)]
+pub struct QuicConfig {
+    /// TLS configuration used when dialing.
+    client_tls: Arc<rustls::ClientConfig>,
+    /// TLS configuration used when listening.
+    server_tls: Arc<rustls::ServerConfig>,
+    /// Duration after which an idle connection is closed.
+    idle_timeout: Duration,
+    /// Interval at which keep-alive packets are sent, or `None` to not send any.
+    keep_alive_interval: Option<Duration>,
+}
+
+impl QuicConfig {
+    /// Creates a new configuration object for QUIC, authenticating the connections with
+    /// `keypair`, with the following default settings:
+    ///
+    ///   * [`QuicConfig::idle_timeout`] 30s
+    ///   * [`QuicConfig::keep_alive_interval`] 10s
+    pub fn new(keypair: &identity::Keypair) -> Result<QuicConfig, CertificateError> {
+        let (client_tls, server_tls) = libp2p_tls::make_configs(keypair)?;
+
+        Ok(QuicConfig {
+            client_tls: Arc::new(client_tls),
+            server_tls: Arc::new(server_tls),
+            idle_timeout: Duration::from_secs(30),
+            keep_alive_interval: Some(Duration::from_secs(10)),
+        })
+    }
+
+    /// Sets the duration after which an idle connection is closed.
+    pub fn idle_timeout(mut self, value: Duration) -> Self {
+        self.idle_timeout = value;
+        self
+    }
+
+    /// Sets the interval at which keep-alive packets are sent, or `None` to not send any.
+    ///
+    /// Without keep-alive packets, connections on which no data is exchanged are closed after
+    /// the idle timeout.
+    pub fn keep_alive_interval(mut self, value: Option<Duration>) -> Self {
+        self.keep_alive_interval = value;
+        self
+    }
+
+    /// Builds the QUIC transport parameters.
+    fn transport_config(&self) -> Arc<quinn::TransportConfig> {
+        let mut transport = quinn::TransportConfig::default();
+        transport.idle_timeout = self.idle_timeout.as_millis() as u64;
+        transport.keep_alive_interval = self.keep_alive_interval.map_or(0, |d| d.as_millis() as u32);
+        Arc::new(transport)
+    }
+
+    /// Builds the configuration used when dialing.
+    fn client_config(&self) -> quinn::ClientConfig {
+        quinn::ClientConfig {
+            transport: self.transport_config(),
+            crypto: self.client_tls.clone(),
+        }
+    }
+
+    /// Builds the configuration used when listening.
+    fn server_config(&self) -> quinn::ServerConfig {
+        let mut server = quinn::ServerConfig::default();
+        server.transport = self.transport_config();
+        server.crypto = self.server_tls.clone();
+        server
+    }
+}
+
+impl fmt::Debug for QuicConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuicConfig")
+            .field("idle_timeout", &self.idle_timeout)
+            .field("keep_alive_interval", &self.keep_alive_interval)
+            .finish()
+    }
+}
+
+impl Transport for QuicConfig {
+    type Output = (PeerId, QuicMuxer);
+    type Error = io::Error;
+    type Listener = QuicListenStream;
+    type ListenerUpgrade = QuicUpgrade;
+    type Dial = QuicUpgrade;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let socket_addr =
+            if let Ok(sa) = multiaddr_to_socketaddr(&addr) {
+                sa
+            } else {
+                return Err(TransportError::MultiaddrNotSupported(addr))
+            };
+
+        let mut builder = quinn::Endpoint::builder();
+        builder.listen(self.server_config());
+        let (driver, endpoint, incoming) = builder.bind(&socket_addr)
+            .map_err(|e| TransportError::Other(into_io_error(e)))?;
+        let local_addr = endpoint.local_addr().map_err(TransportError::Other)?;
+
+        // Determine all our listen addresses which is either a single local IP address
+        // or (if a wildcard IP address was used) the addresses of all our interfaces
+        // of the same IP version, as reported by `get_if_addrs`.
+        let addrs =
+            if local_addr.ip().is_unspecified() {
+                host_addresses(&local_addr).map_err(TransportError::Other)?
+            } else {
+                vec![socketaddr_to_multiaddr(&local_addr)]
+            };
+        debug!("Listening on {:?}", addrs);
+
+        Ok(QuicListenStream {
+            driver: Some(driver),
+            _endpoint: endpoint,
+            incoming,
+            local_addr: socketaddr_to_multiaddr(&local_addr),
+            pending: addrs.into_iter().map(ListenerEvent::NewAddress).collect(),
+        })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let socket_addr =
+            if let Ok(socket_addr) = multiaddr_to_socketaddr(&addr) {
+                if socket_addr.port() == 0 || socket_addr.ip().is_unspecified() {
+                    debug!("Instantly refusing dialing {}, as it is invalid", addr);
+                    return Err(TransportError::Other(io::ErrorKind::ConnectionRefused.into()))
+                }
+                socket_addr
+            } else {
+                return Err(TransportError::MultiaddrNotSupported(addr))
+            };
+
+        debug!("Dialing {}", addr);
+
+        let bind_addr = match socket_addr {
+            SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+            SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+        };
+        let mut builder = quinn::Endpoint::builder();
+        builder.default_client_config(self.client_config());
+        let (driver, endpoint, _) = builder.bind(&bind_addr)
+            .map_err(|e| TransportError::Other(into_io_error(e)))?;
+        let connecting = endpoint.connect(&socket_addr, SERVER_NAME)
+            .map_err(|e| TransportError::Other(into_io_error(e)))?;
+
+        Ok(QuicUpgrade {
+            endpoint_driver: Some(driver),
+            endpoint: Some(endpoint),
+            connecting,
+        })
+    }
+}
+
+/// Stream of the connections received by a QUIC listener.
+pub struct QuicListenStream {
+    /// The driver of the endpoint, to spawn on the first poll.
+    driver: Option<quinn::EndpointDriver>,
+    /// The endpoint, kept alive along with the listener.
+    _endpoint: quinn::Endpoint,
+    /// The incoming connections.
+    incoming: quinn::Incoming,
+    /// The address the endpoint is bound to.
+    local_addr: Multiaddr,
+    /// Events to report before any incoming connection.
+    pending: VecDeque<ListenerEvent<QuicUpgrade>>,
+}
+
+impl Stream for QuicListenStream {
+    type Item = ListenerEvent<QuicUpgrade>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Async::Ready(Some(event)))
+        }
+
+        if let Some(driver) = self.driver.take() {
+            spawn(driver.map_err(|e| debug!("QUIC endpoint error: {}", e)))?;
+        }
+
+        match self.incoming.poll() {
+            Ok(Async::Ready(Some(connecting))) => {
+                let remote_addr = socketaddr_to_multiaddr(&connecting.remote_address());
+                debug!("Incoming connection from {}", remote_addr);
+                let upgrade = QuicUpgrade {
+                    endpoint_driver: None,
+                    endpoint: None,
+                    connecting,
+                };
+                Ok(Async::Ready(Some(ListenerEvent::Upgrade {
+                    upgrade,
+                    local_addr: self.local_addr.clone(),
+                    remote_addr,
+                })))
+            }
+            Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(()) => Err(io::Error::new(io::ErrorKind::Other, "QUIC endpoint closed")),
+        }
+    }
+}
+
+impl fmt::Debug for QuicListenStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuicListenStream")
+            .field("local_addr", &self.local_addr)
+            .finish()
+    }
+}
+
+/// Future resolving to an authenticated QUIC connection, once the handshake is over.
+pub struct QuicUpgrade {
+    /// The driver of the endpoint of a dialed connection, to spawn on the first poll.
+    endpoint_driver: Option<quinn::EndpointDriver>,
+    /// The endpoint of a dialed connection, handed over to the muxer.
+    endpoint: Option<quinn::Endpoint>,
+    /// The connection being established.
+    connecting: quinn::Connecting,
+}
+
+impl Future for QuicUpgrade {
+    type Item = (PeerId, QuicMuxer);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, io::Error> {
+        if let Some(driver) = self.endpoint_driver.take() {
+            spawn(driver.map_err(|e| debug!("QUIC endpoint error: {}", e)))?;
+        }
+
+        let (driver, connection, incoming) = try_ready!(self.connecting.poll().map_err(into_io_error));
+        spawn(driver.map_err(|e| debug!("QUIC connection error: {}", e)))?;
+
+        let certs = connection.peer_der_certificates()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Remote presented no certificate"))?;
+        let peer_id = verifier::verify_presented_certs(&certs).map_err(into_io_error)?;
+        debug!("Connection with {:?} established", peer_id);
+
+        let muxer = QuicMuxer::new(connection, incoming, self.endpoint.take());
+        Ok(Async::Ready((peer_id, muxer)))
+    }
+}
+
+impl fmt::Debug for QuicUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("QuicUpgrade")
+    }
+}
+
+/// Spawns one of the background tasks driving the endpoints and connections.
+fn spawn<F>(future: F) -> io::Result<()>
+where
+    F: Future<Item = (), Error = ()> + Send + 'static
+{
+    tokio_executor::DefaultExecutor::current()
+        .spawn(Box::new(future))
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Failed to spawn QUIC task: {:?}", e)))
+}
+
+// This type of logic should probably be moved into the multiaddr package
+fn multiaddr_to_socketaddr(addr: &Multiaddr) -> Result<SocketAddr, ()> {
+    let mut iter = addr.iter();
+    let proto1 = iter.next().ok_or(())?;
+    let proto2 = iter.next().ok_or(())?;
+    let proto3 = iter.next().ok_or(())?;
+
+    if iter.next().is_some() {
+        return Err(());
+    }
+
+    match (proto1, proto2, proto3) {
+        (Protocol::Ip4(ip), Protocol::Udp(port), Protocol::Quic) => Ok(SocketAddr::new(ip.into(), port)),
+        (Protocol::Ip6(ip), Protocol::Udp(port), Protocol::Quic) => Ok(SocketAddr::new(ip.into(), port)),
+        _ => Err(()),
+    }
+}
+
+// Create a [`Multiaddr`] from the given socket address.
+fn socketaddr_to_multiaddr(addr: &SocketAddr) -> Multiaddr {
+    let proto = match addr.ip() {
+        IpAddr::V4(ip) => Protocol::Ip4(ip),
+        IpAddr::V6(ip) => Protocol::Ip6(ip)
+    };
+    let it = iter::once(proto)
+        .chain(iter::once(Protocol::Udp(addr.port())))
+        .chain(iter::once(Protocol::Quic));
+    Multiaddr::from_iter(it)
+}
+
+// Collect the local host addresses of the same IP version as `local_addr`, using its port.
+fn host_addresses(local_addr: &SocketAddr) -> io::Result<Vec<Multiaddr>> {
+    let addrs = get_if_addrs()?
+        .into_iter()
+        .map(|iface| iface.ip())
+        .filter(|ip| ip.is_ipv4() == local_addr.is_ipv4())
+        .map(|ip| socketaddr_to_multiaddr(&SocketAddr::new(ip, local_addr.port())))
+        .collect();
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::prelude::*;
+    use libp2p_core::{Transport, identity, muxing, multiaddr::Multiaddr, transport::ListenerEvent};
+    use std::{net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}, sync::Arc};
+    use super::{multiaddr_to_socketaddr, socketaddr_to_multiaddr, QuicConfig};
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn multiaddr_to_quic_conversion() {
+        assert!(
+            multiaddr_to_socketaddr(&"/ip4/127.0.0.1/tcp/1234".parse::<Multiaddr>().unwrap())
+                .is_err()
+        );
+        assert!(
+            multiaddr_to_socketaddr(&"/ip4/127.0.0.1/udp/1234".parse::<Multiaddr>().unwrap())
+                .is_err()
+        );
+
+        assert_eq!(
+            multiaddr_to_socketaddr(&"/ip4/127.0.0.1/udp/12345/quic".parse::<Multiaddr>().unwrap()),
+            Ok(SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                12345,
+            ))
+        );
+        assert_eq!(
+            multiaddr_to_socketaddr(&"/ip6/::1/udp/12345/quic".parse::<Multiaddr>().unwrap()),
+            Ok(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+                12345,
+            ))
+        );
+
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 4001);
+        assert_eq!(multiaddr_to_socketaddr(&socketaddr_to_multiaddr(&addr)), Ok(addr));
+    }
+
+    #[test]
+    fn replace_port_0_in_returned_multiaddr() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let quic = QuicConfig::new(&keypair).unwrap();
+
+        let addr = "/ip4/127.0.0.1/udp/0/quic".parse::<Multiaddr>().unwrap();
+        let new_addr = quic.listen_on(addr).unwrap().wait()
+            .next()
+            .expect("some event")
+            .expect("no error")
+            .into_new_address()
+            .expect("listen address");
+
+        assert!(!new_addr.to_string().contains("udp/0"));
+    }
+
+    #[test]
+    fn communicating_between_dialer_and_listener() {
+        let listener_keys = identity::Keypair::generate_ed25519();
+        let listener_id = listener_keys.public().into_peer_id();
+        let dialer_keys = identity::Keypair::generate_ed25519();
+        let dialer_id = dialer_keys.public().into_peer_id();
+
+        let mut listener = QuicConfig::new(&listener_keys).unwrap()
+            .listen_on("/ip4/127.0.0.1/udp/0/quic".parse().unwrap())
+            .unwrap();
+        let addr = listener.by_ref()
+            .wait()
+            .next()
+            .expect("some event")
+            .expect("no error")
+            .into_new_address()
+            .expect("listen address");
+
+        let server = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(e, _)| e)
+            .and_then(|(upgrade, listener)| {
+                let (upgrade, _) = upgrade.expect("incoming connection");
+                upgrade.map(move |output| (output, listener))
+            })
+            .and_then(move |((peer_id, muxer), listener)| {
+                assert_eq!(peer_id, dialer_id);
+                muxing::inbound_from_ref_and_wrap(Arc::new(muxer))
+                    .and_then(|substream| tokio::io::read_to_end(substream, Vec::new()))
+                    .map(move |(_, buf)| {
+                        assert_eq!(buf, b"hello");
+                        drop(listener);
+                    })
+            });
+
+        let client = QuicConfig::new(&dialer_keys).unwrap()
+            .dial(addr)
+            .unwrap()
+            .and_then(move |(peer_id, muxer)| {
+                assert_eq!(peer_id, listener_id);
+                let muxer = Arc::new(muxer);
+                muxing::outbound_from_ref_and_wrap(muxer.clone())
+                    .and_then(|substream| tokio::io::write_all(substream, b"hello"))
+                    .and_then(|(substream, _)| tokio::io::shutdown(substream))
+                    .map(move |_| muxer)
+            });
+
+        let mut rt = Runtime::new().unwrap();
+        let (_, _muxer) = rt.block_on(server.join(client)).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::{prelude::*, try_ready};
+use libp2p_core::muxing::StreamMuxer;
+use log::debug;
+use parking_lot::Mutex;
+use std::{fmt, io};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A QUIC connection, whose bidirectional streams are used as substreams.
+pub struct QuicMuxer {
+    /// The connection.
+    connection: quinn::Connection,
+    /// The streams opened by the remote.
+    incoming: Mutex<quinn::IncomingStreams>,
+    /// The endpoint the connection was dialed from, kept alive along with the connection.
+    _endpoint: Option<quinn::Endpoint>,
+}
+
+impl QuicMuxer {
+    pub(crate) fn new(
+        connection: quinn::Connection,
+        incoming: quinn::IncomingStreams,
+        endpoint: Option<quinn::Endpoint>
+    ) -> Self {
+        QuicMuxer {
+            connection,
+            incoming: Mutex::new(incoming),
+            _endpoint: endpoint,
+        }
+    }
+}
+
+impl fmt::Debug for QuicMuxer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuicMuxer")
+            .field("remote_address", &self.connection.remote_address())
+            .finish()
+    }
+}
+
+/// A bidirectional QUIC stream.
+pub struct QuicSubstream {
+    /// The sending half of the stream.
+    send: quinn::SendStream,
+    /// The receiving half of the stream.
+    recv: quinn::RecvStream,
+}
+
+impl fmt::Debug for QuicSubstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("QuicSubstream")
+    }
+}
+
+/// Future opening a bidirectional stream.
+pub struct QuicOutboundSubstream {
+    inner: Box<dyn Future<Item = (quinn::SendStream, quinn::RecvStream), Error = quinn::ConnectionError> + Send>,
+}
+
+impl fmt::Debug for QuicOutboundSubstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("QuicOutboundSubstream")
+    }
+}
+
+impl StreamMuxer for QuicMuxer {
+    type Substream = QuicSubstream;
+    type OutboundSubstream = QuicOutboundSubstream;
+    type Error = io::Error;
+
+    fn poll_inbound(&self) -> Poll<Self::Substream, io::Error> {
+        let mut incoming = self.incoming.lock();
+        loop {
+            match incoming.poll().map_err(into_io_error)? {
+                Async::Ready(Some(quinn::NewStream::Bi(send, recv))) => {
+                    return Ok(Async::Ready(QuicSubstream { send, recv }))
+                }
+                Async::Ready(Some(quinn::NewStream::Uni(_))) => {
+                    debug!("Ignoring unidirectional stream opened by the remote");
+                }
+                Async::Ready(None) => return Err(io::ErrorKind::BrokenPipe.into()),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        QuicOutboundSubstream {
+            inner: Box::new(self.connection.open_bi()),
+        }
+    }
+
+    fn poll_outbound(&self, s: &mut Self::OutboundSubstream) -> Poll<Self::Substream, io::Error> {
+        let (send, recv) = try_ready!(s.inner.poll().map_err(into_io_error));
+        Ok(Async::Ready(QuicSubstream { send, recv }))
+    }
+
+    fn destroy_outbound(&self, _: Self::OutboundSubstream) {
+    }
+
+    fn read_substream(&self, s: &mut Self::Substream, buf: &mut [u8]) -> Poll<usize, io::Error> {
+        s.recv.poll_read(buf)
+    }
+
+    fn write_substream(&self, s: &mut Self::Substream, buf: &[u8]) -> Poll<usize, io::Error> {
+        s.send.poll_write(buf)
+    }
+
+    fn flush_substream(&self, s: &mut Self::Substream) -> Poll<(), io::Error> {
+        s.send.poll_flush()
+    }
+
+    fn shutdown_substream(&self, s: &mut Self::Substream) -> Poll<(), io::Error> {
+        s.send.shutdown()
+    }
+
+    fn destroy_substream(&self, _: Self::Substream) {
+    }
+
+    fn is_remote_acknowledged(&self) -> bool {
+        // The handshake is over by the time the muxer is created.
+        true
+    }
+
+    fn close(&self) -> Poll<(), io::Error> {
+        self.connection.close(0, &[]);
+        Ok(Async::Ready(()))
+    }
+
+    fn flush_all(&self) -> Poll<(), io::Error> {
+        // Data written to a stream is sent without waiting for a flush.
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Turns an error of `quinn` into an `io::Error`.
+pub(crate) fn into_io_error<E>(e: E) -> io::Error
+where
+    E: std::error::Error + Send + Sync + 'static
+{
+    io::Error::new(io::ErrorKind::Other, e)
+}